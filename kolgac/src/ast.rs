@@ -1,5 +1,5 @@
-use crate::token::Token;
-use crate::ty_rec::TyRecord;
+use crate::token::{TknTy, Token};
+use crate::ty_rec::{KolgaTy, TyRecord};
 
 use std::collections::HashMap;
 
@@ -132,6 +132,10 @@ pub enum Ast {
         ret_ty: TyRecord,
         fn_body: Box<Ast>,
         sc: usize,
+
+        /// True for class methods declared with 'mut fn'. Only these methods are
+        /// allowed to change the properties of self.
+        is_mut: bool,
     },
 
     FnCallExpr {
@@ -186,6 +190,13 @@ pub enum Ast {
         fn_params: Vec<Ast>,
         sc: usize,
     },
+
+    /// Takes the place of a declaration or statement that failed to parse. The
+    /// error has already been reported, so later passes should skip these nodes
+    /// and keep checking the rest of the program.
+    Error {
+        meta: MetaAst,
+    },
 }
 
 impl Ast {
@@ -208,6 +219,33 @@ impl Ast {
         }
     }
 
+    pub fn get_meta(&self) -> &MetaAst {
+        match self {
+            Ast::Prog { meta, .. }
+            | Ast::BlckStmt { meta, .. }
+            | Ast::IfStmt { meta, .. }
+            | Ast::ElifStmt { meta, .. }
+            | Ast::WhileStmt { meta, .. }
+            | Ast::ForStmt { meta, .. }
+            | Ast::RetStmt { meta, .. }
+            | Ast::ExprStmt { meta, .. }
+            | Ast::VarDeclExpr { meta, .. }
+            | Ast::VarAssignExpr { meta, .. }
+            | Ast::LogicalExpr { meta, .. }
+            | Ast::BinaryExpr { meta, .. }
+            | Ast::UnaryExpr { meta, .. }
+            | Ast::PrimaryExpr { meta, .. }
+            | Ast::FnDeclStmt { meta, .. }
+            | Ast::FnCallExpr { meta, .. }
+            | Ast::ClassDeclStmt { meta, .. }
+            | Ast::ClassConstrExpr { meta, .. }
+            | Ast::ClassPropAccessExpr { meta, .. }
+            | Ast::ClassPropSetExpr { meta, .. }
+            | Ast::ClassFnCallExpr { meta, .. }
+            | Ast::Error { meta } => meta,
+        }
+    }
+
     pub fn get_ty_rec(&self) -> Option<TyRecord> {
         match self {
             Ast::PrimaryExpr {
@@ -253,4 +291,22 @@ impl Ast {
             _ => None,
         }
     }
+
+    /// The type of an expression, once it's been type checked. Inference leaves
+    /// the types of operators alone, so we work those out from their operands.
+    pub fn expr_ty(&self) -> KolgaTy {
+        match self {
+            Ast::ExprStmt { meta: _, expr } => expr.expr_ty(),
+            Ast::BinaryExpr { op_tkn, .. } if op_tkn.ty.is_cmp_op() => KolgaTy::Bool,
+            Ast::BinaryExpr { .. } => KolgaTy::Num,
+            Ast::LogicalExpr { .. } => KolgaTy::Bool,
+            Ast::UnaryExpr { op_tkn, .. } if op_tkn.ty == TknTy::Minus => KolgaTy::Num,
+            Ast::UnaryExpr { .. } => KolgaTy::Bool,
+            Ast::ClassConstrExpr { class_name, .. } => KolgaTy::Class(class_name.clone()),
+            _ => self
+                .get_ty_rec()
+                .map(|ty_rec| ty_rec.ty)
+                .unwrap_or(KolgaTy::Void),
+        }
+    }
 }
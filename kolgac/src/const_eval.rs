@@ -0,0 +1,142 @@
+use crate::{ast::Ast, token::TknTy};
+
+use std::collections::HashMap;
+
+/// A value known at compile time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstVal {
+    Num(f64),
+    Bool(bool),
+}
+
+/// Evaluates expressions at compile time. Expressions made of literals, operators,
+/// and immutable variables with constant values are folded into a single value.
+/// Anything else, like a function call or a mutable variable, isn't constant.
+///
+/// Variables are looked up by name, so the evaluator has to be told about every
+/// variable declared while walking the AST, in the same scopes, for names to
+/// resolve to the right variable. A variable that isn't constant hides constants
+/// with the same name in outer scopes.
+pub struct ConstEval {
+    scopes: Vec<HashMap<String, Option<ConstVal>>>,
+}
+
+impl Default for ConstEval {
+    fn default() -> ConstEval {
+        ConstEval::new()
+    }
+}
+
+impl ConstEval {
+    pub fn new() -> ConstEval {
+        ConstEval {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn init_sc(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn close_sc(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Declares a variable with a value in the current scope. Only immutable
+    /// variables can be constant, since anything else could be assigned later.
+    pub fn bind(&mut self, name: &str, is_imm: bool, value: &Ast) {
+        let val = if is_imm { self.eval(value) } else { None };
+        self.store(name, val);
+    }
+
+    /// Declares a variable that isn't constant in the current scope, like a
+    /// function parameter.
+    pub fn shadow(&mut self, name: &str) {
+        self.store(name, None);
+    }
+
+    fn store(&mut self, name: &str, val: Option<ConstVal>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(String::from(name), val);
+        }
+    }
+
+    fn retrieve(&self, name: &str) -> Option<ConstVal> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .flatten()
+    }
+
+    /// Evaluates an expression, returning None if it isn't constant.
+    pub fn eval(&self, expr: &Ast) -> Option<ConstVal> {
+        match expr {
+            // Properties of self aren't variables, even if a variable has the
+            // same name.
+            Ast::PrimaryExpr { is_self: true, .. } => None,
+            Ast::PrimaryExpr { ty_rec, .. } => match ty_rec.tkn.ty {
+                TknTy::Val(val) => Some(ConstVal::Num(val)),
+                TknTy::True => Some(ConstVal::Bool(true)),
+                TknTy::False => Some(ConstVal::Bool(false)),
+                TknTy::Ident(ref name) => self.retrieve(name),
+                _ => None,
+            },
+            Ast::UnaryExpr { op_tkn, rhs, .. } => match (&op_tkn.ty, self.eval(rhs)?) {
+                (TknTy::Minus, ConstVal::Num(val)) => Some(ConstVal::Num(-val)),
+                (TknTy::Bang, ConstVal::Bool(val)) => Some(ConstVal::Bool(!val)),
+                _ => None,
+            },
+            Ast::BinaryExpr {
+                op_tkn, lhs, rhs, ..
+            } => eval_bin(&op_tkn.ty, self.eval(lhs)?, self.eval(rhs)?),
+            Ast::LogicalExpr {
+                op_tkn, lhs, rhs, ..
+            } => {
+                let lhs = match self.eval(lhs)? {
+                    ConstVal::Bool(val) => val,
+                    _ => return None,
+                };
+
+                // The rhs only decides the result if the lhs doesn't, so a constant
+                // lhs is enough when it short circuits.
+                match (&op_tkn.ty, lhs) {
+                    (TknTy::And, false) | (TknTy::AmpAmp, false) => Some(ConstVal::Bool(false)),
+                    (TknTy::Or, true) | (TknTy::PipePipe, true) => Some(ConstVal::Bool(true)),
+                    _ => match self.eval(rhs)? {
+                        ConstVal::Bool(val) => Some(ConstVal::Bool(val)),
+                        _ => None,
+                    },
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn eval_bin(op: &TknTy, lhs: ConstVal, rhs: ConstVal) -> Option<ConstVal> {
+    let val = match (lhs, rhs) {
+        (ConstVal::Num(lhs), ConstVal::Num(rhs)) => match op {
+            TknTy::Plus => ConstVal::Num(lhs + rhs),
+            TknTy::Minus => ConstVal::Num(lhs - rhs),
+            TknTy::Star => ConstVal::Num(lhs * rhs),
+            TknTy::Slash => ConstVal::Num(lhs / rhs),
+            TknTy::Gt => ConstVal::Bool(lhs > rhs),
+            TknTy::GtEq => ConstVal::Bool(lhs >= rhs),
+            TknTy::Lt => ConstVal::Bool(lhs < rhs),
+            TknTy::LtEq => ConstVal::Bool(lhs <= rhs),
+            TknTy::EqEq => ConstVal::Bool(lhs == rhs),
+            TknTy::BangEq => ConstVal::Bool(lhs != rhs),
+            _ => return None,
+        },
+        (ConstVal::Bool(lhs), ConstVal::Bool(rhs)) => match op {
+            TknTy::EqEq => ConstVal::Bool(lhs == rhs),
+            TknTy::BangEq => ConstVal::Bool(lhs != rhs),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(val)
+}
@@ -53,6 +53,7 @@ impl Lexer {
         let r: HashMap<String, TknTy> = [
             (String::from("let"), TknTy::Let),
             (String::from("imm"), TknTy::Imm),
+            (String::from("mut"), TknTy::Mut),
             (String::from("fn"), TknTy::Fn),
             (String::from("return"), TknTy::Return),
             (String::from("class"), TknTy::Class),
@@ -1,8 +1,11 @@
 extern crate kolgac_errors;
 
 pub mod ast;
+pub mod const_eval;
 pub mod lexer;
+pub mod opt;
 pub mod parser;
+pub mod scope;
 pub mod sym;
 pub mod symtab;
 pub mod token;
@@ -0,0 +1,37 @@
+use std::str::FromStr;
+
+/// How much to optimize, from the -O flag. Each backend decides which passes
+/// a level runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptLevel {
+    /// No optimization, so the code is quick to generate and easy to debug.
+    O0,
+
+    /// Cheap optimizations, that only look at a little code at a time.
+    O1,
+
+    /// Everything that doesn't trade code size for speed.
+    O2,
+
+    /// Everything, including optimizations that make code bigger to make it
+    /// faster, like unrolling loops.
+    O3,
+
+    /// Like O2, but leaves out optimizations that make code bigger.
+    Os,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            "s" => Ok(OptLevel::Os),
+            _ => Err(format!("invalid optimization level '{}'", s)),
+        }
+    }
+}
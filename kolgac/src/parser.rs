@@ -12,12 +12,15 @@ use kolgac_errors::{
     KolgaErr,
 };
 
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 const FN_PARAM_MAX_LEN: usize = 64;
 
 /// ParserResult handles the result from parsing a file. This contains an optional
-/// AST structure, as well as a flag indicating whether or not continuable errors
+/// AST structure, as well as a flag indicating whether or not any errors
 /// were encountered during the parsing phase. This is returned from the parse()
 /// method, and should be checked for errors before continuing further phases
 /// of the compiler.
@@ -54,6 +57,11 @@ pub struct ClassContext {
     pub prop_map: HashMap<String, usize>,
     pub methods: Vec<Ast>,
     pub ident_is_self: bool,
+
+    /// True while parsing a property declaration in a class body. Immutable
+    /// properties are declared without a value, since they're set by the class
+    /// constructor.
+    pub parsing_prop: bool,
 }
 
 impl ClassContext {
@@ -62,6 +70,7 @@ impl ClassContext {
             prop_map: pm,
             methods: mtods,
             ident_is_self: false,
+            parsing_prop: false,
         }
     }
 
@@ -69,6 +78,7 @@ impl ClassContext {
         self.prop_map.clear();
         self.methods.clear();
         self.ident_is_self = false;
+        self.parsing_prop = false;
     }
 
     pub fn exists(&self, key: &str) -> bool {
@@ -83,9 +93,8 @@ pub struct Parser<'l, 's> {
     /// Reference to a symbol table, used to store symbols defined in this file.
     symtab: &'s mut SymbolTable,
 
-    /// Vector of errors. We continue to store errors because we may encounter
-    /// continuable errors, where we keep parsing but we need to report the
-    /// errors later.
+    /// Vector of errors. We keep parsing after an error, so errors are stored
+    /// as they're found and reported once the whole file has been parsed.
     // TODO: this should probably go into the ParserResult struct
     errors: Vec<ParseErr>,
 
@@ -97,6 +106,15 @@ pub struct Parser<'l, 's> {
 
     /// Number of symbols encountered.
     sym_count: usize,
+
+    /// Position of the token we last recovered from an error at. If we fail again
+    /// at the same token, we skip it so that recovery always makes progress.
+    recovered_at: Option<(usize, usize)>,
+
+    /// Index of the first error found in the statement or class member we're
+    /// parsing. An error that's the same as one already found in it has the same
+    /// cause, so it isn't stored again.
+    stmt_errs: usize,
 }
 
 impl<'l, 's> Parser<'l, 's> {
@@ -110,6 +128,8 @@ impl<'l, 's> Parser<'l, 's> {
             currtkn: firsttkn,
             node_count: 1, // start at 1 because the entry node always has id 0
             sym_count: 0,
+            recovered_at: None,
+            stmt_errs: 0,
         }
     }
 
@@ -117,9 +137,11 @@ impl<'l, 's> Parser<'l, 's> {
     /// file and return a result containing the AST and any parsing errors encountered.
     /// The error vector should be checked after parsing, and any errors should
     /// be handled before continuing to future compiler passes.
+    /// When a declaration fails to parse, we skip ahead to the next statement boundary
+    /// and keep going, so that one error doesn't hide the rest. The failed declaration
+    /// is replaced by an Error node in the AST.
     pub fn parse(&mut self) -> ParserResult {
         let mut stmts: Vec<Ast> = Vec::new();
-        let mut found_err = false;
 
         // Create a new empty parse context, whose reference is passed to all
         // parsing functions
@@ -130,12 +152,13 @@ impl<'l, 's> Parser<'l, 's> {
             match self.decl(&mut pctx) {
                 Ok(a) => stmts.push(a),
                 Err(e) => {
-                    found_err = true;
-                    e.emit();
-                    match e.continuable() {
-                        true => (),
-                        false => break,
-                    };
+                    stmts.push(self.recover(e));
+
+                    // There's no block for a '}' to close in the global scope, so
+                    // skip it instead of reporting it as another error.
+                    if self.currtkn.ty == TknTy::RightBrace {
+                        self.consume();
+                    }
                 }
             }
         }
@@ -143,6 +166,16 @@ impl<'l, 's> Parser<'l, 's> {
         // Finalize the global scope to access scopes in future passes.
         self.symtab.finalize_global_sc();
 
+        // Errors found while recovering often point at the same token as the error
+        // that caused them, so only the first error at each position is reported.
+        let mut reported = HashSet::new();
+        for err in &self.errors {
+            if reported.insert((err.line, err.pos)) {
+                err.emit();
+            }
+        }
+        let found_err = !self.errors.is_empty();
+
         let head = Ast::Prog {
             meta: MetaAst::new(0, 0, 0),
             stmts: stmts,
@@ -156,9 +189,11 @@ impl<'l, 's> Parser<'l, 's> {
 
     /// Parses a declaration. In kolga we can declare variables, functions, and classes.
     fn decl(&mut self, pctx: &mut ParseContext) -> Result<Ast, ParseErr> {
+        self.stmt_errs = self.errors.len();
         match self.currtkn.ty {
             TknTy::Let => self.var_decl(pctx),
             TknTy::Fn => self.fn_decl(pctx),
+            TknTy::Mut => Err(self.error(ParseErrTy::InvalidMut)),
             TknTy::Class => self.class_decl(pctx),
             _ => self.stmt(pctx),
         }
@@ -234,8 +269,26 @@ impl<'l, 's> Parser<'l, 's> {
                 };
 
                 self.consume();
-                let var_val = self.expr(pctx)?;
-                self.expect(TknTy::Semicolon)?;
+                let var_val = match self.expr(pctx) {
+                    Ok(val) => val,
+                    Err(e) => {
+                        // Declare the variable anyway, so its uses aren't reported as
+                        // undeclared on top of the error in its value.
+                        if let Some(tkn) = ident_tkn {
+                            let mut err_ty_rec = ty_rec;
+                            err_ty_rec.ty = KolgaTy::Error;
+                            let name = tkn.get_name();
+                            let sym = Sym::new(SymTy::Var, is_imm, err_ty_rec, tkn, None, None);
+                            self.symtab.store(&name, sym);
+                        }
+                        return Err(e);
+                    }
+                };
+
+                // If only the ';' is missing, we still have a complete declaration.
+                // We report the error but keep the declaration, so later uses of the
+                // variable aren't reported as undeclared.
+                self.expect(TknTy::Semicolon).ok();
 
                 let sym = Sym::new(
                     SymTy::Var,
@@ -259,11 +312,22 @@ impl<'l, 's> Parser<'l, 's> {
                     value: Box::new(var_val),
                 })
             }
-            TknTy::Semicolon => {
+            // A declaration that's followed by the next declaration is only missing
+            // its ';'. This is usually a class property, followed by the next member.
+            TknTy::Semicolon
+            | TknTy::Let
+            | TknTy::Fn
+            | TknTy::Mut
+            | TknTy::Class
+            | TknTy::RightBrace
+            | TknTy::Eof
+                if var_ty_tkn.is_some() || self.currtkn.ty == TknTy::Semicolon =>
+            {
                 // Check if we're trying to create an immutable variable but with no
                 // value assigned to it. We treat this as an error, since we should never
-                // be able to assign to an immutable var later.
-                if is_imm {
+                // be able to assign to an immutable var later. Class properties are
+                // the exception, since the constructor sets their value.
+                if is_imm && !pctx.clsctx.parsing_prop {
                     let ty_str = self.currtkn.ty.to_string();
                     return Err(self.error(ParseErrTy::ImmDecl(ty_str)));
                 }
@@ -272,7 +336,7 @@ impl<'l, 's> Parser<'l, 's> {
                     return Err(self.error(ParseErrTy::InvalidClassConstr));
                 }
 
-                self.consume();
+                self.expect(TknTy::Semicolon).ok();
 
                 // For a var declaration without an assignment, we require a type annotation.
                 // The inferrer isn't smart enough (yet) to infer types without this information
@@ -397,8 +461,14 @@ impl<'l, 's> Parser<'l, 's> {
         }
     }
 
-    /// Parses a function declaration.
+    /// Parses a function declaration. Class methods can be marked with 'mut'
+    /// before 'fn', which the class declaration checks for us.
     fn fn_decl(&mut self, pctx: &mut ParseContext) -> Result<Ast, ParseErr> {
+        let is_mut = self.currtkn.ty == TknTy::Mut;
+        if is_mut {
+            self.consume();
+        }
+
         self.expect(TknTy::Fn)?;
         let fn_ident_tkn = self.currtkn.clone();
         self.consume();
@@ -508,6 +578,7 @@ impl<'l, 's> Parser<'l, 's> {
             ret_ty: fn_ty_rec,
             fn_body: Box::new(fn_body),
             sc: self.symtab.finalized_level,
+            is_mut: is_mut,
         })
     }
 
@@ -527,9 +598,23 @@ impl<'l, 's> Parser<'l, 's> {
 
         let mut prop_ctr = 0;
         loop {
+            self.stmt_errs = self.errors.len();
             match self.currtkn.ty {
                 TknTy::Let => {
-                    let prop_ast = self.var_decl(pctx)?;
+                    pctx.clsctx.parsing_prop = true;
+                    let prop_ast = self.var_decl(pctx);
+                    pctx.clsctx.parsing_prop = false;
+
+                    let prop_ast = match prop_ast {
+                        Ok(ast) => ast,
+                        Err(e) => {
+                            self.recover(e);
+                            continue;
+                        }
+                    };
+
+                    // The whole declaration was parsed, so we can report these
+                    // errors and carry on with the next property.
                     match prop_ast.clone() {
                         Ast::VarDeclExpr {
                             meta: _,
@@ -540,29 +625,46 @@ impl<'l, 's> Parser<'l, 's> {
                             prop_map.insert(ident_tkn.get_name(), prop_ctr);
                             pctx.clsctx.prop_map.insert(ident_tkn.get_name(), prop_ctr);
                         }
-                        Ast::VarAssignExpr { .. } => {
-                            return Err(self.error(ParseErrTy::ClassPropAssign));
+                        Ast::VarAssignExpr { ident_tkn, .. } => {
+                            self.error_w_pos(
+                                ident_tkn.line,
+                                ident_tkn.pos,
+                                ParseErrTy::ClassPropAssign,
+                            );
+                            continue;
                         }
                         _ => {
-                            return Err(self.error(ParseErrTy::InvalidClassProp));
+                            self.error(ParseErrTy::InvalidClassProp);
+                            continue;
                         }
                     }
                     props.push(prop_ast);
                     prop_ctr = prop_ctr + 1;
                 }
-                TknTy::Fn => {
-                    let result = self.fn_decl(pctx)?;
-                    pctx.clsctx.methods.push(result.clone());
-                    methods.push(result);
-                }
+                TknTy::Fn | TknTy::Mut => match self.fn_decl(pctx) {
+                    Ok(result) => {
+                        pctx.clsctx.methods.push(result.clone());
+                        methods.push(result);
+                    }
+                    Err(e) => {
+                        self.recover(e);
+                    }
+                },
                 TknTy::RightBrace => {
                     self.consume();
                     break;
                 }
+                TknTy::Eof => {
+                    // Reports the missing '}'. There's nothing left to parse, so we
+                    // finish the class with what we have.
+                    self.expect(TknTy::RightBrace).ok();
+                    break;
+                }
                 _ => {
                     let ty_str = self.currtkn.ty.to_string();
-                    self.error(ParseErrTy::InvalidTkn(ty_str));
-                    break;
+                    let err = self.error(ParseErrTy::InvalidTkn(ty_str));
+                    self.consume();
+                    self.recover(err);
                 }
             }
         }
@@ -613,6 +715,8 @@ impl<'l, 's> Parser<'l, 's> {
     /// Parses a block statement, beginning with a '{' token. This creates a new scope,
     /// parses any statements within the block, and closes the block scope at the end.
     fn block_stmt(&mut self, pctx: &mut ParseContext) -> Result<Ast, ParseErr> {
+        let ast_line = self.currtkn.line;
+        let ast_pos = self.currtkn.pos;
         self.expect(TknTy::LeftBrace)?;
         let mut stmts = Vec::new();
         self.symtab.init_sc();
@@ -620,18 +724,21 @@ impl<'l, 's> Parser<'l, 's> {
         loop {
             match self.currtkn.ty {
                 TknTy::RightBrace | TknTy::Eof => break,
-                _ => {
-                    let result = self.decl(pctx)?;
-                    stmts.push(result);
-                }
+                _ => match self.decl(pctx) {
+                    Ok(result) => stmts.push(result),
+                    Err(e) => stmts.push(self.recover(e)),
+                },
             };
         }
 
-        self.expect(TknTy::RightBrace)?;
+        // Close the scope even if the closing brace is missing, so the symbol
+        // table stays balanced for the rest of the file.
+        let close = self.expect(TknTy::RightBrace);
         let sc_lvl = self.symtab.finalize_sc();
+        close?;
 
         Ok(Ast::BlckStmt {
-            meta: MetaAst::new(self.next(), self.currtkn.line, self.currtkn.pos),
+            meta: MetaAst::new(self.next(), ast_line, ast_pos),
             stmts: stmts,
             sc: sc_lvl,
         })
@@ -653,11 +760,13 @@ impl<'l, 's> Parser<'l, 's> {
         loop {
             match self.currtkn.ty {
                 TknTy::Elif => {
+                    let elif_line = self.currtkn.line;
+                    let elif_pos = self.currtkn.pos;
                     self.consume();
                     let elif_ast = self.expr(pctx)?;
                     let elif_blck = self.block_stmt(pctx)?;
                     let stmt_ast = Ast::ElifStmt {
-                        meta: MetaAst::new(self.next(), self.currtkn.line, self.currtkn.pos),
+                        meta: MetaAst::new(self.next(), elif_line, elif_pos),
                         cond_expr: Box::new(elif_ast),
                         stmts: Box::new(elif_blck),
                     };
@@ -811,15 +920,11 @@ impl<'l, 's> Parser<'l, 's> {
                                 }
 
                                 let sym = maybe_sym.unwrap();
-                                if sym.imm {
-                                    return Err(self.error(ParseErrTy::InvalidImmAssign(name)));
-                                }
-
                                 return Ok(Ast::VarAssignExpr {
                                     meta: MetaAst::new(
                                         self.next(),
-                                        sym.ident_tkn.line,
-                                        sym.ident_tkn.pos,
+                                        ty_rec.tkn.line,
+                                        ty_rec.tkn.pos,
                                     ),
                                     ty_rec: sym.ty_rec.clone(),
                                     ident_tkn: sym.ident_tkn.clone(),
@@ -1365,25 +1470,6 @@ impl<'l, 's> Parser<'l, 's> {
                 let sym = mb_sym.unwrap();
                 let expr_in_cls = pctx.clsctx.exists(ident_name);
 
-                // If we have no assign value, but we are looking at a param
-                // or function decl sym, we can return the sym. But no assign value on
-                // any other type requires a check that we are assigning to it, otherwise
-                // we are trying to access an undefined variable.
-                // Fn is here to support recursive calls.
-                let unassigned_err =
-                    (sym.sym_ty != SymTy::Param && sym.sym_ty != SymTy::Fn) && !expr_in_cls;
-
-                if sym.assign_val.is_none() && unassigned_err {
-                    let next_tkn = self.lexer.peek_tkn();
-                    // If the following token is '=', we don't need to report an error
-                    // for unitialized var (we are initializing it here).
-                    if next_tkn.ty != TknTy::Eq {
-                        let err = self.error(ParseErrTy::UnassignedVar(ident_name.to_string()));
-                        self.consume();
-                        return Err(err);
-                    }
-                }
-
                 let mut ty_rec = sym.ty_rec.clone();
                 ty_rec.tkn = self.currtkn.clone();
                 let ast = Ok(Ast::PrimaryExpr {
@@ -1415,7 +1501,12 @@ impl<'l, 's> Parser<'l, 's> {
             _ => {
                 let ty_str = self.currtkn.ty.to_string();
                 let err = self.error(ParseErrTy::InvalidTkn(ty_str));
-                self.consume();
+
+                // Leave statement boundaries for error recovery to find, otherwise
+                // it would skip the whole next statement.
+                if !self.at_sync_tkn() {
+                    self.consume();
+                }
                 Err(err)
             }
         }
@@ -1445,7 +1536,52 @@ impl<'l, 's> Parser<'l, 's> {
         } else {
             let ty_str = self.currtkn.ty.to_string();
             let err_ty = ParseErrTy::TknMismatch(tknty.to_string(), ty_str);
-            Err(ParseErr::new(self.currtkn.line, self.currtkn.pos, err_ty))
+            Err(self.error(err_ty))
+        }
+    }
+
+    /// Recovers from an error in a declaration or statement, by skipping tokens until
+    /// we reach a point where it's safe to start parsing again. The error should
+    /// already have been stored. Returns an Error node to take the place of the
+    /// statement that failed to parse.
+    fn recover(&mut self, err: ParseErr) -> Ast {
+        let at = (self.currtkn.line, self.currtkn.pos);
+        if self.recovered_at == Some(at) && self.currtkn.ty != TknTy::Eof {
+            self.consume();
+        }
+
+        self.synchronize();
+        self.recovered_at = Some((self.currtkn.line, self.currtkn.pos));
+
+        Ast::Error {
+            meta: MetaAst::new(self.next(), err.line, err.pos),
+        }
+    }
+
+    /// Skips tokens until the end of the current statement (which is consumed), or the
+    /// start of the next declaration. We also stop at '}' without consuming it, so
+    /// the enclosing block can still be closed.
+    fn synchronize(&mut self) {
+        while !self.at_sync_tkn() {
+            self.consume();
+        }
+
+        if self.currtkn.ty == TknTy::Semicolon {
+            self.consume();
+        }
+    }
+
+    /// True if the current token is a point that error recovery can stop at.
+    fn at_sync_tkn(&self) -> bool {
+        match self.currtkn.ty {
+            TknTy::Semicolon
+            | TknTy::RightBrace
+            | TknTy::Let
+            | TknTy::Fn
+            | TknTy::Mut
+            | TknTy::Class
+            | TknTy::Eof => true,
+            _ => false,
         }
     }
 
@@ -1456,15 +1592,15 @@ impl<'l, 's> Parser<'l, 's> {
 
     /// Report a parsing error from the current token, with the given parser error type.
     fn error(&mut self, ty: ParseErrTy) -> ParseErr {
-        let err = ParseErr::new(self.currtkn.line, self.currtkn.pos, ty);
-        self.errors.push(err.clone());
-        err
+        self.error_w_pos(self.currtkn.line, self.currtkn.pos, ty)
     }
 
     /// Report a parsing error at a given location with a provided error type.
     fn error_w_pos(&mut self, line: usize, pos: usize, ty: ParseErrTy) -> ParseErr {
         let err = ParseErr::new(line, pos, ty);
-        self.errors.push(err.clone());
+        if !self.errors[self.stmt_errs..].iter().any(|e| e.ty == err.ty) {
+            self.errors.push(err.clone());
+        }
         err
     }
 
@@ -0,0 +1,230 @@
+use crate::{
+    ast::{Ast, MetaAst},
+    token::Token,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// True if an assignment is also the declaration of its variable. A
+/// declaration points at the variable it declares, anything else assigns to an
+/// existing variable.
+pub fn is_decl(meta: &MetaAst, ident_tkn: &Token) -> bool {
+    (meta.line, meta.pos) == (ident_tkn.line, ident_tkn.pos)
+}
+
+/// The statements of a block, or a single statement used as a block.
+pub fn block_stmts(node: &Ast) -> &[Ast] {
+    match node {
+        Ast::BlckStmt { meta: _, stmts, .. } => stmts,
+        _ => std::slice::from_ref(node),
+    }
+}
+
+/// The variables declared in a function, for the code generators. Each backend
+/// keeps variables in something different, like a C variable, a wasm local or
+/// a bytecode slot, but which declaration a name refers to is the same.
+///
+/// Variables follow the scopes of the source: each declaration is a new
+/// variable, so a name can be declared again in the same block, and the
+/// innermost declaration of a name hides the others.
+#[derive(Debug)]
+pub struct Scopes<T> {
+    scopes: Vec<HashMap<String, T>>,
+
+    /// Number of times each name has been declared in the function, so each
+    /// declaration can be given a name of its own.
+    decls: HashMap<String, usize>,
+}
+
+impl<T> Default for Scopes<T> {
+    fn default() -> Scopes<T> {
+        Scopes::new()
+    }
+}
+
+impl<T> Scopes<T> {
+    pub fn new() -> Scopes<T> {
+        Scopes {
+            scopes: vec![HashMap::new()],
+            decls: HashMap::new(),
+        }
+    }
+
+    pub fn init_sc(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn close_sc(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Declares a variable in the innermost scope. The backend is given the
+    /// number of earlier declarations of the name in the function, and returns
+    /// what it keeps the new variable in.
+    pub fn declare(&mut self, name: &str, var: impl FnOnce(usize) -> T) -> &T {
+        let cnt = self.decls.entry(String::from(name)).or_insert(0);
+        let var = var(*cnt);
+        *cnt += 1;
+
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(String::from(name), var);
+        &scope[name]
+    }
+
+    /// The innermost variable declared with a name. If there isn't one, the
+    /// name refers to a global.
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
+
+/// Every global, in the order they're first declared. The top level code has
+/// no scopes, so a global declared in a block is the same global as one
+/// declared outside it.
+#[derive(Debug)]
+pub struct Globals<T> {
+    globals: Vec<(String, T)>,
+}
+
+impl<T> Default for Globals<T> {
+    fn default() -> Globals<T> {
+        Globals::new()
+    }
+}
+
+impl<T> Globals<T> {
+    pub fn new() -> Globals<T> {
+        Globals {
+            globals: Vec::new(),
+        }
+    }
+
+    /// Declares a global, unless it's been declared already, and returns its
+    /// position.
+    pub fn declare(&mut self, name: &str, val: T) -> usize {
+        match self.globals.iter().position(|(global, _)| global == name) {
+            Some(idx) => idx,
+            None => {
+                self.globals.push((String::from(name), val));
+                self.globals.len() - 1
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globals.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, T)> {
+        self.globals.iter()
+    }
+}
+
+/// The class whose methods are being generated.
+#[derive(Debug)]
+pub struct ClassScope {
+    pub name: String,
+    pub prop_pos: HashMap<String, usize>,
+
+    /// Position of each property, by its declaration token.
+    prop_decls: HashMap<(usize, usize), usize>,
+
+    methods: HashSet<String>,
+}
+
+impl ClassScope {
+    pub fn new(
+        ident_tkn: &Token,
+        props: &[Ast],
+        methods: &[Ast],
+        prop_pos: &HashMap<String, usize>,
+    ) -> ClassScope {
+        let prop_decls = props
+            .iter()
+            .filter_map(|prop| match prop {
+                Ast::VarDeclExpr { ident_tkn, .. } => prop_pos
+                    .get(&ident_tkn.get_name())
+                    .map(|idx| ((ident_tkn.line, ident_tkn.pos), *idx)),
+                _ => None,
+            })
+            .collect();
+
+        let methods = methods
+            .iter()
+            .filter_map(|method| match method {
+                Ast::FnDeclStmt { ident_tkn, .. } => Some(ident_tkn.get_name()),
+                _ => None,
+            })
+            .collect();
+
+        ClassScope {
+            name: ident_tkn.get_name(),
+            prop_pos: prop_pos.clone(),
+            prop_decls,
+            methods,
+        }
+    }
+
+    /// The position of the property of self that an assignment inside a method
+    /// assigns to, if it does. Properties of self are assigned by the name
+    /// they're declared with, so the assignment points at the declaration.
+    pub fn self_prop(&self, ident_tkn: &Token) -> Option<usize> {
+        self.prop_decls
+            .get(&(ident_tkn.line, ident_tkn.pos))
+            .cloned()
+    }
+
+    /// The name of the property at a position.
+    pub fn prop_name(&self, idx: usize) -> &str {
+        let (name, _) = self.prop_pos.iter().find(|(_, pos)| **pos == idx).unwrap();
+        name
+    }
+
+    /// True if a call inside a method calls another method of the class. These
+    /// are called without naming self, but it's still passed to them.
+    pub fn calls_method(&self, name: &str) -> bool {
+        self.methods.contains(name)
+    }
+}
+
+/// The parts of a while or for loop.
+pub struct Loop<'a> {
+    /// The loop variable of a for loop. It's declared in the enclosing scope,
+    /// so it's still around after the loop.
+    pub var_decl: Option<&'a Ast>,
+
+    pub cond_expr: &'a Ast,
+    pub step_expr: Option<&'a Ast>,
+    pub stmts: &'a Ast,
+}
+
+impl<'a> Loop<'a> {
+    /// Splits up a while or for statement. Panics on anything else.
+    pub fn of(node: &'a Ast) -> Loop<'a> {
+        match node {
+            Ast::WhileStmt {
+                meta: _,
+                cond_expr,
+                stmts,
+            } => Loop {
+                var_decl: None,
+                cond_expr,
+                step_expr: None,
+                stmts,
+            },
+            Ast::ForStmt {
+                meta: _,
+                for_var_decl,
+                for_cond_expr,
+                for_step_expr,
+                stmts,
+            } => Loop {
+                var_decl: Some(for_var_decl),
+                cond_expr: for_cond_expr,
+                step_expr: Some(for_step_expr),
+                stmts,
+            },
+            _ => panic!("invalid ast provided as a loop"),
+        }
+    }
+}
@@ -116,6 +116,16 @@ impl SymbolTable {
         None
     }
 
+    /// Get every symbol from every finalized scope. Because finalized scopes contain
+    /// the symbols from all their parent scopes, the same symbol will usually be
+    /// returned more than once.
+    pub fn finalized_syms(&self) -> Vec<Rc<Sym>> {
+        self.finalized
+            .iter()
+            .flat_map(|sc| sc.values().map(Rc::clone))
+            .collect()
+    }
+
     /// Get a symbol from a finalized scope level. We don't need to search
     /// up any scope stack, since all viable scope levels are put into one
     /// level of the finalized map.
@@ -40,6 +40,7 @@ pub enum TknTy {
     // Keywords
     Let,
     Imm,
+    Mut,
     Fn,
     Return,
     Class,
@@ -147,6 +148,7 @@ impl fmt::Debug for TknTy {
             TknTy::Val(val) => format!("Val-> {}", val),
             TknTy::Let => "let".to_string(),
             TknTy::Imm => "imm".to_string(),
+            TknTy::Mut => "mut".to_string(),
             TknTy::Fn => "fn".to_string(),
             TknTy::Return => "return".to_string(),
             TknTy::Class => "class".to_string(),
@@ -210,6 +212,7 @@ impl fmt::Display for TknTy {
             TknTy::Val(val) => format!("{}", val),
             TknTy::Let => "let".to_string(),
             TknTy::Imm => "imm".to_string(),
+            TknTy::Mut => "mut".to_string(),
             TknTy::Fn => "fn".to_string(),
             TknTy::Return => "return".to_string(),
             TknTy::Class => "class".to_string(),
@@ -9,6 +9,11 @@ pub enum KolgaTy {
     Void,
     Symbolic(String),
     Class(String),
+
+    /// The type of a variable whose initializer failed to parse. The variable is
+    /// still declared, so its uses aren't reported as undeclared, and the type
+    /// matches any other type, so they aren't reported as mismatched either.
+    Error,
 }
 
 impl KolgaTy {
@@ -25,6 +30,14 @@ impl KolgaTy {
             _ => false,
         }
     }
+
+    pub fn is_symbolic(&self) -> bool {
+        matches!(self, KolgaTy::Symbolic(_))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, KolgaTy::Error)
+    }
 }
 
 impl fmt::Display for KolgaTy {
@@ -36,6 +49,7 @@ impl fmt::Display for KolgaTy {
             KolgaTy::Void => "void".to_string(),
             KolgaTy::Class(name) => format!("class '{}'", name),
             KolgaTy::Symbolic(name) => format!("symbolic '{}'", name),
+            KolgaTy::Error => "error".to_string(),
         };
 
         write!(f, "{}", display_ty)
@@ -0,0 +1,81 @@
+use kolgac::{
+    ast::Ast,
+    const_eval::{ConstEval, ConstVal},
+    lexer::Lexer,
+    parser::Parser,
+    symtab::SymbolTable,
+};
+
+use std::fs::File;
+
+#[test]
+fn const_eval_globals() {
+    let file = File::open("./tests/const_eval/globals.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let stmts = match ast {
+        Ast::Prog { stmts, .. } => stmts,
+        _ => panic!("expected a program"),
+    };
+
+    let mut consts = ConstEval::new();
+    let mut found = Vec::new();
+    for stmt in &stmts {
+        if let Ast::VarAssignExpr {
+            ident_tkn,
+            is_imm,
+            value,
+            ..
+        } = stmt
+        {
+            found.push((ident_tkn.get_name(), consts.eval(value)));
+            consts.bind(&ident_tkn.get_name(), *is_imm, value);
+        }
+    }
+
+    // 'n' is mutable, so nothing that reads it is constant, except when a logical
+    // operator short circuits before reaching it.
+    let expected = vec![
+        (String::from("MIN"), Some(ConstVal::Num(60.0))),
+        (String::from("K"), Some(ConstVal::Num(120.0))),
+        (String::from("NEG"), Some(ConstVal::Num(-30.0))),
+        (String::from("BIG"), Some(ConstVal::Bool(true))),
+        (String::from("ON"), Some(ConstVal::Bool(true))),
+        (String::from("n"), Some(ConstVal::Num(5.0))),
+        (String::from("EITHER"), Some(ConstVal::Bool(true))),
+        (String::from("N2"), None),
+        (String::from("SAME"), Some(ConstVal::Bool(true))),
+    ];
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn const_eval_shadowing() {
+    let file = File::open("./tests/const_eval/globals.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut consts = ConstEval::new();
+    let (min, k) = match ast {
+        Ast::Prog { ref stmts, .. } => (stmts[0].clone(), stmts[1].clone()),
+        _ => panic!("expected a program"),
+    };
+
+    let (min_val, k_val) = match (min, k) {
+        (Ast::VarAssignExpr { value: m, .. }, Ast::VarAssignExpr { value: k, .. }) => (m, k),
+        _ => panic!("expected variable assignments"),
+    };
+
+    consts.bind("MIN", true, &min_val);
+    assert_eq!(consts.eval(&k_val), Some(ConstVal::Num(120.0)));
+
+    // A parameter with the same name hides the global until its scope closes.
+    consts.init_sc();
+    consts.shadow("MIN");
+    assert_eq!(consts.eval(&k_val), None);
+    consts.close_sc();
+    assert_eq!(consts.eval(&k_val), Some(ConstVal::Num(120.0)));
+}
@@ -1,4 +1,4 @@
-use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac::{ast::Ast, lexer::Lexer, parser::Parser, symtab::SymbolTable};
 
 use std::{
     fs,
@@ -123,3 +123,41 @@ fn parse_expectations(expectations: String) -> Result<ParseExpect, &'static str>
         pos: pos.unwrap(),
     })
 }
+
+#[test]
+fn parser_recovery() {
+    let mut symtab = SymbolTable::new();
+    let file = File::open("./tests/parser/recovery.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut parser = Parser::new(&mut lexer, &mut symtab);
+
+    let parse_result = parser.parse();
+    assert!(parse_result.has_err);
+
+    let stmts = match parse_result.ast.unwrap() {
+        Ast::Prog { meta: _, stmts } => stmts,
+        _ => panic!("expected a program"),
+    };
+
+    // The declaration of 'x' fails and is replaced with an error node. 'y' is only
+    // missing its ';', so it's kept, and so is everything after it.
+    assert_eq!(stmts.len(), 4);
+    assert!(matches!(stmts[0], Ast::Error { .. }));
+    assert!(matches!(stmts[1], Ast::VarAssignExpr { .. }));
+    assert!(matches!(stmts[3], Ast::VarAssignExpr { .. }));
+
+    // The bad statement in the function body is replaced, but the return after it
+    // is still parsed.
+    let body = match &stmts[2] {
+        Ast::FnDeclStmt { fn_body, .. } => fn_body,
+        _ => panic!("expected a function declaration"),
+    };
+    match &**body {
+        Ast::BlckStmt { stmts, .. } => {
+            assert_eq!(stmts.len(), 2);
+            assert!(matches!(stmts[0], Ast::Error { .. }));
+            assert!(matches!(stmts[1], Ast::RetStmt { .. }));
+        }
+        _ => panic!("expected a block"),
+    }
+}
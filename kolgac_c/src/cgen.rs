@@ -0,0 +1,888 @@
+use kolgac::{
+    ast::Ast,
+    scope::{self, ClassScope, Globals, Loop, Scopes},
+    token::{TknTy, Token},
+    ty_rec::{KolgaTy, TyRecord},
+};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Generates C99 source for a type checked program. The top level code becomes
+/// `main`, and needs nothing but the C standard library to build.
+///
+/// Numbers are doubles, bools are bools from stdbool.h, and strings are
+/// pointers to constant, NUL terminated data. Each class becomes a struct, and
+/// instances are pointers to one, allocated when they're constructed. Methods
+/// become functions that take the instance as their first argument, `self`.
+/// Like the other backends, nothing is ever freed.
+///
+/// Every variable declared in the top level code is a global, even inside a
+/// block, like in KIR. Each variable declared in a function gets its own C
+/// variable.
+///
+/// Operands are evaluated left to right, like in the other backends, and &&
+/// and || short circuit. C leaves the order of most operands up to the
+/// compiler, so when the order matters, the operands before the one that
+/// matters are evaluated into temporaries first.
+pub fn gen(ast: &Ast) -> String {
+    let mut cgen = CGen {
+        classes: HashMap::new(),
+        class_order: Vec::new(),
+        structs: String::new(),
+        globals: Globals::new(),
+        protos: String::new(),
+        fns: String::new(),
+        curr: FnCtx::new(true),
+        class: None,
+        uses_new: false,
+    };
+
+    match ast {
+        Ast::Prog { meta: _, stmts } => {
+            for stmt in stmts {
+                cgen.stmt(stmt);
+            }
+        }
+        _ => panic!("invalid ast provided to c gen"),
+    }
+
+    cgen.finish()
+}
+
+/// Name of the C variable for a global.
+pub fn global_sym(name: &str) -> String {
+    format!("kol_{}", name)
+}
+
+/// Name of the C function for a function.
+pub fn fn_sym(name: &str) -> String {
+    format!("kol_fn_{}", name)
+}
+
+/// Name of the C function for a method. Kolga identifiers can't contain '_', so
+/// these can't clash with functions or globals.
+pub fn method_sym(class: &str, name: &str) -> String {
+    format!("kol_{}_{}", class, name)
+}
+
+fn struct_sym(class: &str) -> String {
+    format!("struct kol_{}", class)
+}
+
+fn prop_sym(name: &str) -> String {
+    format!("p_{}", name)
+}
+
+/// The function we're generating code for. Function declarations are moved out
+/// of the code around them, so this is saved and restored around each one.
+struct FnCtx {
+    out: String,
+    indent: usize,
+
+    /// C name of each variable declared in the function.
+    scopes: Scopes<String>,
+
+    /// Number of temporaries declared in the function.
+    temps: usize,
+
+    /// True for the top level code, which goes in main.
+    is_init: bool,
+}
+
+impl FnCtx {
+    fn new(is_init: bool) -> FnCtx {
+        FnCtx {
+            out: String::new(),
+            indent: is_init as usize,
+            scopes: Scopes::new(),
+            temps: 0,
+            is_init,
+        }
+    }
+}
+
+struct CGen {
+    /// Property positions for each class declared so far.
+    classes: HashMap<String, HashMap<String, usize>>,
+    class_order: Vec<String>,
+
+    structs: String,
+
+    /// Every global, with its type.
+    globals: Globals<KolgaTy>,
+
+    protos: String,
+    fns: String,
+
+    curr: FnCtx,
+
+    /// The class we're generating methods for.
+    class: Option<ClassScope>,
+
+    /// True once we need the runtime function that constructs class instances.
+    uses_new: bool,
+}
+
+impl CGen {
+    fn stmt(&mut self, node: &Ast) {
+        match node {
+            Ast::BlckStmt { .. } => {
+                self.line("{");
+                self.block_body(node);
+                self.line("}");
+            }
+
+            Ast::VarDeclExpr {
+                ty_rec, ident_tkn, ..
+            } => {
+                let name = ident_tkn.get_name();
+                if self.curr.is_init {
+                    self.global(&name, &ty_rec.ty);
+                } else {
+                    let var = self.declare(&name);
+                    self.line(&format!("{};", decl(&ty_rec.ty, &var)));
+                }
+            }
+
+            Ast::IfStmt {
+                meta: _,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => {
+                let cond = self.expr(cond_expr);
+                self.line(&format!("if ({}) {{", cond));
+                self.block_body(if_stmts);
+
+                // An elif condition with temporaries goes in an else block, so
+                // they're only set if the conditions before it were false.
+                let mut nested = 0;
+                for elif in elif_exprs {
+                    if let Ast::ElifStmt {
+                        meta: _,
+                        cond_expr,
+                        stmts,
+                    } = elif
+                    {
+                        let (cond, temps) = self.expr_w_temps(cond_expr);
+                        match temps.is_empty() {
+                            true => self.line(&format!("}} else if ({}) {{", cond)),
+                            false => {
+                                self.line("} else {");
+                                self.curr.out.push_str(&temps);
+                                self.curr.indent += 1;
+                                nested += 1;
+                                self.line(&format!("if ({}) {{", cond));
+                            }
+                        }
+                        self.block_body(stmts);
+                    }
+                }
+
+                if !el_stmts.is_empty() {
+                    self.line("} else {");
+                    for stmt in el_stmts {
+                        self.block_body(stmt);
+                    }
+                }
+                self.line("}");
+
+                for _ in 0..nested {
+                    self.curr.indent -= 1;
+                    self.line("}");
+                }
+            }
+
+            Ast::WhileStmt { .. } | Ast::ForStmt { .. } => self.loop_stmt(&Loop::of(node)),
+
+            Ast::RetStmt { meta: _, ret_expr } => match ret_expr {
+                _ if self.curr.is_init => self.line("return 0;"),
+                Some(expr) => {
+                    let val = self.expr(expr);
+                    self.line(&format!("return {};", val));
+                }
+                None => self.line("return;"),
+            },
+
+            Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                ret_ty,
+                fn_body,
+                ..
+            } => {
+                let sym = fn_sym(&ident_tkn.get_name());
+                self.fn_decl(&sym, fn_params, ret_ty, fn_body, None);
+            }
+
+            Ast::ClassDeclStmt {
+                ident_tkn,
+                methods,
+                props,
+                prop_pos,
+                ..
+            } => self.class_decl(ident_tkn, methods, props, prop_pos),
+
+            Ast::Error { .. } => (),
+
+            _ => self.expr_stmt(node),
+        }
+    }
+
+    /// Emits an expression as a statement. Declarations with a value become C
+    /// declarations, unless they declare a global.
+    fn expr_stmt(&mut self, node: &Ast) {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr_stmt(expr),
+
+            Ast::VarAssignExpr {
+                meta,
+                ty_rec,
+                ident_tkn,
+                value,
+                ..
+            } if !self.curr.is_init
+                && scope::is_decl(meta, ident_tkn)
+                && self.self_prop(ident_tkn).is_none() =>
+            {
+                let val = self.expr(value);
+                let var = self.declare(&ident_tkn.get_name());
+                self.line(&format!("{} = {};", decl(&ty_rec.ty, &var), val));
+            }
+
+            _ => {
+                let expr = self.expr(node);
+                self.line(&format!("{};", expr));
+            }
+        }
+    }
+
+    /// Generates an expression. Operands that aren't a single term are put in
+    /// parentheses, but the expression as a whole isn't.
+    fn expr(&mut self, node: &Ast) -> String {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr(expr),
+
+            Ast::VarAssignExpr {
+                meta,
+                ty_rec,
+                ident_tkn,
+                value,
+                ..
+            } => {
+                // C doesn't order two assignments to a variable in the same
+                // expression.
+                let mut val = self.expr(value);
+                if assigns(value) {
+                    val = self.temp(&value.expr_ty(), &val);
+                }
+                let name = ident_tkn.get_name();
+
+                if let Some(idx) = self.self_prop(ident_tkn) {
+                    let prop = self.class.as_ref().unwrap().prop_name(idx);
+                    return format!("self->{} = {}", prop_sym(prop), val);
+                }
+
+                if self.curr.is_init && scope::is_decl(meta, ident_tkn) {
+                    self.global(&name, &ty_rec.ty);
+                }
+                format!("{} = {}", self.var(&name), val)
+            }
+
+            Ast::BinaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            } => self.bin_op(op_tkn, lhs, rhs),
+
+            Ast::LogicalExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            } => self.logical_op(op_tkn, lhs, rhs),
+
+            Ast::UnaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                rhs,
+            } => {
+                let op = match op_tkn.ty {
+                    TknTy::Minus => "-",
+                    TknTy::Bang => "!",
+                    _ => panic!("invalid unary operator token"),
+                };
+                format!("{}{}", op, self.operand(rhs))
+            }
+
+            Ast::PrimaryExpr {
+                meta: _,
+                ty_rec,
+                is_self,
+            } => self.primary(ty_rec, *is_self),
+
+            Ast::FnCallExpr {
+                meta: _,
+                ty_rec: _,
+                fn_tkn,
+                fn_params,
+            } => {
+                let name = fn_tkn.get_name();
+
+                let method = self
+                    .class
+                    .as_ref()
+                    .filter(|cls| cls.calls_method(&name))
+                    .map(|cls| method_sym(&cls.name, &name));
+
+                match method {
+                    Some(method) => self.call(&method, Some(String::from("self")), fn_params),
+                    None => self.call(&fn_sym(&name), None, fn_params),
+                }
+            }
+
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                fn_params,
+                ..
+            } => {
+                // The instance is read before the arguments are evaluated.
+                let mut obj = self.var(&class_tkn.get_name());
+                if fn_params.iter().any(has_effects) {
+                    obj = self.temp(&KolgaTy::Class(class_name.clone()), &obj);
+                }
+                let method = method_sym(class_name, &fn_tkn.get_name());
+                self.call(&method, Some(obj), fn_params)
+            }
+
+            Ast::ClassConstrExpr {
+                meta: _,
+                ty_rec: _,
+                class_name,
+                props,
+            } => {
+                // The properties are set in a compound literal, which zeroes the
+                // ones that aren't given, and copied into the new instance.
+                self.uses_new = true;
+                let prop_pos = self.classes.get(class_name).cloned().unwrap_or_default();
+                let mut vals: Vec<(usize, &String, &Ast)> = props
+                    .iter()
+                    .filter_map(|(name, val)| prop_pos.get(name).map(|idx| (*idx, name, val)))
+                    .collect();
+                vals.sort_by_key(|(idx, _, _)| *idx);
+
+                let mut inits = Vec::new();
+                for (i, (_, name, val)) in vals.iter().enumerate() {
+                    let later: Vec<&Ast> = vals[i + 1..].iter().map(|(_, _, val)| *val).collect();
+                    let val = self.ordered(val, &later);
+                    inits.push(format!(".{} = {}", prop_sym(name), val));
+                }
+                if inits.is_empty() {
+                    inits.push(String::from("0"));
+                }
+
+                let sym = struct_sym(class_name);
+                format!(
+                    "kol_new(&({}){{ {} }}, sizeof({}))",
+                    sym,
+                    inits.join(", "),
+                    sym
+                )
+            }
+
+            Ast::ClassPropAccessExpr {
+                ident_tkn,
+                prop_name,
+                ..
+            } => format!(
+                "{}->{}",
+                self.var(&ident_tkn.get_name()),
+                prop_sym(prop_name)
+            ),
+
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                prop_name,
+                assign_val,
+                ..
+            } => {
+                // The value is evaluated before the instance is read.
+                let mut val = self.expr(assign_val);
+                if has_effects(assign_val) {
+                    val = self.temp(&assign_val.expr_ty(), &val);
+                }
+                let obj = self.var(&ident_tkn.get_name());
+                format!("{}->{} = {}", obj, prop_sym(prop_name), val)
+            }
+
+            _ => panic!("invalid expression provided to c gen"),
+        }
+    }
+
+    /// Generates an expression used as an operand, in parentheses if it needs
+    /// them.
+    fn operand(&mut self, node: &Ast) -> String {
+        let expr = self.expr(node);
+        match is_compound(node) {
+            true => format!("({})", expr),
+            false => expr,
+        }
+    }
+
+    fn bin_op(&mut self, op_tkn: &Token, lhs: &Ast, rhs: &Ast) -> String {
+        let lhs = match needs_temp(lhs, &[rhs]) {
+            true => self.ordered(lhs, &[rhs]),
+            false => self.operand(lhs),
+        };
+        let rhs = self.operand(rhs);
+        format!("{} {} {}", lhs, c_op(&op_tkn.ty), rhs)
+    }
+
+    /// Generates && or ||. C evaluates the left side first, and only evaluates
+    /// the right side if it's needed, but if the right side has temporaries,
+    /// they have to be set inside an if.
+    fn logical_op(&mut self, op_tkn: &Token, lhs: &Ast, rhs: &Ast) -> String {
+        let op = c_op(&op_tkn.ty);
+        let lhs = self.operand(lhs);
+        let (rhs_val, temps) = self.expr_w_temps(rhs);
+        if temps.is_empty() {
+            let rhs_val = match is_compound(rhs) {
+                true => format!("({})", rhs_val),
+                false => rhs_val,
+            };
+            return format!("{} {} {}", lhs, op, rhs_val);
+        }
+
+        let res = self.temp(&KolgaTy::Bool, &lhs);
+        match op {
+            "&&" => self.line(&format!("if ({}) {{", res)),
+            _ => self.line(&format!("if (!{}) {{", res)),
+        }
+        self.curr.out.push_str(&temps);
+        self.curr.indent += 1;
+        self.line(&format!("{} = {};", res, rhs_val));
+        self.curr.indent -= 1;
+        self.line("}");
+        res
+    }
+
+    /// Generates an operand that's followed by others, into a temporary if
+    /// it has to be evaluated before them.
+    fn ordered(&mut self, node: &Ast, later: &[&Ast]) -> String {
+        match needs_temp(node, later) {
+            true => {
+                let val = self.expr(node);
+                self.temp(&node.expr_ty(), &val)
+            }
+            false => self.expr(node),
+        }
+    }
+
+    /// Declares a temporary holding a value, and returns its name. The value is
+    /// evaluated before anything generated after it.
+    fn temp(&mut self, ty: &KolgaTy, val: &str) -> String {
+        let tmp = format!("t_{}", self.curr.temps);
+        self.curr.temps += 1;
+        self.line(&format!("{} = {};", decl(ty, &tmp), val));
+        tmp
+    }
+
+    /// Generates an expression whose temporaries can't go before the statement
+    /// it's in, like a loop condition, which is evaluated each time around the
+    /// loop. Returns the expression, and the lines that set its temporaries,
+    /// one level further in, for the caller to put where it's evaluated.
+    fn expr_w_temps(&mut self, node: &Ast) -> (String, String) {
+        let outer = std::mem::take(&mut self.curr.out);
+        self.curr.indent += 1;
+        let expr = self.expr(node);
+        self.curr.indent -= 1;
+        let temps = std::mem::replace(&mut self.curr.out, outer);
+        (expr, temps)
+    }
+
+    fn primary(&mut self, ty_rec: &TyRecord, is_self: bool) -> String {
+        match &ty_rec.tkn.ty {
+            // Debug prints the shortest string that reads back as the same
+            // double, which is also a valid C literal.
+            TknTy::Val(v) => format!("{:?}", v),
+            TknTy::Str(s) => format!("\"{}\"", escape(s)),
+            TknTy::True => String::from("true"),
+            TknTy::False => String::from("false"),
+            TknTy::Ident(i) => {
+                let is_prop = is_self
+                    && self
+                        .class
+                        .as_ref()
+                        .is_some_and(|cls| cls.prop_pos.contains_key(i));
+
+                match is_prop {
+                    true => format!("self->{}", prop_sym(i)),
+                    false => self.var(i),
+                }
+            }
+            _ => panic!("invalid primary tkn ty"),
+        }
+    }
+
+    fn call(&mut self, sym: &str, obj: Option<String>, args: &[Ast]) -> String {
+        let mut params: Vec<String> = obj.into_iter().collect();
+        for (i, arg) in args.iter().enumerate() {
+            let later: Vec<&Ast> = args[i + 1..].iter().collect();
+            let val = self.ordered(arg, &later);
+            params.push(val);
+        }
+
+        format!("{}({})", sym, params.join(", "))
+    }
+
+    /// Generates a function, after the ones we've generated so far, and a
+    /// prototype for it, so it can be called from anywhere. Methods take the
+    /// instance they're called on as `self`.
+    fn fn_decl(
+        &mut self,
+        sym: &str,
+        params: &[TyRecord],
+        ret_ty: &TyRecord,
+        body: &Ast,
+        class: Option<&str>,
+    ) {
+        let outer = std::mem::replace(&mut self.curr, FnCtx::new(false));
+
+        let mut c_params = Vec::new();
+        if let Some(class) = class {
+            self.curr.scopes.declare("self", |_| String::from("self"));
+            c_params.push(format!("{} *self", struct_sym(class)));
+        }
+        for param in params {
+            let var = self.declare(&param.tkn.get_name());
+            c_params.push(decl(&param.ty, &var));
+        }
+        if c_params.is_empty() {
+            c_params.push(String::from("void"));
+        }
+
+        let sig = decl(&ret_ty.ty, &format!("{}({})", sym, c_params.join(", ")));
+        writeln!(self.protos, "{};", sig).unwrap();
+
+        self.line(&format!("{} {{", sig));
+        self.block_body(body);
+        self.line("}");
+
+        let f = std::mem::replace(&mut self.curr, outer);
+        writeln!(self.fns, "{}", f.out).unwrap();
+    }
+
+    /// Generates a struct for a class, and its methods.
+    fn class_decl(
+        &mut self,
+        ident_tkn: &Token,
+        methods: &[Ast],
+        props: &[Ast],
+        prop_pos: &HashMap<String, usize>,
+    ) {
+        let name = ident_tkn.get_name();
+        self.classes.insert(name.clone(), prop_pos.clone());
+        self.class_order.push(name.clone());
+
+        let mut fields = Vec::new();
+        for prop in props {
+            if let Ast::VarDeclExpr {
+                ty_rec, ident_tkn, ..
+            } = prop
+            {
+                if let Some(idx) = prop_pos.get(&ident_tkn.get_name()) {
+                    fields.push((*idx, decl(&ty_rec.ty, &prop_sym(&ident_tkn.get_name()))));
+                }
+            }
+        }
+        fields.sort();
+
+        // C doesn't allow empty structs.
+        if fields.is_empty() {
+            fields.push((0, String::from("char unused")));
+        }
+
+        writeln!(self.structs, "{} {{", struct_sym(&name)).unwrap();
+        for (_, field) in &fields {
+            writeln!(self.structs, "    {};", field).unwrap();
+        }
+        writeln!(self.structs, "}};\n").unwrap();
+
+        let class = ClassScope::new(ident_tkn, props, methods, prop_pos);
+        let prev_class = self.class.replace(class);
+
+        for method in methods {
+            if let Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                ret_ty,
+                fn_body,
+                ..
+            } = method
+            {
+                let sym = method_sym(&name, &ident_tkn.get_name());
+                self.fn_decl(&sym, fn_params, ret_ty, fn_body, Some(&name));
+            }
+        }
+
+        self.class = prev_class;
+    }
+
+    /// Generates a while or for loop. If the condition or step have
+    /// temporaries, they're set inside the loop, each time it evaluates them.
+    fn loop_stmt(&mut self, lp: &Loop) {
+        if let Some(decl) = lp.var_decl {
+            self.expr_stmt(decl);
+        }
+
+        let (cond, cond_temps) = self.expr_w_temps(lp.cond_expr);
+        let (step, step_temps) = match lp.step_expr {
+            Some(step) => {
+                let (step, temps) = self.expr_w_temps(step);
+                (Some(step), temps)
+            }
+            None => (None, String::new()),
+        };
+
+        if cond_temps.is_empty() && step_temps.is_empty() {
+            match step {
+                Some(step) => self.line(&format!("for (; {}; {}) {{", cond, step)),
+                None => self.line(&format!("while ({}) {{", cond)),
+            }
+            self.block_body(lp.stmts);
+            self.line("}");
+            return;
+        }
+
+        self.line("while (true) {");
+        self.curr.out.push_str(&cond_temps);
+        self.curr.indent += 1;
+        self.line(&format!("if (!({})) break;", cond));
+        self.curr.indent -= 1;
+
+        self.block_body(lp.stmts);
+
+        self.curr.out.push_str(&step_temps);
+        if let Some(step) = step {
+            self.curr.indent += 1;
+            self.line(&format!("{};", step));
+            self.curr.indent -= 1;
+        }
+        self.line("}");
+    }
+
+    /// Emits the statements of a block one level further in, in a scope of
+    /// their own. The braces are left to the caller.
+    fn block_body(&mut self, node: &Ast) {
+        self.curr.indent += 1;
+        self.curr.scopes.init_sc();
+        for stmt in scope::block_stmts(node) {
+            self.stmt(stmt);
+        }
+        self.curr.scopes.close_sc();
+        self.curr.indent -= 1;
+    }
+
+    fn self_prop(&self, ident_tkn: &Token) -> Option<usize> {
+        self.class.as_ref().and_then(|cls| cls.self_prop(ident_tkn))
+    }
+
+    /// Declares a local variable in the innermost scope, and returns its C name.
+    /// The first declaration of a name in a function gets the name itself,
+    /// later ones get a number after it.
+    fn declare(&mut self, name: &str) -> String {
+        let var = self.curr.scopes.declare(name, |n| match n {
+            0 => format!("v_{}", name),
+            n => format!("v_{}_{}", name, n),
+        });
+        var.clone()
+    }
+
+    fn global(&mut self, name: &str, ty: &KolgaTy) {
+        self.globals.declare(name, ty.clone());
+    }
+
+    /// The C name of a variable: the innermost local declared with that name,
+    /// or else the global.
+    fn var(&self, name: &str) -> String {
+        self.curr
+            .scopes
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| global_sym(name))
+    }
+
+    fn line(&mut self, line: &str) {
+        let indent = "    ".repeat(self.curr.indent);
+        writeln!(self.curr.out, "{}{}", indent, line).unwrap();
+    }
+
+    fn finish(mut self) -> String {
+        let mut out = String::from("/* Generated by kolgac. */\n\n");
+        for header in &["math.h", "stdbool.h", "stdlib.h", "string.h"] {
+            writeln!(out, "#include <{}>", header).unwrap();
+        }
+        writeln!(out).unwrap();
+
+        // Classes can refer to each other in any order.
+        if !self.class_order.is_empty() {
+            for class in &self.class_order {
+                writeln!(out, "{};", struct_sym(class)).unwrap();
+            }
+            writeln!(out).unwrap();
+            out.push_str(&self.structs);
+        }
+
+        if self.uses_new {
+            out.push_str(NEW_FN);
+        }
+
+        if !self.globals.is_empty() {
+            for (name, ty) in self.globals.iter() {
+                writeln!(out, "{};", decl(ty, &global_sym(name))).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        if !self.protos.is_empty() {
+            writeln!(out, "{}", self.protos).unwrap();
+            out.push_str(&self.fns);
+        }
+
+        let main = std::mem::take(&mut self.curr.out);
+        writeln!(out, "int main(void) {{").unwrap();
+        out.push_str(&main);
+        writeln!(out, "    return 0;").unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+const NEW_FN: &str = "static void *kol_new(const void *init, size_t size) {
+    void *obj = malloc(size);
+    memcpy(obj, init, size);
+    return obj;
+}
+
+";
+
+/// True if evaluating an expression can change a variable or property, so
+/// anything evaluated after it can depend on when it's evaluated.
+fn has_effects(node: &Ast) -> bool {
+    match node {
+        Ast::ExprStmt { meta: _, expr } => has_effects(expr),
+        Ast::BinaryExpr { lhs, rhs, .. } | Ast::LogicalExpr { lhs, rhs, .. } => {
+            has_effects(lhs) || has_effects(rhs)
+        }
+        Ast::UnaryExpr { rhs, .. } => has_effects(rhs),
+        Ast::ClassConstrExpr { props, .. } => props.iter().any(|(_, val)| has_effects(val)),
+        Ast::PrimaryExpr { .. } | Ast::ClassPropAccessExpr { .. } => false,
+        _ => true,
+    }
+}
+
+/// True if an expression assigns to a variable or property itself, rather than
+/// in a function it calls. Calls are ordered with everything around them.
+fn assigns(node: &Ast) -> bool {
+    match node {
+        Ast::ExprStmt { meta: _, expr } => assigns(expr),
+        Ast::VarAssignExpr { .. } | Ast::ClassPropSetExpr { .. } => true,
+        Ast::BinaryExpr { lhs, rhs, .. } | Ast::LogicalExpr { lhs, rhs, .. } => {
+            assigns(lhs) || assigns(rhs)
+        }
+        Ast::UnaryExpr { rhs, .. } => assigns(rhs),
+        _ => false,
+    }
+}
+
+/// True if an operand has to be evaluated into a temporary, to be evaluated
+/// before the operands after it: when they have side effects that could change
+/// it, or it has side effects that could change them. Literals never change.
+fn needs_temp(node: &Ast, later: &[&Ast]) -> bool {
+    let is_lit = |node: &Ast| match node {
+        Ast::PrimaryExpr { ty_rec, .. } => !matches!(ty_rec.tkn.ty, TknTy::Ident(_)),
+        _ => false,
+    };
+
+    !is_lit(node)
+        && (later.iter().any(|n| has_effects(n))
+            || (has_effects(node) && later.iter().any(|n| !is_lit(n))))
+}
+
+/// True for expressions that need parentheses to be used as an operand.
+fn is_compound(node: &Ast) -> bool {
+    match node {
+        Ast::ExprStmt { meta: _, expr } => is_compound(expr),
+        Ast::BinaryExpr { .. }
+        | Ast::LogicalExpr { .. }
+        | Ast::UnaryExpr { .. }
+        | Ast::VarAssignExpr { .. }
+        | Ast::ClassPropSetExpr { .. } => true,
+        _ => false,
+    }
+}
+
+fn c_ty(ty: &KolgaTy) -> String {
+    match ty {
+        KolgaTy::String => String::from("const char *"),
+        KolgaTy::Num => String::from("double"),
+        KolgaTy::Bool => String::from("bool"),
+        KolgaTy::Void => String::from("void"),
+        KolgaTy::Class(name) => format!("{} *", struct_sym(name)),
+        KolgaTy::Symbolic(_) => panic!("Found a type in codegen that wasn't inferred!"),
+        KolgaTy::Error => {
+            panic!("Found a type in codegen from a declaration that failed to parse!")
+        }
+    }
+}
+
+/// Declares a name with a type, like 'double x' or 'const char *s'.
+fn decl(ty: &KolgaTy, name: &str) -> String {
+    let ty = c_ty(ty);
+    match ty.ends_with('*') {
+        true => format!("{}{}", ty, name),
+        false => format!("{} {}", ty, name),
+    }
+}
+
+fn c_op(ty: &TknTy) -> &'static str {
+    match ty {
+        TknTy::Plus => "+",
+        TknTy::Minus => "-",
+        TknTy::Star => "*",
+        TknTy::Slash => "/",
+        TknTy::Lt => "<",
+        TknTy::Gt => ">",
+        TknTy::EqEq => "==",
+        TknTy::LtEq => "<=",
+        TknTy::GtEq => ">=",
+        TknTy::BangEq => "!=",
+        TknTy::AmpAmp | TknTy::And => "&&",
+        TknTy::PipePipe | TknTy::Or => "||",
+        _ => panic!("invalid operator token"),
+    }
+}
+
+/// Escapes a string for a C string literal. '?' is escaped too, so it can't
+/// start a trigraph.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b'?' => escaped.push_str("\\?"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
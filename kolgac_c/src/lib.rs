@@ -0,0 +1,3 @@
+extern crate kolgac;
+
+pub mod cgen;
@@ -0,0 +1,47 @@
+/* Generated by kolgac. */
+
+#include <math.h>
+#include <stdbool.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct kol_counter;
+
+struct kol_counter {
+    double p_count;
+    double p_step;
+};
+
+static void *kol_new(const void *init, size_t size) {
+    void *obj = malloc(size);
+    memcpy(obj, init, size);
+    return obj;
+}
+
+struct kol_counter *kol_c;
+double kol_total;
+double kol_after;
+
+double kol_counter_tick(struct kol_counter *self);
+double kol_counter_run(struct kol_counter *self, double v_n);
+
+double kol_counter_tick(struct kol_counter *self) {
+    self->p_count = self->p_count + self->p_step;
+    return self->p_count;
+}
+
+double kol_counter_run(struct kol_counter *self, double v_n) {
+    double v_i = 0.0;
+    for (; v_i < v_n; v_i = v_i + 1.0) {
+        kol_counter_tick(self);
+    }
+    return self->p_count;
+}
+
+int main(void) {
+    kol_c = kol_new(&(struct kol_counter){ .p_count = 0.0, .p_step = 3.0 }, sizeof(struct kol_counter));
+    kol_total = kol_counter_run(kol_c, 4.0);
+    kol_c->p_step = 10.0;
+    kol_after = kol_counter_tick(kol_c);
+    return 0;
+}
@@ -0,0 +1,106 @@
+use kolgac_c::cgen;
+use kolgac_ir::pass::OptLevel;
+use kolgac_testutil as util;
+
+use std::fs;
+
+/// Compiles a program to C, builds it, and checks that it leaves its globals
+/// with the same values as the KIR interpreter.
+fn run(name: &str) {
+    let path = util::program(name);
+    if !util::has_tool("cc") {
+        return;
+    }
+
+    let ast = util::build(&path);
+    let globals = util::interp(&util::kir(&ast, OptLevel::O0));
+
+    let dir = util::temp_dir("kolgac_c", &path);
+    fs::write(dir.join("prog.c"), cgen::gen(&ast)).unwrap();
+    let harness = util::c_harness(&globals, "bool", cgen::global_sym);
+    let args = [
+        "-std=c99",
+        "-pedantic-errors",
+        "-Wall",
+        "-Werror",
+        "-Wno-unused",
+        "prog.c",
+        "-lm",
+    ];
+    util::cc_run(&dir, &args, &harness, &globals);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn c_recursion() {
+    run("fib");
+}
+
+#[test]
+fn c_loops() {
+    run("loops");
+}
+
+#[test]
+fn c_classes() {
+    run("classes");
+}
+
+#[test]
+fn c_many_args() {
+    run("regs");
+}
+
+#[test]
+fn c_equality() {
+    run("equality");
+}
+
+#[test]
+fn c_scopes() {
+    run("scopes");
+
+    // Each declaration of a name gets its own variable, and the loop variable
+    // outlives the loop.
+    let out = cgen::gen(&util::build(&util::program("scopes")));
+    assert!(out.contains("    double v_i = 0.0;\n    for (; v_i < v_n; v_i = v_i + 1.0) {\n"));
+    assert!(out.contains("    double v_i_1 = 0.0;\n"));
+    assert!(out.contains("    double v_t_1 = v_t + 1.0;\n    return v_t_1;\n"));
+    assert!(out.contains("\"say hi\\?\\?=\""));
+
+    // Top level variables are globals, even inside a block.
+    assert!(out.contains("\nbool kol_big;\n"));
+    assert!(out.contains("    if (kol_total > 10.0) {\n        kol_big = true;\n    }\n"));
+    assert!(out.contains("kol_neg = -(-kol_total);"));
+}
+
+#[test]
+fn c_class_output() {
+    let out = cgen::gen(&util::build(&util::program("classes")));
+    let expected = fs::read_to_string("./tests/cgen/classes.c").unwrap();
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn c_eval_order() {
+    run("order");
+
+    // Operands before a call are put in temporaries, so they're evaluated
+    // first, and temporaries in a condition are set where it's evaluated.
+    let out = cgen::gen(&util::build(&util::program("order")));
+    assert!(out.contains(
+        "    double t_0 = kol_fn_next();\n    kol_diff = t_0 - (kol_fn_next() * 10.0);\n"
+    ));
+    assert!(out.contains(
+        "    double t_1 = kol_fn_next();\n    kol_args = kol_fn_sub(t_1, kol_fn_next());\n"
+    ));
+    assert!(out.contains(
+        "    } else {\n        double t_3 = kol_fn_next();\n        if (t_3 < kol_n) {\n"
+    ));
+    assert!(out.contains("    while (true) {\n        double t_5 = kol_fn_next();\n        if (!((t_5 < (kol_n + 1.0)) && (kol_k < 3.0))) break;\n"));
+    assert!(out.contains("    bool t_7 = false;\n    if (t_7) {\n        double t_6 = kol_k;\n        t_7 = t_6 < kol_fn_sub(kol_n, 1.0);\n    }\n"));
+    assert!(out.contains(
+        "    struct kol_box *t_14 = kol_b;\n    kol_got = kol_box_get(t_14, kol_fn_next());\n"
+    ));
+}
@@ -1,9 +1,13 @@
 extern crate clap;
 
+extern crate kolgac_c;
 extern crate kolgac_errors;
 extern crate kolgac_ir;
 extern crate kolgac_llvm;
 extern crate kolgac_types;
+extern crate kolgac_vm;
+extern crate kolgac_wasm;
+extern crate kolgac_x86;
 
 use clap::Clap;
 
@@ -13,17 +17,44 @@ use kolgac::{
     parser::{Parser, ParserResult},
     symtab::SymbolTable,
 };
-use kolgac_errors::KolgaErr;
-use kolgac_ir::irgen::IRGen;
-use kolgac_llvm::{llvm::CodeGenerator, obj::ObjGenerator, valtab::ValTab};
-use kolgac_types::{check::TyCheck, infer::TyInfer};
+use kolgac_c::cgen;
+use kolgac_errors::{
+    explain,
+    render::{self, ErrorFormat},
+    source,
+    ty::TypeErr,
+    warn::{Lint, LintLevel, LintLevels},
+    KolgaErr,
+};
+use kolgac_ir::{
+    cfg,
+    interp::Interp,
+    irgen::IRGen,
+    pass::{OptLevel, PassManager},
+    ssa,
+};
+use kolgac_llvm::{
+    llvm::CodeGenerator,
+    obj::ObjGenerator,
+    target::{TargetMachine, TargetOpts},
+    valtab::ValTab,
+};
+use kolgac_types::{
+    assign::AssignCheck, check::TyCheck, infer::TyInfer, ret::UnreachableCheck, unused::UnusedCheck,
+};
+use kolgac_vm::{compile, kbc, program::Program, vm::Vm};
+use kolgac_wasm::wat;
+use kolgac_x86::asm;
 
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Clap)]
 #[clap(version = "1.0")]
 pub struct KolgaOpts {
-    filename: String,
+    #[clap(required_unless = "explain")]
+    filename: Option<String>,
 
     #[clap(long)]
     use_llvm: bool,
@@ -33,25 +64,218 @@ pub struct KolgaOpts {
     show_llvm_ir: bool,
     #[clap(long)]
     show_kir: bool,
+
+    /// Prints the control flow graph of each function in KIR, in Graphviz DOT
+    /// format.
+    #[clap(long)]
+    show_kir_cfg: bool,
+
+    /// Converts KIR into SSA form, with phis, before printing it.
+    #[clap(long)]
+    kir_ssa: bool,
+
+    /// Optimization level: 0, 1, 2, 3 or s. For KIR, 0 runs no passes, 1 runs
+    /// passes that work within a block, and 2 and above add passes that work
+    /// across blocks. With --use-llvm, it picks LLVM's function and module
+    /// passes and its codegen level. s optimizes for size.
+    #[clap(short = "O", default_value = "0")]
+    opt_level: OptLevel,
+
+    /// Target triple to generate code for with --use-llvm, like
+    /// 'aarch64-unknown-linux-gnu' or 'riscv64-unknown-linux-gnu'. Defaults to
+    /// the machine we're running on.
+    #[clap(long)]
+    target: Option<String>,
+
+    /// CPU to generate code for with --use-llvm. Defaults to the target's
+    /// baseline CPU. 'native' picks the CPU we're running on, and its features.
+    #[clap(long)]
+    cpu: Option<String>,
+
+    /// CPU features to turn on or off with --use-llvm, like '+neon,-fp-armv8'.
+    #[clap(long)]
+    features: Option<String>,
+
+    /// Prints KIR after each optimization pass.
+    #[clap(long)]
+    show_kir_passes: bool,
+
+    /// Runs the program with the KIR interpreter, and prints the final value of
+    /// each global.
+    #[clap(long)]
+    interp: bool,
+
+    /// Compiles the program to bytecode and runs it with the VM, and prints the
+    /// final value of each global. Passing a .kbc file instead of a source file
+    /// runs it the same way.
+    #[clap(long)]
+    vm: bool,
+
+    /// Generates x86-64 assembly from KIR, and writes it to a .s file named
+    /// after the source file, or to the -o path.
+    #[clap(long)]
+    kir_asm: bool,
+
+    /// Comma separated list of files to write, each named after the source
+    /// file. 'c' generates C99, 'wat' a WebAssembly module in the text format,
+    /// and 'kbc' bytecode for the VM. 'obj', 'asm', 'llvm-ir' and 'llvm-bc'
+    /// use LLVM to write an object file, assembly, and textual or bitcode IR.
+    /// --use-llvm writes an object file if none of these are given.
+    #[clap(long, require_delimiter = true)]
+    emit: Vec<Emit>,
+
+    /// Where to write the output file, instead of next to the source file. Only
+    /// one file can be written when this is given.
+    #[clap(short = "o")]
+    output: Option<PathBuf>,
+
+    /// How to print errors: 'human' (default) or 'json', which prints one JSON
+    /// object per line to stderr.
+    #[clap(long, default_value = "human")]
+    error_format: ErrorFormat,
+
+    /// Prints a longer description of an error code, like K0301, with an example
+    /// of code that causes it and how to fix it.
+    #[clap(long)]
+    explain: Option<String>,
+
+    /// Reports a lint as a warning, like '-W unused-variables'. Every lint is a
+    /// warning by default. Passing 'unused' sets every lint at once.
+    #[clap(short = "W", number_of_values = 1)]
+    warn: Vec<String>,
+
+    /// Hides the warnings for a lint.
+    #[clap(short = "A", number_of_values = 1)]
+    allow: Vec<String>,
+
+    /// Reports a lint as an error, and stops compilation if it's found.
+    #[clap(short = "D", number_of_values = 1)]
+    deny: Vec<String>,
+}
+
+/// Files that --emit can write. The first few are other languages, translated
+/// from the AST, and the rest are generated by LLVM.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emit {
+    C,
+    Wat,
+    Kbc,
+    Obj,
+    Asm,
+    LlvmIr,
+    LlvmBc,
+}
+
+impl Emit {
+    fn is_llvm(self) -> bool {
+        match self {
+            Emit::C | Emit::Wat | Emit::Kbc => false,
+            Emit::Obj | Emit::Asm | Emit::LlvmIr | Emit::LlvmBc => true,
+        }
+    }
+
+    /// Extension of the file we write.
+    fn ext(self) -> &'static str {
+        match self {
+            Emit::C => "c",
+            Emit::Wat => "wat",
+            Emit::Kbc => "kbc",
+            Emit::Obj => "o",
+            Emit::Asm => "s",
+            Emit::LlvmIr => "ll",
+            Emit::LlvmBc => "bc",
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Emit, String> {
+        match s {
+            "c" => Ok(Emit::C),
+            "wat" => Ok(Emit::Wat),
+            "kbc" => Ok(Emit::Kbc),
+            "obj" => Ok(Emit::Obj),
+            "asm" => Ok(Emit::Asm),
+            "llvm-ir" => Ok(Emit::LlvmIr),
+            "llvm-bc" => Ok(Emit::LlvmBc),
+            _ => Err(format!(
+                "invalid output '{}', expected 'c', 'wat', 'kbc', 'obj', 'asm', 'llvm-ir' or 'llvm-bc'",
+                s
+            )),
+        }
+    }
 }
 
 fn main() {
     let opts: KolgaOpts = KolgaOpts::parse();
+    render::set_format(opts.error_format);
+
+    if let Some(code) = &opts.explain {
+        match explain::lookup(code) {
+            Some(explanation) => print!("{}", explanation.render()),
+            None => println!("kolgac: '{}' is not a valid error code", code),
+        }
+        return;
+    }
+
+    // clap requires the filename unless --explain is passed.
+    let filename = opts.filename.as_deref().unwrap();
+
+    if let Err(msg) = check_opts(&opts) {
+        println!("kolgac: {}", msg);
+        return;
+    }
+
+    // Check the target before compiling anything, since there's no point
+    // compiling for a target we can't generate code for.
+    let target = match opts.uses_llvm() {
+        true => match TargetMachine::new(&opts.target_opts(), opts.opt_level) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                println!("kolgac: {}", e);
+                return;
+            }
+        },
+        false => None,
+    };
+
+    // Bytecode has already been compiled, so it skips straight to the VM.
+    if Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext == "kbc")
+    {
+        if run_kbc(filename).is_err() {
+            println!("kolgac: Exiting due to VM errors");
+        }
+        return;
+    }
+
+    let lint_levels = match build_lint_levels(&opts) {
+        Ok(levels) => levels,
+        Err(msg) => {
+            println!("kolgac: {}", msg);
+            return;
+        }
+    };
 
     // 1. Run the lexer/parser.
     let mut symtab = SymbolTable::new();
-    let parse_result = run_parser(&opts.filename, &mut symtab);
+    let parse_result = run_parser(filename, &mut symtab);
+
+    // 2. Run the type inferrer and the type checker. Any parser errors should
+    // already have been emitted by the parser. Statements that failed to parse
+    // are left out of the AST, so we still check the rest of the file to report
+    // as many errors as we can.
+    let mut ast = parse_result.ast.unwrap();
+    let ty_result = run_tys(&mut ast, &mut symtab);
 
-    // Any errors should already have been emitted by the
-    // parser, whether or not they are continuable.
     if parse_result.has_err {
         println!("kolgac: Exiting due to parser errors");
         return;
     }
 
-    // 2. Run the type inferrer and the type checker.
-    let mut ast = parse_result.ast.unwrap();
-    let ty_result = run_tys(&mut ast, &mut symtab);
     match ty_result {
         Ok(()) => (),
         Err(()) => {
@@ -60,33 +284,144 @@ fn main() {
         }
     };
 
+    // 3. Report unused symbols and unreachable code. Warnings don't stop
+    // compilation unless they've been denied.
+    let lint_result = run_lints(&ast, &symtab, &lint_levels);
+    match lint_result {
+        Ok(()) => (),
+        Err(()) => {
+            println!("kolgac: Exiting due to denied warnings");
+            return;
+        }
+    };
+
     if opts.show_ast {
         println!("{:#?}", ast);
     }
 
-    // 3. Choose backend from options and generate appropriate code.
-    if opts.use_llvm {
-        // Using LLVM will create an object file containing bytecode.
-        let llvm_result = run_llvm_codegen(&ast, &opts);
-        match llvm_result {
-            Ok(()) => (),
-            Err(()) => {
-                println!("kolgac: Exiting due to LLVM IR errors");
-                return;
-            }
+    // 4. Choose backend from options and generate appropriate code. Other
+    // languages are translated straight from the AST, and then we go on to
+    // whichever backend was asked for. check_opts() has already made sure
+    // we're not asked for more than one.
+    for emit in opts.emit.iter().filter(|e| !e.is_llvm()) {
+        if run_emit(&ast, filename, *emit, &opts).is_err() {
+            println!("kolgac: Exiting due to output errors");
+            return;
+        }
+    }
+
+    if let Some(target) = target {
+        // Using LLVM will create an object file, unless --emit asks for
+        // something else.
+        if let Err(stage) = run_llvm_codegen(&ast, filename, target, &opts) {
+            println!("kolgac: Exiting due to {}", stage);
+        }
+    } else if opts.vm {
+        if run_vm(&compile::compile(&ast)).is_err() {
+            println!("kolgac: Exiting due to VM errors");
         }
-    } else {
+    } else if opts.emit.is_empty() || opts.kir_flag().is_some() {
         // If not using LLVM, we generate KIR and can perform
         // analysis on it before generating native code.
-        let kir_result = run_kir_codegen(&ast, &opts);
-        match kir_result {
-            Ok(()) => (),
-            Err(()) => {
-                println!("kolgac: Exiting due to KolIR errors");
-                return;
+        if run_kir_codegen(&ast, filename, &opts).is_err() {
+            println!("kolgac: Exiting due to KolIR errors");
+        }
+    }
+}
+
+impl KolgaOpts {
+    fn target_opts(&self) -> TargetOpts {
+        TargetOpts {
+            triple: self.target.clone(),
+            cpu: self.cpu.clone().unwrap_or_default(),
+            features: self.features.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Whether anything asks for output from LLVM.
+    fn uses_llvm(&self) -> bool {
+        self.use_llvm || self.emit.iter().any(|e| e.is_llvm())
+    }
+
+    /// The first flag that only means something for KIR, if any were given.
+    fn kir_flag(&self) -> Option<&'static str> {
+        let flags = [
+            ("--interp", self.interp),
+            ("--kir-asm", self.kir_asm),
+            ("--kir-ssa", self.kir_ssa),
+            ("--show-kir", self.show_kir),
+            ("--show-kir-cfg", self.show_kir_cfg),
+            ("--show-kir-passes", self.show_kir_passes),
+        ];
+        flags.iter().find(|(_, set)| *set).map(|(flag, _)| *flag)
+    }
+
+    /// Number of files we'll write, not counting anything printed.
+    fn output_count(&self) -> usize {
+        let mut emits: Vec<Emit> = Vec::new();
+        for emit in &self.emit {
+            if !emits.contains(emit) {
+                emits.push(*emit);
+            }
+        }
+        let mut count = emits.len() + self.kir_asm as usize;
+        if self.use_llvm && !self.emit.iter().any(|e| e.is_llvm()) {
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Rejects combinations of flags where some of them would be ignored, or
+/// where two outputs would be written to the same file. Only one backend can
+/// run at a time: LLVM, the VM, or KIR.
+fn check_opts(opts: &KolgaOpts) -> Result<(), String> {
+    if let Some(flag) = opts.kir_flag() {
+        if opts.uses_llvm() {
+            return Err(format!(
+                "'{}' uses KIR, so it can't be combined with LLVM output",
+                flag
+            ));
+        }
+        if opts.vm {
+            return Err(format!(
+                "'{}' uses KIR, so it can't be combined with '--vm'",
+                flag
+            ));
+        }
+    }
+
+    if opts.vm && opts.uses_llvm() {
+        return Err(String::from("'--vm' can't be combined with LLVM output"));
+    }
+
+    let target_flags = [
+        ("--target", opts.target.is_some()),
+        ("--cpu", opts.cpu.is_some()),
+        ("--features", opts.features.is_some()),
+    ];
+    if let Some((flag, _)) = target_flags.iter().find(|(_, set)| *set) {
+        if !opts.uses_llvm() {
+            return Err(format!(
+                "'{}' only applies to LLVM output, like '--use-llvm' or '--emit=obj'",
+                flag
+            ));
+        }
+    }
+
+    if opts.output.is_some() {
+        match opts.output_count() {
+            0 => return Err(String::from("'-o' was given, but no file is written")),
+            1 => (),
+            _ => {
+                return Err(String::from(
+                    "'-o' can't be used when writing more than one file",
+                ))
             }
         }
     }
+
+    Ok(())
 }
 
 /// Opens the file from the filename provided, creates a lexer for that file
@@ -101,6 +436,12 @@ fn run_parser(filename: &str, symtab: &mut SymbolTable) -> ParserResult {
         }
     };
 
+    // Register the source text so that errors from every stage can print
+    // the lines they refer to.
+    if let Ok(text) = fs::read_to_string(filename) {
+        source::register(filename, &text);
+    }
+
     let mut lexer = Lexer::new(infile);
     let mut parser = Parser::new(&mut lexer, symtab);
     parser.parse()
@@ -108,40 +449,98 @@ fn run_parser(filename: &str, symtab: &mut SymbolTable) -> ParserResult {
 
 /// Given a valid AST from parsing, infers any types that were not defined in the
 /// source code. After inferring, runs a second pass to check all the types in
-/// the AST. Returns an empty result, which can be used as a flag to decide
+/// the AST, and checks that variables are assigned before they're read.
+/// Returns an empty result, which can be used as a flag to decide
 /// whether to continue to other compilation stages or not.
 /// This function also prints any errors encountered during inference/checking.
 fn run_tys(ast: &mut Ast, symtab: &mut SymbolTable) -> Result<(), ()> {
-    let result = TyInfer::new().infer(ast);
-    match result {
-        Ok(()) => (),
-        Err(e) => {
-            e.emit();
-            return Err(());
-        }
-    }
+    let infer_errs = match TyInfer::new().infer(ast) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs,
+    };
 
-    let check_result = TyCheck::new(ast, symtab).check();
+    // Types that were inferred have been written back to the AST, so we check
+    // them even if some equations failed.
+    let check_errs = TyCheck::new(ast, symtab)
+        .with_infer_errs(&infer_errs)
+        .check();
 
-    if check_result.len() > 0 {
-        for err in &check_result {
-            err.emit();
-        }
+    let assign_errs = AssignCheck::new(ast, symtab).check();
+
+    let mut errs: Vec<&TypeErr> = infer_errs
+        .iter()
+        .chain(&check_errs)
+        .chain(&assign_errs)
+        .collect();
+    errs.sort_by_key(|e| (e.line, e.pos));
+
+    for err in &errs {
+        err.emit();
+    }
 
+    if !errs.is_empty() {
         return Err(());
     }
 
     Ok(())
 }
 
+/// Builds the level for each lint from the -W, -A and -D flags. Group names like
+/// 'unused' are applied before single lints, so '-A unused -W unused-params'
+/// only warns about unused parameters. Otherwise, -D beats -A, which beats -W.
+fn build_lint_levels(opts: &KolgaOpts) -> Result<LintLevels, String> {
+    let mut levels = LintLevels::new();
+    let flags = [
+        (&opts.warn, LintLevel::Warn),
+        (&opts.allow, LintLevel::Allow),
+        (&opts.deny, LintLevel::Deny),
+    ];
+
+    for groups in [true, false].iter() {
+        for (names, level) in flags.iter() {
+            for name in names.iter() {
+                let is_group = Lint::from_name(name).is_some_and(|l| l.len() > 1);
+                if is_group == *groups {
+                    levels.set(name, *level)?;
+                }
+            }
+        }
+    }
+
+    Ok(levels)
+}
+
+/// Runs the unused symbol and unreachable code analyses over the type checked AST
+/// and the finalized symbol table, and prints any warnings that aren't allowed.
+/// Returns an error if any of the warnings were denied.
+fn run_lints(ast: &Ast, symtab: &SymbolTable, levels: &LintLevels) -> Result<(), ()> {
+    let mut warnings = UnusedCheck::new(ast, symtab).check();
+    warnings.extend(UnreachableCheck::new(ast).check());
+    warnings.sort_by_key(|w| (w.line, w.pos));
+    let warnings = levels.apply(warnings);
+
+    for warning in &warnings {
+        warning.emit();
+    }
+
+    match warnings.iter().any(|w| w.is_denied()) {
+        true => Err(()),
+        false => Ok(()),
+    }
+}
+
 /// Given a valid AST with all types inferred and checked, generates LLVM IR
-/// from that AST, and then creates an object file from that IR. Like the
-/// run_tys() function, this returns an empty result to be used as a flag to decide
-/// whether or not to continue with compilation stages. This will print any errors
-/// encountered during codegen.
-fn run_llvm_codegen(ast: &Ast, opts: &KolgaOpts) -> Result<(), ()> {
+/// from that AST, and then writes the files --emit asks for from that IR. This
+/// will print any errors encountered during codegen, and on failure returns the
+/// stage that failed, for the message we exit with.
+fn run_llvm_codegen(
+    ast: &Ast,
+    filename: &str,
+    target: TargetMachine,
+    opts: &KolgaOpts,
+) -> Result<(), &'static str> {
     let mut valtab = ValTab::new();
-    let mut llvm_codegen = CodeGenerator::new(&ast, &mut valtab);
+    let mut llvm_codegen = CodeGenerator::new(ast, &mut valtab, opts.opt_level, &target);
 
     llvm_codegen.gen_ir();
 
@@ -150,32 +549,154 @@ fn run_llvm_codegen(ast: &Ast, opts: &KolgaOpts) -> Result<(), ()> {
             err.emit();
         }
 
-        return Err(());
+        return Err("LLVM IR errors");
     }
 
     if opts.show_llvm_ir {
         llvm_codegen.dump_ir();
     }
 
-    // Generate an object file from LLVM IR
-    let prefix = opts.filename.split(".").collect::<Vec<&str>>()[0];
-    let obj_filename = format!("{}.{}", prefix, "o");
+    let mut emits: Vec<Emit> = opts.emit.iter().cloned().filter(|e| e.is_llvm()).collect();
+    if emits.is_empty() {
+        emits.push(Emit::Obj);
+    }
+
+    let mut obj_gen = ObjGenerator::new(llvm_codegen.module, target);
+    for emit in emits {
+        let out_filename = output_path(filename, emit, opts);
+        let out_str = out_filename.to_string_lossy();
+        let result = match emit {
+            Emit::Obj => obj_gen.emit(&out_str),
+            Emit::Asm => obj_gen.emit_asm(&out_str),
+            Emit::LlvmIr => llvm_codegen.print_ir(&out_str),
+            Emit::LlvmBc => llvm_codegen.write_bitcode(&out_str),
+            Emit::C | Emit::Wat | Emit::Kbc => unreachable!(),
+        };
+
+        if let Err(e) = result {
+            println!("kolgac: could not write '{}': {}", out_str, e);
+            return Err("output errors");
+        }
+    }
+
+    Ok(())
+}
+
+/// Where to write an output file: the -o path if it was given, or else next to
+/// the source file, with the extension for the kind of file.
+fn output_path(filename: &str, emit: Emit, opts: &KolgaOpts) -> PathBuf {
+    match &opts.output {
+        Some(output) => output.clone(),
+        None => Path::new(filename).with_extension(emit.ext()),
+    }
+}
+
+/// Translates the AST into another language, and writes it to the output file
+/// for that language.
+fn run_emit(ast: &Ast, filename: &str, emit: Emit, opts: &KolgaOpts) -> Result<(), ()> {
+    let out = match emit {
+        Emit::C => cgen::gen(ast).into_bytes(),
+        Emit::Wat => wat::gen(ast).into_bytes(),
+        Emit::Kbc => kbc::write(&compile::compile(ast)),
+        Emit::Obj | Emit::Asm | Emit::LlvmIr | Emit::LlvmBc => unreachable!(),
+    };
 
-    let mut obj_gen = ObjGenerator::new(llvm_codegen.module);
-    obj_gen.emit(&obj_filename);
+    let out_filename = output_path(filename, emit, opts);
+    if let Err(e) = fs::write(&out_filename, out) {
+        println!(
+            "kolgac: could not write '{}': {}",
+            out_filename.display(),
+            e
+        );
+        return Err(());
+    }
 
     Ok(())
 }
 
-fn run_kir_codegen(ast: &Ast, opts: &KolgaOpts) -> Result<(), ()> {
+/// Loads a bytecode file written by --emit=kbc, and runs it.
+fn run_kbc(filename: &str) -> Result<(), ()> {
+    let bytes = match fs::read(filename) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("kolgac: could not read '{}': {}", filename, e);
+            return Err(());
+        }
+    };
+
+    match kbc::read(&bytes) {
+        Ok(program) => run_vm(&program),
+        Err(e) => {
+            println!("kolgac: could not load '{}': {}", filename, e);
+            Err(())
+        }
+    }
+}
+
+/// Runs bytecode with the VM, and prints the final value of each global.
+fn run_vm(program: &Program) -> Result<(), ()> {
+    let mut vm = Vm::new(program);
+    if let Err(e) = vm.run() {
+        println!("kolgac: runtime error: {}", e);
+        return Err(());
+    }
+
+    for (name, val) in vm.globals() {
+        println!("{} = {}", name, vm.fmt_val(&val));
+    }
+
+    Ok(())
+}
+
+fn run_kir_codegen(ast: &Ast, filename: &str, opts: &KolgaOpts) -> Result<(), ()> {
     let mut kir = IRGen::new(ast);
     kir.gen();
 
+    let mut fns = cfg::build(&kir.ir);
+
+    let mut pm = PassManager::for_level(opts.opt_level);
+    pm.print_after_each = opts.show_kir_passes;
+    pm.run(&mut fns);
+
+    if opts.kir_asm {
+        let asm_filename = output_path(filename, Emit::Asm, opts);
+        if let Err(e) = fs::write(&asm_filename, asm::gen(&fns)) {
+            println!(
+                "kolgac: could not write '{}': {}",
+                asm_filename.display(),
+                e
+            );
+            return Err(());
+        }
+    }
+
+    if opts.kir_ssa {
+        for func in &mut fns {
+            ssa::construct(func);
+        }
+    }
+
     if opts.show_kir {
-        for instr in kir.ir {
+        for instr in fns.iter().flat_map(|func| func.instrs()) {
             println!("{}", instr);
         }
     }
 
+    if opts.show_kir_cfg {
+        print!("{}", cfg::to_dot(&fns));
+    }
+
+    if opts.interp {
+        let mut interp = Interp::new(&fns);
+        if let Err(e) = interp.run() {
+            println!("kolgac: runtime error: {}", e);
+            return Err(());
+        }
+
+        for (name, val) in interp.globals() {
+            println!("{} = {}", name, interp.fmt_val(&val));
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,28 @@
+; ModuleID = 'kolga'
+source_filename = "kolga"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define i32 @main() {
+entry:
+  ret i32 0
+}
+
+define double @twice(double %x) {
+twice:
+  %y = alloca double, align 8
+  %x1 = alloca double, align 8
+  store double %x, double* %x1, align 8
+  %x2 = load double, double* %x1, align 8
+  store double %x2, double* %y, align 8
+  %y3 = load double, double* %y, align 8
+  %y4 = load double, double* %y, align 8
+  %addtmp = fadd double %y3, %y4
+  ret double %addtmp
+}
+
+define double @four() {
+four:
+  %0 = call double @twice(double 2.000000e+00)
+  ret double %0
+}
@@ -0,0 +1,106 @@
+use std::{fs, process::Command};
+
+/// Runs the kolga binary on a file in driver_input, and returns what it wrote
+/// to stdout and stderr.
+fn kolga(args: &[&str], input: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_kolga"))
+        .args(args)
+        .arg(format!("./tests/driver_input/{}", input))
+        .output()
+        .unwrap();
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    text
+}
+
+#[test]
+fn driver_llvm_opt() {
+    let out = kolga(
+        &["-O0", "--emit=llvm-ir", "-o", "./tests/driver_o0.ll"],
+        "twice.kol",
+    );
+    let ir = fs::read_to_string("./tests/driver_o0.ll").unwrap();
+    fs::remove_file("./tests/driver_o0.ll").ok();
+    assert!(!out.contains("kolgac:"), "{}", out);
+    assert!(ir.contains("alloca"));
+    assert!(ir.contains("call"));
+
+    let out = kolga(
+        &["-O2", "--emit=llvm-ir", "-o", "./tests/driver_o2.ll"],
+        "twice.kol",
+    );
+    let ir = fs::read_to_string("./tests/driver_o2.ll").unwrap();
+    fs::remove_file("./tests/driver_o2.ll").ok();
+    assert!(!out.contains("kolgac:"), "{}", out);
+    assert!(!ir.contains("alloca"));
+    assert!(!ir.contains("call"));
+    assert!(ir.contains("ret double 4.0"));
+}
+
+#[test]
+fn driver_llvm_flags() {
+    let out = kolga(&["--target=x86_64-linux-gnu"], "twice.kol");
+    assert!(out.contains("kolgac:"), "{}", out);
+
+    let out = kolga(&["--emit=llvm-ir", "--target=bogus"], "twice.kol");
+    assert!(out.contains("no target for 'bogus'"), "{}", out);
+
+    let out = kolga(
+        &[
+            "--emit=llvm-ir",
+            "--target=x86_64-linux-gnu",
+            "--cpu=skylak",
+        ],
+        "twice.kol",
+    );
+    assert!(
+        out.contains("kolgac: unknown CPU 'skylak' for 'x86_64-unknown-linux-gnu'"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("not a recognized processor"), "{}", out);
+}
+
+#[test]
+fn driver_llvm_top_level() {
+    let out = kolga(
+        &[
+            "-A",
+            "unused",
+            "--emit=llvm-ir",
+            "-o",
+            "./tests/driver_top.ll",
+        ],
+        "top_level.kol",
+    );
+    let ir = fs::read_to_string("./tests/driver_top.ll").unwrap();
+    fs::remove_file("./tests/driver_top.ll").ok();
+    assert!(!out.contains("kolgac:"), "{}", out);
+
+    // Constant initializers are folded, and the rest are stored by main.
+    assert!(ir.contains("@K = global double 1.2"));
+    assert!(ir.contains("@t = global double 0.0"));
+    assert!(ir.contains("@d = global double 0.0"));
+    assert!(ir.contains("define i32 @main()"));
+    assert!(ir.contains("store double %0, double* @t"));
+
+    // The A declared in the block is gone by the time B is folded.
+    assert!(ir.contains("@B = global double 2.0"));
+}
+
+#[test]
+fn driver_parse_recovery() {
+    let out = kolga(&[], "recovery.kol");
+    let found: Vec<&str> = out
+        .lines()
+        .filter(|line| line.starts_with("error["))
+        .map(|line| &line[6..11])
+        .collect();
+
+    // One error for each mistake: 'b' is still declared after its value fails to
+    // parse, the property missing its ';' doesn't swallow the method after it,
+    // and the bad call is only reported once.
+    assert_eq!(found, vec!["K0203", "K0212", "K0215"], "{}", out);
+    assert!(out.contains("recovery.kol:8:3"), "{}", out);
+    assert!(out.contains("recovery.kol:14:18"), "{}", out);
+}
@@ -0,0 +1,131 @@
+/// Severity of a diagnostic. This decides the header printed for the
+/// diagnostic and the color it is printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        }
+    }
+}
+
+/// Location of some source text that a diagnostic refers to. Lines start at 1,
+/// and positions are the 0-based char offset into the line, which is what the
+/// lexer stores in tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub pos: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, pos: usize, len: usize) -> Span {
+        Span {
+            line,
+            pos,
+            len: if len == 0 { 1 } else { len },
+        }
+    }
+}
+
+/// A span with a message attached to it, rendered underneath the source line
+/// that contains the span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl Label {
+    pub fn new(span: Span, msg: &str) -> Label {
+        Label {
+            span,
+            msg: msg.to_string(),
+        }
+    }
+}
+
+/// Diagnostic is the common representation of any error produced by the compiler.
+/// Each error type converts itself into a Diagnostic, so that all stages share
+/// the same renderer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+
+    /// Stable identifier for the kind of error, like K0212. This doesn't change
+    /// when the message wording does, so tools can match on it.
+    pub code: Option<&'static str>,
+
+    /// Main message, printed in the header of the diagnostic.
+    pub msg: String,
+
+    /// Primary location of the diagnostic. Errors that aren't tied to a location
+    /// in the source (like codegen errors) have no span.
+    pub span: Option<Span>,
+
+    /// Message printed under the primary span.
+    pub span_msg: Option<String>,
+
+    /// Secondary locations related to this diagnostic, like where a variable
+    /// was declared.
+    pub labels: Vec<Label>,
+
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, msg: String) -> Diagnostic {
+        Diagnostic {
+            level,
+            code: None,
+            msg,
+            span: None,
+            span_msg: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(msg: String) -> Diagnostic {
+        Diagnostic::new(Level::Error, msg)
+    }
+
+    pub fn warning(msg: String) -> Diagnostic {
+        Diagnostic::new(Level::Warning, msg)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span, msg: Option<String>) -> Diagnostic {
+        self.span = Some(span);
+        self.span_msg = msg;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: &[Label]) -> Diagnostic {
+        self.labels.extend_from_slice(labels);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Diagnostic {
+        self.help = Some(help.to_string());
+        self
+    }
+}
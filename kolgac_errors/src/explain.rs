@@ -0,0 +1,437 @@
+/// Long form description of an error code, printed by `kolga --explain`.
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub desc: &'static str,
+
+    /// Source that produces the error, and the same source with the error fixed.
+    /// Internal errors and retired codes can't be triggered by source code, so they
+    /// have no examples.
+    pub wrong: Option<&'static str>,
+    pub fixed: Option<&'static str>,
+}
+
+impl Explanation {
+    pub fn render(&self) -> String {
+        let mut out = format!("{}: {}\n\n{}\n", self.code, self.title, self.desc);
+
+        if let Some(wrong) = self.wrong {
+            out.push_str(&format!("\nErroneous code example:\n\n{}\n", indent(wrong)));
+        }
+
+        if let Some(fixed) = self.fixed {
+            out.push_str(&format!("\nCorrected:\n\n{}\n", indent(fixed)));
+        }
+
+        out
+    }
+}
+
+/// Finds the explanation for an error code. Codes are matched case insensitively,
+/// so `k0301` finds K0301.
+pub fn lookup(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .find(|e| e.code.eq_ignore_ascii_case(code.trim()))
+}
+
+fn indent(src: &str) -> String {
+    src.lines()
+        .map(|l| format!("    {}", l))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Codes that are no longer emitted. Codes are never reused, so these keep their
+/// entry in the table, saying what reports the same mistake now.
+pub const RETIRED: &[&str] = &["K0205", "K0216"];
+
+/// Every error code, in order. Codes are grouped by the stage that emits them:
+/// K01xx for the lexer, K02xx for the parser, K03xx for type inference and
+/// checking, K04xx for code generation, and K05xx for warnings.
+pub const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "K0101",
+        title: "unrecognized character",
+        desc: "The lexer found a character that isn't part of any kolga token. \
+               This is often a stray symbol, or an operator from another language.",
+        wrong: Some("let x~num = 5 @ 2;"),
+        fixed: Some("let x~num = 5 * 2;"),
+    },
+    Explanation {
+        code: "K0102",
+        title: "unterminated string literal",
+        desc: "A string literal was opened with '\"' but never closed. Strings must \
+               be closed on the same line they are opened.",
+        wrong: Some("let s~string = \"hello;"),
+        fixed: Some("let s~string = \"hello\";"),
+    },
+    Explanation {
+        code: "K0201",
+        title: "assignment in a class declaration",
+        desc: "Class properties can be declared with a type, but can't be given a \
+               value in the class body. Values are set when an instance of the class \
+               is constructed.",
+        wrong: Some("class point {\n  let x~num = 0;\n}"),
+        fixed: Some("class point {\n  let x~num;\n}\n\nlet p~point{\n  x = 0,\n};"),
+    },
+    Explanation {
+        code: "K0202",
+        title: "invalid identifier",
+        desc: "An identifier was expected, like the name of a variable, function or \
+               class, but some other token was found. Names must start with a letter.",
+        wrong: Some("let 1~num = 1;"),
+        fixed: Some("let one~num = 1;"),
+    },
+    Explanation {
+        code: "K0203",
+        title: "invalid token",
+        desc: "A token was found where it can't start an expression. This usually \
+               means an operand is missing, or there are too many operators.",
+        wrong: Some("let x~num = * 2;"),
+        fixed: Some("let x~num = 1 * 2;"),
+    },
+    Explanation {
+        code: "K0204",
+        title: "invalid assignment",
+        desc: "The left side of an assignment isn't something that can be assigned \
+               to. Only variables and class properties can be assigned.",
+        wrong: Some("let x~num = 1;\n1 = x;"),
+        fixed: Some("let x~num = 1;\nx = 1;"),
+    },
+    Explanation {
+        code: "K0205",
+        title: "assignment to an immutable variable (retired)",
+        desc: "This code is no longer emitted. Assigning to a variable declared with \
+               'imm' is reported by the type checker instead, as K0310.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0206",
+        title: "invalid type",
+        desc: "A type annotation names something that isn't a type. Valid types are \
+               num, string, bool, or the name of a declared class.",
+        wrong: Some("let x~int = 1;"),
+        fixed: Some("let x~num = 1;"),
+    },
+    Explanation {
+        code: "K0207",
+        title: "invalid for loop",
+        desc: "A for loop must start by declaring its loop variable, followed by the \
+               loop condition and the step, each ending in ';'.",
+        wrong: Some("let i~num = 0;\nfor i < 10; i = i + 1; {\n}"),
+        fixed: Some("for let i~num = 0; i < 10; i = i + 1; {\n}"),
+    },
+    Explanation {
+        code: "K0208",
+        title: "more than one else in an if statement",
+        desc: "An if statement can only have a single else block, and it must come \
+               last. Use elif to check additional conditions.",
+        wrong: Some("let x~num = 1;\nif x < 1 {\n} else {\n} else {\n}"),
+        fixed: Some("let x~num = 1;\nif x < 1 {\n} elif x < 2 {\n} else {\n}"),
+    },
+    Explanation {
+        code: "K0209",
+        title: "invalid class property",
+        desc: "A class property was declared or accessed incorrectly. Class bodies \
+               may only contain 'let' declarations and functions, and properties can \
+               only be accessed on variables holding a class instance.",
+        wrong: Some("let x~num = 1;\nlet y~num = x.p;"),
+        fixed: Some(
+            "class point {\n  let p~num;\n}\n\nlet x~point{\n  p = 1,\n};\nlet y~num = x.p;",
+        ),
+    },
+    Explanation {
+        code: "K0210",
+        title: "invalid class constructor",
+        desc: "A variable with a class type must be constructed when it is declared, \
+               using braces to set the values of its properties.",
+        wrong: Some("class point {\n  let x~num;\n}\n\nlet p~point;"),
+        fixed: Some("class point {\n  let x~num;\n}\n\nlet p~point{\n  x = 1,\n};"),
+    },
+    Explanation {
+        code: "K0211",
+        title: "immutable variable without a value",
+        desc: "A variable declared with 'imm' can never be assigned after it is \
+               declared, so it must be given a value in its declaration.",
+        wrong: Some("let imm x~num;"),
+        fixed: Some("let imm x~num = 1;"),
+    },
+    Explanation {
+        code: "K0212",
+        title: "unexpected token",
+        desc: "The parser expected a specific token, like ';' or '}', but found a \
+               different one. The most common cause is a missing semicolon.",
+        wrong: Some("let x~num = 1"),
+        fixed: Some("let x~num = 1;"),
+    },
+    Explanation {
+        code: "K0213",
+        title: "too many parameters",
+        desc: "Functions can have at most 64 parameters. Consider grouping related \
+               parameters into a class.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0214",
+        title: "wrong number of arguments",
+        desc: "A function was called with a different number of arguments than it \
+               declares parameters.",
+        wrong: Some("fn add(a~num, b~num)~num {\n  return a + b;\n}\n\nadd(1);"),
+        fixed: Some("fn add(a~num, b~num)~num {\n  return a + b;\n}\n\nadd(1, 2);"),
+    },
+    Explanation {
+        code: "K0215",
+        title: "undeclared symbol",
+        desc: "A name was used that hasn't been declared in the current scope or any \
+               enclosing scope. Variables, functions and classes must be declared \
+               before they are used.",
+        wrong: Some("let x~num = y + 1;"),
+        fixed: Some("let y~num = 1;\nlet x~num = y + 1;"),
+    },
+    Explanation {
+        code: "K0216",
+        title: "use of an unassigned variable (retired)",
+        desc: "This code is no longer emitted. Reading a variable before it's given a \
+               value is reported by the definite assignment check instead, as K0308.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0217",
+        title: "type annotation required",
+        desc: "A variable declared without a value needs a type annotation, since \
+               there is no value to infer the type from.",
+        wrong: Some("let x~;"),
+        fixed: Some("let x~num;"),
+    },
+    Explanation {
+        code: "K0218",
+        title: "'mut' outside of a class",
+        desc: "'mut' marks a class method that is allowed to change the properties of \
+               self. Functions outside of a class have no self, so they can't be \
+               declared 'mut'.",
+        wrong: Some("mut fn one()~num {\n  return 1;\n}"),
+        fixed: Some("fn one()~num {\n  return 1;\n}"),
+    },
+    Explanation {
+        code: "K0301",
+        title: "type mismatch",
+        desc: "A value has a different type than the one required where it is used, \
+               like assigning a bool to a num variable, or returning a string from a \
+               function declared to return a num.",
+        wrong: Some("let x~num = true;"),
+        fixed: Some("let x~bool = true;"),
+    },
+    Explanation {
+        code: "K0302",
+        title: "class property type mismatch",
+        desc: "A class property was given a value with a different type than the \
+               property was declared with.",
+        wrong: Some("class point {\n  let x~num;\n}\n\nlet p~point{\n  x = \"one\",\n};"),
+        fixed: Some("class point {\n  let x~num;\n}\n\nlet p~point{\n  x = 1,\n};"),
+    },
+    Explanation {
+        code: "K0303",
+        title: "invalid operands for an operator",
+        desc: "An operator was used with operands of the wrong type. Arithmetic and \
+               comparison operators need nums, and logical operators need bools.",
+        wrong: Some("let x~num = 1 + true;"),
+        fixed: Some("let x~num = 1 + 1;"),
+    },
+    Explanation {
+        code: "K0304",
+        title: "invalid function",
+        desc: "Something that isn't a function was called like one.",
+        wrong: Some("let f~num = 1;\nf();"),
+        fixed: Some("fn f()~num {\n  return 1;\n}\n\nf();"),
+    },
+    Explanation {
+        code: "K0305",
+        title: "missing return",
+        desc: "A function declares a return type, but doesn't return a value.",
+        wrong: Some("fn one()~num {\n  let x~num = 1;\n}"),
+        fixed: Some("fn one()~num {\n  let x~num = 1;\n  return x;\n}"),
+    },
+    Explanation {
+        code: "K0306",
+        title: "infinite type",
+        desc: "Type inference found a type that would have to contain itself. This \
+               happens when a variable is defined in terms of itself.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0307",
+        title: "type inference failed",
+        desc: "Type inference found an expression it couldn't find a type for. This \
+               is an internal compiler error, and should be reported as a bug.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0308",
+        title: "use of an unassigned variable",
+        desc: "A variable declared without a value was read before it was assigned. \
+               Every path through the program that reaches the read has to assign the \
+               variable first, including the paths where an 'if' condition is false \
+               or a loop doesn't run.",
+        wrong: Some("let x~num;\nlet c~bool = true;\nif c {\n  x = 1;\n}\nlet y~num = x + 1;"),
+        fixed: Some(
+            "let x~num;\nlet c~bool = true;\nif c {\n  x = 1;\n} else {\n  x = 2;\n}\nlet y~num = x + 1;",
+        ),
+    },
+    Explanation {
+        code: "K0309",
+        title: "missing return on some paths",
+        desc: "A function declares a return type, and returns a value on some paths, \
+               but not on all of them. This usually happens when one branch of an 'if' \
+               returns and another doesn't, or when there is no 'else' branch.",
+        wrong: Some("fn sign(x~num)~num {\n  if x < 0 {\n    return 0 - 1;\n  }\n}"),
+        fixed: Some(
+            "fn sign(x~num)~num {\n  if x < 0 {\n    return 0 - 1;\n  }\n  return 1;\n}",
+        ),
+    },
+    Explanation {
+        code: "K0310",
+        title: "assignment to an immutable variable",
+        desc: "A variable declared with 'imm' was assigned a new value. Immutable \
+               variables keep the value they are declared with, in every scope, and \
+               the properties of an immutable class instance can't be assigned either.",
+        wrong: Some("let imm x~num = 1;\nx = 2;"),
+        fixed: Some("let x~num = 1;\nx = 2;"),
+    },
+    Explanation {
+        code: "K0311",
+        title: "assignment to an immutable property",
+        desc: "A class property declared with 'imm' was assigned a new value. Immutable \
+               properties are set when the class is constructed, and never change \
+               after that.",
+        wrong: Some(
+            "class point {\n  let imm x~num;\n}\n\nlet p~point{\n  x = 1,\n};\np.x = 2;",
+        ),
+        fixed: Some("class point {\n  let x~num;\n}\n\nlet p~point{\n  x = 1,\n};\np.x = 2;"),
+    },
+    Explanation {
+        code: "K0312",
+        title: "method changes self without 'mut'",
+        desc: "A method assigned to a property of self, or called a 'mut' method on \
+               self, without being declared 'mut' itself. Only methods declared with \
+               'mut fn' can change the instance they're called on.",
+        wrong: Some(
+            "class counter {\n  let n~num;\n\n  fn reset()~num {\n    self.n = 0;\n    return 0;\n  }\n}",
+        ),
+        fixed: Some(
+            "class counter {\n  let n~num;\n\n  mut fn reset()~num {\n    self.n = 0;\n    return 0;\n  }\n}",
+        ),
+    },
+    Explanation {
+        code: "K0313",
+        title: "'mut' method called on an immutable variable",
+        desc: "A method declared 'mut' can change the instance it's called on, so it \
+               can't be called on a class instance declared with 'imm'.",
+        wrong: Some(
+            "class counter {\n  let n~num;\n\n  mut fn reset()~num {\n    self.n = 0;\n    return 0;\n  }\n}\n\nlet imm c~counter{\n  n = 1,\n};\nc.reset();",
+        ),
+        fixed: Some(
+            "class counter {\n  let n~num;\n\n  mut fn reset()~num {\n    self.n = 0;\n    return 0;\n  }\n}\n\nlet c~counter{\n  n = 1,\n};\nc.reset();",
+        ),
+    },
+    Explanation {
+        code: "K0401",
+        title: "code generation failed",
+        desc: "Code generation was given an AST it can't generate code for. This is \
+               an internal compiler error, and should be reported as a bug.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0402",
+        title: "unknown function during code generation",
+        desc: "Code generation couldn't find a function that passed type checking. \
+               This is an internal compiler error, and should be reported as a bug.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0403",
+        title: "invalid function parameter during code generation",
+        desc: "Code generation couldn't generate a function parameter that passed type \
+               checking. This is an internal compiler error, and should be reported as \
+               a bug.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0404",
+        title: "unknown class during code generation",
+        desc: "Code generation couldn't find a class that passed type checking. This is \
+               an internal compiler error, and should be reported as a bug.",
+        wrong: None,
+        fixed: None,
+    },
+    Explanation {
+        code: "K0501",
+        title: "unused variable",
+        desc: "A variable is declared but its value is never read. Assigning to a \
+               variable doesn't count as reading it. This warning is controlled by the \
+               'unused-variables' lint.",
+        wrong: Some("fn two()~num {\n  let x~num = 1;\n  x = 2;\n  return 2;\n}\n\ntwo();"),
+        fixed: Some("fn two()~num {\n  let x~num = 1;\n  x = 2;\n  return x;\n}\n\ntwo();"),
+    },
+    Explanation {
+        code: "K0502",
+        title: "unused parameter",
+        desc: "A function parameter is never read in the function body. This warning \
+               is controlled by the 'unused-params' lint.",
+        wrong: Some("fn one(x~num)~num {\n  return 1;\n}\n\none(5);"),
+        fixed: Some("fn one()~num {\n  return 1;\n}\n\none();"),
+    },
+    Explanation {
+        code: "K0503",
+        title: "function never called",
+        desc: "A function is declared but never called. Calls a function makes to \
+               itself don't count. Class methods are reported as K0506. This warning \
+               is controlled by the 'unused-fns' lint.",
+        wrong: Some("fn one()~num {\n  return 1;\n}"),
+        fixed: Some("fn one()~num {\n  return 1;\n}\n\none();"),
+    },
+    Explanation {
+        code: "K0504",
+        title: "class property never read",
+        desc: "A class property is declared but never read, either through an instance \
+               of the class or with 'self' in a method. Setting a property doesn't count \
+               as reading it. This warning is controlled by the 'unused-props' lint.",
+        wrong: Some(
+            "class point {\n  let x~num;\n  let y~num;\n\n  fn getX()~num {\n    return self.x;\n  }\n}",
+        ),
+        fixed: Some(
+            "class point {\n  let x~num;\n\n  fn getX()~num {\n    return self.x;\n  }\n}",
+        ),
+    },
+    Explanation {
+        code: "K0505",
+        title: "unreachable statement",
+        desc: "A statement comes after a return in the same block, so it can never run. \
+               An 'if' returns when all of its branches return, including an 'else', and \
+               a 'while' whose condition is always true never finishes. \
+               This warning is controlled by the 'unreachable-code' lint.",
+        wrong: Some("fn one()~num {\n  return 1;\n  one();\n}\n\nlet y~num = one();\ny = y + 1;"),
+        fixed: Some("fn one()~num {\n  return 1;\n}\n\nlet y~num = one();\ny = y + 1;"),
+    },
+    Explanation {
+        code: "K0506",
+        title: "method never called",
+        desc: "A class method is declared but never called, either through an instance \
+               of the class or from another method. Calls a method makes to itself don't \
+               count. This warning is controlled by the 'unused-fns' lint.",
+        wrong: Some(
+            "class point {\n  let x~num;\n\n  fn getX()~num {\n    return self.x;\n  }\n}\n\nlet p~point{\n  x = 1,\n};",
+        ),
+        fixed: Some(
+            "class point {\n  let x~num;\n\n  fn getX()~num {\n    return self.x;\n  }\n}\n\nlet p~point{\n  x = 1,\n};\nlet x~num = p.getX();",
+        ),
+    },
+];
@@ -1,4 +1,4 @@
-use crate::KolgaErr;
+use crate::{diag::Diagnostic, KolgaErr};
 
 #[derive(Debug, Clone)]
 pub enum GenErrTy {
@@ -16,13 +16,19 @@ impl GenErr {
     pub fn new(ty: GenErrTy) -> GenErr {
         GenErr { ty: ty }
     }
-}
 
-impl KolgaErr for GenErr {
-    fn emit(&self) {
-        println!("kolgac: Failed to generate LLVM IR - {}", self.to_msg());
+    /// Stable code identifying the kind of error.
+    pub fn code(&self) -> &'static str {
+        match self.ty {
+            GenErrTy::InvalidAst => "K0401",
+            GenErrTy::InvalidFn(_) => "K0402",
+            GenErrTy::InvalidFnParam => "K0403",
+            GenErrTy::InvalidClass(_) => "K0404",
+        }
     }
+}
 
+impl KolgaErr for GenErr {
     fn to_msg(&self) -> String {
         match self.ty {
             GenErrTy::InvalidAst => format!("Code generation failed for provided AST"),
@@ -31,4 +37,10 @@ impl KolgaErr for GenErr {
             GenErrTy::InvalidClass(ref found) => format!("'{}' is not a valid class", found),
         }
     }
+
+    fn to_diag(&self) -> Diagnostic {
+        Diagnostic::error(format!("Failed to generate LLVM IR - {}", self.to_msg()))
+            .with_code(self.code())
+            .with_note("this is an internal compiler error, the program passed type checking")
+    }
 }
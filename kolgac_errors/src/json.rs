@@ -0,0 +1,71 @@
+use crate::diag::{Diagnostic, Span};
+
+/// Serializes a diagnostic into a single line JSON object, for editors and other
+/// tools that consume compiler output. Columns are 1-based, to match the human
+/// readable output. Fields that don't apply to a diagnostic are null rather than
+/// missing, so every object has the same shape:
+///
+/// {"code":"K0212","severity":"error","message":"...","file":"main.kol",
+///  "span":{"line":2,"column":15,"len":1,"label":"expected ';'"},
+///  "labels":[],"notes":[],"help":null}
+pub fn to_json(diag: &Diagnostic, file: Option<&str>) -> String {
+    let labels: Vec<String> = diag
+        .labels
+        .iter()
+        .map(|l| span_json(&l.span, Some(&l.msg)))
+        .collect();
+    let notes: Vec<String> = diag.notes.iter().map(|n| string(n)).collect();
+
+    format!(
+        "{{\"code\":{},\"severity\":{},\"message\":{},\"file\":{},\"span\":{},\"labels\":[{}],\"notes\":[{}],\"help\":{}}}",
+        opt_string(diag.code),
+        string(diag.level.name()),
+        string(&diag.msg),
+        opt_string(file),
+        diag.span
+            .as_ref()
+            .map(|s| span_json(s, diag.span_msg.as_deref()))
+            .unwrap_or_else(|| String::from("null")),
+        labels.join(","),
+        notes.join(","),
+        opt_string(diag.help.as_deref()),
+    )
+}
+
+fn span_json(span: &Span, label: Option<&str>) -> String {
+    format!(
+        "{{\"line\":{},\"column\":{},\"len\":{},\"label\":{}}}",
+        span.line,
+        span.pos + 1,
+        span.len,
+        opt_string(label)
+    )
+}
+
+fn opt_string(s: Option<&str>) -> String {
+    match s {
+        Some(s) => string(s),
+        None => String::from("null"),
+    }
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
@@ -1,4 +1,7 @@
-use crate::KolgaErr;
+use crate::{
+    diag::{Diagnostic, Span},
+    KolgaErr,
+};
 
 #[derive(Debug, Clone)]
 pub enum LexErrTy {
@@ -20,21 +23,44 @@ impl LexErr {
             ty: ty,
         }
     }
+
+    /// Stable code identifying the kind of error.
+    pub fn code(&self) -> &'static str {
+        match self.ty {
+            LexErrTy::UnknownChar(_) => "K0101",
+            LexErrTy::UnterminatedStr(_) => "K0102",
+        }
+    }
+
+    fn desc(&self) -> String {
+        match self.ty {
+            LexErrTy::UnknownChar(ref ch) => format!("Unrecognized character '{}'", ch),
+            LexErrTy::UnterminatedStr(ref found) => {
+                format!("Unterminated string literal '{}'", found)
+            }
+        }
+    }
 }
 
 impl KolgaErr for LexErr {
-    fn emit(&self) {
-        println!("kolgac: Parse error - {}", self.to_msg());
+    fn to_msg(&self) -> String {
+        format!("[Line {}:{}] {}", self.line, self.pos, self.desc())
     }
 
-    fn to_msg(&self) -> String {
-        let str_pos = format!("[Line {}:{}]", self.line, self.pos);
+    fn to_diag(&self) -> Diagnostic {
+        let diag = Diagnostic::error(self.desc()).with_code(self.code());
 
         match self.ty {
-            LexErrTy::UnknownChar(ref ch) => format!("{} Unrecognized character '{}'", str_pos, ch),
-            LexErrTy::UnterminatedStr(ref found) => {
-                format!("{} Unterminated string literal '{}'", str_pos, found)
-            }
+            LexErrTy::UnknownChar(_) => diag.with_span(
+                Span::new(self.line, self.pos, 1),
+                Some(String::from("not valid in kolga source")),
+            ),
+            LexErrTy::UnterminatedStr(_) => diag
+                .with_span(
+                    Span::new(self.line, self.pos, 1),
+                    Some(String::from("string literal is never closed")),
+                )
+                .with_help("add a closing '\"' to the end of the string"),
         }
     }
 }
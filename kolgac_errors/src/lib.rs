@@ -1,9 +1,25 @@
+pub mod diag;
+pub mod explain;
 pub mod gen;
+pub mod json;
 pub mod lex;
 pub mod parse;
+pub mod render;
+pub mod source;
 pub mod ty;
+pub mod warn;
+
+use crate::diag::Diagnostic;
 
 pub trait KolgaErr {
-    fn emit(&self);
+    /// Prints the error to stderr, using the shared diagnostic renderer.
+    fn emit(&self) {
+        render::emit(&self.to_diag());
+    }
+
     fn to_msg(&self) -> String;
+
+    /// Converts the error into a diagnostic, which carries the location of the
+    /// error along with any labels, notes and help text.
+    fn to_diag(&self) -> Diagnostic;
 }
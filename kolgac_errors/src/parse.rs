@@ -1,12 +1,14 @@
-use crate::KolgaErr;
+use crate::{
+    diag::{Diagnostic, Span},
+    KolgaErr,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseErrTy {
     ClassPropAssign,
     InvalidIdent(String),
     InvalidTkn(String),
     InvalidAssign(String),
-    InvalidImmAssign(String),
     InvalidTy(String),
     InvalidForStmt,
     InvalidIfStmt,
@@ -17,8 +19,8 @@ pub enum ParseErrTy {
     FnParamCntExceeded(usize),
     WrongFnParamCnt(usize, usize),
     UndeclaredSym(String),
-    UnassignedVar(String),
     TyRequired,
+    InvalidMut,
 }
 
 #[derive(Debug, Clone)]
@@ -37,83 +39,131 @@ impl ParseErr {
         }
     }
 
-    pub fn continuable(self) -> bool {
+    /// Stable code identifying the kind of error. Codes are never reused, so new
+    /// variants should take the next free number.
+    pub fn code(&self) -> &'static str {
         match self.ty {
-            ParseErrTy::TknMismatch(_, _) => false,
-            ParseErrTy::FnParamCntExceeded(_) => false,
-            ParseErrTy::TyRequired => false,
-            _ => true,
+            ParseErrTy::ClassPropAssign => "K0201",
+            ParseErrTy::InvalidIdent(_) => "K0202",
+            ParseErrTy::InvalidTkn(_) => "K0203",
+            ParseErrTy::InvalidAssign(_) => "K0204",
+            ParseErrTy::InvalidTy(_) => "K0206",
+            ParseErrTy::InvalidForStmt => "K0207",
+            ParseErrTy::InvalidIfStmt => "K0208",
+            ParseErrTy::InvalidClassProp => "K0209",
+            ParseErrTy::InvalidClassConstr => "K0210",
+            ParseErrTy::ImmDecl(_) => "K0211",
+            ParseErrTy::TknMismatch(_, _) => "K0212",
+            ParseErrTy::FnParamCntExceeded(_) => "K0213",
+            ParseErrTy::WrongFnParamCnt(_, _) => "K0214",
+            ParseErrTy::UndeclaredSym(_) => "K0215",
+            ParseErrTy::TyRequired => "K0217",
+            ParseErrTy::InvalidMut => "K0218",
+        }
+    }
+
+    fn desc(&self) -> String {
+        match self.ty {
+            ParseErrTy::InvalidIdent(ref found) => format!("Invalid identifier '{}' found", found),
+            ParseErrTy::InvalidTkn(ref found) => format!("Invalid token '{}' found", found),
+            ParseErrTy::InvalidAssign(ref found) => {
+                format!("'{}' is not a valid assignment value", found)
+            }
+            ParseErrTy::InvalidTy(ref found) => format!("'{}' is not a valid type", found),
+            ParseErrTy::InvalidForStmt => {
+                String::from("Invalid for loop: must start with a variable declaration")
+            }
+            ParseErrTy::InvalidIfStmt => {
+                String::from("Invalid if statement: cannot contain more than one else condition")
+            }
+            ParseErrTy::InvalidClassProp => String::from("Invalid class property declaration"),
+            ParseErrTy::ImmDecl(ref found) => {
+                format!(
+                    "Cannot declare immutable variable '{}' with no value",
+                    found
+                )
+            }
+            ParseErrTy::TknMismatch(ref expected, ref found) => {
+                format!("Expected token '{}', but found '{}'", expected, found)
+            }
+            ParseErrTy::FnParamCntExceeded(ref expected) => {
+                format!("Parameter count exceeds limit of {}", expected)
+            }
+            ParseErrTy::WrongFnParamCnt(ref expected, ref found) => {
+                format!("Expected {} parameters, but found {}", expected, found)
+            }
+            ParseErrTy::UndeclaredSym(ref found) => format!("Undeclared symbol '{}' found", found),
+            ParseErrTy::TyRequired => {
+                String::from("Type annotation is required for variables without assignments")
+            }
+            ParseErrTy::ClassPropAssign => {
+                String::from("Cannot assign to a var in a class declaration (use a constructor)")
+            }
+            ParseErrTy::InvalidClassConstr => String::from("Invalid class constructor"),
+            ParseErrTy::InvalidMut => String::from("Only class methods can be declared 'mut'"),
         }
     }
 }
 
 impl KolgaErr for ParseErr {
-    fn emit(&self) {
-        println!("kolgac: Parse error - {}", self.to_msg());
+    fn to_msg(&self) -> String {
+        format!("[Line {}:{}] {}", self.line, self.pos, self.desc())
     }
 
-    fn to_msg(&self) -> String {
-        let str_pos = format!("[Line {}:{}]", self.line, self.pos);
+    fn to_diag(&self) -> Diagnostic {
+        let diag = Diagnostic::error(self.desc()).with_code(self.code());
+        let span = |len: usize| Span::new(self.line, self.pos, len);
 
         match self.ty {
-            ParseErrTy::InvalidIdent(ref found) => {
-                format!("{} Invalid identifier '{}' found", str_pos, found)
-            }
+            ParseErrTy::InvalidIdent(ref found) => diag.with_span(
+                span(found.len()),
+                Some(String::from("expected an identifier")),
+            ),
             ParseErrTy::InvalidTkn(ref found) => {
-                format!("{} Invalid token '{}' found", str_pos, found)
+                diag.with_span(span(found.len()), Some(String::from("unexpected token")))
             }
             ParseErrTy::InvalidAssign(ref found) => {
-                format!("{} '{}' is not a valid assignment value", str_pos, found)
-            }
-            ParseErrTy::InvalidImmAssign(ref found) => format!(
-                "{} Cannot re-assign immutable variable '{}'",
-                str_pos, found
-            ),
-            ParseErrTy::InvalidTy(ref found) => {
-                format!("{} '{}' is not a valid type", str_pos, found)
+                diag.with_span(span(found.len()), Some(String::from("cannot be assigned")))
             }
-            ParseErrTy::InvalidForStmt => format!(
-                "{} Invalid for loop: must start with a variable declaration",
-                str_pos
+            ParseErrTy::InvalidTy(ref found) => diag
+                .with_span(span(found.len()), Some(String::from("not a type")))
+                .with_help("valid types are num, string, bool, or the name of a declared class"),
+            ParseErrTy::InvalidForStmt => diag.with_span(span(1), None).with_help(
+                "declare the loop variable first, like 'for let i~num = 0; i < 10; i = i + 1; { }'",
             ),
-            ParseErrTy::InvalidIfStmt => format!(
-                "{} Invalid if statement: cannot contain more than one else condition",
-                str_pos
-            ),
-            ParseErrTy::InvalidClassProp => {
-                format!("{} Invalid class property declaration", str_pos)
+            ParseErrTy::InvalidIfStmt => diag
+                .with_span(span(1), None)
+                .with_help("use 'elif' to check additional conditions"),
+            ParseErrTy::InvalidClassProp | ParseErrTy::FnParamCntExceeded(_) => {
+                diag.with_span(span(1), None)
             }
-            ParseErrTy::ImmDecl(ref found) => format!(
-                "{} Cannot declare immutable variable '{}' with no value",
-                str_pos, found
-            ),
-            ParseErrTy::TknMismatch(ref expected, ref found) => format!(
-                "{} Expected token '{}', but found '{}'",
-                str_pos, expected, found
-            ),
-            ParseErrTy::FnParamCntExceeded(ref expected) => {
-                format!("{} Parameter count exceeds limit of {}", str_pos, expected)
+            ParseErrTy::InvalidClassConstr => diag
+                .with_span(span(1), None)
+                .with_help("construct classes with braces, like 'let x~myClass { prop = 1, };'"),
+            ParseErrTy::ImmDecl(_) => diag
+                .with_span(span(1), Some(String::from("expected a value here")))
+                .with_note("immutable variables can never be assigned after they are declared")
+                .with_help("assign a value in the declaration, or remove 'imm'"),
+            ParseErrTy::TknMismatch(ref expected, ref found) => {
+                diag.with_span(span(found.len()), Some(format!("expected '{}'", expected)))
             }
-            ParseErrTy::WrongFnParamCnt(ref expected, ref found) => format!(
-                "{} Expected {} parameters, but found {}",
-                str_pos, expected, found
-            ),
-            ParseErrTy::UnassignedVar(ref found) => format!(
-                "{} Cannot reference un-assigned variable '{}'",
-                str_pos, found
-            ),
-            ParseErrTy::UndeclaredSym(ref found) => {
-                format!("{} Undeclared symbol '{}' found", str_pos, found)
+            ParseErrTy::WrongFnParamCnt(ref expected, _) => {
+                diag.with_span(span(1), Some(format!("expected {} parameters", expected)))
             }
-            ParseErrTy::TyRequired => format!(
-                "{} Type annotation is required for variables without assignments",
-                str_pos
-            ),
-            ParseErrTy::ClassPropAssign => format!(
-                "{} Cannot assign to a var in a class declaration (use a constructor)",
-                str_pos
+            ParseErrTy::UndeclaredSym(ref found) => diag.with_span(
+                span(found.len()),
+                Some(String::from("not found in this scope")),
             ),
-            ParseErrTy::InvalidClassConstr => format!("{} Invalid class constructor", str_pos),
+            ParseErrTy::TyRequired => diag
+                .with_span(span(1), None)
+                .with_help("add a type annotation, like 'let x~num;'"),
+            ParseErrTy::ClassPropAssign => diag
+                .with_span(span(1), None)
+                .with_help("set property values when constructing the class instead"),
+            ParseErrTy::InvalidMut => diag
+                .with_span(span(3), None)
+                .with_note("'mut' lets a method change the properties of self")
+                .with_help("remove 'mut', or move the function into a class"),
         }
     }
 }
@@ -0,0 +1,240 @@
+use crate::{
+    diag::{Diagnostic, Level, Span},
+    json,
+    source::{self, Source},
+};
+
+use std::{
+    cell::Cell,
+    env,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Output format used when emitting diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// Rendered with source snippets, for people reading a terminal.
+    Human,
+
+    /// One JSON object per line, for editors and other tools.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ErrorFormat, String> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "invalid error format '{}' (expected 'human' or 'json')",
+                s
+            )),
+        }
+    }
+}
+
+thread_local! {
+    static FORMAT: Cell<ErrorFormat> = const { Cell::new(ErrorFormat::Human) };
+}
+
+/// Sets the format used by emit for the rest of the compilation.
+pub fn set_format(fmt: ErrorFormat) {
+    FORMAT.with(|f| f.set(fmt));
+}
+
+/// Renders diagnostics in a human readable format, similar to rustc. Each diagnostic
+/// prints a header, the location of the error, and the source lines for the primary
+/// span and any labels, with carets under the primary span and dashes under labels.
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Renderer {
+        Renderer { color }
+    }
+
+    /// Creates a renderer for stderr. Colors are used only if stderr is a terminal,
+    /// and the NO_COLOR environment variable isn't set.
+    pub fn stderr() -> Renderer {
+        let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+        Renderer::new(color)
+    }
+
+    pub fn render(&self, diag: &Diagnostic, src: Option<&Source>) -> String {
+        let mut out = String::new();
+        let lvl_color = self.level_color(diag.level);
+
+        let header = match diag.code {
+            Some(code) => format!("{}[{}]", diag.level.name(), code),
+            None => diag.level.name().to_string(),
+        };
+        out.push_str(&format!(
+            "{}{}",
+            self.paint(lvl_color, &header),
+            self.paint(BOLD, &format!(": {}", diag.msg))
+        ));
+        out.push('\n');
+
+        // Collect every annotation we want to draw. The primary span always comes first
+        // so it's drawn first when there are several annotations on the same line.
+        let mut annotations: Vec<(&Span, Option<&str>, bool)> = Vec::new();
+        if let Some(span) = &diag.span {
+            annotations.push((span, diag.span_msg.as_deref(), true));
+        }
+        for label in &diag.labels {
+            annotations.push((&label.span, Some(label.msg.as_str()), false));
+        }
+
+        let max_line = annotations.iter().map(|a| a.0.line).max().unwrap_or(0);
+        let width = max_line.to_string().len();
+        let pad = " ".repeat(width);
+
+        if let Some(span) = &diag.span {
+            let loc = match src {
+                Some(s) => format!("{}:{}:{}", s.name, span.line, span.pos + 1),
+                None => format!("{}:{}", span.line, span.pos + 1),
+            };
+            out.push_str(&format!("{}{} {}\n", pad, self.paint(BLUE, "-->"), loc));
+        }
+
+        let mut lines: Vec<usize> = annotations.iter().map(|a| a.0.line).collect();
+        lines.sort();
+        lines.dedup();
+
+        let snippets: Vec<(usize, &str)> = match src {
+            Some(s) => lines
+                .iter()
+                .filter_map(|l| s.line(*l).map(|text| (*l, text)))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        if !snippets.is_empty() {
+            let gutter = self.paint(BLUE, "|");
+            out.push_str(&format!("{} {}\n", pad, gutter));
+
+            let mut prev_line = None;
+            for (line_num, text) in snippets {
+                // Mark gaps between the lines we print, so it's clear they
+                // aren't next to each other in the source.
+                if let Some(prev) = prev_line {
+                    if line_num > prev + 1 {
+                        out.push_str(&format!("{}\n", self.paint(BLUE, "...")));
+                    }
+                }
+                prev_line = Some(line_num);
+
+                out.push_str(&format!(
+                    "{} {} {}\n",
+                    self.paint(BLUE, &format!("{:>w$}", line_num, w = width)),
+                    gutter,
+                    text
+                ));
+
+                for (span, msg, primary) in annotations.iter().filter(|a| a.0.line == line_num) {
+                    let (mark, color) = match primary {
+                        true => ("^", lvl_color),
+                        false => ("-", BLUE),
+                    };
+                    let underline = format!(
+                        "{}{}",
+                        mark.repeat(underline_len(text, span)),
+                        msg.map(|m| format!(" {}", m)).unwrap_or_default()
+                    );
+
+                    out.push_str(&format!(
+                        "{} {} {}{}\n",
+                        pad,
+                        gutter,
+                        indent(text, span.pos),
+                        self.paint(color, &underline)
+                    ));
+                }
+            }
+
+            if !diag.notes.is_empty() || diag.help.is_some() {
+                out.push_str(&format!("{} {}\n", pad, gutter));
+            }
+        }
+
+        for note in &diag.notes {
+            out.push_str(&format!(
+                "{} = {}: {}\n",
+                pad,
+                self.paint(BOLD, "note"),
+                note
+            ));
+        }
+
+        if let Some(help) = &diag.help {
+            out.push_str(&format!(
+                "{} = {}: {}\n",
+                pad,
+                self.paint(BOLD, "help"),
+                help
+            ));
+        }
+
+        out
+    }
+
+    fn level_color(&self, level: Level) -> &'static str {
+        match level {
+            Level::Error => RED,
+            Level::Warning => YELLOW,
+        }
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        match self.color {
+            true => format!("{}{}{}", color, text, RESET),
+            false => text.to_string(),
+        }
+    }
+}
+
+/// Renders a diagnostic against the registered source file and prints it to stderr,
+/// in whichever format was set with set_format.
+pub fn emit(diag: &Diagnostic) {
+    let rendered = match FORMAT.with(|f| f.get()) {
+        ErrorFormat::Human => source::with_source(|src| Renderer::stderr().render(diag, src)),
+        ErrorFormat::Json => {
+            source::with_source(|src| json::to_json(diag, src.map(|s| s.name.as_str())))
+        }
+    };
+    let stderr = io::stderr();
+    let mut handle = stderr.lock();
+    writeln!(handle, "{}", rendered).ok();
+}
+
+/// Builds the whitespace needed to line up an underline with a position in the
+/// source line. Tabs in the source are kept so the underline stays aligned.
+fn indent(text: &str, pos: usize) -> String {
+    let mut chars = text.chars();
+    (0..pos)
+        .map(|_| match chars.next() {
+            Some('\t') => '\t',
+            _ => ' ',
+        })
+        .collect()
+}
+
+/// Clamps the span length so the underline doesn't run past the end of the line.
+fn underline_len(text: &str, span: &Span) -> usize {
+    let line_len = text.chars().count();
+    if span.pos >= line_len {
+        return 1;
+    }
+
+    span.len.min(line_len - span.pos).max(1)
+}
@@ -0,0 +1,44 @@
+use std::cell::RefCell;
+
+/// The source file currently being compiled. Diagnostics read from this to print
+/// the lines they refer to.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub name: String,
+    pub lines: Vec<String>,
+}
+
+impl Source {
+    pub fn new(name: &str, text: &str) -> Source {
+        Source {
+            name: name.to_string(),
+            lines: text.lines().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    /// Returns the text of a line. Lines start at 1, to match the line numbers
+    /// stored in tokens.
+    pub fn line(&self, num: usize) -> Option<&str> {
+        if num == 0 {
+            return None;
+        }
+
+        self.lines.get(num - 1).map(String::as_str)
+    }
+}
+
+thread_local! {
+    static SOURCE: RefCell<Option<Source>> = const { RefCell::new(None) };
+}
+
+/// Registers the source file for the current compilation. This should be called
+/// before lexing, so that errors emitted from any stage can show source snippets.
+/// If no source is registered, diagnostics are still emitted, just without snippets.
+pub fn register(name: &str, text: &str) {
+    SOURCE.with(|s| *s.borrow_mut() = Some(Source::new(name, text)));
+}
+
+/// Runs the provided function with the registered source, if there is one.
+pub fn with_source<T>(f: impl FnOnce(Option<&Source>) -> T) -> T {
+    SOURCE.with(|s| f(s.borrow().as_ref()))
+}
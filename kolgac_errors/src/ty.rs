@@ -1,6 +1,9 @@
-use crate::KolgaErr;
+use crate::{
+    diag::{Diagnostic, Label, Span},
+    KolgaErr,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypeErrTy {
     TyMismatch(String, String),
     PropMismatch(String, String),
@@ -9,6 +12,12 @@ pub enum TypeErrTy {
     InvalidRet(String, String),
     InfiniteType,
     InvalidInfer,
+    UnassignedVar(String),
+    MissingRet(String, String, String),
+    ImmAssign(String),
+    ImmPropAssign(String, String),
+    ImmSelf(String, String),
+    ImmMutCall(String, String),
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +25,15 @@ pub struct TypeErr {
     pub line: usize,
     pub pos: usize,
     pub ty: TypeErrTy,
+
+    /// Length of the source text the error points at.
+    pub len: usize,
+
+    /// Related locations, like where a mismatched variable was declared.
+    pub labels: Vec<Label>,
+
+    /// Id of the AST node the error was found in, if it's known.
+    pub node: Option<usize>,
 }
 
 impl TypeErr {
@@ -24,26 +42,54 @@ impl TypeErr {
             line: line,
             pos: pos,
             ty: ty,
+            len: 1,
+            labels: Vec::new(),
+            node: None,
         }
     }
-}
 
-impl KolgaErr for TypeErr {
-    fn emit(&self) {
-        println!("kolgac: Type error - {}", self.to_msg());
+    pub fn with_len(mut self, len: usize) -> TypeErr {
+        self.len = len;
+        self
     }
 
-    fn to_msg(&self) -> String {
-        let str_pos = format!("[Line {}:{}]", self.line, self.pos);
+    pub fn with_label(mut self, line: usize, pos: usize, len: usize, msg: &str) -> TypeErr {
+        self.labels.push(Label::new(Span::new(line, pos, len), msg));
+        self
+    }
+
+    pub fn with_node(mut self, id: usize) -> TypeErr {
+        self.node = Some(id);
+        self
+    }
 
+    /// Stable code identifying the kind of error.
+    pub fn code(&self) -> &'static str {
         match self.ty {
-            TypeErrTy::TyMismatch(ref expected, ref found) => format!(
-                "{} type mismatch: Wanted {} but found {}",
-                str_pos, expected, found
-            ),
+            TypeErrTy::TyMismatch(_, _) => "K0301",
+            TypeErrTy::PropMismatch(_, _) => "K0302",
+            TypeErrTy::BinOpMismatch(..) => "K0303",
+            TypeErrTy::InvalidFn(_) => "K0304",
+            TypeErrTy::InvalidRet(_, _) => "K0305",
+            TypeErrTy::InfiniteType => "K0306",
+            TypeErrTy::InvalidInfer => "K0307",
+            TypeErrTy::UnassignedVar(_) => "K0308",
+            TypeErrTy::MissingRet(..) => "K0309",
+            TypeErrTy::ImmAssign(_) => "K0310",
+            TypeErrTy::ImmPropAssign(_, _) => "K0311",
+            TypeErrTy::ImmSelf(_, _) => "K0312",
+            TypeErrTy::ImmMutCall(_, _) => "K0313",
+        }
+    }
+
+    fn desc(&self) -> String {
+        match self.ty {
+            TypeErrTy::TyMismatch(ref expected, ref found) => {
+                format!("type mismatch: Wanted {} but found {}", expected, found)
+            }
             TypeErrTy::PropMismatch(ref expected, ref found) => format!(
-                "{} property type mismatch: Wanted {} but found {}",
-                str_pos, expected, found
+                "property type mismatch: Wanted {} but found {}",
+                expected, found
             ),
             TypeErrTy::BinOpMismatch(
                 ref op,
@@ -52,20 +98,107 @@ impl KolgaErr for TypeErr {
                 ref found_frst,
                 ref found_scnd,
             ) => format!(
-                "{} operator mismatch: {} wants {} and {}, but found {} and {}",
-                str_pos, op, expected_frst, expected_scnd, found_frst, found_scnd
+                "operator mismatch: {} wants {} and {}, but found {} and {}",
+                op, expected_frst, expected_scnd, found_frst, found_scnd
             ),
-            TypeErrTy::InvalidFn(ref found) => {
-                format!("{} invalid function name {}", str_pos, found)
-            }
+            TypeErrTy::InvalidFn(ref found) => format!("invalid function name {}", found),
             TypeErrTy::InvalidRet(ref found, ref ret_ty) => format!(
-                "{} {} expects a return type of {}, but no return found",
-                str_pos, found, ret_ty
+                "{} expects a return type of {}, but no return found",
+                found, ret_ty
             ),
             TypeErrTy::InfiniteType => {
                 String::from("Could not infer types (infinite recursive type found)")
             }
-            TypeErrTy::InvalidInfer => String::from("Could not infer types due to invalid input"),
+            TypeErrTy::InvalidInfer => String::from("Could not infer a type for this expression"),
+            TypeErrTy::UnassignedVar(ref name) => {
+                format!("Variable '{}' may be read before it is assigned", name)
+            }
+            TypeErrTy::MissingRet(ref name, ref ret_ty, _) => format!(
+                "{} expects a return type of {}, but not every path returns",
+                name, ret_ty
+            ),
+            TypeErrTy::ImmAssign(ref name) => {
+                format!("Cannot assign to immutable variable '{}'", name)
+            }
+            TypeErrTy::ImmPropAssign(ref class, ref prop) => format!(
+                "Cannot assign to immutable property '{}' of class '{}'",
+                prop, class
+            ),
+            TypeErrTy::ImmSelf(ref method, _) => format!(
+                "Cannot change self in method '{}', which isn't declared 'mut'",
+                method
+            ),
+            TypeErrTy::ImmMutCall(ref method, ref var) => format!(
+                "Cannot call 'mut' method '{}' on immutable variable '{}'",
+                method, var
+            ),
+        }
+    }
+}
+
+impl KolgaErr for TypeErr {
+    fn to_msg(&self) -> String {
+        format!("[Line {}:{}] {}", self.line, self.pos, self.desc())
+    }
+
+    fn to_diag(&self) -> Diagnostic {
+        let span = Span::new(self.line, self.pos, self.len);
+        let diag = Diagnostic::error(self.desc())
+            .with_code(self.code())
+            .with_labels(&self.labels);
+
+        match self.ty {
+            TypeErrTy::TyMismatch(ref expected, ref found)
+            | TypeErrTy::PropMismatch(ref expected, ref found) => diag.with_span(
+                span,
+                Some(format!("expected {}, found {}", expected, found)),
+            ),
+            TypeErrTy::BinOpMismatch(ref op, ..) => diag
+                .with_span(span, Some(format!("invalid operands for '{}'", op)))
+                .with_note("arithmetic and comparison operators need numbers, logical operators need bools"),
+            TypeErrTy::InvalidFn(_) => {
+                diag.with_span(span, Some(String::from("not a declared function")))
+            }
+            TypeErrTy::InvalidRet(_, ref ret_ty) => diag
+                .with_span(span, None)
+                .with_help(&format!("add a return statement that returns a {}", ret_ty)),
+            TypeErrTy::InfiniteType => diag
+                .with_span(span, None)
+                .with_note("a variable can't be defined in terms of itself"),
+            TypeErrTy::InvalidInfer => diag.with_span(span, None),
+            TypeErrTy::UnassignedVar(ref name) => diag
+                .with_span(span, Some(String::from("read here")))
+                .with_help(&format!(
+                    "assign a value to '{}' on every path before reading it",
+                    name
+                )),
+            TypeErrTy::MissingRet(_, ref ret_ty, ref path) => diag
+                .with_span(span, Some(path.clone()))
+                .with_help(&format!(
+                    "add a return statement that returns a {} to this path",
+                    ret_ty
+                )),
+            TypeErrTy::ImmAssign(ref name) => diag
+                .with_span(span, Some(String::from("cannot be assigned")))
+                .with_help(&format!(
+                    "remove 'imm' from the declaration of '{}' to allow assigning to it",
+                    name
+                )),
+            TypeErrTy::ImmPropAssign(..) => diag
+                .with_span(span, Some(String::from("cannot assign to this property")))
+                .with_note("immutable properties can only be set when the class is constructed"),
+            TypeErrTy::ImmSelf(ref method, ref change) => diag
+                .with_span(span, Some(change.clone()))
+                .with_help(&format!(
+                    "declare the method with 'mut fn {}' to let it change self",
+                    method
+                )),
+            TypeErrTy::ImmMutCall(_, ref var) => diag
+                .with_span(span, Some(String::from("this call can change the variable")))
+                .with_help(&format!(
+                    "remove 'imm' from the declaration of '{}' to allow calling 'mut' methods",
+                    var
+                )),
         }
     }
 }
@@ -0,0 +1,268 @@
+use crate::{
+    diag::{Diagnostic, Label, Span},
+    KolgaErr,
+};
+
+use std::collections::HashMap;
+
+/// A lint is a named group of warnings that can be allowed, warned about,
+/// or denied as a whole from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariables,
+    UnusedParams,
+    UnusedFns,
+    UnusedProps,
+    UnreachableCode,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedVariables,
+        Lint::UnusedParams,
+        Lint::UnusedFns,
+        Lint::UnusedProps,
+        Lint::UnreachableCode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused-variables",
+            Lint::UnusedParams => "unused-params",
+            Lint::UnusedFns => "unused-fns",
+            Lint::UnusedProps => "unused-props",
+            Lint::UnreachableCode => "unreachable-code",
+        }
+    }
+
+    /// Finds the lints for a name passed on the command line. This is either the
+    /// name of a single lint, or a group name: 'unused' refers to every unused
+    /// lint, and 'warnings' refers to every lint.
+    pub fn from_name(name: &str) -> Option<Vec<Lint>> {
+        match name {
+            "unused" => Some(
+                Lint::ALL
+                    .iter()
+                    .filter(|l| l.name().starts_with("unused-"))
+                    .copied()
+                    .collect(),
+            ),
+            "warnings" => Some(Lint::ALL.to_vec()),
+            _ => Lint::ALL
+                .iter()
+                .find(|l| l.name() == name)
+                .map(|l| vec![*l]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    /// Don't report the warning at all.
+    Allow,
+
+    /// Report the warning, but keep compiling.
+    Warn,
+
+    /// Report the warning as an error, and stop compiling.
+    Deny,
+}
+
+impl LintLevel {
+    fn flag(&self) -> &'static str {
+        match self {
+            LintLevel::Allow => "-A",
+            LintLevel::Warn => "-W",
+            LintLevel::Deny => "-D",
+        }
+    }
+}
+
+/// The level each lint is reported at. Every lint starts at Warn, and can be
+/// changed with the -W, -A and -D driver flags.
+#[derive(Debug, Clone)]
+pub struct LintLevels {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintLevels {
+    pub fn new() -> LintLevels {
+        LintLevels {
+            levels: Lint::ALL.iter().map(|l| (*l, LintLevel::Warn)).collect(),
+        }
+    }
+
+    /// Sets the level for a lint or lint group by name. Returns an error message
+    /// if the name isn't a known lint.
+    pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        match Lint::from_name(name) {
+            Some(lints) => {
+                for lint in lints {
+                    self.levels.insert(lint, level);
+                }
+                Ok(())
+            }
+            None => Err(format!(
+                "unknown lint '{}' passed to {} (expected one of: warnings, unused, {})",
+                name,
+                level.flag(),
+                Lint::ALL
+                    .iter()
+                    .map(|l| l.name())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels[&lint]
+    }
+
+    /// Sets the level of each warning from its lint, and removes warnings
+    /// that are allowed.
+    pub fn apply(&self, warnings: Vec<Warn>) -> Vec<Warn> {
+        warnings
+            .into_iter()
+            .filter_map(|w| match self.level(w.ty.lint()) {
+                LintLevel::Allow => None,
+                level => Some(w.with_level(level)),
+            })
+            .collect()
+    }
+}
+
+impl Default for LintLevels {
+    fn default() -> LintLevels {
+        LintLevels::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WarnTy {
+    UnusedVar(String),
+    UnusedParam(String),
+    UnusedFn(String),
+    UnusedProp(String, String),
+
+    /// A class method that's never called. Holds the class name and the method
+    /// name.
+    UnusedMethod(String, String),
+
+    /// A statement after a return. Holds the position of the statement that
+    /// always returns.
+    UnreachableCode(usize, usize),
+}
+
+impl WarnTy {
+    pub fn lint(&self) -> Lint {
+        match self {
+            WarnTy::UnusedVar(_) => Lint::UnusedVariables,
+            WarnTy::UnusedParam(_) => Lint::UnusedParams,
+            WarnTy::UnusedFn(_) => Lint::UnusedFns,
+            WarnTy::UnusedProp(_, _) => Lint::UnusedProps,
+            WarnTy::UnusedMethod(_, _) => Lint::UnusedFns,
+            WarnTy::UnreachableCode(_, _) => Lint::UnreachableCode,
+        }
+    }
+}
+
+/// A warning is a problem in the source that doesn't stop compilation, unless
+/// its lint is denied.
+#[derive(Debug, Clone)]
+pub struct Warn {
+    pub line: usize,
+    pub pos: usize,
+    pub ty: WarnTy,
+    pub level: LintLevel,
+}
+
+impl Warn {
+    pub fn new(line: usize, pos: usize, ty: WarnTy) -> Warn {
+        Warn {
+            line,
+            pos,
+            ty,
+            level: LintLevel::Warn,
+        }
+    }
+
+    pub fn with_level(mut self, level: LintLevel) -> Warn {
+        self.level = level;
+        self
+    }
+
+    pub fn is_denied(&self) -> bool {
+        self.level == LintLevel::Deny
+    }
+
+    /// Stable code identifying the kind of warning.
+    pub fn code(&self) -> &'static str {
+        match self.ty {
+            WarnTy::UnusedVar(_) => "K0501",
+            WarnTy::UnusedParam(_) => "K0502",
+            WarnTy::UnusedFn(_) => "K0503",
+            WarnTy::UnusedProp(_, _) => "K0504",
+            WarnTy::UnreachableCode(_, _) => "K0505",
+            WarnTy::UnusedMethod(_, _) => "K0506",
+        }
+    }
+
+    /// Length of the source text the warning points at. Unused symbols point at
+    /// their name, and unreachable code points at the start of the statement.
+    fn len(&self) -> usize {
+        match self.ty {
+            WarnTy::UnusedVar(ref name)
+            | WarnTy::UnusedParam(ref name)
+            | WarnTy::UnusedFn(ref name)
+            | WarnTy::UnusedProp(_, ref name)
+            | WarnTy::UnusedMethod(_, ref name) => name.len(),
+            WarnTy::UnreachableCode(_, _) => 1,
+        }
+    }
+
+    fn labels(&self) -> Vec<Label> {
+        match self.ty {
+            WarnTy::UnreachableCode(line, pos) => vec![Label::new(
+                Span::new(line, pos, 1),
+                "any code following this statement is unreachable",
+            )],
+            _ => Vec::new(),
+        }
+    }
+
+    fn desc(&self) -> String {
+        match self.ty {
+            WarnTy::UnusedVar(ref name) => format!("unused variable '{}'", name),
+            WarnTy::UnusedParam(ref name) => format!("unused parameter '{}'", name),
+            WarnTy::UnusedFn(ref name) => format!("function '{}' is never called", name),
+            WarnTy::UnusedProp(ref class, ref name) => {
+                format!("property '{}' of class '{}' is never read", name, class)
+            }
+            WarnTy::UnusedMethod(ref class, ref name) => {
+                format!("method '{}' of class '{}' is never called", name, class)
+            }
+            WarnTy::UnreachableCode(_, _) => String::from("unreachable statement"),
+        }
+    }
+}
+
+impl KolgaErr for Warn {
+    fn to_msg(&self) -> String {
+        format!("[Line {}:{}] {}", self.line, self.pos, self.desc())
+    }
+
+    fn to_diag(&self) -> Diagnostic {
+        let lint = self.ty.lint().name();
+        let diag = match self.level {
+            LintLevel::Deny => Diagnostic::error(self.desc())
+                .with_note(&format!("'-D {}' turns this warning into an error", lint)),
+            _ => Diagnostic::warning(self.desc())
+                .with_note(&format!("use '-A {}' to hide this warning", lint)),
+        };
+
+        diag.with_code(self.code())
+            .with_span(Span::new(self.line, self.pos, self.len()), None)
+            .with_labels(&self.labels())
+    }
+}
@@ -0,0 +1,130 @@
+use kolgac_errors::{
+    explain::{self, EXPLANATIONS, RETIRED},
+    gen::{GenErr, GenErrTy},
+    lex::{LexErr, LexErrTy},
+    parse::{ParseErr, ParseErrTy},
+    ty::{TypeErr, TypeErrTy},
+    warn::{Warn, WarnTy},
+};
+
+fn s() -> String {
+    String::from("x")
+}
+
+#[test]
+fn every_code_has_explanation() {
+    let mut codes = vec![
+        LexErr::new(1, 0, LexErrTy::UnknownChar('@')).code(),
+        LexErr::new(1, 0, LexErrTy::UnterminatedStr(s())).code(),
+    ];
+
+    let parse_tys = vec![
+        ParseErrTy::ClassPropAssign,
+        ParseErrTy::InvalidIdent(s()),
+        ParseErrTy::InvalidTkn(s()),
+        ParseErrTy::InvalidAssign(s()),
+        ParseErrTy::InvalidTy(s()),
+        ParseErrTy::InvalidForStmt,
+        ParseErrTy::InvalidIfStmt,
+        ParseErrTy::InvalidClassProp,
+        ParseErrTy::InvalidClassConstr,
+        ParseErrTy::ImmDecl(s()),
+        ParseErrTy::TknMismatch(s(), s()),
+        ParseErrTy::FnParamCntExceeded(64),
+        ParseErrTy::WrongFnParamCnt(1, 2),
+        ParseErrTy::UndeclaredSym(s()),
+        ParseErrTy::TyRequired,
+        ParseErrTy::InvalidMut,
+    ];
+    codes.extend(
+        parse_tys
+            .into_iter()
+            .map(|ty| ParseErr::new(1, 0, ty).code()),
+    );
+
+    let ty_tys = vec![
+        TypeErrTy::TyMismatch(s(), s()),
+        TypeErrTy::PropMismatch(s(), s()),
+        TypeErrTy::BinOpMismatch(s(), s(), s(), s(), s()),
+        TypeErrTy::InvalidFn(s()),
+        TypeErrTy::InvalidRet(s(), s()),
+        TypeErrTy::InfiniteType,
+        TypeErrTy::InvalidInfer,
+        TypeErrTy::UnassignedVar(s()),
+        TypeErrTy::MissingRet(s(), s(), s()),
+        TypeErrTy::ImmAssign(s()),
+        TypeErrTy::ImmPropAssign(s(), s()),
+        TypeErrTy::ImmSelf(s(), s()),
+        TypeErrTy::ImmMutCall(s(), s()),
+    ];
+    codes.extend(ty_tys.into_iter().map(|ty| TypeErr::new(1, 0, ty).code()));
+
+    let gen_tys = vec![
+        GenErrTy::InvalidAst,
+        GenErrTy::InvalidFn(s()),
+        GenErrTy::InvalidFnParam,
+        GenErrTy::InvalidClass(s()),
+    ];
+    codes.extend(gen_tys.into_iter().map(|ty| GenErr::new(ty).code()));
+
+    let warn_tys = vec![
+        WarnTy::UnusedVar(s()),
+        WarnTy::UnusedParam(s()),
+        WarnTy::UnusedFn(s()),
+        WarnTy::UnusedProp(s(), s()),
+        WarnTy::UnreachableCode(1, 0),
+        WarnTy::UnusedMethod(s(), s()),
+    ];
+    codes.extend(warn_tys.into_iter().map(|ty| Warn::new(1, 0, ty).code()));
+
+    let mut deduped = codes.clone();
+    deduped.sort();
+    deduped.dedup();
+    assert_eq!(deduped.len(), codes.len(), "error codes must be unique");
+
+    for code in &codes {
+        assert!(
+            explain::lookup(code).is_some(),
+            "no explanation for {}",
+            code
+        );
+    }
+
+    // Retired codes keep their entry, and nothing emits them anymore.
+    for code in RETIRED {
+        assert!(explain::lookup(code).is_some(), "no entry for {}", code);
+        assert!(
+            !codes.contains(code),
+            "{} is retired but still emitted",
+            code
+        );
+    }
+    assert_eq!(codes.len() + RETIRED.len(), EXPLANATIONS.len());
+}
+
+#[test]
+fn lookup_ignores_case() {
+    let explanation = explain::lookup("k0301").unwrap();
+    assert_eq!(explanation.code, "K0301");
+    assert!(explain::lookup("K9999").is_none());
+}
+
+#[test]
+fn render_explanation() {
+    let rendered = explain::lookup("K0212").unwrap().render();
+    let expected = "\
+K0212: unexpected token
+
+The parser expected a specific token, like ';' or '}', but found a different one. The most common cause is a missing semicolon.
+
+Erroneous code example:
+
+    let x~num = 1
+
+Corrected:
+
+    let x~num = 1;
+";
+
+    assert_eq!(rendered, expected);
+}
@@ -0,0 +1,63 @@
+use kolgac_errors::{
+    gen::{GenErr, GenErrTy},
+    json,
+    parse::{ParseErr, ParseErrTy},
+    render::ErrorFormat,
+    ty::{TypeErr, TypeErrTy},
+    KolgaErr,
+};
+
+#[test]
+fn json_parse_err() {
+    let err = ParseErr::new(
+        2,
+        14,
+        ParseErrTy::TknMismatch(String::from(";"), String::from("EOF")),
+    );
+
+    let out = json::to_json(&err.to_diag(), Some("test.kol"));
+    let expected = concat!(
+        r#"{"code":"K0212","severity":"error","message":"Expected token ';', but found 'EOF'","#,
+        r#""file":"test.kol","span":{"line":2,"column":15,"len":3,"label":"expected ';'"},"#,
+        r#""labels":[],"notes":[],"help":null}"#
+    );
+
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn json_type_err_w_label_and_escapes() {
+    let err = TypeErr::new(
+        3,
+        4,
+        TypeErrTy::TyMismatch(String::from("\"num\""), String::from("bool")),
+    )
+    .with_label(1, 4, 1, "declared here");
+
+    let out = json::to_json(&err.to_diag(), None);
+    let expected = concat!(
+        r#"{"code":"K0301","severity":"error","#,
+        r#""message":"type mismatch: Wanted \"num\" but found bool","file":null,"#,
+        r#""span":{"line":3,"column":5,"len":1,"label":"expected \"num\", found bool"},"#,
+        r#""labels":[{"line":1,"column":5,"len":1,"label":"declared here"}],"notes":[],"help":null}"#
+    );
+
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn json_no_span() {
+    let err = GenErr::new(GenErrTy::InvalidAst);
+    let out = json::to_json(&err.to_diag(), Some("test.kol"));
+
+    assert!(out.starts_with(r#"{"code":"K0401","severity":"error""#));
+    assert!(out.contains(r#""span":null"#));
+    assert!(!out.contains('\n'));
+}
+
+#[test]
+fn error_format_from_str() {
+    assert_eq!("human".parse::<ErrorFormat>(), Ok(ErrorFormat::Human));
+    assert_eq!("json".parse::<ErrorFormat>(), Ok(ErrorFormat::Json));
+    assert!("xml".parse::<ErrorFormat>().is_err());
+}
@@ -0,0 +1,67 @@
+use kolgac_errors::{
+    parse::{ParseErr, ParseErrTy},
+    render::Renderer,
+    source::Source,
+    ty::{TypeErr, TypeErrTy},
+    KolgaErr,
+};
+
+#[test]
+fn render_parse_err() {
+    let src = Source::new("test.kol", "let x~num = 1;\nlet y~bool = 3\n");
+    let err = ParseErr::new(
+        2,
+        14,
+        ParseErrTy::TknMismatch(String::from(";"), String::from("EOF")),
+    );
+
+    let rendered = Renderer::new(false).render(&err.to_diag(), Some(&src));
+    let expected = "\
+error[K0212]: Expected token ';', but found 'EOF'
+ --> test.kol:2:15
+  |
+2 | let y~bool = 3
+  |               ^ expected ';'
+";
+
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn render_type_err_w_label() {
+    let src = Source::new("test.kol", "fn f(a~num)~num {\n  return \"s\";\n}\n");
+    let err = TypeErr::new(
+        2,
+        9,
+        TypeErrTy::TyMismatch(String::from("number"), String::from("string")),
+    )
+    .with_len(3)
+    .with_label(1, 3, 1, "return type declared here");
+
+    let rendered = Renderer::new(false).render(&err.to_diag(), Some(&src));
+    let expected = "\
+error[K0301]: type mismatch: Wanted number but found string
+ --> test.kol:2:10
+  |
+1 | fn f(a~num)~num {
+  |    - return type declared here
+2 |   return \"s\";
+  |          ^^^ expected number, found string
+";
+
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn render_w_help_no_source() {
+    let err = ParseErr::new(3, 13, ParseErrTy::TyRequired);
+
+    let rendered = Renderer::new(false).render(&err.to_diag(), None);
+    let expected = "\
+error[K0217]: Type annotation is required for variables without assignments
+ --> 3:14
+  = help: add a type annotation, like 'let x~num;'
+";
+
+    assert_eq!(rendered, expected);
+}
@@ -0,0 +1,170 @@
+use crate::instr::{IROp, Instr};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// A basic block: a run of instructions with a single entry at the top, ending
+/// in a terminator. Edges to other blocks are indices into the blocks of the
+/// function that holds this one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub lbl: String,
+    pub instrs: Vec<Instr>,
+    pub preds: Vec<usize>,
+    pub succs: Vec<usize>,
+}
+
+impl Block {
+    pub fn new(lbl: &str) -> Block {
+        Block {
+            lbl: String::from(lbl),
+            instrs: Vec::new(),
+            preds: Vec::new(),
+            succs: Vec::new(),
+        }
+    }
+}
+
+/// A function and its control flow graph. The first block is the entry, and has
+/// the function's name as its label. The top level code is a function too,
+/// named INIT_LBL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    /// Splits the instructions of a function into blocks, and connects them.
+    /// Blocks keep the order they have in the instruction stream, so a block
+    /// whose last instruction isn't an unconditional jump falls through to the
+    /// one after it.
+    pub fn new(name: &str, instrs: &[Instr]) -> Function {
+        let mut blocks: Vec<Block> = Vec::new();
+        for instr in instrs {
+            if blocks.last().is_none_or(|b| b.lbl != instr.lbl) {
+                blocks.push(Block::new(&instr.lbl));
+            }
+            blocks.last_mut().unwrap().instrs.push(instr.clone());
+        }
+
+        let mut func = Function {
+            name: String::from(name),
+            blocks,
+        };
+        func.build_edges();
+        func
+    }
+
+    /// Recomputes the predecessors and successors of every block from their
+    /// last instructions. Passes that change jumps should call this afterwards.
+    pub fn build_edges(&mut self) {
+        let idxs: HashMap<String, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, b)| (b.lbl.clone(), idx))
+            .collect();
+
+        for block in &mut self.blocks {
+            block.preds.clear();
+            block.succs.clear();
+        }
+
+        for idx in 0..self.blocks.len() {
+            let mut succs = Vec::new();
+            let last = self.blocks[idx].instrs.last();
+
+            if let Some(target) = last.and_then(|instr| instr.jmp_target()) {
+                if let Some(target_idx) = idxs.get(target) {
+                    succs.push(*target_idx);
+                }
+            }
+
+            let falls_through =
+                last.is_none_or(|instr| !matches!(instr.opcode, IROp::Jmp | IROp::Ret));
+            if falls_through && idx + 1 < self.blocks.len() && !succs.contains(&(idx + 1)) {
+                succs.push(idx + 1);
+            }
+
+            for succ in &succs {
+                self.blocks[*succ].preds.push(idx);
+            }
+            self.blocks[idx].succs = succs;
+        }
+    }
+
+    pub fn block_idx(&self, lbl: &str) -> Option<usize> {
+        self.blocks.iter().position(|b| b.lbl == lbl)
+    }
+
+    /// Flattens the blocks back into a labeled instruction stream.
+    pub fn instrs(&self) -> Vec<Instr> {
+        self.blocks
+            .iter()
+            .flat_map(|b| b.instrs.iter().cloned())
+            .collect()
+    }
+
+    /// Writes the function as a DOT subgraph, with one node per block.
+    fn write_dot(&self, out: &mut String) {
+        writeln!(out, "  subgraph \"cluster_{}\" {{", escape(&self.name)).unwrap();
+        writeln!(out, "    label=\"{}\";", escape(&self.name)).unwrap();
+
+        for block in &self.blocks {
+            let mut text = format!("{}:\\l", escape(&block.lbl));
+            for instr in &block.instrs {
+                text.push_str(&format!("  {}\\l", escape(&instr.body())));
+            }
+            writeln!(out, "    \"{}\" [label=\"{}\"];", escape(&block.lbl), text).unwrap();
+        }
+
+        for block in &self.blocks {
+            for succ in &block.succs {
+                writeln!(
+                    out,
+                    "    \"{}\" -> \"{}\";",
+                    escape(&block.lbl),
+                    escape(&self.blocks[*succ].lbl)
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out, "  }}").unwrap();
+    }
+}
+
+/// Splits the output of IRGen into functions. Every function starts with a
+/// block labeled with its name, and generated block labels start with '.', so
+/// any other label starts a new function.
+pub fn build(ir: &[Instr]) -> Vec<Function> {
+    let mut fns = Vec::new();
+    let mut start = 0;
+
+    for idx in 1..=ir.len() {
+        let starts_fn =
+            idx == ir.len() || (ir[idx].lbl != ir[idx - 1].lbl && !ir[idx].lbl.starts_with('.'));
+        if starts_fn {
+            fns.push(Function::new(&ir[start].lbl, &ir[start..idx]));
+            start = idx;
+        }
+    }
+
+    fns
+}
+
+/// Dumps the control flow graphs of every function in Graphviz DOT format. Each
+/// function is drawn in its own cluster.
+pub fn to_dot(fns: &[Function]) -> String {
+    let mut out = String::from("digraph kir {\n  node [shape=box, fontname=monospace];\n");
+    for func in fns {
+        func.write_dot(&mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,73 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp};
+use crate::pass::Pass;
+
+use std::collections::HashMap;
+
+/// Copy propagation. In SSA form, a register that's only a copy of another
+/// register, or a phi that picks the same register on every path, always holds
+/// the same value as the original, so uses can read the original instead. The
+/// copies are left for DCE to remove.
+pub struct CopyProp;
+
+impl Pass for CopyProp {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        let mut copies: HashMap<String, String> = HashMap::new();
+        for instr in func.blocks.iter().flat_map(|b| b.instrs.iter()) {
+            let src = match (&instr.opcode, &instr.op1) {
+                (IROp::Mv, Some(IRArg::Reg(src))) => Some(src.clone()),
+                (IROp::Phi, Some(IRArg::Phi(args))) => same_reg(args, instr.def()),
+                _ => None,
+            };
+
+            if let (Some(dest), Some(src)) = (instr.def(), src) {
+                copies.insert(dest.clone(), src);
+            }
+        }
+
+        for instr in func.blocks.iter_mut().flat_map(|b| b.instrs.iter_mut()) {
+            for reg in instr.uses_mut() {
+                *reg = resolve(&copies, reg);
+            }
+        }
+    }
+}
+
+/// The register a phi picks on every path, if there's only one. A loop that
+/// doesn't change the value passes the phi's own result back to it, which
+/// doesn't count.
+fn same_reg(args: &[(IRArg, String)], dest: Option<&String>) -> Option<String> {
+    let mut regs = args
+        .iter()
+        .map(|(arg, _)| match arg {
+            IRArg::Reg(reg) => Some(reg),
+            _ => None,
+        })
+        .filter(|reg| *reg != dest);
+
+    let first = regs.next()??;
+    if regs.all(|reg| reg == Some(first)) {
+        Some(first.clone())
+    } else {
+        None
+    }
+}
+
+/// Follows a chain of copies back to the original register. Phis can make the
+/// chain loop back on itself, so we stop if we see a register twice.
+fn resolve(copies: &HashMap<String, String>, reg: &str) -> String {
+    let mut curr = reg;
+    let mut seen = vec![reg];
+    while let Some(src) = copies.get(curr) {
+        if seen.contains(&src.as_str()) {
+            break;
+        }
+        seen.push(src);
+        curr = src;
+    }
+    String::from(curr)
+}
@@ -0,0 +1,144 @@
+use crate::cfg::Function;
+use crate::dom::DomTree;
+use crate::instr::{IRArg, IROp, Instr};
+use crate::pass::Pass;
+
+use std::collections::HashMap;
+
+/// Local common subexpression elimination. Within a block, an instruction that
+/// computes the same thing as an earlier one is replaced with a copy of the
+/// earlier result. Loads count too, until something could have changed memory.
+/// Expects SSA form, so operands with the same name hold the same value.
+pub struct LocalCse;
+
+impl Pass for LocalCse {
+    fn name(&self) -> &'static str {
+        "local-cse"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        let mut copies: HashMap<String, String> = HashMap::new();
+        for block in &mut func.blocks {
+            let mut avail: HashMap<String, String> = HashMap::new();
+            let mut loads: HashMap<String, String> = HashMap::new();
+
+            for instr in &mut block.instrs {
+                match instr.opcode {
+                    IROp::St => loads.retain(|key, _| !key.starts_with("ld ")),
+                    IROp::StField => loads.retain(|key, _| !key.starts_with("ldf ")),
+                    IROp::Call => loads.clear(),
+                    IROp::Ld | IROp::LdField => reuse(instr, &mut loads, &mut copies),
+                    _ => reuse(instr, &mut avail, &mut copies),
+                }
+            }
+        }
+    }
+}
+
+/// Global common subexpression elimination. An instruction that computes the
+/// same thing as one in a dominating block is replaced with a copy of the
+/// earlier result, since that result is available on every path here. Only
+/// instructions that don't touch memory are considered, since a store could
+/// happen anywhere in between.
+pub struct GlobalCse;
+
+impl Pass for GlobalCse {
+    fn name(&self) -> &'static str {
+        "global-cse"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        if func.blocks.is_empty() {
+            return;
+        }
+
+        let dom = DomTree::new(func);
+        let mut copies: HashMap<String, String> = HashMap::new();
+        let mut work = vec![(0, HashMap::new())];
+        while let Some((b, mut avail)) = work.pop() {
+            for instr in &mut func.blocks[b].instrs {
+                if !matches!(instr.opcode, IROp::Ld | IROp::LdField) {
+                    reuse(instr, &mut avail, &mut copies);
+                }
+            }
+
+            for child in &dom.children[b] {
+                work.push((*child, avail.clone()));
+            }
+        }
+    }
+}
+
+/// Replaces an instruction with a copy if the same value is already available,
+/// or records it as available otherwise.
+///
+/// Operands are first renamed through the copies seen so far, so that values
+/// computed from equal operands are found equal too. This is safe in SSA form,
+/// since the original of a copy dominates every use of the copy.
+fn reuse(
+    instr: &mut Instr,
+    avail: &mut HashMap<String, String>,
+    copies: &mut HashMap<String, String>,
+) {
+    for reg in instr.uses_mut() {
+        if let Some(orig) = copies.get(reg) {
+            *reg = orig.clone();
+        }
+    }
+
+    let dest = match instr.def() {
+        Some(dest) => dest.clone(),
+        None => return,
+    };
+
+    if let (IROp::Mv, Some(IRArg::Reg(src))) = (instr.opcode, &instr.op1) {
+        copies.insert(dest, src.clone());
+        return;
+    }
+
+    let key = match key(instr) {
+        Some(key) => key,
+        None => return,
+    };
+
+    match avail.get(&key) {
+        Some(prev) => {
+            copies.insert(dest, prev.clone());
+            instr.opcode = IROp::Mv;
+            instr.op1 = Some(IRArg::Reg(prev.clone()));
+            instr.op2 = None;
+        }
+        None => {
+            avail.insert(key, dest);
+        }
+    }
+}
+
+/// What an instruction computes, as text, if it can be shared. Operands of
+/// commutative operations are sorted, so 'add r1 r2' matches 'add r2 r1'.
+fn key(instr: &Instr) -> Option<String> {
+    match instr.opcode {
+        // Every instance is a different object, and phis depend on where we
+        // came from.
+        IROp::New | IROp::Phi => return None,
+        op if op.has_side_effects() => return None,
+        _ => (),
+    }
+
+    let mut ops: Vec<String> = instr
+        .op1
+        .iter()
+        .chain(instr.op2.iter())
+        .map(|op| format!("{}", op))
+        .collect();
+
+    let commutes = matches!(
+        instr.opcode,
+        IROp::Add | IROp::Mul | IROp::EqEq | IROp::Neq | IROp::LogAnd | IROp::LogOr
+    );
+    if commutes {
+        ops.sort();
+    }
+
+    Some(format!("{} {}", instr.opcode, ops.join(" ")))
+}
@@ -0,0 +1,46 @@
+use crate::cfg::Function;
+use crate::pass::Pass;
+
+use std::collections::{HashMap, HashSet};
+
+/// Dead code elimination. Instructions with side effects are live, along with
+/// every instruction that computes a value they use, and so on. Everything else
+/// is removed, including computations that only feed each other around a loop.
+/// Expects SSA form, so each register has a single definition.
+pub struct Dce;
+
+impl Pass for Dce {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        let mut defs: HashMap<String, Vec<String>> = HashMap::new();
+        let mut work: Vec<String> = Vec::new();
+        for instr in func.blocks.iter().flat_map(|b| b.instrs.iter()) {
+            let uses = instr.uses().into_iter().cloned().collect();
+            match instr.def() {
+                Some(dest) if !instr.opcode.has_side_effects() => {
+                    defs.insert(dest.clone(), uses);
+                }
+                _ => work.extend(uses),
+            }
+        }
+
+        let mut live: HashSet<String> = HashSet::new();
+        while let Some(reg) = work.pop() {
+            if live.insert(reg.clone()) {
+                if let Some(uses) = defs.get(&reg) {
+                    work.extend(uses.iter().cloned());
+                }
+            }
+        }
+
+        for block in &mut func.blocks {
+            block.instrs.retain(|instr| match instr.def() {
+                Some(dest) if !instr.opcode.has_side_effects() => live.contains(dest),
+                _ => true,
+            });
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use crate::cfg::Function;
+
+/// The dominator tree of a function, and the dominance frontier of each block.
+/// Blocks are referred to by their index in the function. The entry block, and
+/// any block that can't be reached from it, have no immediate dominator.
+///
+/// Dominators are computed with the iterative algorithm from Cooper, Harvey and
+/// Kennedy's "A Simple, Fast Dominance Algorithm", which is quick enough for the
+/// size of graphs we build for a single function.
+#[derive(Debug)]
+pub struct DomTree {
+    pub idom: Vec<Option<usize>>,
+    pub children: Vec<Vec<usize>>,
+    pub frontiers: Vec<Vec<usize>>,
+
+    /// Reachable blocks in reverse postorder. Every block comes after its
+    /// dominators.
+    pub rpo: Vec<usize>,
+}
+
+impl DomTree {
+    pub fn new(func: &Function) -> DomTree {
+        let cnt = func.blocks.len();
+        let rpo = reverse_postorder(func);
+
+        // Position of each block in the reverse postorder. Dominators always
+        // come first, so walking up the tree means walking to smaller numbers.
+        let mut order = vec![usize::MAX; cnt];
+        for (pos, b) in rpo.iter().enumerate() {
+            order[*b] = pos;
+        }
+
+        let mut doms: Vec<Option<usize>> = vec![None; cnt];
+        if let Some(entry) = rpo.first() {
+            doms[*entry] = Some(*entry);
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in rpo.iter().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for p in &func.blocks[*b].preds {
+                    if doms[*p].is_none() {
+                        continue;
+                    }
+                    new_idom = match new_idom {
+                        None => Some(*p),
+                        Some(curr) => Some(intersect(&doms, &order, *p, curr)),
+                    };
+                }
+
+                if new_idom.is_some() && doms[*b] != new_idom {
+                    doms[*b] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut idom = doms;
+        if let Some(entry) = rpo.first() {
+            idom[*entry] = None;
+        }
+
+        let mut children = vec![Vec::new(); cnt];
+        for b in &rpo {
+            if let Some(parent) = idom[*b] {
+                children[parent].push(*b);
+            }
+        }
+
+        let mut tree = DomTree {
+            idom,
+            children,
+            frontiers: vec![Vec::new(); cnt],
+            rpo,
+        };
+        tree.build_frontiers(func);
+        tree
+    }
+
+    /// A block is in the frontier of each block that dominates one of its
+    /// predecessors, up to (but not including) its own immediate dominator.
+    fn build_frontiers(&mut self, func: &Function) {
+        for b in self.rpo.clone() {
+            let preds: Vec<usize> = func.blocks[b]
+                .preds
+                .iter()
+                .cloned()
+                .filter(|p| self.is_reachable(*p))
+                .collect();
+            if preds.len() < 2 {
+                continue;
+            }
+
+            for p in preds {
+                let mut runner = Some(p);
+                while let Some(r) = runner {
+                    if Some(r) == self.idom[b] {
+                        break;
+                    }
+                    if !self.frontiers[r].contains(&b) {
+                        self.frontiers[r].push(b);
+                    }
+                    runner = self.idom[r];
+                }
+            }
+        }
+    }
+
+    pub fn is_reachable(&self, b: usize) -> bool {
+        self.rpo.first() == Some(&b) || self.idom[b].is_some()
+    }
+
+    /// True if every path from the entry to b goes through a. Every block
+    /// dominates itself.
+    pub fn dominates(&self, a: usize, b: usize) -> bool {
+        let mut curr = Some(b);
+        while let Some(c) = curr {
+            if c == a {
+                return true;
+            }
+            curr = self.idom[c];
+        }
+        false
+    }
+}
+
+fn intersect(doms: &[Option<usize>], order: &[usize], b1: usize, b2: usize) -> usize {
+    let mut finger1 = b1;
+    let mut finger2 = b2;
+    while finger1 != finger2 {
+        while order[finger1] > order[finger2] {
+            finger1 = doms[finger1].unwrap();
+        }
+        while order[finger2] > order[finger1] {
+            finger2 = doms[finger2].unwrap();
+        }
+    }
+    finger1
+}
+
+/// Orders the blocks reachable from the entry so that each block comes before
+/// its successors, except along back edges.
+pub fn reverse_postorder(func: &Function) -> Vec<usize> {
+    let mut order = Vec::new();
+    if func.blocks.is_empty() {
+        return order;
+    }
+
+    let mut visited = vec![false; func.blocks.len()];
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+
+    // Each stack entry is a block and the next successor to visit from it.
+    while let Some((b, next)) = stack.pop() {
+        match func.blocks[b].succs.get(next) {
+            Some(succ) => {
+                stack.push((b, next + 1));
+                if !visited[*succ] {
+                    visited[*succ] = true;
+                    stack.push((*succ, 0));
+                }
+            }
+            None => order.push(b),
+        }
+    }
+
+    order.reverse();
+    order
+}
@@ -1,13 +1,21 @@
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum IROp {
     Mv,
     St,
     Ld,
 
+    /// Unconditional jump to the label in the first operand.
     Jmp,
+
+    /// Jumps to the label in the second operand if the first operand is true,
+    /// otherwise falls through to the next block.
     JmpE,
+
+    /// Jumps to the label in the second operand if the first operand is false,
+    /// otherwise falls through to the next block.
     JmpNe,
 
     Add,
@@ -24,7 +32,105 @@ pub enum IROp {
     LogAnd,
     LogOr,
 
+    Neg,
+    Not,
+
+    /// Passes an argument to the next call. Arguments are passed in order.
+    Param,
+
+    /// Calls the function named by the first operand, with the number of
+    /// arguments in the second operand.
+    Call,
+
+    /// Reads an argument inside the called function, by its position.
+    Arg,
+
+    /// Creates a class instance, with the number of properties in the second
+    /// operand.
+    New,
+
+    /// Loads a property from a class instance.
+    LdField,
+
+    /// Stores the second operand into a property of a class instance.
+    StField,
+
     Ret,
+
+    /// Picks a value depending on which block we came from. Only found in SSA
+    /// form, at the start of a block.
+    Phi,
+}
+
+impl IROp {
+    /// True for instructions that end a basic block. Conditional jumps fall
+    /// through to the next block when they don't jump.
+    pub fn is_terminator(&self) -> bool {
+        matches!(self, IROp::Jmp | IROp::JmpE | IROp::JmpNe | IROp::Ret)
+    }
+
+    /// True for instructions that do more than compute their result, so they
+    /// can't be removed or merged even if the result isn't used.
+    pub fn has_side_effects(&self) -> bool {
+        self.is_terminator() || matches!(self, IROp::St | IROp::StField | IROp::Param | IROp::Call)
+    }
+
+    /// True for instructions that write a register.
+    pub fn has_result(&self) -> bool {
+        !self.is_terminator() && !matches!(self, IROp::St | IROp::StField | IROp::Param)
+    }
+
+    /// The operands this operator takes, and how many of them can be left out
+    /// from the end.
+    pub fn operands(&self) -> (&'static [OperandKind], usize) {
+        use OperandKind::*;
+
+        match self {
+            IROp::Mv | IROp::Neg | IROp::Not | IROp::Param => (&[Val], 0),
+            IROp::St => (&[Loc, Val], 0),
+            IROp::Ld => (&[Loc], 0),
+            IROp::Jmp => (&[Lbl], 0),
+            IROp::JmpE | IROp::JmpNe => (&[Val, Lbl], 0),
+            IROp::Call | IROp::New => (&[Lbl, Num], 0),
+            IROp::Arg => (&[Num], 0),
+            IROp::LdField => (&[Field], 0),
+            IROp::StField => (&[Field, Val], 0),
+            IROp::Ret => (&[Val], 1),
+            IROp::Phi => (&[Phi], 0),
+            _ => (&[Val, Val], 0),
+        }
+    }
+}
+
+/// What an operand slot of an instruction holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperandKind {
+    /// A literal or a register.
+    Val,
+    Lbl,
+    Num,
+
+    /// A local variable, or a global.
+    Loc,
+    Field,
+    Phi,
+}
+
+impl OperandKind {
+    /// True if the argument can be passed in this slot.
+    pub fn accepts(&self, arg: &IRArg) -> bool {
+        match self {
+            OperandKind::Val => matches!(
+                arg,
+                IRArg::Num(_) | IRArg::Bool(_) | IRArg::Str(_) | IRArg::Reg(_)
+            ),
+            OperandKind::Lbl => matches!(arg, IRArg::Lbl(_)),
+            OperandKind::Num => matches!(arg, IRArg::Num(_)),
+            OperandKind::Loc => matches!(arg, IRArg::Var(_) | IRArg::Global(_)),
+            OperandKind::Field => matches!(arg, IRArg::Field(..)),
+            OperandKind::Phi => matches!(arg, IRArg::Phi(_)),
+        }
+    }
 }
 
 impl fmt::Display for IROp {
@@ -48,26 +154,108 @@ impl fmt::Display for IROp {
             IROp::Neq => "neq".to_string(),
             IROp::LogAnd => "and".to_string(),
             IROp::LogOr => "or".to_string(),
+            IROp::Neg => "neg".to_string(),
+            IROp::Not => "not".to_string(),
+            IROp::Param => "param".to_string(),
+            IROp::Call => "call".to_string(),
+            IROp::Arg => "arg".to_string(),
+            IROp::New => "new".to_string(),
+            IROp::LdField => "ldf".to_string(),
+            IROp::StField => "stf".to_string(),
             IROp::Ret => "ret".to_string(),
+            IROp::Phi => "phi".to_string(),
         };
 
         write!(f, "{}", formatted)
     }
 }
 
-#[derive(Debug)]
+impl FromStr for IROp {
+    type Err = String;
+
+    /// Reads an operator by the name it's printed with.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let op = match s {
+            "mv" => IROp::Mv,
+            "st" => IROp::St,
+            "ld" => IROp::Ld,
+            "jmp" => IROp::Jmp,
+            "jmpe" => IROp::JmpE,
+            "jmpne" => IROp::JmpNe,
+            "add" => IROp::Add,
+            "sub" => IROp::Sub,
+            "mul" => IROp::Mul,
+            "div" => IROp::Div,
+            "lt" => IROp::Lt,
+            "gt" => IROp::Gt,
+            "eq" => IROp::EqEq,
+            "lteq" => IROp::LtEq,
+            "gteq" => IROp::GtEq,
+            "neq" => IROp::Neq,
+            "and" => IROp::LogAnd,
+            "or" => IROp::LogOr,
+            "neg" => IROp::Neg,
+            "not" => IROp::Not,
+            "param" => IROp::Param,
+            "call" => IROp::Call,
+            "arg" => IROp::Arg,
+            "new" => IROp::New,
+            "ldf" => IROp::LdField,
+            "stf" => IROp::StField,
+            "ret" => IROp::Ret,
+            "phi" => IROp::Phi,
+            _ => return Err(format!("unknown operator '{}'", s)),
+        };
+
+        Ok(op)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum IRArg {
     Num(f64),
+    Bool(bool),
+
+    /// String literal.
     Str(String),
+
+    /// Virtual register.
     Reg(String),
+
+    /// Local variable or parameter of the current function.
+    Var(String),
+
+    /// Global variable.
+    Global(String),
+
+    /// Basic block label or function name.
+    Lbl(String),
+
+    /// Property of the class instance in a register, by its position in the
+    /// class declaration.
+    Field(String, usize),
+
+    /// Operands of a phi instruction: the value coming from each predecessor,
+    /// with the predecessor's label.
+    Phi(Vec<(IRArg, String)>),
 }
 
 impl fmt::Display for IRArg {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let formatted = match self {
             IRArg::Num(n) => format!("{}", n),
-            IRArg::Str(s) => s.to_string(),
+            IRArg::Bool(b) => format!("{}", b),
+            IRArg::Str(s) => format!("{:?}", s),
             IRArg::Reg(r) => r.to_string(),
+            IRArg::Var(v) => v.to_string(),
+            IRArg::Global(g) => format!("@{}", g),
+            IRArg::Lbl(l) => l.to_string(),
+            IRArg::Field(r, idx) => format!("{}.{}", r, idx),
+            IRArg::Phi(args) => args
+                .iter()
+                .map(|(arg, lbl)| format!("[{} {}]", arg, lbl))
+                .collect::<Vec<String>>()
+                .join(" "),
         };
 
         write!(f, "{}", formatted)
@@ -77,40 +265,29 @@ impl fmt::Display for IRArg {
 /// Instr is the representation of an instruction in the three-address
 /// linear code for kolga's ir. Each instruction expects two operands,
 /// an operator, and a result (or resulting location).
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Instr {
     /// First operand.
-    op1: Option<IRArg>,
+    pub op1: Option<IRArg>,
 
     /// Second operand. This is optional for some instructions (ie. mv, jmp).
-    op2: Option<IRArg>,
+    pub op2: Option<IRArg>,
 
     /// Operator.
-    opcode: IROp,
+    pub opcode: IROp,
 
-    /// Result destination.
-    result: IRArg,
+    /// Result destination. Instructions that don't produce a value, like
+    /// jumps and stores, have no result.
+    pub result: Option<IRArg>,
 
-    /// Current label. This encodes the top level label
-    /// of the instruction.
-    lbl: String,
+    /// Current label. This encodes the top level label of the instruction,
+    /// which is the label of the basic block it belongs to.
+    pub lbl: String,
 }
 
 impl fmt::Display for Instr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let dest = format!("{}", self.result);
-        let opc = format!("{}", self.opcode);
-        let op1 = match &self.op1 {
-            Some(o) => format!("{}", o),
-            None => "".to_string(),
-        };
-        let op2 = match &self.op2 {
-            Some(o) => format!("{}", o),
-            None => "".to_string(),
-        };
-        let lbl = self.lbl.to_string();
-
-        write!(f, "{} = {} {} {} : {}", dest, opc, op1, op2, lbl)
+        write!(f, "{} : {}", self.body(), self.lbl)
     }
 }
 
@@ -119,7 +296,7 @@ impl Instr {
         op1: Option<IRArg>,
         op2: Option<IRArg>,
         opc: IROp,
-        result: IRArg,
+        result: Option<IRArg>,
         lbl: String,
     ) -> Instr {
         Instr {
@@ -130,4 +307,78 @@ impl Instr {
             lbl: lbl,
         }
     }
+
+    /// The instruction without its label, like 'r3 = add r1 r2'.
+    pub fn body(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(dest) = &self.result {
+            parts.push(format!("{} =", dest));
+        }
+        parts.push(format!("{}", self.opcode));
+        for op in self.op1.iter().chain(self.op2.iter()) {
+            parts.push(format!("{}", op));
+        }
+
+        parts.join(" ")
+    }
+
+    /// The label a jump can go to, if this is a jump.
+    pub fn jmp_target(&self) -> Option<&str> {
+        let target = match self.opcode {
+            IROp::Jmp => self.op1.as_ref(),
+            IROp::JmpE | IROp::JmpNe => self.op2.as_ref(),
+            _ => None,
+        };
+
+        match target {
+            Some(IRArg::Lbl(lbl)) => Some(lbl),
+            _ => None,
+        }
+    }
+
+    /// The registers this instruction reads, in operand order.
+    pub fn uses(&self) -> Vec<&String> {
+        let mut regs = Vec::new();
+        for op in self.op1.iter().chain(self.op2.iter()) {
+            match op {
+                IRArg::Reg(reg) | IRArg::Field(reg, _) => regs.push(reg),
+                IRArg::Phi(args) => {
+                    for (arg, _) in args {
+                        if let IRArg::Reg(reg) = arg {
+                            regs.push(reg);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        regs
+    }
+
+    /// Mutable version of uses, for passes that rename registers.
+    pub fn uses_mut(&mut self) -> Vec<&mut String> {
+        let mut regs = Vec::new();
+        for op in self.op1.iter_mut().chain(self.op2.iter_mut()) {
+            match op {
+                IRArg::Reg(reg) | IRArg::Field(reg, _) => regs.push(reg),
+                IRArg::Phi(args) => {
+                    for (arg, _) in args {
+                        if let IRArg::Reg(reg) = arg {
+                            regs.push(reg);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        regs
+    }
+
+    /// The register this instruction assigns, if any.
+    pub fn def(&self) -> Option<&String> {
+        match &self.result {
+            Some(IRArg::Reg(reg)) => Some(reg),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,481 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp, Instr};
+use crate::irgen::INIT_LBL;
+use crate::sccp;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Deepest the call stack can get before we give up, so runaway recursion
+/// reports an error instead of using up all memory.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// A runtime value. Class instances live on the interpreter's heap, and are
+/// referred to by their index there.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Bool(bool),
+    Str(String),
+    Obj(usize),
+
+    /// The result of a function that returns nothing, or a property that
+    /// hasn't been set.
+    Void,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum InterpErr {
+    UndefinedFn(String),
+    UndefinedLbl(String),
+    UndefinedReg(String),
+    UndefinedVar(String),
+    InvalidOperands(IROp),
+    InvalidInstr(String),
+    StackOverflow,
+}
+
+impl fmt::Display for InterpErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpErr::UndefinedFn(name) => write!(f, "call to undefined function '{}'", name),
+            InterpErr::UndefinedLbl(lbl) => write!(f, "jump to undefined label '{}'", lbl),
+            InterpErr::UndefinedReg(reg) => write!(f, "register '{}' read before it's set", reg),
+            InterpErr::UndefinedVar(var) => write!(f, "variable '{}' read before it's set", var),
+            InterpErr::InvalidOperands(op) => write!(f, "invalid operands for '{}'", op),
+            InterpErr::InvalidInstr(instr) => write!(f, "invalid instruction '{}'", instr),
+            InterpErr::StackOverflow => write!(f, "call stack overflow"),
+        }
+    }
+}
+
+/// A class instance: the class name and property values, in declaration order.
+#[derive(Debug)]
+struct Object {
+    class: String,
+    props: Vec<Value>,
+}
+
+/// State of a single call.
+#[derive(Debug)]
+struct Frame {
+    func: usize,
+    block: usize,
+    instr: usize,
+
+    /// Label of the block we jumped from, so phis can pick their operand.
+    prev_lbl: String,
+
+    regs: HashMap<String, Value>,
+    locals: HashMap<String, Value>,
+    args: Vec<Value>,
+
+    /// Arguments passed with 'param' so far, for the next call.
+    params: Vec<Value>,
+
+    /// Register in the caller that receives the return value.
+    ret_dest: Option<String>,
+}
+
+/// Runs KIR directly. Execution starts at the top level code, which calls into
+/// everything else. Globals keep their values after the program ends, so they
+/// can be inspected as its result.
+pub struct Interp<'f> {
+    fns: &'f [Function],
+    fn_idxs: HashMap<String, usize>,
+    blocks: Vec<HashMap<String, usize>>,
+
+    stack: Vec<Frame>,
+    heap: Vec<Object>,
+
+    globals: HashMap<String, Value>,
+
+    /// Names of globals, in the order they were first set.
+    global_order: Vec<String>,
+}
+
+impl<'f> Interp<'f> {
+    pub fn new(fns: &'f [Function]) -> Interp<'f> {
+        Interp {
+            fns,
+            fn_idxs: fns
+                .iter()
+                .enumerate()
+                .map(|(idx, f)| (f.name.clone(), idx))
+                .collect(),
+            blocks: fns
+                .iter()
+                .map(|f| {
+                    f.blocks
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, b)| (b.lbl.clone(), idx))
+                        .collect()
+                })
+                .collect(),
+            stack: Vec::new(),
+            heap: Vec::new(),
+            globals: HashMap::new(),
+            global_order: Vec::new(),
+        }
+    }
+
+    /// Runs the top level code to the end.
+    pub fn run(&mut self) -> Result<(), InterpErr> {
+        self.call(INIT_LBL, Vec::new(), None)?;
+        self.exec()
+    }
+
+    /// Every global and its final value, in the order they were first set.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.global_order
+            .iter()
+            .map(|name| (name.clone(), self.globals[name].clone()))
+            .collect()
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// Formats a value for printing. Class instances show their class and the
+    /// values of their properties.
+    pub fn fmt_val(&self, val: &Value) -> String {
+        match val {
+            Value::Num(n) => format!("{}", n),
+            Value::Bool(b) => format!("{}", b),
+            Value::Str(s) => s.clone(),
+            Value::Obj(idx) => {
+                let obj = &self.heap[*idx];
+                let props: Vec<String> = obj.props.iter().map(|p| self.fmt_val(p)).collect();
+                format!("{} {{ {} }}", obj.class, props.join(", "))
+            }
+            Value::Void => String::from("void"),
+        }
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        ret_dest: Option<String>,
+    ) -> Result<(), InterpErr> {
+        if self.stack.len() >= MAX_CALL_DEPTH {
+            return Err(InterpErr::StackOverflow);
+        }
+
+        let func = match self.fn_idxs.get(name) {
+            Some(func) => *func,
+            None => return Err(InterpErr::UndefinedFn(String::from(name))),
+        };
+
+        self.stack.push(Frame {
+            func,
+            block: 0,
+            instr: 0,
+            prev_lbl: String::new(),
+            regs: HashMap::new(),
+            locals: HashMap::new(),
+            args,
+            params: Vec::new(),
+            ret_dest,
+        });
+        Ok(())
+    }
+
+    /// The dispatch loop. Runs instructions from the top frame until the stack
+    /// is empty.
+    fn exec(&mut self) -> Result<(), InterpErr> {
+        let fns = self.fns;
+        while let Some(frame) = self.stack.last() {
+            let block = &fns[frame.func].blocks[frame.block];
+            let instr = match block.instrs.get(frame.instr) {
+                Some(instr) => instr,
+                None => {
+                    // Falling off the end of a block continues with the next one.
+                    self.goto_next()?;
+                    continue;
+                }
+            };
+
+            self.step(instr)?;
+        }
+
+        Ok(())
+    }
+
+    fn step(&mut self, instr: &'f Instr) -> Result<(), InterpErr> {
+        match instr.opcode {
+            IROp::Jmp => {
+                let lbl = self.lbl(&instr.op1)?;
+                return self.goto(&lbl);
+            }
+            IROp::JmpE | IROp::JmpNe => {
+                let cond = match self.val(&instr.op1)? {
+                    Value::Bool(cond) => cond,
+                    _ => return Err(InterpErr::InvalidOperands(instr.opcode)),
+                };
+
+                return if cond == (instr.opcode == IROp::JmpE) {
+                    let lbl = self.lbl(&instr.op2)?;
+                    self.goto(&lbl)
+                } else {
+                    self.goto_next()
+                };
+            }
+            IROp::Ret => {
+                let ret_val = match &instr.op1 {
+                    Some(_) => self.val(&instr.op1)?,
+                    None => Value::Void,
+                };
+
+                let frame = self.stack.pop().unwrap();
+                if let (Some(dest), Some(caller)) = (frame.ret_dest, self.stack.last_mut()) {
+                    caller.regs.insert(dest, ret_val);
+                }
+                return Ok(());
+            }
+            IROp::Call => {
+                let name = self.lbl(&instr.op1)?;
+                let argc = match &instr.op2 {
+                    Some(IRArg::Num(n)) => *n as usize,
+                    _ => return Err(InterpErr::InvalidOperands(instr.opcode)),
+                };
+
+                let frame = self.frame();
+                if frame.params.len() < argc {
+                    return Err(InterpErr::InvalidOperands(instr.opcode));
+                }
+                let start = frame.params.len() - argc;
+                let args = frame.params.split_off(start);
+                frame.instr += 1;
+
+                return self.call(&name, args, instr.def().cloned());
+            }
+            _ => (),
+        }
+
+        let result = match instr.opcode {
+            IROp::Mv => Some(self.val(&instr.op1)?),
+            IROp::Ld => Some(self.load(&instr.op1)?),
+            IROp::St => {
+                let val = self.val(&instr.op2)?;
+                self.store(&instr.op1, val)?;
+                None
+            }
+            IROp::Neg | IROp::Not => {
+                let val = self.val(&instr.op1)?;
+                Some(self.arith(instr.opcode, val, None)?)
+            }
+            IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Lt
+            | IROp::Gt
+            | IROp::EqEq
+            | IROp::LtEq
+            | IROp::GtEq
+            | IROp::Neq
+            | IROp::LogAnd
+            | IROp::LogOr => {
+                let lhs = self.val(&instr.op1)?;
+                let rhs = self.val(&instr.op2)?;
+                Some(self.arith(instr.opcode, lhs, Some(rhs))?)
+            }
+            IROp::Param => {
+                let val = self.val(&instr.op1)?;
+                self.frame().params.push(val);
+                None
+            }
+            IROp::Arg => {
+                let idx = match &instr.op1 {
+                    Some(IRArg::Num(n)) => *n as usize,
+                    _ => return Err(InterpErr::InvalidOperands(instr.opcode)),
+                };
+                match self.frame().args.get(idx) {
+                    Some(arg) => Some(arg.clone()),
+                    None => return Err(InterpErr::InvalidOperands(instr.opcode)),
+                }
+            }
+            IROp::New => {
+                let class = self.lbl(&instr.op1)?;
+                let cnt = match &instr.op2 {
+                    Some(IRArg::Num(n)) => *n as usize,
+                    _ => return Err(InterpErr::InvalidOperands(instr.opcode)),
+                };
+                self.heap.push(Object {
+                    class,
+                    props: vec![Value::Void; cnt],
+                });
+                Some(Value::Obj(self.heap.len() - 1))
+            }
+            IROp::LdField => {
+                let (obj, idx) = self.field(&instr.op1, instr)?;
+                Some(self.heap[obj].props[idx].clone())
+            }
+            IROp::StField => {
+                let val = self.val(&instr.op2)?;
+                let (obj, idx) = self.field(&instr.op1, instr)?;
+                self.heap[obj].props[idx] = val;
+                None
+            }
+            IROp::Phi => {
+                let prev = self.frame().prev_lbl.clone();
+                let arg = match &instr.op1 {
+                    Some(IRArg::Phi(args)) => args.iter().find(|(_, lbl)| *lbl == prev),
+                    _ => None,
+                };
+                match arg {
+                    Some((arg, _)) => Some(self.arg(arg)?),
+                    None => return Err(InterpErr::InvalidInstr(instr.body())),
+                }
+            }
+            _ => return Err(InterpErr::InvalidInstr(instr.body())),
+        };
+
+        if let (Some(val), Some(dest)) = (result, instr.def()) {
+            self.frame().regs.insert(dest.clone(), val);
+        }
+        self.frame().instr += 1;
+        Ok(())
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.stack.last_mut().unwrap()
+    }
+
+    fn goto(&mut self, lbl: &str) -> Result<(), InterpErr> {
+        let frame = self.stack.last().unwrap();
+        let block = match self.blocks[frame.func].get(lbl) {
+            Some(block) => *block,
+            None => return Err(InterpErr::UndefinedLbl(String::from(lbl))),
+        };
+        self.enter(block);
+        Ok(())
+    }
+
+    fn goto_next(&mut self) -> Result<(), InterpErr> {
+        let frame = self.stack.last().unwrap();
+        let func = &self.fns[frame.func];
+        let next = frame.block + 1;
+        if next >= func.blocks.len() {
+            let lbl = func.blocks[frame.block].lbl.clone();
+            return Err(InterpErr::UndefinedLbl(format!("after {}", lbl)));
+        }
+        self.enter(next);
+        Ok(())
+    }
+
+    fn enter(&mut self, block: usize) {
+        let fns = self.fns;
+        let frame = self.frame();
+        frame.prev_lbl = fns[frame.func].blocks[frame.block].lbl.clone();
+        frame.block = block;
+        frame.instr = 0;
+    }
+
+    fn lbl(&self, arg: &Option<IRArg>) -> Result<String, InterpErr> {
+        match arg {
+            Some(IRArg::Lbl(lbl)) => Ok(lbl.clone()),
+            _ => Err(InterpErr::InvalidInstr(format!("{:?}", arg))),
+        }
+    }
+
+    fn val(&self, arg: &Option<IRArg>) -> Result<Value, InterpErr> {
+        match arg {
+            Some(arg) => self.arg(arg),
+            None => Err(InterpErr::InvalidInstr(String::from("missing operand"))),
+        }
+    }
+
+    fn arg(&self, arg: &IRArg) -> Result<Value, InterpErr> {
+        match arg {
+            IRArg::Num(n) => Ok(Value::Num(*n)),
+            IRArg::Bool(b) => Ok(Value::Bool(*b)),
+            IRArg::Str(s) => Ok(Value::Str(s.clone())),
+            IRArg::Reg(reg) => self
+                .stack
+                .last()
+                .unwrap()
+                .regs
+                .get(reg)
+                .cloned()
+                .ok_or_else(|| InterpErr::UndefinedReg(reg.clone())),
+            _ => Err(InterpErr::InvalidInstr(format!("{}", arg))),
+        }
+    }
+
+    fn load(&self, arg: &Option<IRArg>) -> Result<Value, InterpErr> {
+        let (val, name) = match arg {
+            Some(IRArg::Var(var)) => (self.stack.last().unwrap().locals.get(var), var),
+            Some(IRArg::Global(g)) => (self.globals.get(g), g),
+            _ => return Err(InterpErr::InvalidOperands(IROp::Ld)),
+        };
+        val.cloned()
+            .ok_or_else(|| InterpErr::UndefinedVar(name.clone()))
+    }
+
+    fn store(&mut self, arg: &Option<IRArg>, val: Value) -> Result<(), InterpErr> {
+        match arg {
+            Some(IRArg::Var(var)) => {
+                self.frame().locals.insert(var.clone(), val);
+            }
+            Some(IRArg::Global(g)) => {
+                if self.globals.insert(g.clone(), val).is_none() {
+                    self.global_order.push(g.clone());
+                }
+            }
+            _ => return Err(InterpErr::InvalidOperands(IROp::St)),
+        }
+        Ok(())
+    }
+
+    /// The object and property index a field operand refers to.
+    fn field(&self, arg: &Option<IRArg>, instr: &Instr) -> Result<(usize, usize), InterpErr> {
+        let (reg, idx) = match arg {
+            Some(IRArg::Field(reg, idx)) => (reg, *idx),
+            _ => return Err(InterpErr::InvalidOperands(instr.opcode)),
+        };
+
+        match self.arg(&IRArg::Reg(reg.clone()))? {
+            Value::Obj(obj) if idx < self.heap[obj].props.len() => Ok((obj, idx)),
+            _ => Err(InterpErr::InvalidOperands(instr.opcode)),
+        }
+    }
+
+    fn arith(&self, op: IROp, lhs: Value, rhs: Option<Value>) -> Result<Value, InterpErr> {
+        let to_arg = |val: &Value| match val {
+            Value::Num(n) => Some(IRArg::Num(*n)),
+            Value::Bool(b) => Some(IRArg::Bool(*b)),
+            _ => None,
+        };
+
+        // Strings can be joined and compared, everything else works like
+        // constant folding.
+        let val = match (&lhs, &rhs) {
+            (Value::Str(l), Some(Value::Str(r))) => match op {
+                IROp::Add => Some(Value::Str(format!("{}{}", l, r))),
+                IROp::EqEq => Some(Value::Bool(l == r)),
+                IROp::Neq => Some(Value::Bool(l != r)),
+                _ => None,
+            },
+            _ => {
+                let lhs = to_arg(&lhs);
+                let rhs = rhs.as_ref().map(to_arg);
+                match (lhs, rhs) {
+                    (Some(l), None) => sccp::fold(op, &l, None),
+                    (Some(l), Some(Some(r))) => sccp::fold(op, &l, Some(&r)),
+                    _ => None,
+                }
+                .map(|arg| match arg {
+                    IRArg::Num(n) => Value::Num(n),
+                    IRArg::Bool(b) => Value::Bool(b),
+                    _ => Value::Void,
+                })
+            }
+        };
+
+        val.ok_or(InterpErr::InvalidOperands(op))
+    }
+}
@@ -1,11 +1,57 @@
+use crate::cfg;
 use crate::instr::{IRArg, IROp, Instr};
+use crate::verify;
 
 use kolgac::{
     ast::Ast,
+    scope::{self, ClassScope, Loop, Scopes},
     token::{TknTy, Token},
     ty_rec::TyRecord,
 };
 
+use std::collections::HashMap;
+
+/// Label of the code that runs the top level statements of a program. This
+/// can't clash with a function name, since '@' isn't allowed in identifiers.
+pub const INIT_LBL: &str = "@init";
+
+/// The function we're generating code for. Function declarations are lowered
+/// separately from the code around them, so this is saved and restored around
+/// each one.
+#[derive(Debug)]
+struct FnCtx {
+    code: Vec<Instr>,
+
+    /// Label of the block we're adding instructions to.
+    lbl: String,
+
+    /// False once the current block ends in a terminator. Anything generated
+    /// after that is unreachable, and goes into a new block.
+    open: bool,
+
+    reg: usize,
+
+    /// Parameters and variables declared in the function, and the KIR variable
+    /// each one is kept in. Any other variable is a global.
+    scopes: Scopes<String>,
+
+    /// True for the top level code.
+    is_init: bool,
+}
+
+impl FnCtx {
+    fn new(lbl: &str) -> FnCtx {
+        FnCtx {
+            code: Vec::new(),
+            lbl: String::from(lbl),
+            open: true,
+            reg: 0,
+            scopes: Scopes::new(),
+            is_init: lbl == INIT_LBL,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct IRGen<'t> {
     /// Syntax tree obtained from parsing. This is assumed to
@@ -13,10 +59,23 @@ pub struct IRGen<'t> {
     pub ast: &'t Ast,
 
     /// The resulting three address linear code. This is represented
-    /// as a vector of instructions.
+    /// as a vector of instructions. The top level code comes first, under
+    /// INIT_LBL, followed by each function in the order they're declared.
+    /// Methods are named after their class, like 'point.len'.
     pub ir: Vec<Instr>,
 
-    reg: usize,
+    curr: FnCtx,
+
+    /// Code for each function we've finished generating.
+    fns: Vec<Vec<Instr>>,
+
+    lbl_count: usize,
+
+    /// Property positions for each class declared so far.
+    classes: HashMap<String, HashMap<String, usize>>,
+
+    /// The class we're generating methods for.
+    class: Option<ClassScope>,
 }
 
 impl<'t> IRGen<'t> {
@@ -24,7 +83,11 @@ impl<'t> IRGen<'t> {
         IRGen {
             ast: ast,
             ir: Vec::new(),
-            reg: 0,
+            curr: FnCtx::new(INIT_LBL),
+            fns: Vec::new(),
+            lbl_count: 0,
+            classes: HashMap::new(),
+            class: None,
         }
     }
 
@@ -32,42 +95,115 @@ impl<'t> IRGen<'t> {
         match self.ast {
             Ast::Prog { meta: _, stmts } => {
                 for stmt in stmts {
-                    let iseq = self.instr_seq(stmt);
-                    self.ir.extend(iseq);
+                    self.stmt(stmt);
                 }
             }
             _ => panic!("invalid ast provided to ir gen"),
         }
+
+        self.close_fn();
+        let init = std::mem::replace(&mut self.curr, FnCtx::new(INIT_LBL));
+        self.ir = init.code;
+        for f in self.fns.drain(..) {
+            self.ir.extend(f);
+        }
+
+        verify::debug_verify(&cfg::build(&self.ir), "irgen");
     }
 
-    pub fn instr_seq(&mut self, node: &Ast) -> Vec<Instr> {
+    fn stmt(&mut self, node: &Ast) {
         match node {
-            Ast::ExprStmt { meta: _, expr } => self.instr_seq(expr),
-
             Ast::BlckStmt { meta: _, stmts, .. } => {
-                let mut seq = Vec::new();
+                self.curr.scopes.init_sc();
                 for stmt in stmts {
-                    let vr = self.instr_seq(stmt);
-                    seq.extend(vr);
+                    self.stmt(stmt);
                 }
-                seq
+                self.curr.scopes.close_sc();
             }
 
-            Ast::VarDeclExpr { .. } => {
+            Ast::VarDeclExpr { ident_tkn, .. } => {
                 // we don't generate any instructions for an empty declaration,
                 // we assume that the ident exists later when we refer to it
                 // in expressions.
-                Vec::new()
+                self.declare(&ident_tkn.get_name());
             }
 
-            Ast::VarAssignExpr {
+            Ast::IfStmt {
                 meta: _,
-                ty_rec: _,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => self.if_stmt(cond_expr, if_stmts, elif_exprs, el_stmts),
+
+            Ast::WhileStmt { .. } | Ast::ForStmt { .. } => self.loop_stmt(&Loop::of(node)),
+
+            Ast::RetStmt { meta: _, ret_expr } => self.ret(ret_expr),
+
+            Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                fn_body,
+                ..
+            } => self.fn_decl(&ident_tkn.get_name(), fn_params, fn_body, false),
+
+            Ast::ClassDeclStmt {
+                ident_tkn,
+                methods,
+                props,
+                prop_pos,
+                ..
+            } => self.class_decl(ident_tkn, methods, props, prop_pos),
+
+            // Parse errors stop compilation before we get here.
+            Ast::Error { .. } => (),
+
+            _ => {
+                self.expr(node);
+            }
+        }
+    }
+
+    /// Generates the code for an expression, and returns the register that holds
+    /// its value.
+    fn expr(&mut self, node: &Ast) -> IRArg {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr(expr),
+
+            Ast::VarAssignExpr {
+                meta,
                 ident_tkn,
-                is_imm: _,
-                is_global: _,
                 value,
-            } => self.var_assign(ident_tkn, value),
+                ..
+            } => {
+                let val = self.expr(value);
+                let name = ident_tkn.get_name();
+
+                // Properties of self are assigned by the name they're declared
+                // with inside a method.
+                let prop_idx = self.class.as_ref().and_then(|cls| cls.self_prop(ident_tkn));
+
+                if let Some(idx) = prop_idx {
+                    let obj = self.ld(IRArg::Var(String::from("self")));
+                    self.emit(
+                        IROp::StField,
+                        Some(field(&obj, idx)),
+                        Some(val.clone()),
+                        None,
+                    );
+                    return val;
+                }
+
+                // The value is generated first, so a declaration can read the
+                // variable it hides.
+                let var = if scope::is_decl(meta, ident_tkn) {
+                    self.declare(&name)
+                } else {
+                    self.var(&name)
+                };
+                self.emit(IROp::St, Some(var), Some(val.clone()), None);
+                val
+            }
 
             Ast::BinaryExpr {
                 meta: _,
@@ -84,120 +220,390 @@ impl<'t> IRGen<'t> {
                 rhs,
             } => self.bin_op(op_tkn, lhs, rhs),
 
+            Ast::UnaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                rhs,
+            } => {
+                let rhs_reg = self.expr(rhs);
+                let op = match op_tkn.ty {
+                    TknTy::Minus => IROp::Neg,
+                    TknTy::Bang => IROp::Not,
+                    _ => panic!("invalid unary operator token"),
+                };
+                self.emit_val(op, Some(rhs_reg), None)
+            }
+
             Ast::PrimaryExpr {
-                meta: _, ty_rec, ..
-            } => self.primary(ty_rec),
+                meta: _,
+                ty_rec,
+                is_self,
+            } => self.primary(ty_rec, *is_self),
 
-            Ast::RetStmt { meta: _, ret_expr } => self.ret(ret_expr),
+            Ast::FnCallExpr {
+                meta: _,
+                ty_rec: _,
+                fn_tkn,
+                fn_params,
+            } => {
+                let name = fn_tkn.get_name();
+
+                // Inside a method, other methods of the class are called without
+                // naming self.
+                let method = self
+                    .class
+                    .as_ref()
+                    .filter(|cls| cls.calls_method(&name))
+                    .map(|cls| format!("{}.{}", cls.name, name));
+
+                match method {
+                    Some(method) => {
+                        let obj = self.ld(IRArg::Var(String::from("self")));
+                        self.call(&method, Some(obj), fn_params)
+                    }
+                    None => self.call(&name, None, fn_params),
+                }
+            }
 
-            _ => Vec::new(),
-        }
-    }
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                fn_params,
+                ..
+            } => {
+                let var = self.var(&class_tkn.get_name());
+                let obj = self.ld(var);
+                let method = format!("{}.{}", class_name, fn_tkn.get_name());
+                self.call(&method, Some(obj), fn_params)
+            }
 
-    fn bin_op(&mut self, op_tkn: &Token, lhs: &Ast, rhs: &Ast) -> Vec<Instr> {
-        let mut seq = Vec::new();
+            Ast::ClassConstrExpr {
+                meta: _,
+                ty_rec: _,
+                class_name,
+                props,
+            } => {
+                let prop_pos = self.classes.get(class_name).cloned().unwrap_or_default();
+                let obj = self.emit_val(
+                    IROp::New,
+                    Some(IRArg::Lbl(class_name.clone())),
+                    Some(IRArg::Num(prop_pos.len() as f64)),
+                );
+
+                let mut vals: Vec<(usize, &Ast)> = props
+                    .iter()
+                    .filter_map(|(name, val)| prop_pos.get(name).map(|idx| (*idx, val)))
+                    .collect();
+                vals.sort_by_key(|(idx, _)| *idx);
+
+                for (idx, val) in vals {
+                    let val_reg = self.expr(val);
+                    self.emit(IROp::StField, Some(field(&obj, idx)), Some(val_reg), None);
+                }
 
-        let lhs_seq = self.instr_seq(lhs);
-        seq.extend(lhs_seq);
+                obj
+            }
 
-        // save current register here, for use in final instr
-        let lhs_reg = self.last_used_reg_name();
+            Ast::ClassPropAccessExpr { ident_tkn, idx, .. } => {
+                let var = self.var(&ident_tkn.get_name());
+                let obj = self.ld(var);
+                self.emit_val(IROp::LdField, Some(field(&obj, *idx)), None)
+            }
 
-        let rhs_seq = self.instr_seq(rhs);
-        seq.extend(rhs_seq);
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                idx,
+                assign_val,
+                ..
+            } => {
+                let val = self.expr(assign_val);
+                let var = self.var(&ident_tkn.get_name());
+                let obj = self.ld(var);
+                self.emit(
+                    IROp::StField,
+                    Some(field(&obj, *idx)),
+                    Some(val.clone()),
+                    None,
+                );
+                val
+            }
 
-        // save current register here again, for second op in last instr
-        let rhs_reg = self.last_used_reg_name();
+            _ => panic!("invalid expression provided to ir gen"),
+        }
+    }
 
-        let op1 = IRArg::Reg(lhs_reg);
-        let op2 = IRArg::Reg(rhs_reg);
+    fn bin_op(&mut self, op_tkn: &Token, lhs: &Ast, rhs: &Ast) -> IRArg {
+        let op1 = self.expr(lhs);
+        let op2 = self.expr(rhs);
         let op = self.opr_from_tkn(op_tkn);
-        let dest = IRArg::Reg(self.reg_name());
-
-        let bin_instr = Instr::build(Some(op1), Some(op2), op, dest, String::new());
-        seq.push(bin_instr);
 
-        seq
+        self.emit_val(op, Some(op1), Some(op2))
     }
 
-    fn primary(&mut self, ty_rec: &TyRecord) -> Vec<Instr> {
+    fn primary(&mut self, ty_rec: &TyRecord, is_self: bool) -> IRArg {
         let op1 = match &ty_rec.tkn.ty {
             TknTy::Val(v) => IRArg::Num(*v),
             TknTy::Str(s) => IRArg::Str(s.to_string()),
-            TknTy::Ident(i) => IRArg::Str(i.to_string()),
+            TknTy::True => IRArg::Bool(true),
+            TknTy::False => IRArg::Bool(false),
+            TknTy::Ident(i) => {
+                let prop_idx = self
+                    .class
+                    .as_ref()
+                    .filter(|_| is_self)
+                    .and_then(|cls| cls.prop_pos.get(i))
+                    .cloned();
+
+                return match prop_idx {
+                    Some(idx) => {
+                        let obj = self.ld(IRArg::Var(String::from("self")));
+                        self.emit_val(IROp::LdField, Some(field(&obj, idx)), None)
+                    }
+                    None => {
+                        let var = self.var(i);
+                        self.ld(var)
+                    }
+                };
+            }
             _ => panic!("invalid primary tkn ty"),
         };
 
-        let opr = match &ty_rec.tkn.ty {
-            TknTy::Ident(_) => IROp::Ld,
-            _ => IROp::Mv,
-        };
+        self.emit_val(IROp::Mv, Some(op1), None)
+    }
+
+    /// Generates a call. Arguments are evaluated first, then passed in order, so
+    /// that calls inside the arguments don't get mixed up with this one. Methods
+    /// get the instance they're called on as their first argument.
+    fn call(&mut self, name: &str, obj: Option<IRArg>, args: &[Ast]) -> IRArg {
+        let mut params: Vec<IRArg> = obj.into_iter().collect();
+        for arg in args {
+            params.push(self.expr(arg));
+        }
 
-        let dest = IRArg::Reg(self.reg_name());
-        self.next_reg();
+        let cnt = params.len();
+        for param in params {
+            self.emit(IROp::Param, Some(param), None, None);
+        }
 
-        vec![Instr::build(Some(op1), None, opr, dest, String::new())]
+        self.emit_val(
+            IROp::Call,
+            Some(IRArg::Lbl(String::from(name))),
+            Some(IRArg::Num(cnt as f64)),
+        )
     }
 
-    fn var_assign(&mut self, ident_tkn: &Token, value: &Ast) -> Vec<Instr> {
-        let mut seq = Vec::new();
-        let assign_seq = self.instr_seq(value);
-        seq.extend(assign_seq);
+    /// Generates an if statement. Each condition jumps to the next one when it's
+    /// false, and each branch jumps to the end when it's done.
+    fn if_stmt(&mut self, cond_expr: &Ast, if_stmts: &Ast, elif_exprs: &[Ast], el_stmts: &[Ast]) {
+        let end_lbl = self.next_lbl();
+        let mut next_lbl = self.cond_jmp(cond_expr);
+        self.stmt(if_stmts);
+        self.jmp(&end_lbl);
 
-        // We must advance to the next reg first here, in order to
-        // correctly get the previously used reg name, which is where the
-        // rhs of the assign expr is stored/
-        self.next_reg();
-        let rhs_reg = self.last_used_reg_name();
+        for elif in elif_exprs {
+            if let Ast::ElifStmt {
+                meta: _,
+                cond_expr,
+                stmts,
+            } = elif
+            {
+                self.start_block(&next_lbl);
+                next_lbl = self.cond_jmp(cond_expr);
+                self.stmt(stmts);
+                self.jmp(&end_lbl);
+            }
+        }
+
+        self.start_block(&next_lbl);
+        self.curr.scopes.init_sc();
+        for stmt in el_stmts {
+            self.stmt(stmt);
+        }
+        self.curr.scopes.close_sc();
 
-        let op1 = IRArg::Str(ident_tkn.get_name());
-        let op2 = IRArg::Reg(rhs_reg);
-        let dest = IRArg::Reg(self.reg_name());
-        let op = IROp::St;
+        self.start_block(&end_lbl);
+    }
 
-        let bin_instr = Instr::build(Some(op1), Some(op2), op, dest, String::new());
-        seq.push(bin_instr);
+    /// Generates a while or for loop. The condition is checked in its own block,
+    /// which the end of the body jumps back to.
+    fn loop_stmt(&mut self, lp: &Loop) {
+        if let Some(decl) = lp.var_decl {
+            self.stmt(decl);
+        }
 
-        seq
+        let cond_lbl = self.next_lbl();
+        self.start_block(&cond_lbl);
+        let end_lbl = self.cond_jmp(lp.cond_expr);
+        self.stmt(lp.stmts);
+        if let Some(step) = lp.step_expr {
+            self.stmt(step);
+        }
+        self.jmp(&cond_lbl);
+
+        self.start_block(&end_lbl);
     }
 
-    fn ret(&mut self, ret_expr: &Option<Box<Ast>>) -> Vec<Instr> {
-        match ret_expr {
-            Some(expr) => {
-                let mut seq = Vec::new();
-                let ret_seq = self.instr_seq(&expr);
-                seq.extend(ret_seq);
-                let op1 = IRArg::Reg(self.last_used_reg_name());
-                let op = IROp::Ret;
-                let dest = IRArg::Reg(self.reg_name());
+    /// Evaluates a condition, and jumps to a new label if it's false. Otherwise,
+    /// we fall through to a new block. Returns the label for the false case.
+    fn cond_jmp(&mut self, cond_expr: &Ast) -> String {
+        let cond = self.expr(cond_expr);
+        let false_lbl = self.next_lbl();
+        self.emit(
+            IROp::JmpNe,
+            Some(cond),
+            Some(IRArg::Lbl(false_lbl.clone())),
+            None,
+        );
+
+        let true_lbl = self.next_lbl();
+        self.start_block(&true_lbl);
+        false_lbl
+    }
 
-                self.next_reg();
+    fn ret(&mut self, ret_expr: &Option<Box<Ast>>) {
+        let ret_val = ret_expr.as_ref().map(|expr| self.expr(expr));
+        self.emit(IROp::Ret, ret_val, None, None);
+    }
 
-                let ret_instr = Instr::build(Some(op1), None, op, dest, String::new());
-                seq.push(ret_instr);
-                seq
-            }
-            None => {
-                let op = IROp::Ret;
-                let dest = IRArg::Reg(self.reg_name());
-                self.next_reg();
+    /// Generates a function. Arguments are stored into variables named after the
+    /// parameters when the function starts, so the body can treat them like any
+    /// other variable.
+    fn fn_decl(&mut self, name: &str, params: &[TyRecord], body: &Ast, is_method: bool) {
+        let outer = std::mem::replace(&mut self.curr, FnCtx::new(name));
+
+        let self_param = if is_method {
+            Some(String::from("self"))
+        } else {
+            None
+        };
+        let names = self_param
+            .into_iter()
+            .chain(params.iter().map(|p| p.tkn.get_name()));
+
+        for (idx, param) in names.enumerate() {
+            let arg = self.emit_val(IROp::Arg, Some(IRArg::Num(idx as f64)), None);
+            let var = self.declare(&param);
+            self.emit(IROp::St, Some(var), Some(arg), None);
+        }
 
-                vec![Instr::build(None, None, op, dest, String::new())]
+        self.stmt(body);
+        self.close_fn();
+
+        let f = std::mem::replace(&mut self.curr, outer);
+        self.fns.push(f.code);
+    }
+
+    /// Generates the methods of a class. Properties don't need any code, since
+    /// they're created when the class is constructed.
+    fn class_decl(
+        &mut self,
+        ident_tkn: &Token,
+        methods: &[Ast],
+        props: &[Ast],
+        prop_pos: &HashMap<String, usize>,
+    ) {
+        let name = ident_tkn.get_name();
+        self.classes.insert(name.clone(), prop_pos.clone());
+
+        let class = ClassScope::new(ident_tkn, props, methods, prop_pos);
+        let prev_class = self.class.replace(class);
+
+        for method in methods {
+            if let Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                fn_body,
+                ..
+            } = method
+            {
+                let method_name = format!("{}.{}", name, ident_tkn.get_name());
+                self.fn_decl(&method_name, fn_params, fn_body, true);
             }
         }
+
+        self.class = prev_class;
+    }
+
+    /// Ends the current function. If control can reach the end, we return from it.
+    fn close_fn(&mut self) {
+        if self.curr.open {
+            self.emit(IROp::Ret, None, None, None);
+        }
     }
 
-    fn reg_name(&self) -> String {
-        format!("r{}", self.reg)
+    /// Starts a new block. If the current block doesn't end in a terminator, it
+    /// jumps to the new one, so that every block ends in a terminator.
+    fn start_block(&mut self, lbl: &str) {
+        self.jmp(lbl);
+        self.curr.lbl = String::from(lbl);
+        self.curr.open = true;
     }
 
-    fn next_reg(&mut self) {
-        // TOOD: gets more complex with reg constraints.
-        self.reg = self.reg + 1
+    /// Jumps to a label, unless the current block already ended.
+    fn jmp(&mut self, lbl: &str) {
+        if self.curr.open {
+            self.emit(IROp::Jmp, Some(IRArg::Lbl(String::from(lbl))), None, None);
+        }
+    }
+
+    fn ld(&mut self, var: IRArg) -> IRArg {
+        self.emit_val(IROp::Ld, Some(var), None)
+    }
+
+    /// Adds an instruction that produces a value to the current block, and
+    /// returns the new register holding the value.
+    fn emit_val(&mut self, op: IROp, op1: Option<IRArg>, op2: Option<IRArg>) -> IRArg {
+        let dest = IRArg::Reg(self.next_reg());
+        self.emit(op, op1, op2, Some(dest.clone()));
+        dest
     }
 
-    fn last_used_reg_name(&self) -> String {
-        format!("r{}", self.reg - 1)
+    /// Adds an instruction to the current block. Code after a terminator can't
+    /// be reached, but we still generate it, in a block that nothing jumps to.
+    fn emit(&mut self, op: IROp, op1: Option<IRArg>, op2: Option<IRArg>, result: Option<IRArg>) {
+        if !self.curr.open {
+            self.curr.lbl = self.next_lbl();
+        }
+
+        let instr = Instr::build(op1, op2, op, result, self.curr.lbl.clone());
+        self.curr.code.push(instr);
+        self.curr.open = !op.is_terminator();
+    }
+
+    /// Declares a variable in the current scope, and returns the KIR variable
+    /// it's kept in. Each declaration of a name in a function gets a variable of
+    /// its own, so one declared in a block doesn't overwrite the one it hides.
+    /// The top level code only has globals, so it doesn't declare anything.
+    fn declare(&mut self, name: &str) -> IRArg {
+        if !self.curr.is_init {
+            self.curr.scopes.declare(name, |cnt| var_name(name, cnt));
+        }
+        self.var(name)
+    }
+
+    fn var(&self, name: &str) -> IRArg {
+        match self.curr.scopes.get(name) {
+            Some(var) => IRArg::Var(var.clone()),
+            None => IRArg::Global(String::from(name)),
+        }
+    }
+
+    /// Registers are virtual, so there's no limit on how many we use. They're
+    /// mapped onto a machine's registers by regalloc, when generating native
+    /// code.
+    fn next_reg(&mut self) -> String {
+        let reg = format!("r{}", self.curr.reg);
+        self.curr.reg += 1;
+        reg
+    }
+
+    /// Block labels start with '.', so they can't clash with function names.
+    fn next_lbl(&mut self) -> String {
+        let lbl = format!(".L{}", self.lbl_count);
+        self.lbl_count += 1;
+        lbl
     }
 
     fn opr_from_tkn(&self, tkn: &Token) -> IROp {
@@ -212,9 +618,27 @@ impl<'t> IRGen<'t> {
             TknTy::LtEq => IROp::LtEq,
             TknTy::GtEq => IROp::GtEq,
             TknTy::BangEq => IROp::Neq,
-            TknTy::AmpAmp => IROp::LogAnd,
-            TknTy::PipePipe => IROp::LogOr,
+            TknTy::AmpAmp | TknTy::And => IROp::LogAnd,
+            TknTy::PipePipe | TknTy::Or => IROp::LogOr,
             _ => panic!("invalid operator token"),
         }
     }
 }
+
+fn field(obj: &IRArg, idx: usize) -> IRArg {
+    match obj {
+        IRArg::Reg(reg) => IRArg::Field(reg.clone(), idx),
+        _ => panic!("class instances must be loaded into a register"),
+    }
+}
+
+/// The KIR variable for a declaration of a name, given how many times it's been
+/// declared before in the function. The first one keeps the name. '#' isn't
+/// allowed in identifiers, so the others can't clash with another variable, or
+/// with the versions SSA gives a variable, like 'x.2'.
+fn var_name(name: &str, cnt: usize) -> String {
+    match cnt {
+        0 => String::from(name),
+        _ => format!("{}#{}", name, cnt),
+    }
+}
@@ -1,5 +1,18 @@
 extern crate kolgac;
 extern crate kolgac_errors;
 
+pub mod cfg;
+pub mod copy_prop;
+pub mod cse;
+pub mod dce;
+pub mod dom;
 pub mod instr;
+pub mod interp;
 pub mod irgen;
+pub mod liveness;
+pub mod pass;
+pub mod regalloc;
+pub mod sccp;
+pub mod ssa;
+pub mod text;
+pub mod verify;
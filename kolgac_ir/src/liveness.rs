@@ -0,0 +1,94 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp};
+
+use std::collections::HashSet;
+
+/// The registers live on entry to and exit from each block of a function. A
+/// register is live at a point if some path from there reads it before writing
+/// it.
+///
+/// Phi operands are read on the edge they come in on: they're live out of the
+/// predecessor they name, but not live into the phi's block.
+#[derive(Debug)]
+pub struct Liveness {
+    pub live_in: Vec<HashSet<String>>,
+    pub live_out: Vec<HashSet<String>>,
+}
+
+impl Liveness {
+    /// Solves the usual backwards dataflow problem, going over the blocks in
+    /// reverse until nothing changes.
+    pub fn new(func: &Function) -> Liveness {
+        let cnt = func.blocks.len();
+        let mut uses = vec![HashSet::new(); cnt];
+        let mut defs = vec![HashSet::new(); cnt];
+        let mut phi_uses = vec![HashSet::new(); cnt];
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            for instr in &block.instrs {
+                if let (IROp::Phi, Some(IRArg::Phi(args))) = (instr.opcode, &instr.op1) {
+                    for (arg, pred) in args {
+                        if let (IRArg::Reg(reg), Some(p)) = (arg, func.block_idx(pred)) {
+                            phi_uses[p].insert(reg.clone());
+                        }
+                    }
+                } else {
+                    for reg in instr.uses() {
+                        if !defs[b].contains(reg) {
+                            uses[b].insert(reg.clone());
+                        }
+                    }
+                }
+
+                if let Some(def) = instr.def() {
+                    defs[b].insert(def.clone());
+                }
+            }
+        }
+
+        let mut live_in = uses.clone();
+        let mut live_out: Vec<HashSet<String>> = phi_uses;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..cnt).rev() {
+                for succ in &func.blocks[b].succs {
+                    for reg in &live_in[*succ] {
+                        if !live_out[b].contains(reg) {
+                            live_out[b].insert(reg.clone());
+                            changed = true;
+                        }
+                    }
+                }
+
+                for reg in &live_out[b] {
+                    if !defs[b].contains(reg) && !live_in[b].contains(reg) {
+                        live_in[b].insert(reg.clone());
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        Liveness { live_in, live_out }
+    }
+
+    /// The registers live after each instruction of a block.
+    pub fn live_after(&self, func: &Function, b: usize) -> Vec<HashSet<String>> {
+        let instrs = &func.blocks[b].instrs;
+        let mut live = self.live_out[b].clone();
+        let mut after = vec![HashSet::new(); instrs.len()];
+
+        for (idx, instr) in instrs.iter().enumerate().rev() {
+            after[idx] = live.clone();
+            if let Some(def) = instr.def() {
+                live.remove(def);
+            }
+            if instr.opcode != IROp::Phi {
+                live.extend(instr.uses().into_iter().cloned());
+            }
+        }
+
+        after
+    }
+}
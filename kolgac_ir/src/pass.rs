@@ -0,0 +1,114 @@
+use crate::cfg::Function;
+use crate::copy_prop::CopyProp;
+use crate::cse::{GlobalCse, LocalCse};
+use crate::dce::Dce;
+use crate::sccp::Sccp;
+use crate::ssa;
+use crate::verify;
+
+pub use kolgac::opt::OptLevel;
+
+/// A transformation over a single function.
+pub trait Pass {
+    /// Name printed when showing KIR after each pass.
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, func: &mut Function);
+}
+
+/// Converts a function into SSA form. Every optimization pass expects this.
+pub struct IntoSsa;
+
+impl Pass for IntoSsa {
+    fn name(&self) -> &'static str {
+        "ssa"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        ssa::construct(func);
+    }
+}
+
+/// Converts a function out of SSA form, replacing phis with copies.
+pub struct OutOfSsa;
+
+impl Pass for OutOfSsa {
+    fn name(&self) -> &'static str {
+        "out-of-ssa"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        ssa::destruct(func);
+    }
+}
+
+/// Runs passes over every function, in the order they were added.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+
+    /// Prints every function after each pass runs on it.
+    pub print_after_each: bool,
+}
+
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager::default()
+    }
+
+    /// The passes for an optimization level. Anything above O0 runs in SSA
+    /// form, and converts back at the end. KIR has nothing more aggressive
+    /// than O2, and none of its passes make code bigger, so O3 and Os run the
+    /// same passes as O2.
+    pub fn for_level(level: OptLevel) -> PassManager {
+        let mut pm = PassManager::new();
+        match level {
+            OptLevel::O0 => return pm,
+            OptLevel::O1 => {
+                pm.add(Box::new(IntoSsa));
+                pm.add(Box::new(Sccp));
+                pm.add(Box::new(LocalCse));
+                pm.add(Box::new(CopyProp));
+                pm.add(Box::new(Dce));
+            }
+            OptLevel::O2 | OptLevel::O3 | OptLevel::Os => {
+                pm.add(Box::new(IntoSsa));
+                pm.add(Box::new(Sccp));
+                pm.add(Box::new(CopyProp));
+                pm.add(Box::new(GlobalCse));
+                pm.add(Box::new(CopyProp));
+                pm.add(Box::new(Sccp));
+                pm.add(Box::new(Dce));
+            }
+        }
+
+        pm.add(Box::new(OutOfSsa));
+        pm
+    }
+
+    pub fn add(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Runs every pass. In debug builds, the functions are verified after each
+    /// one, so a pass that breaks them is caught right away.
+    pub fn run(&mut self, fns: &mut [Function]) {
+        for pass in &mut self.passes {
+            for func in fns.iter_mut() {
+                pass.run(func);
+            }
+            verify::debug_verify(fns, pass.name());
+
+            if self.print_after_each {
+                println!("; after {}", pass.name());
+                for instr in fns.iter().flat_map(|func| func.instrs()) {
+                    println!("{}", instr);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp, Instr};
+use crate::liveness::Liveness;
+
+use std::collections::{HashMap, HashSet};
+
+/// The registers a machine has to offer the allocator.
+#[derive(Debug)]
+pub struct Target {
+    /// Registers a called function has to preserve. Values that are live across
+    /// a call can only go here, and the function using them has to save and
+    /// restore them itself.
+    pub callee_saved: &'static [&'static str],
+
+    /// Registers a call may clobber. These are used first for values that
+    /// don't live across a call, since they don't need saving.
+    pub caller_saved: &'static [&'static str],
+
+    /// Registers kept out of allocation, to load spilled values into around
+    /// the instructions that use them. Instructions read at most two registers,
+    /// so two are enough.
+    pub scratch: &'static [&'static str],
+}
+
+/// Where a virtual register ended up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loc {
+    Reg(&'static str),
+
+    /// A stack slot, numbered from 0.
+    Spill(usize),
+}
+
+/// The range a virtual register is live over. Each instruction has two
+/// positions: it reads its operands at the first, and writes its result at the
+/// second, so a register can be reused by the instruction that reads it last.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub reg: String,
+    pub start: usize,
+    pub end: usize,
+
+    /// True if the register is live across a call, so it can't be clobbered
+    /// by one.
+    pub crosses_call: bool,
+}
+
+/// The result of allocating registers for a function.
+#[derive(Debug)]
+pub struct Allocation {
+    pub locs: HashMap<String, Loc>,
+
+    /// Number of stack slots needed for spilled registers.
+    pub spills: usize,
+
+    /// Callee-saved registers the function uses, in the order the target lists
+    /// them.
+    pub callee_saved: Vec<&'static str>,
+}
+
+/// Name of the local variable holding a spill slot. Kolga identifiers can't
+/// contain '.', so these can't clash with anything in the program.
+pub fn spill_var(slot: usize) -> String {
+    format!("spill.{}", slot)
+}
+
+/// Computes a live interval for each virtual register, sorted by start.
+/// Intervals are a single range from the first point a register is live to the
+/// last, which can cover holes where it isn't live.
+pub fn intervals(func: &Function, liveness: &Liveness) -> Vec<Interval> {
+    let mut ranges: HashMap<String, Interval> = HashMap::new();
+    let mut extend = |reg: &str, pos: usize, crosses_call: bool| {
+        let interval = ranges.entry(String::from(reg)).or_insert(Interval {
+            reg: String::from(reg),
+            start: pos,
+            end: pos,
+            crosses_call: false,
+        });
+        interval.start = interval.start.min(pos);
+        interval.end = interval.end.max(pos);
+        interval.crosses_call |= crosses_call;
+    };
+
+    let mut idx = 0;
+    for (b, block) in func.blocks.iter().enumerate() {
+        let start = 2 * idx;
+        for reg in &liveness.live_in[b] {
+            extend(reg, start, false);
+        }
+
+        let live_after = liveness.live_after(func, b);
+        for (instr, live) in block.instrs.iter().zip(live_after) {
+            let (read, write) = (2 * idx, 2 * idx + 1);
+            for reg in instr.uses() {
+                extend(reg, read, false);
+            }
+
+            let def = instr.def();
+            if let Some(def) = def {
+                extend(def, write, false);
+            }
+
+            let is_call = matches!(instr.opcode, IROp::Call | IROp::New);
+            for reg in &live {
+                let crosses_call = is_call && Some(reg) != def;
+                extend(reg, write, crosses_call);
+            }
+            idx += 1;
+        }
+    }
+
+    let mut intervals: Vec<Interval> = ranges.into_values().collect();
+    intervals.sort_by(|a, b| (a.start, &a.reg).cmp(&(b.start, &b.reg)));
+    intervals
+}
+
+/// Assigns registers with linear scan, from Poletto and Sarkar's "Linear Scan
+/// Register Allocation". Intervals are visited by their start, and registers
+/// are freed as intervals end. When no register is free, whichever interval
+/// ends last is spilled to the stack.
+///
+/// Values that live across a call only get callee-saved registers. Everything
+/// else prefers caller-saved ones.
+pub fn linear_scan(func: &Function, target: &Target) -> Allocation {
+    let liveness = Liveness::new(func);
+    let intervals = intervals(func, &liveness);
+
+    let mut alloc = Allocation {
+        locs: HashMap::new(),
+        spills: 0,
+        callee_saved: Vec::new(),
+    };
+    let mut free_caller: Vec<&'static str> = target.caller_saved.iter().rev().cloned().collect();
+    let mut free_callee: Vec<&'static str> = target.callee_saved.iter().rev().cloned().collect();
+
+    // Intervals holding a register, sorted by end.
+    let mut active: Vec<(&Interval, &'static str)> = Vec::new();
+
+    for interval in &intervals {
+        active.retain(|(live, reg)| {
+            if live.end >= interval.start {
+                return true;
+            }
+            match target.caller_saved.contains(reg) {
+                true => free_caller.push(*reg),
+                false => free_callee.push(*reg),
+            }
+            false
+        });
+
+        let reg = match interval.crosses_call {
+            true => free_callee.pop(),
+            false => free_caller.pop().or_else(|| free_callee.pop()),
+        };
+
+        let reg = match reg {
+            Some(reg) => reg,
+            None => {
+                // Takes the register of the interval that ends last, if this one
+                // could use it and ends before it.
+                let victim = active.iter().rposition(|(_, reg)| {
+                    !interval.crosses_call || target.callee_saved.contains(reg)
+                });
+                match victim {
+                    Some(idx) if active[idx].0.end > interval.end => {
+                        let (spilled, reg) = active.remove(idx);
+                        alloc
+                            .locs
+                            .insert(spilled.reg.clone(), Loc::Spill(alloc.spills));
+                        alloc.spills += 1;
+                        reg
+                    }
+                    _ => {
+                        alloc
+                            .locs
+                            .insert(interval.reg.clone(), Loc::Spill(alloc.spills));
+                        alloc.spills += 1;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        alloc.locs.insert(interval.reg.clone(), Loc::Reg(reg));
+        let idx = active
+            .iter()
+            .position(|(live, _)| live.end > interval.end)
+            .unwrap_or(active.len());
+        active.insert(idx, (interval, reg));
+    }
+
+    let used: HashSet<&'static str> = alloc
+        .locs
+        .values()
+        .filter_map(|loc| match loc {
+            Loc::Reg(reg) => Some(*reg),
+            Loc::Spill(_) => None,
+        })
+        .collect();
+    alloc.callee_saved = target
+        .callee_saved
+        .iter()
+        .filter(|reg| used.contains(*reg))
+        .cloned()
+        .collect();
+
+    alloc
+}
+
+/// Allocates registers for a function, and rewrites it to use them. Spilled
+/// registers live in local variables named by `spill_var`: they're loaded into
+/// a scratch register before each instruction that reads them, and stored
+/// after each instruction that writes them. Copies from a register to itself
+/// are removed.
+///
+/// The function must not be in SSA form. Once a physical register holds values
+/// of different types, the verifier can't check types anymore, so the result
+/// isn't verified.
+pub fn allocate(func: &mut Function, target: &Target) -> Allocation {
+    let alloc = linear_scan(func, target);
+
+    for block in &mut func.blocks {
+        let mut instrs = Vec::new();
+        for mut instr in block.instrs.drain(..) {
+            assert!(
+                instr.opcode != IROp::Phi,
+                "phis must be removed with ssa::destruct before allocating registers"
+            );
+
+            // Spilled registers read by this instruction, with the scratch
+            // register each is loaded into.
+            let mut loaded: Vec<(String, &'static str)> = Vec::new();
+            for reg in instr.uses_mut() {
+                *reg = match alloc.locs[reg.as_str()] {
+                    Loc::Reg(phys) => String::from(phys),
+                    Loc::Spill(slot) => {
+                        let scratch = match loaded.iter().find(|(r, _)| r == reg) {
+                            Some((_, scratch)) => *scratch,
+                            None => {
+                                let scratch = target.scratch[loaded.len()];
+                                loaded.push((reg.clone(), scratch));
+                                instrs.push(spill_instr(IROp::Ld, slot, scratch, &block.lbl));
+                                scratch
+                            }
+                        };
+                        String::from(scratch)
+                    }
+                };
+            }
+
+            let mut store = None;
+            if let Some(IRArg::Reg(dest)) = &mut instr.result {
+                *dest = match alloc.locs[dest.as_str()] {
+                    Loc::Reg(phys) => String::from(phys),
+                    Loc::Spill(slot) => {
+                        store = Some(spill_instr(IROp::St, slot, target.scratch[0], &block.lbl));
+                        String::from(target.scratch[0])
+                    }
+                };
+            }
+
+            let is_self_copy = instr.opcode == IROp::Mv
+                && matches!((&instr.op1, &instr.result), (Some(src), Some(dest)) if src == dest);
+            if !is_self_copy {
+                instrs.push(instr);
+            }
+            instrs.extend(store);
+        }
+        block.instrs = instrs;
+    }
+
+    alloc
+}
+
+/// Loads a spill slot into a scratch register, or stores a scratch register
+/// into a spill slot.
+fn spill_instr(op: IROp, slot: usize, scratch: &str, lbl: &str) -> Instr {
+    let var = Some(IRArg::Var(spill_var(slot)));
+    let reg = Some(IRArg::Reg(String::from(scratch)));
+    match op {
+        IROp::Ld => Instr::build(var, None, op, reg, String::from(lbl)),
+        _ => Instr::build(var, reg, op, None, String::from(lbl)),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp, Instr};
+use crate::pass::Pass;
+
+use std::collections::{HashMap, HashSet};
+
+/// Sparse conditional constant propagation, from Wegman and Zadeck's "Constant
+/// Propagation with Conditional Branches". Registers start out unknown, and are
+/// only lowered to a constant, and then to "not constant", as we learn more.
+/// Blocks are only evaluated once a branch that can actually be taken reaches
+/// them, so constants on one side of a constant branch don't spoil the other.
+///
+/// Afterwards, registers with constant values are replaced with moves of the
+/// constant, branches on constants become jumps, and blocks that can't run are
+/// removed. Expects SSA form.
+pub struct Sccp;
+
+impl Pass for Sccp {
+    fn name(&self) -> &'static str {
+        "sccp"
+    }
+
+    fn run(&mut self, func: &mut Function) {
+        if func.blocks.is_empty() {
+            return;
+        }
+
+        let mut solver = Solver::new(func);
+        solver.solve(func);
+        solver.rewrite(func);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Val {
+    /// Nothing known yet, the definition hasn't been reached.
+    Top,
+    Const(IRArg),
+    Bottom,
+}
+
+fn meet(a: &Val, b: &Val) -> Val {
+    match (a, b) {
+        (Val::Top, v) | (v, Val::Top) => v.clone(),
+        (Val::Const(x), Val::Const(y)) if x == y => a.clone(),
+        _ => Val::Bottom,
+    }
+}
+
+struct Solver {
+    vals: HashMap<String, Val>,
+    users: HashMap<String, Vec<(usize, usize)>>,
+    lbls: HashMap<String, usize>,
+    visited: Vec<bool>,
+    exec_edges: HashSet<(usize, usize)>,
+    flow_work: Vec<(Option<usize>, usize)>,
+    ssa_work: Vec<String>,
+}
+
+impl Solver {
+    fn new(func: &Function) -> Solver {
+        let mut users: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (i, instr) in block.instrs.iter().enumerate() {
+                for reg in instr.uses() {
+                    users.entry(reg.clone()).or_default().push((b, i));
+                }
+            }
+        }
+
+        Solver {
+            vals: HashMap::new(),
+            users,
+            lbls: func
+                .blocks
+                .iter()
+                .enumerate()
+                .map(|(idx, b)| (b.lbl.clone(), idx))
+                .collect(),
+            visited: vec![false; func.blocks.len()],
+            exec_edges: HashSet::new(),
+            flow_work: vec![(None, 0)],
+            ssa_work: Vec::new(),
+        }
+    }
+
+    fn solve(&mut self, func: &Function) {
+        loop {
+            if let Some((from, to)) = self.flow_work.pop() {
+                if let Some(from) = from {
+                    if !self.exec_edges.insert((from, to)) {
+                        continue;
+                    }
+                }
+
+                let first_visit = !self.visited[to];
+                self.visited[to] = true;
+                for i in 0..func.blocks[to].instrs.len() {
+                    let is_phi = func.blocks[to].instrs[i].opcode == IROp::Phi;
+                    if first_visit || is_phi {
+                        self.visit(func, to, i);
+                    }
+                }
+            } else if let Some(reg) = self.ssa_work.pop() {
+                for (b, i) in self.users.get(&reg).cloned().unwrap_or_default() {
+                    if self.visited[b] {
+                        self.visit(func, b, i);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn val(&self, arg: &IRArg) -> Val {
+        match arg {
+            IRArg::Num(_) | IRArg::Bool(_) => Val::Const(arg.clone()),
+            IRArg::Reg(reg) => self.vals.get(reg).cloned().unwrap_or(Val::Top),
+            _ => Val::Bottom,
+        }
+    }
+
+    fn visit(&mut self, func: &Function, b: usize, i: usize) {
+        let block = &func.blocks[b];
+        let instr = &block.instrs[i];
+        let is_last = i + 1 == block.instrs.len();
+
+        match instr.opcode {
+            IROp::Jmp => self.add_succs(func, b, None),
+            IROp::JmpE | IROp::JmpNe => {
+                let cond = self.val(instr.op1.as_ref().unwrap());
+                match cond {
+                    Val::Top => (),
+                    Val::Const(IRArg::Bool(c)) => {
+                        let jumps = c == (instr.opcode == IROp::JmpE);
+                        self.add_succs(func, b, Some(jumps));
+                    }
+                    _ => self.add_succs(func, b, None),
+                }
+            }
+            IROp::Ret => (),
+            _ => {
+                if let Some(dest) = instr.def() {
+                    let new_val = self.eval(b, instr);
+                    let old_val = self.vals.get(dest).cloned().unwrap_or(Val::Top);
+                    let merged = meet(&old_val, &new_val);
+                    if merged != old_val {
+                        self.vals.insert(dest.clone(), merged);
+                        self.ssa_work.push(dest.clone());
+                    }
+                }
+
+                if is_last {
+                    self.add_succs(func, b, None);
+                }
+            }
+        }
+    }
+
+    /// Marks the edges out of a block as executable. For a conditional jump,
+    /// `jumps` says whether we know that it jumps, or falls through.
+    fn add_succs(&mut self, func: &Function, b: usize, jumps: Option<bool>) {
+        let target = func.blocks[b]
+            .instrs
+            .last()
+            .and_then(|instr| instr.jmp_target())
+            .and_then(|lbl| self.lbls.get(lbl).cloned());
+
+        for succ in func.blocks[b].succs.clone() {
+            let is_target = Some(succ) == target;
+            let taken = match jumps {
+                None => true,
+                Some(jumps) => jumps == is_target,
+            };
+            if taken {
+                self.flow_work.push((Some(b), succ));
+            }
+        }
+    }
+
+    fn eval(&self, b: usize, instr: &Instr) -> Val {
+        match instr.opcode {
+            IROp::Phi => {
+                let mut val = Val::Top;
+                if let Some(IRArg::Phi(args)) = &instr.op1 {
+                    for (arg, lbl) in args {
+                        let pred = self.lbls.get(lbl).cloned();
+                        let exec = pred.is_some_and(|p| self.exec_edges.contains(&(p, b)));
+                        if exec {
+                            val = meet(&val, &self.val(arg));
+                        }
+                    }
+                }
+                val
+            }
+            IROp::Mv => self.val(instr.op1.as_ref().unwrap()),
+            IROp::Neg | IROp::Not => match self.val(instr.op1.as_ref().unwrap()) {
+                Val::Const(c) => fold(instr.opcode, &c, None).map_or(Val::Bottom, Val::Const),
+                other => other,
+            },
+            IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Lt
+            | IROp::Gt
+            | IROp::EqEq
+            | IROp::LtEq
+            | IROp::GtEq
+            | IROp::Neq
+            | IROp::LogAnd
+            | IROp::LogOr => {
+                let lhs = self.val(instr.op1.as_ref().unwrap());
+                let rhs = self.val(instr.op2.as_ref().unwrap());
+                match (lhs, rhs) {
+                    (Val::Const(l), Val::Const(r)) => {
+                        fold(instr.opcode, &l, Some(&r)).map_or(Val::Bottom, Val::Const)
+                    }
+                    (Val::Bottom, _) | (_, Val::Bottom) => Val::Bottom,
+                    _ => Val::Top,
+                }
+            }
+            _ => Val::Bottom,
+        }
+    }
+
+    fn rewrite(&self, func: &mut Function) {
+        let cnt = func.blocks.len();
+        let lbls: Vec<String> = func.blocks.iter().map(|b| b.lbl.clone()).collect();
+
+        for b in 0..cnt {
+            if !self.visited[b] {
+                continue;
+            }
+
+            let block = &mut func.blocks[b];
+            for instr in &mut block.instrs {
+                match instr.opcode {
+                    IROp::JmpE | IROp::JmpNe => {
+                        if let Val::Const(IRArg::Bool(c)) = self.val(instr.op1.as_ref().unwrap()) {
+                            let target = if c == (instr.opcode == IROp::JmpE) {
+                                instr.op2.clone().unwrap()
+                            } else {
+                                IRArg::Lbl(lbls[b + 1].clone())
+                            };
+                            instr.opcode = IROp::Jmp;
+                            instr.op1 = Some(target);
+                            instr.op2 = None;
+                        }
+                    }
+                    IROp::Phi => {
+                        if let Some(IRArg::Phi(args)) = &mut instr.op1 {
+                            args.retain(|(_, lbl)| {
+                                let pred = lbls.iter().position(|l| l == lbl);
+                                pred.is_some_and(|p| self.exec_edges.contains(&(p, b)))
+                            });
+                            if args.len() == 1 {
+                                let (arg, _) = args.pop().unwrap();
+                                instr.opcode = IROp::Mv;
+                                instr.op1 = Some(arg);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+
+                let folded = match (instr.def(), instr.opcode.has_side_effects()) {
+                    (Some(dest), false) => match self.vals.get(dest) {
+                        Some(Val::Const(c)) => Some(c.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(c) = folded {
+                    instr.opcode = IROp::Mv;
+                    instr.op1 = Some(c);
+                    instr.op2 = None;
+                }
+            }
+
+            // Phis that became moves go after the ones that are left.
+            block.instrs.sort_by_key(|instr| instr.opcode != IROp::Phi);
+        }
+
+        let mut idx = 0;
+        func.blocks.retain(|_| {
+            idx += 1;
+            self.visited[idx - 1]
+        });
+        func.build_edges();
+    }
+}
+
+/// Folds an operation on constants. Returns None if the operands don't have
+/// the types the operation expects.
+pub fn fold(op: IROp, lhs: &IRArg, rhs: Option<&IRArg>) -> Option<IRArg> {
+    let val = match (lhs, rhs) {
+        (IRArg::Num(n), None) => match op {
+            IROp::Neg => IRArg::Num(-n),
+            _ => return None,
+        },
+        (IRArg::Bool(b), None) => match op {
+            IROp::Not => IRArg::Bool(!b),
+            _ => return None,
+        },
+        (IRArg::Num(l), Some(IRArg::Num(r))) => match op {
+            IROp::Add => IRArg::Num(l + r),
+            IROp::Sub => IRArg::Num(l - r),
+            IROp::Mul => IRArg::Num(l * r),
+            IROp::Div => IRArg::Num(l / r),
+            IROp::Lt => IRArg::Bool(l < r),
+            IROp::Gt => IRArg::Bool(l > r),
+            IROp::EqEq => IRArg::Bool(l == r),
+            IROp::LtEq => IRArg::Bool(l <= r),
+            IROp::GtEq => IRArg::Bool(l >= r),
+            IROp::Neq => IRArg::Bool(l != r),
+            _ => return None,
+        },
+        (IRArg::Bool(l), Some(IRArg::Bool(r))) => match op {
+            IROp::EqEq => IRArg::Bool(l == r),
+            IROp::Neq => IRArg::Bool(l != r),
+            IROp::LogAnd => IRArg::Bool(*l && *r),
+            IROp::LogOr => IRArg::Bool(*l || *r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(val)
+}
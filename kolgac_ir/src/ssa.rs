@@ -0,0 +1,467 @@
+use crate::cfg::{Block, Function};
+use crate::dom::{reverse_postorder, DomTree};
+use crate::instr::{IRArg, IROp, Instr};
+
+use std::collections::{HashMap, HashSet};
+
+/// Something that can be assigned more than once before SSA construction: a
+/// register, or a local variable. Locals are promoted to registers, since only
+/// the current function can change them. Globals stay in memory.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Name {
+    Reg(String),
+    Var(String),
+}
+
+impl Name {
+    fn base(&self) -> &str {
+        match self {
+            Name::Reg(n) | Name::Var(n) => n,
+        }
+    }
+}
+
+/// Converts a function into SSA form, where every register is assigned exactly
+/// once. Local variables become registers: stores become moves into a new
+/// version of the variable, and loads read the current version. Where versions
+/// meet, a phi picks the right one.
+///
+/// Phis are only inserted where the variable is live, and blocks that can't be
+/// reached are removed, since nothing could define their operands.
+///
+/// Versions are named after the variable, like 'x.2'. Registers that were
+/// already assigned once keep their names.
+pub fn construct(func: &mut Function) {
+    remove_unreachable(func);
+    if func.blocks.is_empty() {
+        return;
+    }
+
+    let dom = DomTree::new(func);
+    let live_in = live_in(func);
+
+    let mut def_blocks: HashMap<Name, Vec<usize>> = HashMap::new();
+    let mut def_cnt: HashMap<Name, usize> = HashMap::new();
+    for (idx, block) in func.blocks.iter().enumerate() {
+        for instr in &block.instrs {
+            if let Some(name) = def(instr) {
+                *def_cnt.entry(name.clone()).or_insert(0) += 1;
+                let blocks = def_blocks.entry(name).or_default();
+                if !blocks.contains(&idx) {
+                    blocks.push(idx);
+                }
+            }
+        }
+    }
+
+    // Place phis at the dominance frontier of every definition, including the
+    // definitions made by the phis themselves.
+    let mut phis: Vec<Vec<Name>> = vec![Vec::new(); func.blocks.len()];
+    let mut names: Vec<&Name> = def_blocks.keys().collect();
+    names.sort_by(|a, b| a.base().cmp(b.base()));
+
+    for name in names {
+        let mut work = def_blocks[name].clone();
+        let mut visited: HashSet<usize> = work.iter().cloned().collect();
+        while let Some(b) = work.pop() {
+            for f in &dom.frontiers[b] {
+                if phis[*f].contains(name) || !live_in[*f].contains(name) {
+                    continue;
+                }
+                phis[*f].push(name.clone());
+                if visited.insert(*f) {
+                    work.push(*f);
+                }
+            }
+        }
+    }
+
+    let mut versioned: HashSet<Name> = def_cnt
+        .iter()
+        .filter(|(name, cnt)| matches!(name, Name::Var(_)) || **cnt > 1)
+        .map(|(name, _)| name.clone())
+        .collect();
+    versioned.extend(phis.iter().flatten().cloned());
+
+    let mut renamer = Renamer {
+        versioned,
+        stacks: HashMap::new(),
+        counts: HashMap::new(),
+        phis: phis
+            .iter()
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|n| PendingPhi {
+                        name: n.clone(),
+                        dest: String::new(),
+                        args: Vec::new(),
+                    })
+                    .collect()
+            })
+            .collect(),
+    };
+    renamer.rename(func, &dom, 0);
+
+    for (block, phis) in func.blocks.iter_mut().zip(renamer.phis) {
+        let lbl = block.lbl.clone();
+        let phi_instrs = phis.into_iter().map(|phi| {
+            Instr::build(
+                Some(IRArg::Phi(phi.args)),
+                None,
+                IROp::Phi,
+                Some(IRArg::Reg(phi.dest)),
+                lbl.clone(),
+            )
+        });
+        block.instrs.splice(0..0, phi_instrs);
+    }
+}
+
+/// Turns a function in SSA form back into regular code, so backends don't have
+/// to understand phis. Each phi is replaced with a copy into a temporary at the
+/// end of every predecessor, and a copy out of the temporary where the phi was.
+/// Going through a temporary keeps phis that read each other's results correct.
+///
+/// Copies can't be placed on an edge from a block with several successors
+/// without running on the other paths, so those edges are split first.
+pub fn destruct(func: &mut Function) {
+    split_critical_edges(func);
+
+    let mut copies: Vec<(String, Instr)> = Vec::new();
+    for block in &mut func.blocks {
+        let phi_cnt = block
+            .instrs
+            .iter()
+            .take_while(|instr| instr.opcode == IROp::Phi)
+            .count();
+
+        let mut moves = Vec::new();
+        for phi in block.instrs.drain(..phi_cnt) {
+            let dest = match &phi.result {
+                Some(IRArg::Reg(dest)) => dest.clone(),
+                _ => panic!("phi without a destination register"),
+            };
+            let tmp = format!("{}.phi", dest);
+
+            if let Some(IRArg::Phi(args)) = phi.op1 {
+                for (arg, pred) in args {
+                    let copy = Instr::build(
+                        Some(arg),
+                        None,
+                        IROp::Mv,
+                        Some(IRArg::Reg(tmp.clone())),
+                        pred.clone(),
+                    );
+                    copies.push((pred, copy));
+                }
+            }
+
+            moves.push(Instr::build(
+                Some(IRArg::Reg(tmp)),
+                None,
+                IROp::Mv,
+                Some(IRArg::Reg(dest)),
+                block.lbl.clone(),
+            ));
+        }
+        block.instrs.splice(0..0, moves);
+    }
+
+    for (pred, copy) in copies {
+        if let Some(idx) = func.block_idx(&pred) {
+            let instrs = &mut func.blocks[idx].instrs;
+            let pos = match instrs.last() {
+                Some(last) if last.opcode.is_terminator() => instrs.len() - 1,
+                _ => instrs.len(),
+            };
+            instrs.insert(pos, copy);
+        }
+    }
+
+    func.build_edges();
+}
+
+/// Splits every edge from a block with several successors into a block with
+/// phis and several predecessors, by putting a block that just jumps to the
+/// target in between. Phi operands are updated to name the new block.
+fn split_critical_edges(func: &mut Function) {
+    while let Some((pred, succ)) = find_critical_edge(func) {
+        let pred_lbl = func.blocks[pred].lbl.clone();
+        let target_lbl = func.blocks[succ].lbl.clone();
+        let split_lbl = format!(
+            ".{}_{}",
+            pred_lbl.trim_start_matches('.'),
+            target_lbl.trim_start_matches('.')
+        );
+
+        let mut split = Block::new(&split_lbl);
+        split.instrs.push(Instr::build(
+            Some(IRArg::Lbl(target_lbl.clone())),
+            None,
+            IROp::Jmp,
+            None,
+            split_lbl.clone(),
+        ));
+
+        for instr in &mut func.blocks[succ].instrs {
+            if let Some(IRArg::Phi(args)) = &mut instr.op1 {
+                for (_, lbl) in args.iter_mut() {
+                    if *lbl == pred_lbl {
+                        *lbl = split_lbl.clone();
+                    }
+                }
+            }
+        }
+
+        // A jump is redirected to the new block. Otherwise, the edge is the
+        // fall through, so the new block has to come right after.
+        let last = func.blocks[pred].instrs.last_mut().unwrap();
+        if last.jmp_target() == Some(target_lbl.as_str()) {
+            redirect(last, &split_lbl);
+            func.blocks.push(split);
+        } else {
+            func.blocks.insert(pred + 1, split);
+        }
+
+        func.build_edges();
+    }
+}
+
+fn find_critical_edge(func: &Function) -> Option<(usize, usize)> {
+    for (idx, block) in func.blocks.iter().enumerate() {
+        if block.succs.len() < 2 {
+            continue;
+        }
+
+        for succ in &block.succs {
+            let target = &func.blocks[*succ];
+            let has_phis = target
+                .instrs
+                .first()
+                .is_some_and(|instr| instr.opcode == IROp::Phi);
+            if target.preds.len() > 1 && has_phis {
+                return Some((idx, *succ));
+            }
+        }
+    }
+
+    None
+}
+
+fn redirect(jmp: &mut Instr, lbl: &str) {
+    let target = match jmp.opcode {
+        IROp::Jmp => &mut jmp.op1,
+        _ => &mut jmp.op2,
+    };
+    *target = Some(IRArg::Lbl(String::from(lbl)));
+}
+
+fn remove_unreachable(func: &mut Function) {
+    let reachable: HashSet<usize> = reverse_postorder(func).into_iter().collect();
+    if reachable.len() == func.blocks.len() {
+        return;
+    }
+
+    let mut idx = 0;
+    func.blocks.retain(|_| {
+        idx += 1;
+        reachable.contains(&(idx - 1))
+    });
+    func.build_edges();
+}
+
+/// The name an instruction assigns to, if any.
+fn def(instr: &Instr) -> Option<Name> {
+    match (&instr.opcode, &instr.op1, &instr.result) {
+        (IROp::St, Some(IRArg::Var(var)), _) => Some(Name::Var(var.clone())),
+        (_, _, Some(IRArg::Reg(reg))) => Some(Name::Reg(reg.clone())),
+        _ => None,
+    }
+}
+
+fn arg_uses(arg: &IRArg, uses: &mut Vec<Name>) {
+    match arg {
+        IRArg::Reg(reg) | IRArg::Field(reg, _) => uses.push(Name::Reg(reg.clone())),
+        IRArg::Phi(args) => {
+            for (arg, _) in args {
+                arg_uses(arg, uses);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The names an instruction reads.
+fn uses(instr: &Instr) -> Vec<Name> {
+    let mut uses = Vec::new();
+    match (&instr.opcode, &instr.op1) {
+        (IROp::Ld, Some(IRArg::Var(var))) => uses.push(Name::Var(var.clone())),
+        (IROp::St, Some(IRArg::Var(_))) => (),
+        (_, Some(op1)) => arg_uses(op1, &mut uses),
+        _ => (),
+    }
+    if let Some(op2) = &instr.op2 {
+        arg_uses(op2, &mut uses);
+    }
+    uses
+}
+
+/// The names live on entry to each block: read before they're assigned in the
+/// block, or live after it and not assigned in it.
+fn live_in(func: &Function) -> Vec<HashSet<Name>> {
+    let cnt = func.blocks.len();
+    let mut gen: Vec<HashSet<Name>> = vec![HashSet::new(); cnt];
+    let mut kill: Vec<HashSet<Name>> = vec![HashSet::new(); cnt];
+    for (idx, block) in func.blocks.iter().enumerate() {
+        for instr in &block.instrs {
+            for name in uses(instr) {
+                if !kill[idx].contains(&name) {
+                    gen[idx].insert(name);
+                }
+            }
+            if let Some(name) = def(instr) {
+                kill[idx].insert(name);
+            }
+        }
+    }
+
+    let mut live_in = gen.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in (0..cnt).rev() {
+            let mut live = gen[idx].clone();
+            for succ in &func.blocks[idx].succs {
+                for name in &live_in[*succ] {
+                    if !kill[idx].contains(name) {
+                        live.insert(name.clone());
+                    }
+                }
+            }
+            if live.len() != live_in[idx].len() {
+                live_in[idx] = live;
+                changed = true;
+            }
+        }
+    }
+
+    live_in
+}
+
+/// A phi being built during renaming.
+struct PendingPhi {
+    name: Name,
+    dest: String,
+    args: Vec<(IRArg, String)>,
+}
+
+struct Renamer {
+    versioned: HashSet<Name>,
+    stacks: HashMap<Name, Vec<String>>,
+    counts: HashMap<Name, usize>,
+
+    /// Phis for each block.
+    phis: Vec<Vec<PendingPhi>>,
+}
+
+impl Renamer {
+    fn new_version(&mut self, name: &Name) -> String {
+        let version = if self.versioned.contains(name) {
+            let cnt = self.counts.entry(name.clone()).or_insert(0);
+            *cnt += 1;
+            format!("{}.{}", name.base(), *cnt - 1)
+        } else {
+            String::from(name.base())
+        };
+
+        self.stacks
+            .entry(name.clone())
+            .or_default()
+            .push(version.clone());
+        version
+    }
+
+    /// The version of a name that reaches the current point. A use with no
+    /// definition keeps the original name, which only happens for a variable
+    /// read before it's assigned.
+    fn curr(&self, name: &Name) -> String {
+        self.stacks
+            .get(name)
+            .and_then(|stack| stack.last().cloned())
+            .unwrap_or_else(|| String::from(name.base()))
+    }
+
+    fn rename_arg(&self, arg: &mut IRArg) {
+        match arg {
+            IRArg::Reg(reg) => *reg = self.curr(&Name::Reg(reg.clone())),
+            IRArg::Field(reg, _) => *reg = self.curr(&Name::Reg(reg.clone())),
+            _ => (),
+        }
+    }
+
+    /// Renames the block and the blocks it dominates, in the order of the
+    /// dominator tree, so each use sees the definition that dominates it.
+    fn rename(&mut self, func: &mut Function, dom: &DomTree, b: usize) {
+        let mut pushed: Vec<Name> = Vec::new();
+
+        for idx in 0..self.phis[b].len() {
+            let name = self.phis[b][idx].name.clone();
+            self.phis[b][idx].dest = self.new_version(&name);
+            pushed.push(name);
+        }
+
+        for instr in &mut func.blocks[b].instrs {
+            match (instr.opcode, instr.op1.clone()) {
+                (IROp::Ld, Some(IRArg::Var(var))) => {
+                    instr.opcode = IROp::Mv;
+                    instr.op1 = Some(IRArg::Reg(self.curr(&Name::Var(var))));
+                }
+                (IROp::St, Some(IRArg::Var(var))) => {
+                    let mut val = instr.op2.take().unwrap();
+                    self.rename_arg(&mut val);
+                    let name = Name::Var(var);
+                    let version = self.new_version(&name);
+                    pushed.push(name);
+
+                    instr.opcode = IROp::Mv;
+                    instr.op1 = Some(val);
+                    instr.result = Some(IRArg::Reg(version));
+                }
+                _ => {
+                    if let Some(op1) = &mut instr.op1 {
+                        self.rename_arg(op1);
+                    }
+                    if let Some(op2) = &mut instr.op2 {
+                        self.rename_arg(op2);
+                    }
+                    if let Some(IRArg::Reg(reg)) = &instr.result {
+                        let name = Name::Reg(reg.clone());
+                        let version = self.new_version(&name);
+                        pushed.push(name);
+                        instr.result = Some(IRArg::Reg(version));
+                    }
+                }
+            }
+        }
+
+        let lbl = func.blocks[b].lbl.clone();
+        for succ in func.blocks[b].succs.clone() {
+            for idx in 0..self.phis[succ].len() {
+                let name = self.phis[succ][idx].name.clone();
+                let arg = IRArg::Reg(self.curr(&name));
+                self.phis[succ][idx].args.push((arg, lbl.clone()));
+            }
+        }
+
+        for child in dom.children[b].clone() {
+            self.rename(func, dom, child);
+        }
+
+        for name in pushed {
+            if let Some(stack) = self.stacks.get_mut(&name) {
+                stack.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::cfg::{self, Function};
+use crate::instr::{IRArg, IROp, Instr, OperandKind as Kind};
+
+use std::fmt;
+
+/// Reads KIR back from the text that `Instr`'s Display impl prints, one
+/// instruction per line:
+///
+/// ```text
+/// r3 = add r1 r2 : .L0
+/// st @total r3 : .L0
+/// x.3 = phi [x.1 .L5] [x.2 .L4] : .L3
+/// ```
+///
+/// Blank lines, and lines starting with ';', are skipped, so the output of
+/// --show-kir-passes can be read too.
+///
+/// Operands don't say what kind they are, so we go by their position: the
+/// first operand of 'jmp' is a label, the first operand of 'st' is a
+/// variable, and so on. Values are literals or registers.
+pub fn parse(text: &str) -> Result<Vec<Instr>, KirParseErr> {
+    let mut instrs = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let instr = parse_instr(line).map_err(|msg| KirParseErr { line: idx + 1, msg })?;
+        instrs.push(instr);
+    }
+
+    Ok(instrs)
+}
+
+/// Parses a whole program, and splits it into functions like `cfg::build`.
+pub fn parse_fns(text: &str) -> Result<Vec<Function>, KirParseErr> {
+    Ok(cfg::build(&parse(text)?))
+}
+
+/// An error in KIR text, with the line it's on, counting from 1.
+#[derive(Debug, PartialEq)]
+pub struct KirParseErr {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for KirParseErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+fn parse_instr(line: &str) -> Result<Instr, String> {
+    let (body, lbl) = match line.rfind(" : ") {
+        Some(pos) => (&line[..pos], line[pos + 3..].trim()),
+        None => return Err(String::from("missing ' : label' at the end")),
+    };
+    if lbl.is_empty() || lbl.contains(' ') {
+        return Err(format!("invalid label '{}'", lbl));
+    }
+
+    let mut tkns = tokenize(body)?;
+    let result = if tkns.len() > 1 && tkns[1] == "=" {
+        let dest = tkns.remove(0);
+        tkns.remove(0);
+        Some(IRArg::Reg(dest))
+    } else {
+        None
+    };
+
+    if tkns.is_empty() {
+        return Err(String::from("missing operator"));
+    }
+    let op: IROp = tkns.remove(0).parse()?;
+
+    let (kinds, optional) = op.operands();
+    let ops = match op {
+        IROp::Phi => vec![parse_phi(&tkns)?],
+        _ => {
+            if tkns.len() > kinds.len() || tkns.len() < kinds.len() - optional {
+                return Err(format!(
+                    "'{}' takes {} operands, found {}",
+                    op,
+                    kinds.len(),
+                    tkns.len()
+                ));
+            }
+
+            tkns.iter()
+                .zip(kinds.iter())
+                .map(|(tkn, kind)| parse_arg(tkn, *kind))
+                .collect::<Result<Vec<IRArg>, String>>()?
+        }
+    };
+
+    let mut ops = ops.into_iter();
+    Ok(Instr::build(
+        ops.next(),
+        ops.next(),
+        op,
+        result,
+        String::from(lbl),
+    ))
+}
+
+fn parse_arg(tkn: &str, kind: Kind) -> Result<IRArg, String> {
+    let arg = match kind {
+        Kind::Val => parse_val(tkn)?,
+        Kind::Lbl => IRArg::Lbl(String::from(tkn)),
+        Kind::Num => IRArg::Num(parse_num(tkn)?),
+        Kind::Loc => match tkn.strip_prefix('@') {
+            Some(g) => IRArg::Global(String::from(g)),
+            None => IRArg::Var(String::from(tkn)),
+        },
+        Kind::Field => {
+            let (reg, idx) = match tkn.rfind('.') {
+                Some(pos) => (&tkn[..pos], &tkn[pos + 1..]),
+                None => return Err(format!("expected a field like 'r1.0', found '{}'", tkn)),
+            };
+            let idx = idx
+                .parse::<usize>()
+                .map_err(|_| format!("invalid field index '{}'", idx))?;
+            IRArg::Field(String::from(reg), idx)
+        }
+        Kind::Phi => return Err(String::from("unexpected phi operands")),
+    };
+
+    Ok(arg)
+}
+
+fn parse_val(tkn: &str) -> Result<IRArg, String> {
+    if tkn.starts_with('"') {
+        return Ok(IRArg::Str(unquote(tkn)?));
+    }
+
+    let val = match tkn {
+        "true" => IRArg::Bool(true),
+        "false" => IRArg::Bool(false),
+        _ => match tkn.parse::<f64>() {
+            Ok(n) => IRArg::Num(n),
+            Err(_) => IRArg::Reg(String::from(tkn)),
+        },
+    };
+
+    Ok(val)
+}
+
+fn parse_num(tkn: &str) -> Result<f64, String> {
+    tkn.parse::<f64>()
+        .map_err(|_| format!("expected a number, found '{}'", tkn))
+}
+
+/// Phi operands are pairs of a value and a label in brackets, like
+/// '[x.1 .L5] [x.2 .L4]'.
+fn parse_phi(tkns: &[String]) -> Result<IRArg, String> {
+    let mut args = Vec::new();
+    for chunk in tkns.chunks(4) {
+        match chunk {
+            [open, val, lbl, close] if open == "[" && close == "]" => {
+                args.push((parse_val(val)?, lbl.clone()));
+            }
+            _ => return Err(String::from("expected phi operands like '[r1 .L0]'")),
+        }
+    }
+
+    if args.is_empty() {
+        return Err(String::from("'phi' needs at least one operand"));
+    }
+    Ok(IRArg::Phi(args))
+}
+
+/// Splits an instruction into tokens. Strings are kept whole, with their
+/// quotes, and brackets are tokens on their own.
+fn tokenize(body: &str) -> Result<Vec<String>, String> {
+    let mut tkns = Vec::new();
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => (),
+            '[' | ']' => tkns.push(c.to_string()),
+            '"' => {
+                let mut tkn = String::from("\"");
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    tkn.push(c);
+                    match c {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                tkn.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                if !closed {
+                    return Err(String::from("unterminated string"));
+                }
+                tkns.push(tkn);
+            }
+            _ => {
+                let mut tkn = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if matches!(next, ' ' | '\t' | '[' | ']') {
+                        break;
+                    }
+                    tkn.push(*next);
+                    chars.next();
+                }
+                tkns.push(tkn);
+            }
+        }
+    }
+
+    Ok(tkns)
+}
+
+/// Undoes the escaping that strings get when they're printed with `{:?}`.
+fn unquote(tkn: &str) -> Result<String, String> {
+    let inner = &tkn[1..tkn.len() - 1];
+    let mut s = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => s.push('\n'),
+            Some('t') => s.push('\t'),
+            Some('r') => s.push('\r'),
+            Some('0') => s.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let ch = u32::from_str_radix(code.trim_start_matches('{'), 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| format!("invalid escape '\\u{}}}'", code))?;
+                s.push(ch);
+            }
+            Some(c @ ('\\' | '"' | '\'')) => s.push(c),
+            Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+            None => return Err(String::from("unterminated escape")),
+        }
+    }
+
+    Ok(s)
+}
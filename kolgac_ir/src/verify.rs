@@ -0,0 +1,575 @@
+use crate::cfg::Function;
+use crate::instr::{IRArg, IROp, Instr};
+
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::fmt;
+
+/// Something wrong with the KIR of a function, found by the verifier.
+#[derive(Debug, PartialEq)]
+pub struct VerifyErr {
+    pub func: String,
+
+    /// Label of the block the problem is in.
+    pub lbl: String,
+
+    pub msg: String,
+}
+
+impl fmt::Display for VerifyErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "in {}, block {}: {}", self.func, self.lbl, self.msg)
+    }
+}
+
+/// Checks that a function is well formed. Every block must end in a terminator,
+/// every instruction must have the operands its operator expects, every jump
+/// must go to a block in the function, and every register must be defined on
+/// every path to its uses. Values must also have the types their operators
+/// expect, as far as we can tell without knowing what calls return.
+///
+/// Works on KIR in or out of SSA form.
+pub fn verify(func: &Function) -> Result<(), Vec<VerifyErr>> {
+    let mut verifier = Verifier {
+        func,
+        errs: Vec::new(),
+    };
+
+    verifier.check_blocks();
+    if verifier.errs.is_empty() {
+        // The rest depends on the blocks being laid out correctly.
+        verifier.check_defs();
+        verifier.check_tys();
+    }
+
+    match verifier.errs.is_empty() {
+        true => Ok(()),
+        false => Err(verifier.errs),
+    }
+}
+
+/// Verifies every function in a program, and checks that calls go to functions
+/// that exist.
+pub fn verify_all(fns: &[Function]) -> Result<(), Vec<VerifyErr>> {
+    let names: HashSet<&str> = fns.iter().map(|func| func.name.as_str()).collect();
+    let mut errs = Vec::new();
+
+    for func in fns {
+        if let Err(fn_errs) = verify(func) {
+            errs.extend(fn_errs);
+        }
+
+        for block in &func.blocks {
+            for instr in &block.instrs {
+                if let (IROp::Call, Some(IRArg::Lbl(callee))) = (instr.opcode, &instr.op1) {
+                    if !names.contains(callee.as_str()) {
+                        errs.push(VerifyErr {
+                            func: func.name.clone(),
+                            lbl: block.lbl.clone(),
+                            msg: format!(
+                                "'{}' calls undefined function '{}'",
+                                instr.body(),
+                                callee
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    match errs.is_empty() {
+        true => Ok(()),
+        false => Err(errs),
+    }
+}
+
+/// Verifies a program in debug builds, and panics with every problem found.
+/// `stage` is what produced the KIR, like "irgen" or a pass name, so that the
+/// panic points at the code that broke it.
+pub fn debug_verify(fns: &[Function], stage: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    if let Err(errs) = verify_all(fns) {
+        let errs: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+        panic!("invalid KIR after {}:\n{}", stage, errs.join("\n"));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ty {
+    Num,
+    Bool,
+    Str,
+    Obj,
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let formatted = match self {
+            Ty::Num => "num",
+            Ty::Bool => "bool",
+            Ty::Str => "str",
+            Ty::Obj => "class instance",
+        };
+
+        write!(f, "{}", formatted)
+    }
+}
+
+struct Verifier<'f> {
+    func: &'f Function,
+    errs: Vec<VerifyErr>,
+}
+
+impl<'f> Verifier<'f> {
+    fn error(&mut self, lbl: &str, msg: String) {
+        self.errs.push(VerifyErr {
+            func: self.func.name.clone(),
+            lbl: String::from(lbl),
+            msg,
+        });
+    }
+
+    /// Checks the layout of each block, and the operands of each instruction.
+    fn check_blocks(&mut self) {
+        let func = self.func;
+        if func.blocks.is_empty() {
+            self.error(&func.name, String::from("function has no blocks"));
+            return;
+        }
+
+        let mut lbls = HashSet::new();
+        for (b, block) in func.blocks.iter().enumerate() {
+            if !lbls.insert(block.lbl.as_str()) {
+                self.error(
+                    &block.lbl,
+                    String::from("label is used by more than one block"),
+                );
+            }
+
+            let last = match block.instrs.last() {
+                Some(last) => last,
+                None => {
+                    self.error(&block.lbl, String::from("block is empty"));
+                    continue;
+                }
+            };
+
+            if !last.opcode.is_terminator() {
+                self.error(
+                    &block.lbl,
+                    format!("block ends in '{}', which isn't a terminator", last.body()),
+                );
+            }
+            let falls_through = matches!(last.opcode, IROp::JmpE | IROp::JmpNe);
+            if falls_through && b + 1 == func.blocks.len() {
+                self.error(
+                    &block.lbl,
+                    format!("'{}' has no block to fall through to", last.body()),
+                );
+            }
+
+            let mut seen_non_phi = false;
+            for (i, instr) in block.instrs.iter().enumerate() {
+                if instr.opcode.is_terminator() && i + 1 != block.instrs.len() {
+                    self.error(
+                        &block.lbl,
+                        format!(
+                            "terminator '{}' isn't at the end of the block",
+                            instr.body()
+                        ),
+                    );
+                }
+
+                if instr.opcode == IROp::Phi && seen_non_phi {
+                    self.error(
+                        &block.lbl,
+                        format!(
+                            "'{}' comes after instructions that aren't phis",
+                            instr.body()
+                        ),
+                    );
+                }
+                seen_non_phi |= instr.opcode != IROp::Phi;
+
+                self.check_operands(&block.lbl, instr);
+                self.check_lbls(b, instr);
+            }
+        }
+    }
+
+    fn check_operands(&mut self, lbl: &str, instr: &Instr) {
+        let op = instr.opcode;
+        if instr.op1.is_none() && instr.op2.is_some() {
+            self.error(
+                lbl,
+                format!("'{}' has a second operand, but no first", instr.body()),
+            );
+            return;
+        }
+
+        let ops: Vec<&IRArg> = instr.op1.iter().chain(instr.op2.iter()).collect();
+        let (kinds, optional) = op.operands();
+        let min = kinds.len() - optional;
+        if ops.len() < min || ops.len() > kinds.len() {
+            let expected = match optional {
+                0 => kinds.len().to_string(),
+                _ => format!("{} to {}", min, kinds.len()),
+            };
+            self.error(
+                lbl,
+                format!(
+                    "'{}' takes {} operands, found {} in '{}'",
+                    op,
+                    expected,
+                    ops.len(),
+                    instr.body()
+                ),
+            );
+            return;
+        }
+
+        for (arg, kind) in ops.iter().zip(kinds.iter()) {
+            if !kind.accepts(arg) {
+                self.error(
+                    lbl,
+                    format!("'{}' can't be used as an operand of '{}'", arg, op),
+                );
+            }
+        }
+
+        match (&instr.result, op.has_result()) {
+            (Some(IRArg::Reg(_)), true) | (None, false) => (),
+            (Some(_), true) => self.error(
+                lbl,
+                format!("result of '{}' isn't a register", instr.body()),
+            ),
+            (None, true) => self.error(lbl, format!("'{}' has no result", instr.body())),
+            (Some(_), false) => {
+                self.error(lbl, format!("'{}' doesn't produce a result", instr.body()))
+            }
+        }
+    }
+
+    /// Checks that jumps go to blocks in the function, and that phis only name
+    /// predecessors of their block.
+    fn check_lbls(&mut self, b: usize, instr: &Instr) {
+        let func = self.func;
+        let lbl = &func.blocks[b].lbl;
+
+        if let Some(target) = instr.jmp_target() {
+            if func.block_idx(target).is_none() {
+                self.error(lbl, format!("'{}' jumps to undefined label", instr.body()));
+            }
+        }
+
+        if let Some(IRArg::Phi(args)) = &instr.op1 {
+            for (_, pred) in args {
+                let is_pred = func
+                    .block_idx(pred)
+                    .is_some_and(|p| func.blocks[b].preds.contains(&p));
+                if !is_pred {
+                    self.error(
+                        lbl,
+                        format!(
+                            "'{}' names {}, which isn't a predecessor",
+                            instr.body(),
+                            pred
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks that every register is defined on every path to each of its uses.
+    /// Phi operands only need to be defined at the end of the predecessor they
+    /// come from.
+    fn check_defs(&mut self) {
+        let func = self.func;
+        let defs: Vec<HashSet<&str>> = func
+            .blocks
+            .iter()
+            .map(|block| {
+                block
+                    .instrs
+                    .iter()
+                    .filter_map(|instr| instr.def().map(|d| d.as_str()))
+                    .collect()
+            })
+            .collect();
+
+        // Registers defined at the end of each block. None stands for every
+        // register, for blocks that can only be reached from blocks we haven't
+        // looked at yet.
+        let mut outs: Vec<Option<HashSet<&str>>> = vec![None; func.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in 0..func.blocks.len() {
+                let out = self.defined_at_start(b, &outs).map(|mut set| {
+                    set.extend(&defs[b]);
+                    set
+                });
+                if out != outs[b] {
+                    outs[b] = out;
+                    changed = true;
+                }
+            }
+        }
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            let mut defined = match self.defined_at_start(b, &outs) {
+                Some(defined) => defined,
+                None => continue,
+            };
+
+            for instr in &block.instrs {
+                if let Some(IRArg::Phi(args)) = &instr.op1 {
+                    for (arg, pred) in args {
+                        let reg = match arg {
+                            IRArg::Reg(reg) => reg,
+                            _ => continue,
+                        };
+                        let out = func.block_idx(pred).and_then(|p| outs[p].as_ref());
+                        if out.is_some_and(|out| !out.contains(reg.as_str())) {
+                            self.error(
+                                &block.lbl,
+                                format!(
+                                    "'{}' uses {}, which isn't defined at the end of {}",
+                                    instr.body(),
+                                    reg,
+                                    pred
+                                ),
+                            );
+                        }
+                    }
+                } else {
+                    for reg in instr.uses() {
+                        if !defined.contains(reg.as_str()) {
+                            self.error(
+                                &block.lbl,
+                                format!(
+                                    "'{}' uses {} before it's defined on every path",
+                                    instr.body(),
+                                    reg
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                if let Some(def) = instr.def() {
+                    defined.insert(def);
+                }
+            }
+        }
+    }
+
+    fn defined_at_start<'a>(
+        &self,
+        b: usize,
+        outs: &[Option<HashSet<&'a str>>],
+    ) -> Option<HashSet<&'a str>> {
+        let preds = &self.func.blocks[b].preds;
+        if b == 0 || preds.is_empty() {
+            return Some(HashSet::new());
+        }
+
+        let mut defined: Option<HashSet<&str>> = None;
+        for pred in preds {
+            if let Some(out) = &outs[*pred] {
+                defined = Some(match defined {
+                    Some(set) => set.intersection(out).cloned().collect(),
+                    None => out.clone(),
+                });
+            }
+        }
+
+        defined
+    }
+
+    /// Works out the type of each register and variable from the instructions
+    /// that define them, then checks that operands have the types their
+    /// operators expect. Call results, arguments and properties could be
+    /// anything, so they're only checked once they're copied or stored
+    /// somewhere with a known type.
+    fn check_tys(&mut self) {
+        let instrs: Vec<(&str, &Instr)> = self
+            .func
+            .blocks
+            .iter()
+            .flat_map(|block| block.instrs.iter().map(move |i| (block.lbl.as_str(), i)))
+            .collect();
+
+        // The first type we find for each register wins, and any other
+        // definitions are checked against it.
+        let mut tys = TyEnv::default();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (_, instr) in &instrs {
+                if let (Some(dest), Some(ty)) = (instr.def(), tys.result(instr)) {
+                    if !tys.regs.contains_key(dest) {
+                        tys.regs.insert(dest.clone(), ty);
+                        changed = true;
+                    }
+                }
+
+                if let (IROp::St, Some(loc), Some(ty)) =
+                    (instr.opcode, &instr.op1, tys.val(instr.op2.as_ref()))
+                {
+                    if let Entry::Vacant(entry) = tys.locs.entry(loc.to_string()) {
+                        entry.insert(ty);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        for (lbl, instr) in instrs {
+            for msg in tys.check(instr) {
+                self.error(lbl, format!("'{}': {}", instr.body(), msg));
+            }
+        }
+    }
+}
+
+/// Known types of registers, and of variables by their printed name.
+#[derive(Default)]
+struct TyEnv {
+    regs: HashMap<String, Ty>,
+    locs: HashMap<String, Ty>,
+}
+
+impl TyEnv {
+    fn val(&self, arg: Option<&IRArg>) -> Option<Ty> {
+        match arg? {
+            IRArg::Num(_) => Some(Ty::Num),
+            IRArg::Bool(_) => Some(Ty::Bool),
+            IRArg::Str(_) => Some(Ty::Str),
+            IRArg::Reg(reg) => self.regs.get(reg).cloned(),
+            _ => None,
+        }
+    }
+
+    fn result(&self, instr: &Instr) -> Option<Ty> {
+        match instr.opcode {
+            IROp::Mv => self.val(instr.op1.as_ref()),
+            IROp::Ld => self.locs.get(&instr.op1.as_ref()?.to_string()).cloned(),
+            IROp::Add | IROp::Sub | IROp::Mul | IROp::Div | IROp::Neg => Some(Ty::Num),
+            IROp::Lt
+            | IROp::Gt
+            | IROp::EqEq
+            | IROp::LtEq
+            | IROp::GtEq
+            | IROp::Neq
+            | IROp::LogAnd
+            | IROp::LogOr
+            | IROp::Not => Some(Ty::Bool),
+            IROp::New => Some(Ty::Obj),
+            IROp::Phi => match &instr.op1 {
+                Some(IRArg::Phi(args)) => args.iter().find_map(|(arg, _)| self.val(Some(arg))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns a message for each operand or result with the wrong type.
+    fn check(&self, instr: &Instr) -> Vec<String> {
+        let mut msgs = Vec::new();
+        let op = instr.opcode;
+        let op1 = self.val(instr.op1.as_ref());
+        let op2 = self.val(instr.op2.as_ref());
+
+        let mut expect = |ty: Option<Ty>, expected: Ty, what: &str| {
+            if let Some(ty) = ty {
+                if ty != expected {
+                    msgs.push(format!("{} should be a {}, found a {}", what, expected, ty));
+                }
+            }
+        };
+
+        match op {
+            IROp::Add
+            | IROp::Sub
+            | IROp::Mul
+            | IROp::Div
+            | IROp::Lt
+            | IROp::Gt
+            | IROp::LtEq
+            | IROp::GtEq => {
+                expect(op1, Ty::Num, "first operand");
+                expect(op2, Ty::Num, "second operand");
+            }
+            IROp::LogAnd | IROp::LogOr => {
+                expect(op1, Ty::Bool, "first operand");
+                expect(op2, Ty::Bool, "second operand");
+            }
+            IROp::Neg => expect(op1, Ty::Num, "operand"),
+            IROp::Not | IROp::JmpE | IROp::JmpNe => expect(op1, Ty::Bool, "condition"),
+            IROp::LdField | IROp::StField => {
+                let base = match &instr.op1 {
+                    Some(IRArg::Field(reg, _)) => self.regs.get(reg).cloned(),
+                    _ => None,
+                };
+                expect(base, Ty::Obj, "base of the property");
+            }
+            IROp::EqEq | IROp::Neq => {
+                if let (Some(lhs), Some(rhs)) = (op1, op2) {
+                    if lhs != rhs {
+                        msgs.push(format!("compares a {} with a {}", lhs, rhs));
+                    }
+                }
+            }
+            IROp::St => {
+                let loc = instr.op1.as_ref().map(|loc| loc.to_string());
+                let loc_ty = loc.as_ref().and_then(|loc| self.locs.get(loc));
+                if let (Some(loc), Some(loc_ty), Some(ty)) = (loc, loc_ty, op2) {
+                    if *loc_ty != ty {
+                        msgs.push(format!(
+                            "stores a {} in {}, which holds a {}",
+                            ty, loc, loc_ty
+                        ));
+                    }
+                }
+            }
+            IROp::Phi => {
+                if let (Some(IRArg::Phi(args)), Some(dest)) = (&instr.op1, instr.def()) {
+                    let dest_ty = self.regs.get(dest);
+                    for (arg, pred) in args {
+                        let ty = self.val(Some(arg));
+                        if let (Some(dest_ty), Some(ty)) = (dest_ty, ty) {
+                            if *dest_ty != ty {
+                                msgs.push(format!(
+                                    "value from {} is a {}, but {} is a {}",
+                                    pred, ty, dest, dest_ty
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if op != IROp::Phi {
+            let dest_ty = instr.def().and_then(|dest| self.regs.get(dest));
+            if let (Some(dest), Some(dest_ty), Some(ty)) =
+                (instr.def(), dest_ty, self.result(instr))
+            {
+                if *dest_ty != ty {
+                    msgs.push(format!(
+                        "result is a {}, but {} is a {} elsewhere",
+                        ty, dest, dest_ty
+                    ));
+                }
+            }
+        }
+
+        msgs
+    }
+}
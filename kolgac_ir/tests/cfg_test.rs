@@ -0,0 +1,115 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    instr::Instr,
+    irgen::{IRGen, INIT_LBL},
+};
+
+use std::fs::File;
+
+fn gen(path: &str) -> Vec<Instr> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    irgen.ir
+}
+
+fn build(path: &str) -> Vec<Function> {
+    cfg::build(&gen(path))
+}
+
+fn edges(func: &Function) -> Vec<(String, String)> {
+    let mut edges = Vec::new();
+    for block in &func.blocks {
+        for succ in &block.succs {
+            edges.push((block.lbl.clone(), func.blocks[*succ].lbl.clone()));
+        }
+    }
+    edges
+}
+
+fn edge(from: &str, to: &str) -> (String, String) {
+    (String::from(from), String::from(to))
+}
+
+#[test]
+fn cfg_splits_functions() {
+    let fns = build("./tests/irgen/prog.kol");
+    let names: Vec<&str> = fns.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![INIT_LBL, "point.len", "point.scale", "point.shift", "fib"]
+    );
+
+    for func in &fns {
+        assert_eq!(func.blocks[0].lbl, func.name);
+    }
+}
+
+#[test]
+fn cfg_loops() {
+    let fns = build("./tests/irgen/prog.kol");
+    let init = &fns[0];
+
+    let expected = vec![
+        edge(INIT_LBL, ".L5"),
+        edge(".L5", ".L6"),
+        edge(".L5", ".L7"),
+        edge(".L7", ".L5"),
+        edge(".L6", ".L8"),
+        edge(".L8", ".L9"),
+        edge(".L8", ".L10"),
+        edge(".L10", ".L8"),
+    ];
+    assert_eq!(edges(init), expected);
+
+    let cond = init.block_idx(".L5").unwrap();
+    let preds: Vec<&str> = init.blocks[cond]
+        .preds
+        .iter()
+        .map(|idx| init.blocks[*idx].lbl.as_str())
+        .collect();
+    assert_eq!(preds, vec![INIT_LBL, ".L7"]);
+}
+
+#[test]
+fn cfg_branches() {
+    let fns = build("./tests/irgen/prog.kol");
+    let fib = fns.iter().find(|f| f.name == "fib").unwrap();
+
+    let expected = vec![
+        edge("fib", ".L1"),
+        edge("fib", ".L2"),
+        edge(".L1", ".L3"),
+        edge(".L1", ".L4"),
+    ];
+    assert_eq!(edges(fib), expected);
+
+    // Every branch returns, so the end of the if statement can't be reached.
+    let end = fib.block_idx(".L0").unwrap();
+    assert!(fib.blocks[end].preds.is_empty());
+}
+
+#[test]
+fn cfg_round_trips_instrs() {
+    let ir = gen("./tests/irgen/prog.kol");
+    let fns = cfg::build(&ir);
+    let instrs: Vec<Instr> = fns.iter().flat_map(|f| f.instrs()).collect();
+    assert_eq!(instrs, ir);
+}
+
+#[test]
+fn cfg_to_dot() {
+    let fns = build("./tests/irgen/prog.kol");
+    let dot = cfg::to_dot(&fns);
+
+    assert!(dot.starts_with("digraph kir {"));
+    assert!(dot.contains("  subgraph \"cluster_fib\" {"));
+    assert!(dot.contains("    \".L2\" [label=\".L2:\\l  r4 = ld n\\l  ret r4\\l\"];"));
+    assert!(dot.contains("    \"fib\" -> \".L1\";"));
+    assert!(dot.trim_end().ends_with('}'));
+}
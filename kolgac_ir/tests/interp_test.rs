@@ -0,0 +1,131 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    interp::{Interp, InterpErr, Value},
+    irgen::IRGen,
+    pass::{OptLevel, PassManager},
+};
+
+use std::fs::File;
+
+/// The programs the backends are tested with are shared, in kolgac_testutil.
+/// That depends on this crate, so they're found by their path here.
+fn program(name: &str) -> String {
+    format!("../kolgac_testutil/programs/{}.kol", name)
+}
+
+fn build(name: &str, level: OptLevel) -> Vec<Function> {
+    let file = File::open(program(name)).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+
+    let mut fns = cfg::build(&irgen.ir);
+    PassManager::for_level(level).run(&mut fns);
+    fns
+}
+
+/// Runs a program at every optimization level, and checks that the globals end
+/// up the same each time.
+fn run(name: &str) -> Vec<(String, String)> {
+    let mut results = Vec::new();
+    for level in &[OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let fns = build(name, *level);
+        let mut interp = Interp::new(&fns);
+        interp.run().unwrap();
+
+        let globals: Vec<(String, String)> = interp
+            .globals()
+            .iter()
+            .map(|(name, val)| (name.clone(), interp.fmt_val(val)))
+            .collect();
+        results.push(globals);
+    }
+
+    assert_eq!(results[0], results[1]);
+    assert_eq!(results[0], results[2]);
+    results.pop().unwrap()
+}
+
+fn global(name: &str, val: &str) -> (String, String) {
+    (String::from(name), String::from(val))
+}
+
+#[test]
+fn interp_recursion() {
+    assert_eq!(run("fib"), vec![global("f10", "55")]);
+}
+
+#[test]
+fn interp_classes() {
+    let expected = vec![
+        global("c", "counter { 22, 10 }"),
+        global("total", "12"),
+        global("after", "22"),
+    ];
+    assert_eq!(run("classes"), expected);
+}
+
+#[test]
+fn interp_loops() {
+    let expected = vec![
+        global("evens", "206"),
+        global("odds", "2"),
+        global("i", "10"),
+        global("done", "true"),
+        global("neg", "-10"),
+        global("name", "kolga"),
+    ];
+    assert_eq!(run("loops"), expected);
+}
+
+#[test]
+fn interp_scopes() {
+    // A variable declared in a block hides the one outside it, instead of
+    // overwriting it.
+    let globals = run("scopes");
+    assert!(globals.contains(&global("total", "19")));
+    assert!(globals.contains(&global("shadowed", "13")));
+    assert!(globals.contains(&global("shadowedNeg", "7")));
+}
+
+#[test]
+fn interp_equality() {
+    // Numbers are equal by value, so 0 equals -0, and NaN isn't equal to
+    // itself.
+    let expected = vec![
+        global("zero", "0"),
+        global("zeroEq", "true"),
+        global("zeroNeq", "false"),
+        global("nanEq", "false"),
+        global("nanNeq", "true"),
+        global("oneEq", "true"),
+        global("oneNeq", "true"),
+    ];
+    assert_eq!(run("equality"), expected);
+}
+
+#[test]
+fn interp_values() {
+    let fns = build("loops", OptLevel::O0);
+    let mut interp = Interp::new(&fns);
+    interp.run().unwrap();
+
+    assert_eq!(interp.global("done"), Some(&Value::Bool(true)));
+    assert_eq!(interp.global("neg"), Some(&Value::Num(-10.0)));
+    assert_eq!(
+        interp.global("name"),
+        Some(&Value::Str(String::from("kolga")))
+    );
+    assert_eq!(interp.global("missing"), None);
+}
+
+#[test]
+fn interp_stack_overflow() {
+    let fns = build("overflow", OptLevel::O0);
+    let mut interp = Interp::new(&fns);
+    assert_eq!(interp.run(), Err(InterpErr::StackOverflow));
+}
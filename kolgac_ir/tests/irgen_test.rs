@@ -0,0 +1,131 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    instr::{IRArg, IROp, Instr},
+    irgen::{IRGen, INIT_LBL},
+};
+
+use std::fs::File;
+
+fn gen(path: &str) -> Vec<Instr> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    irgen.ir
+}
+
+fn lines(ir: &[Instr], lbl: &str) -> Vec<String> {
+    ir.iter()
+        .filter(|instr| instr.lbl == lbl)
+        .map(|instr| format!("{}", instr))
+        .collect()
+}
+
+#[test]
+fn irgen_blocks_end_in_terminators() {
+    let ir = gen("./tests/irgen/prog.kol");
+    assert_eq!(ir[0].lbl, INIT_LBL);
+
+    let lbls: Vec<&String> = ir.iter().map(|instr| &instr.lbl).collect();
+    for (idx, instr) in ir.iter().enumerate() {
+        let ends_block = ir.get(idx + 1).is_none_or(|next| next.lbl != instr.lbl);
+        assert_eq!(
+            ends_block,
+            instr.opcode.is_terminator(),
+            "unexpected terminator: {}",
+            instr
+        );
+
+        let target = match instr.opcode {
+            IROp::Jmp => instr.op1.as_ref(),
+            IROp::JmpE | IROp::JmpNe => instr.op2.as_ref(),
+            _ => None,
+        };
+        if let Some(IRArg::Lbl(target)) = target {
+            assert!(lbls.contains(&target), "missing label: {}", target);
+        }
+    }
+}
+
+#[test]
+fn irgen_fn_decl() {
+    let ir = gen("./tests/irgen/prog.kol");
+
+    let expected = vec![
+        "r0 = arg 0 : fib",
+        "st n r0 : fib",
+        "r1 = ld n : fib",
+        "r2 = mv 2 : fib",
+        "r3 = lt r1 r2 : fib",
+        "jmpne r3 .L1 : fib",
+    ];
+    assert_eq!(lines(&ir, "fib"), expected);
+
+    let elif = lines(&ir, ".L1");
+    assert_eq!(elif.last().unwrap(), "jmpne r7 .L3 : .L1");
+
+    let el = lines(&ir, ".L3");
+    assert!(el.contains(&String::from("param r11 : .L3")));
+    assert!(el.contains(&String::from("r12 = call fib 1 : .L3")));
+    assert_eq!(el.last().unwrap(), "ret r17 : .L3");
+}
+
+#[test]
+fn irgen_loops() {
+    let ir = gen("./tests/irgen/prog.kol");
+    let init = lines(&ir, INIT_LBL);
+
+    // The for loop variable is a global at the top level.
+    assert!(init.contains(&String::from("st @i r4 : @init")));
+    assert_eq!(init.last().unwrap(), "jmp .L5 : @init");
+
+    let cond = lines(&ir, ".L5");
+    assert_eq!(cond.last().unwrap(), "jmpne r7 .L6 : .L5");
+
+    let body = lines(&ir, ".L7");
+    assert!(body.contains(&String::from("st @i r14 : .L7")));
+    assert_eq!(body.last().unwrap(), "jmp .L5 : .L7");
+}
+
+#[test]
+fn irgen_scopes() {
+    let ir = gen("../kolgac_testutil/programs/scopes.kol");
+    let stores: Vec<String> = ir
+        .iter()
+        .filter(|instr| instr.opcode == IROp::St)
+        .map(|instr| format!("{}", instr.op1.as_ref().unwrap()))
+        .collect();
+
+    // Each declaration of a name in a function gets its own variable, so the
+    // ones declared in blocks don't overwrite the one they hide.
+    for var in &["y", "y#1", "y#2", "y#3", "t#1", "i#1"] {
+        assert!(stores.contains(&String::from(*var)), "{}", var);
+    }
+    assert!(!stores.contains(&String::from("y#4")));
+}
+
+#[test]
+fn irgen_classes() {
+    let ir = gen("./tests/irgen/prog.kol");
+    let init = lines(&ir, INIT_LBL);
+    assert_eq!(init[0], "r0 = new point 2 : @init");
+    assert!(init.contains(&String::from("stf r0.1 r2 : @init")));
+
+    let end = lines(&ir, ".L9");
+    assert!(end.contains(&String::from("stf r22.0 r21 : .L9")));
+    assert!(end.contains(&String::from("r24 = call point.len 1 : .L9")));
+
+    // Methods take self first, and read properties through it.
+    let len = lines(&ir, "point.len");
+    assert_eq!(len[0], "r0 = arg 0 : point.len");
+    assert_eq!(len[1], "st self r0 : point.len");
+    assert_eq!(len[3], "r2 = ldf r1.0 : point.len");
+
+    let shift = lines(&ir, "point.shift");
+    assert!(shift.contains(&String::from("stf r11.0 r10 : point.shift")));
+    assert!(shift.contains(&String::from("param r15 : point.shift")));
+    assert!(shift.contains(&String::from("r17 = call point.scale 2 : point.shift")));
+}
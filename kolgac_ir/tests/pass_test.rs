@@ -0,0 +1,176 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    copy_prop::CopyProp,
+    cse::{GlobalCse, LocalCse},
+    dce::Dce,
+    instr::{IRArg, IROp},
+    irgen::IRGen,
+    pass::{IntoSsa, OptLevel, Pass, PassManager},
+    sccp::{self, Sccp},
+};
+
+use std::fs::File;
+
+fn build(path: &str, name: &str) -> Function {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    cfg::build(&irgen.ir)
+        .into_iter()
+        .find(|f| f.name == name)
+        .unwrap()
+}
+
+fn run(func: &mut Function, passes: Vec<Box<dyn Pass>>) {
+    let mut pm = PassManager::new();
+    pm.add(Box::new(IntoSsa));
+    for pass in passes {
+        pm.add(pass);
+    }
+    pm.run(std::slice::from_mut(func));
+}
+
+fn lines(func: &Function, lbl: &str) -> Vec<String> {
+    let idx = func.block_idx(lbl).unwrap();
+    func.blocks[idx]
+        .instrs
+        .iter()
+        .map(|instr| instr.body())
+        .collect()
+}
+
+fn contains(func: &Function, body: &str) -> bool {
+    func.instrs().iter().any(|instr| instr.body() == body)
+}
+
+#[test]
+fn pass_levels() {
+    assert!(PassManager::for_level(OptLevel::O0).names().is_empty());
+    assert_eq!(
+        PassManager::for_level(OptLevel::O1).names(),
+        vec!["ssa", "sccp", "local-cse", "copy-prop", "dce", "out-of-ssa"]
+    );
+    assert_eq!(
+        PassManager::for_level(OptLevel::O2).names(),
+        vec![
+            "ssa",
+            "sccp",
+            "copy-prop",
+            "global-cse",
+            "copy-prop",
+            "sccp",
+            "dce",
+            "out-of-ssa"
+        ]
+    );
+    for level in &[OptLevel::O3, OptLevel::Os] {
+        assert_eq!(
+            PassManager::for_level(*level).names(),
+            PassManager::for_level(OptLevel::O2).names()
+        );
+    }
+    assert_eq!("2".parse::<OptLevel>(), Ok(OptLevel::O2));
+    assert!("4".parse::<OptLevel>().is_err());
+}
+
+#[test]
+fn pass_sccp() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    run(&mut f, vec![Box::new(Sccp)]);
+
+    // 'k > 2' is always true, so the else branch is gone, and 'k' is 4 after
+    // the if statement.
+    assert_eq!(lines(&f, "f").last().unwrap(), "jmp .L2");
+    assert!(f.block_idx(".L1").is_none());
+    assert!(contains(&f, "k.3 = mv 4"));
+    assert!(contains(&f, "r29 = mv 4"));
+
+    // The loop counter changes, so the loop stays.
+    assert_eq!(lines(&f, ".L3")[0], "i.1 = phi [i.0 .L0] [i.2 .L5]");
+    assert!(contains(&f, "r23 = lt r21 r22"));
+}
+
+#[test]
+fn pass_copy_prop() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    run(&mut f, vec![Box::new(CopyProp)]);
+
+    assert!(contains(&f, "r3 = mul r0 r2"));
+    assert!(contains(&f, "r23 = lt i.1 r0"));
+    assert!(contains(&f, "r27 = mul r5 r10"));
+    assert_eq!(lines(&f, ".L4"), vec!["r34 = mv s.1", "ret s.1"]);
+}
+
+#[test]
+fn pass_local_cse() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    run(&mut f, vec![Box::new(LocalCse)]);
+
+    // 'n * 2 + 1' is computed twice in the same block.
+    assert!(contains(&f, "r5 = add r3 r4"));
+    assert!(contains(&f, "r7 = mv r2"));
+    assert!(contains(&f, "r8 = mv r3"));
+    assert!(contains(&f, "r10 = mv r5"));
+
+    // Constants in other blocks aren't shared.
+    assert!(contains(&f, "r32 = mv 1"));
+}
+
+#[test]
+fn pass_global_cse() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    run(&mut f, vec![Box::new(GlobalCse)]);
+
+    assert!(contains(&f, "r10 = mv r5"));
+
+    // The entry block dominates the loop body, so its constant can be reused.
+    assert!(contains(&f, "r32 = mv r4"));
+}
+
+#[test]
+fn pass_dce() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    run(&mut f, vec![Box::new(CopyProp), Box::new(Dce)]);
+
+    // Copies are dead once their uses read the original.
+    for instr in f.instrs() {
+        let is_copy = matches!((instr.opcode, &instr.op1), (IROp::Mv, Some(IRArg::Reg(_))));
+        assert!(!is_copy, "copy not removed: {}", instr);
+    }
+    assert!(contains(&f, "r0 = arg 0"));
+    assert_eq!(lines(&f, ".L4"), vec!["ret s.1"]);
+}
+
+#[test]
+fn pass_pipeline() {
+    let mut f = build("./tests/pass/opt.kol", "f");
+    let mut pm = PassManager::for_level(OptLevel::O2);
+    pm.run(std::slice::from_mut(&mut f));
+
+    let instrs = f.instrs();
+    assert!(instrs.iter().all(|instr| instr.opcode != IROp::Phi));
+    assert!(!contains(&f, "r8 = mul r0 r7"));
+    assert!(contains(&f, "r27 = mul r5 r5"));
+    assert_eq!(lines(&f, ".L4"), vec!["ret s.1"]);
+}
+
+#[test]
+fn pass_fold() {
+    let two = IRArg::Num(2.0);
+    let three = IRArg::Num(3.0);
+    assert_eq!(
+        sccp::fold(IROp::Mul, &two, Some(&three)),
+        Some(IRArg::Num(6.0))
+    );
+    assert_eq!(
+        sccp::fold(IROp::Lt, &two, Some(&three)),
+        Some(IRArg::Bool(true))
+    );
+    assert_eq!(sccp::fold(IROp::Neg, &two, None), Some(IRArg::Num(-2.0)));
+    assert_eq!(sccp::fold(IROp::Add, &two, Some(&IRArg::Bool(true))), None);
+}
@@ -0,0 +1,207 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    interp::Interp,
+    irgen::IRGen,
+    liveness::Liveness,
+    pass::{OptLevel, PassManager},
+    regalloc::{self, Loc, Target},
+    text,
+};
+
+use std::collections::HashSet;
+use std::fs::File;
+
+/// A machine with very few registers, so that programs have to spill.
+const SMALL: Target = Target {
+    callee_saved: &["%s0", "%s1"],
+    caller_saved: &["%c0"],
+    scratch: &["%t0", "%t1"],
+};
+
+fn build(path: &str, level: OptLevel) -> Vec<Function> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+
+    let mut fns = cfg::build(&irgen.ir);
+    PassManager::for_level(level).run(&mut fns);
+    fns
+}
+
+fn globals(fns: &[Function]) -> Vec<(String, String)> {
+    let mut interp = Interp::new(fns);
+    interp.run().unwrap();
+    interp
+        .globals()
+        .iter()
+        .map(|(name, val)| (name.clone(), interp.fmt_val(val)))
+        .collect()
+}
+
+fn set(regs: &[&str]) -> HashSet<String> {
+    regs.iter().map(|reg| String::from(*reg)).collect()
+}
+
+const LOOP: &str = "r1 = mv 0 : f\n\
+                    r5 = mv 10 : f\n\
+                    jmp .L0 : f\n\
+                    r2 = lt r1 r5 : .L0\n\
+                    jmpne r2 .L1 : .L0\n\
+                    r3 = add r1 1 : .L2\n\
+                    r1 = mv r3 : .L2\n\
+                    jmp .L0 : .L2\n\
+                    ret r1 : .L1\n";
+
+#[test]
+fn liveness_loop() {
+    let fns = text::parse_fns(LOOP).unwrap();
+    let liveness = Liveness::new(&fns[0]);
+
+    assert_eq!(liveness.live_in[0], set(&[]));
+    assert_eq!(liveness.live_in[1], set(&["r1", "r5"]));
+    assert_eq!(liveness.live_in[2], set(&["r1", "r5"]));
+    assert_eq!(liveness.live_out[2], set(&["r1", "r5"]));
+    assert_eq!(liveness.live_in[3], set(&["r1"]));
+
+    let after = liveness.live_after(&fns[0], 2);
+    assert_eq!(after[0], set(&["r3", "r5"]));
+    assert_eq!(after[1], set(&["r1", "r5"]));
+}
+
+#[test]
+fn liveness_phis() {
+    let src = "r0 = arg 0 : f\n\
+               jmpne r0 .L1 : f\n\
+               r1 = mv 1 : .L0\n\
+               jmp .L2 : .L0\n\
+               r2 = mv 2 : .L1\n\
+               jmp .L2 : .L1\n\
+               r3 = phi [r1 .L0] [r2 .L1] : .L2\n\
+               ret r3 : .L2\n";
+    let fns = text::parse_fns(src).unwrap();
+    let liveness = Liveness::new(&fns[0]);
+
+    assert_eq!(liveness.live_out[1], set(&["r1"]));
+    assert_eq!(liveness.live_out[2], set(&["r2"]));
+    assert_eq!(liveness.live_in[3], set(&[]));
+}
+
+#[test]
+fn regalloc_intervals() {
+    let fns = text::parse_fns(LOOP).unwrap();
+    let liveness = Liveness::new(&fns[0]);
+
+    // r1 and r5 are live all the way around the loop, even though they aren't
+    // read after the top of it.
+    let intervals: Vec<(String, usize, usize)> = regalloc::intervals(&fns[0], &liveness)
+        .into_iter()
+        .map(|i| (i.reg, i.start, i.end))
+        .collect();
+    assert_eq!(
+        intervals,
+        vec![
+            (String::from("r1"), 1, 16),
+            (String::from("r5"), 3, 15),
+            (String::from("r2"), 7, 8),
+            (String::from("r3"), 11, 12),
+        ]
+    );
+}
+
+#[test]
+fn regalloc_rewrite() {
+    let target = Target {
+        callee_saved: &[],
+        caller_saved: &["%a", "%b"],
+        scratch: &["%t0", "%t1"],
+    };
+    let mut fns = text::parse_fns(LOOP).unwrap();
+    let alloc = regalloc::allocate(&mut fns[0], &target);
+
+    // r1, r5 and r2 are all live at the top of the loop, and there are only two
+    // registers, so r1 is spilled since it ends last. r3 reuses r2's register.
+    assert_eq!(alloc.locs["r1"], Loc::Spill(0));
+    assert_eq!(alloc.locs["r5"], Loc::Reg("%b"));
+    assert_eq!(alloc.locs["r2"], Loc::Reg("%a"));
+    assert_eq!(alloc.locs["r3"], Loc::Reg("%a"));
+
+    let instrs: Vec<String> = fns[0].instrs().iter().map(|i| i.to_string()).collect();
+    assert_eq!(
+        instrs,
+        vec![
+            "%t0 = mv 0 : f",
+            "st spill.0 %t0 : f",
+            "%b = mv 10 : f",
+            "jmp .L0 : f",
+            "%t0 = ld spill.0 : .L0",
+            "%a = lt %t0 %b : .L0",
+            "jmpne %a .L1 : .L0",
+            "%t0 = ld spill.0 : .L2",
+            "%a = add %t0 1 : .L2",
+            "%t0 = mv %a : .L2",
+            "st spill.0 %t0 : .L2",
+            "jmp .L0 : .L2",
+            "%t0 = ld spill.0 : .L1",
+            "ret %t0 : .L1",
+        ]
+    );
+}
+
+#[test]
+fn regalloc_calls() {
+    // r1 is needed after the call, so it has to be in a callee-saved register,
+    // while r2 and r3 can use the caller-saved one.
+    let src = "r1 = mv 1 : f\n\
+               r2 = mv 2 : f\n\
+               param r2 : f\n\
+               r3 = call g 1 : f\n\
+               r4 = add r1 r3 : f\n\
+               ret r4 : f\n";
+    let mut fns = text::parse_fns(src).unwrap();
+    let alloc = regalloc::allocate(&mut fns[0], &SMALL);
+
+    assert_eq!(alloc.locs["r1"], Loc::Reg("%s0"));
+    assert_eq!(alloc.locs["r2"], Loc::Reg("%c0"));
+    assert_eq!(alloc.locs["r3"], Loc::Reg("%c0"));
+    assert_eq!(alloc.callee_saved, vec!["%s0"]);
+}
+
+#[test]
+fn regalloc_programs() {
+    let mut total_spills = 0;
+    for path in &[
+        "../kolgac_testutil/programs/fib.kol",
+        "../kolgac_testutil/programs/loops.kol",
+        "../kolgac_testutil/programs/classes.kol",
+        "./tests/irgen/prog.kol",
+        "./tests/regalloc/pressure.kol",
+    ] {
+        for level in &[OptLevel::O0, OptLevel::O2] {
+            let mut fns = build(path, *level);
+            let expected = globals(&fns);
+
+            let mut spills = 0;
+            for func in &mut fns {
+                spills += regalloc::allocate(func, &SMALL).spills;
+            }
+            assert_eq!(globals(&fns), expected, "{} at {:?}", path, level);
+
+            // Every register left is a physical one.
+            for instr in fns.iter().flat_map(|func| func.instrs()) {
+                for reg in instr.uses().into_iter().chain(instr.def()) {
+                    assert!(reg.starts_with('%'), "{} in '{}'", reg, instr);
+                }
+            }
+
+            if *level == OptLevel::O0 {
+                total_spills += spills;
+            }
+        }
+    }
+    assert!(total_spills > 0);
+}
@@ -0,0 +1,183 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    dom::DomTree,
+    instr::{IRArg, IROp, Instr},
+    irgen::IRGen,
+    ssa,
+};
+
+use std::collections::HashSet;
+use std::fs::File;
+
+fn build(path: &str) -> Vec<Function> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    cfg::build(&irgen.ir)
+}
+
+fn find(fns: &[Function], name: &str) -> Function {
+    fns.iter().find(|f| f.name == name).unwrap().clone()
+}
+
+fn lines(func: &Function, lbl: &str) -> Vec<String> {
+    let idx = func.block_idx(lbl).unwrap();
+    func.blocks[idx]
+        .instrs
+        .iter()
+        .map(|instr| instr.body())
+        .collect()
+}
+
+fn instr(op: IROp, op1: Option<IRArg>, op2: Option<IRArg>, dest: Option<&str>, lbl: &str) -> Instr {
+    let dest = dest.map(|d| IRArg::Reg(String::from(d)));
+    Instr::build(op1, op2, op, dest, String::from(lbl))
+}
+
+fn reg(name: &str) -> Option<IRArg> {
+    Some(IRArg::Reg(String::from(name)))
+}
+
+fn lbl(name: &str) -> Option<IRArg> {
+    Some(IRArg::Lbl(String::from(name)))
+}
+
+#[test]
+fn dom_tree() {
+    let fns = build("./tests/ssa/ssa.kol");
+    let pick = find(&fns, "pick");
+    let dom = DomTree::new(&pick);
+
+    let entry = pick.block_idx("pick").unwrap();
+    let then = pick.block_idx(".L5").unwrap();
+    let el = pick.block_idx(".L4").unwrap();
+    let end = pick.block_idx(".L3").unwrap();
+
+    assert_eq!(dom.idom[entry], None);
+    assert_eq!(dom.idom[then], Some(entry));
+    assert_eq!(dom.idom[el], Some(entry));
+    assert_eq!(dom.idom[end], Some(entry));
+    assert!(dom.dominates(entry, end));
+    assert!(!dom.dominates(then, end));
+
+    assert_eq!(dom.frontiers[then], vec![end]);
+    assert_eq!(dom.frontiers[el], vec![end]);
+    assert!(dom.frontiers[entry].is_empty());
+
+    // The loop condition is in the frontier of the loop body, and of itself.
+    let sum = find(&fns, "sum");
+    let dom = DomTree::new(&sum);
+    let cond = sum.block_idx(".L0").unwrap();
+    let body = sum.block_idx(".L2").unwrap();
+    assert_eq!(dom.frontiers[body], vec![cond]);
+    assert_eq!(dom.frontiers[cond], vec![cond]);
+}
+
+#[test]
+fn ssa_construct() {
+    let fns = build("./tests/ssa/ssa.kol");
+
+    let mut pick = find(&fns, "pick");
+    ssa::construct(&mut pick);
+    assert_eq!(
+        lines(&pick, ".L3"),
+        vec!["x.3 = phi [x.1 .L5] [x.2 .L4]", "r5 = mv x.3", "ret r5"]
+    );
+
+    // Only variables that are live at the loop condition get phis, so 't'
+    // doesn't, and 'n' is never assigned in the loop.
+    let mut swap = find(&fns, "swap");
+    ssa::construct(&mut swap);
+    let cond = lines(&swap, ".L6");
+    assert_eq!(
+        cond[..3].to_vec(),
+        vec![
+            "a.1 = phi [a.0 swap] [a.2 .L8]",
+            "b.1 = phi [b.0 swap] [b.2 .L8]",
+            "i.1 = phi [i.0 swap] [i.2 .L8]",
+        ]
+    );
+    assert_eq!(cond[3], "r5 = mv i.1");
+}
+
+#[test]
+fn ssa_assigns_once() {
+    for mut func in build("./tests/ssa/ssa.kol") {
+        ssa::construct(&mut func);
+
+        let mut defs = HashSet::new();
+        for instr in func.instrs() {
+            if let Some(IRArg::Reg(dest)) = &instr.result {
+                assert!(defs.insert(dest.clone()), "{} assigned twice", dest);
+            }
+
+            // Locals are promoted to registers, globals stay in memory.
+            let mem = matches!(instr.opcode, IROp::Ld | IROp::St);
+            assert!(!mem || matches!(instr.op1, Some(IRArg::Global(_))));
+        }
+    }
+}
+
+#[test]
+fn ssa_destruct() {
+    let fns = build("./tests/ssa/ssa.kol");
+    let mut pick = find(&fns, "pick");
+    ssa::construct(&mut pick);
+    ssa::destruct(&mut pick);
+
+    assert!(pick.instrs().iter().all(|instr| instr.opcode != IROp::Phi));
+    assert_eq!(
+        lines(&pick, ".L5"),
+        vec!["r3 = mv 2", "x.1 = mv r3", "x.3.phi = mv x.1", "jmp .L3"]
+    );
+    assert_eq!(
+        lines(&pick, ".L4"),
+        vec!["r4 = mv 3", "x.2 = mv r4", "x.3.phi = mv x.2", "jmp .L3"]
+    );
+    assert_eq!(
+        lines(&pick, ".L3"),
+        vec!["x.3 = mv x.3.phi", "r5 = mv x.3", "ret r5"]
+    );
+}
+
+#[test]
+fn ssa_destruct_splits_critical_edges() {
+    // 'f' either jumps straight to .L1, or goes through .L0 first, so the edge
+    // from 'f' to .L1 needs its own block for the copy.
+    let instrs = vec![
+        instr(IROp::Arg, Some(IRArg::Num(0.0)), None, Some("r0"), "f"),
+        instr(IROp::Mv, Some(IRArg::Num(1.0)), None, Some("r1"), "f"),
+        instr(IROp::JmpNe, reg("r0"), lbl(".L1"), None, "f"),
+        instr(IROp::Mv, Some(IRArg::Num(2.0)), None, Some("r2"), ".L0"),
+        instr(IROp::Jmp, lbl(".L1"), None, None, ".L0"),
+        instr(
+            IROp::Phi,
+            Some(IRArg::Phi(vec![
+                (IRArg::Reg(String::from("r1")), String::from("f")),
+                (IRArg::Reg(String::from("r2")), String::from(".L0")),
+            ])),
+            None,
+            Some("r3"),
+            ".L1",
+        ),
+        instr(IROp::Ret, reg("r3"), None, None, ".L1"),
+    ];
+
+    let mut func = Function::new("f", &instrs);
+    ssa::destruct(&mut func);
+
+    let lbls: Vec<&str> = func.blocks.iter().map(|b| b.lbl.as_str()).collect();
+    assert_eq!(lbls, vec!["f", ".L0", ".L1", ".f_L1"]);
+    assert_eq!(lines(&func, "f")[2], "jmpne r0 .f_L1");
+    assert_eq!(lines(&func, ".f_L1"), vec!["r3.phi = mv r1", "jmp .L1"]);
+    assert_eq!(
+        lines(&func, ".L0"),
+        vec!["r2 = mv 2", "r3.phi = mv r2", "jmp .L1"]
+    );
+    assert_eq!(lines(&func, ".L1"), vec!["r3 = mv r3.phi", "ret r3"]);
+}
@@ -0,0 +1,139 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg,
+    instr::{IRArg, IROp, Instr},
+    interp::{Interp, Value},
+    irgen::IRGen,
+    pass::{Pass, PassManager},
+    sccp::Sccp,
+    ssa,
+    text::{self, KirParseErr},
+};
+
+use std::fs::{self, File};
+
+fn gen(path: &str) -> Vec<Instr> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    irgen.ir
+}
+
+fn print(instrs: &[Instr]) -> String {
+    instrs.iter().map(|instr| format!("{}\n", instr)).collect()
+}
+
+#[test]
+fn text_round_trip() {
+    for path in &[
+        "./tests/irgen/prog.kol",
+        "../kolgac_testutil/programs/loops.kol",
+        "../kolgac_testutil/programs/classes.kol",
+        "../kolgac_testutil/programs/scopes.kol",
+    ] {
+        let ir = gen(path);
+        assert_eq!(text::parse(&print(&ir)).unwrap(), ir);
+    }
+}
+
+#[test]
+fn text_round_trip_ssa() {
+    let mut fns = cfg::build(&gen("./tests/ssa/ssa.kol"));
+    for func in &mut fns {
+        ssa::construct(func);
+    }
+
+    let ir: Vec<Instr> = fns.iter().flat_map(|f| f.instrs()).collect();
+    assert!(ir.iter().any(|instr| instr.opcode == IROp::Phi));
+    assert_eq!(text::parse(&print(&ir)).unwrap(), ir);
+}
+
+#[test]
+fn text_operands() {
+    let instrs = text::parse(
+        "r1 = mv \"a \\\"b\\\" : c\\n\" : @init\n\
+         st @g r1 : @init\n\
+         st x r1 : f\n\
+         r2 = ldf r1.3 : f\n\
+         r3 = mv -1.5 : f\n\
+         ret : f",
+    )
+    .unwrap();
+
+    assert_eq!(
+        instrs[0].op1,
+        Some(IRArg::Str(String::from("a \"b\" : c\n")))
+    );
+    assert_eq!(instrs[1].op1, Some(IRArg::Global(String::from("g"))));
+    assert_eq!(instrs[2].op1, Some(IRArg::Var(String::from("x"))));
+    assert_eq!(instrs[3].op1, Some(IRArg::Field(String::from("r1"), 3)));
+    assert_eq!(instrs[4].op1, Some(IRArg::Num(-1.5)));
+    assert_eq!(instrs[5].op1, None);
+    assert_eq!(instrs[5].lbl, "f");
+}
+
+#[test]
+fn text_errors() {
+    let err = |line, msg: &str| {
+        Err(KirParseErr {
+            line,
+            msg: String::from(msg),
+        })
+    };
+
+    assert_eq!(
+        text::parse("\nr1 = mv 1"),
+        err(2, "missing ' : label' at the end")
+    );
+    assert_eq!(
+        text::parse("r1 = mov 1 : f"),
+        err(1, "unknown operator 'mov'")
+    );
+    assert_eq!(
+        text::parse("ret r1 r2 : f"),
+        err(1, "'ret' takes 1 operands, found 2")
+    );
+    assert_eq!(
+        text::parse("st r1 : f"),
+        err(1, "'st' takes 2 operands, found 1")
+    );
+    assert_eq!(
+        text::parse("r1 = call f x : f"),
+        err(1, "expected a number, found 'x'")
+    );
+    assert_eq!(
+        text::parse("r1 = phi [r0 .L0 : f"),
+        err(1, "expected phi operands like '[r1 .L0]'")
+    );
+    assert_eq!(
+        text::parse("r1 = mv \"abc : f"),
+        err(1, "unterminated string")
+    );
+}
+
+#[test]
+fn text_hand_written() {
+    let src = fs::read_to_string("./tests/text/branch.kir").unwrap();
+    let mut fns = text::parse_fns(&src).unwrap();
+    assert_eq!(fns[0].name, "f");
+
+    let mut before = Interp::new(&fns);
+    before.run().unwrap();
+    assert_eq!(before.global("x"), Some(&Value::Num(4.0)));
+
+    Sccp.run(&mut fns[0]);
+    assert!(fns[0].block_idx(".L1").is_none());
+    assert_eq!(fns[0].blocks.last().unwrap().instrs[0].body(), "r4 = mv 1");
+
+    let mut pm = PassManager::new();
+    pm.add(Box::new(Sccp));
+    pm.run(&mut fns);
+
+    let mut after = Interp::new(&fns);
+    after.run().unwrap();
+    assert_eq!(after.global("x"), Some(&Value::Num(4.0)));
+}
@@ -0,0 +1,201 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    instr::{IRArg, IROp, Instr},
+    irgen::IRGen,
+    pass::{OptLevel, PassManager},
+    text,
+    verify::{self, VerifyErr},
+};
+
+use std::fs::{self, File};
+
+fn gen(path: &str) -> Vec<Function> {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let mut irgen = IRGen::new(&ast);
+    irgen.gen();
+    cfg::build(&irgen.ir)
+}
+
+fn msgs(src: &str) -> Vec<String> {
+    let fns = text::parse_fns(src).unwrap();
+    match verify::verify_all(&fns) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs.iter().map(|err| err.to_string()).collect(),
+    }
+}
+
+#[test]
+fn verify_valid_programs() {
+    for path in &[
+        "./tests/irgen/prog.kol",
+        "../kolgac_testutil/programs/classes.kol",
+        "../kolgac_testutil/programs/loops.kol",
+        "../kolgac_testutil/programs/scopes.kol",
+        "./tests/ssa/ssa.kol",
+    ] {
+        for level in &[OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let mut fns = gen(path);
+            assert_eq!(verify::verify_all(&fns), Ok(()));
+
+            PassManager::for_level(*level).run(&mut fns);
+            assert_eq!(verify::verify_all(&fns), Ok(()), "{} at {:?}", path, level);
+        }
+    }
+}
+
+#[test]
+fn verify_blocks() {
+    let src = fs::read_to_string("./tests/verify/bad.kir").unwrap();
+    assert_eq!(
+        msgs(&src),
+        vec![
+            "in f, block f: 'jmpne r0 .L9' jumps to undefined label",
+            "in f, block .L0: terminator 'jmp .L1' isn't at the end of the block",
+            "in f, block .L1: 'r3 = phi [r2 .L0]' comes after instructions that aren't phis",
+            "in f, block .L1: 'r3 = phi [r2 .L0]' names .L0, which isn't a predecessor",
+            "in f, block .L2: block ends in 'r4 = mv 1', which isn't a terminator",
+        ]
+    );
+
+    assert_eq!(
+        msgs("r0 = mv true : f\njmpe r0 f : f"),
+        vec!["in f, block f: 'jmpe r0 f' has no block to fall through to"]
+    );
+}
+
+#[test]
+fn verify_operands() {
+    let lbl = || String::from("f");
+    let reg = |r: &str| Some(IRArg::Reg(String::from(r)));
+    let instrs = vec![
+        Instr::build(Some(IRArg::Num(1.0)), None, IROp::Mv, reg("r1"), lbl()),
+        Instr::build(
+            Some(IRArg::Var(String::from("x"))),
+            None,
+            IROp::St,
+            None,
+            lbl(),
+        ),
+        Instr::build(None, reg("r1"), IROp::Param, None, lbl()),
+        Instr::build(reg("r1"), None, IROp::Mv, None, lbl()),
+        Instr::build(
+            reg("r1"),
+            reg("r1"),
+            IROp::Add,
+            Some(IRArg::Var(String::from("y"))),
+            lbl(),
+        ),
+        Instr::build(reg("r1"), None, IROp::Jmp, None, lbl()),
+        Instr::build(reg("r1"), reg("r1"), IROp::Ret, None, lbl()),
+    ];
+
+    let msgs: Vec<String> = match verify::verify(&Function::new("f", &instrs)) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs.iter().map(|err| err.msg.clone()).collect(),
+    };
+    assert_eq!(
+        msgs,
+        vec![
+            "'st' takes 2 operands, found 1 in 'st x'",
+            "'param r1' has a second operand, but no first",
+            "'mv r1' has no result",
+            "result of 'y = add r1 r1' isn't a register",
+            "terminator 'jmp r1' isn't at the end of the block",
+            "'r1' can't be used as an operand of 'jmp'",
+            "'ret' takes 0 to 1 operands, found 2 in 'ret r1 r1'",
+        ]
+    );
+}
+
+#[test]
+fn verify_defs() {
+    let src = "r0 = arg 0 : f\n\
+               jmpne r0 .L1 : f\n\
+               r1 = mv 1 : .L0\n\
+               jmp .L2 : .L0\n\
+               r2 = mv 2 : .L1\n\
+               jmp .L2 : .L1\n\
+               r3 = phi [r1 .L0] [r1 .L1] : .L2\n\
+               r4 = add r1 r3 : .L2\n\
+               ret r5 : .L2\n";
+
+    assert_eq!(
+        msgs(src),
+        vec![
+            "in f, block .L2: 'r3 = phi [r1 .L0] [r1 .L1]' uses r1, which isn't defined at the end of .L1",
+            "in f, block .L2: 'r4 = add r1 r3' uses r1 before it's defined on every path",
+            "in f, block .L2: 'ret r5' uses r5 before it's defined on every path",
+        ]
+    );
+}
+
+#[test]
+fn verify_loop_defs() {
+    // r1 is defined before the loop, and redefined inside it, which is fine
+    // outside SSA form.
+    let src = "r1 = mv 0 : f\n\
+               jmp .L0 : f\n\
+               r2 = lt r1 10 : .L0\n\
+               jmpne r2 .L1 : .L0\n\
+               r1 = add r1 1 : .L3\n\
+               jmp .L0 : .L3\n\
+               ret r1 : .L1\n";
+
+    assert_eq!(msgs(src), Vec::<String>::new());
+}
+
+#[test]
+fn verify_tys() {
+    let src = "r0 = mv true : f\n\
+               r1 = add r0 1 : f\n\
+               r2 = eq r1 \"a\" : f\n\
+               jmpne r1 .L0 : f\n\
+               st x 1 : .L0\n\
+               st x r0 : .L0\n\
+               r3 = ldf r1.0 : .L0\n\
+               r0 = mv 2 : .L0\n\
+               ret : .L0\n";
+
+    assert_eq!(
+        msgs(src),
+        vec![
+            "in f, block f: 'r1 = add r0 1': first operand should be a num, found a bool",
+            "in f, block f: 'r2 = eq r1 \"a\"': compares a num with a str",
+            "in f, block f: 'jmpne r1 .L0': condition should be a bool, found a num",
+            "in f, block .L0: 'st x r0': stores a bool in x, which holds a num",
+            "in f, block .L0: 'r3 = ldf r1.0': base of the property should be a class instance, found a num",
+            "in f, block .L0: 'r0 = mv 2': result is a num, but r0 is a bool elsewhere",
+        ]
+    );
+}
+
+#[test]
+fn verify_calls() {
+    let src = "param 1 : @init\n\
+               r0 = call g 1 : @init\n\
+               ret : @init\n\
+               r0 = arg 0 : f\n\
+               ret r0 : f\n";
+
+    let fns = text::parse_fns(src).unwrap();
+    assert_eq!(
+        verify::verify_all(&fns),
+        Err(vec![VerifyErr {
+            func: String::from("@init"),
+            lbl: String::from("@init"),
+            msg: String::from("'r0 = call g 1' calls undefined function 'g'"),
+        }])
+    );
+}
+
+#[test]
+#[should_panic(expected = "invalid KIR after sccp:\nin f, block f: 'ret r9' uses r9")]
+fn verify_debug_panics() {
+    let fns = text::parse_fns("ret r9 : f").unwrap();
+    verify::debug_verify(&fns, "sccp");
+}
@@ -1,6 +1,9 @@
+use crate::pipeline;
+
+use kolgac::opt::OptLevel;
+
 use llvm_sys::core::*;
 use llvm_sys::prelude::{LLVMModuleRef, LLVMPassManagerRef, LLVMValueRef};
-use llvm_sys::transforms::scalar::*;
 
 pub struct FPM {
     /// A reference to the actual function pass manager
@@ -14,29 +17,27 @@ impl Drop for FPM {
 }
 
 impl FPM {
-    /// Creates a new pass manager for the given module.
-    pub fn new(module: LLVMModuleRef) -> FPM {
-        unsafe {
-            FPM {
-                fpm: LLVMCreateFunctionPassManagerForModule(module),
-            }
-        }
-    }
-
-    /// Initializes the pass manager and add all desired function passes.
-    pub fn init(&mut self) {
-        unsafe {
-            //LLVMAddPromoteMemoryToRegisterPass(self.fpm);
-            LLVMAddInstructionCombiningPass(self.fpm);
-            LLVMAddReassociatePass(self.fpm);
-            LLVMInitializeFunctionPassManager(self.fpm);
+    /// Creates a new pass manager for the given module, with the function passes
+    /// for an optimization level.
+    ///
+    /// # Safety
+    ///
+    /// The module has to be valid, and outlive the pass manager.
+    pub unsafe fn new(module: LLVMModuleRef, level: OptLevel) -> FPM {
+        let fpm = LLVMCreateFunctionPassManagerForModule(module);
+        for pass in pipeline::fn_passes(level) {
+            (pass.add)(fpm);
         }
+        LLVMInitializeFunctionPassManager(fpm);
+        FPM { fpm }
     }
 
     /// Runs the pass manager for a given function.
-    pub fn run(&mut self, fn_ref: LLVMValueRef) {
-        unsafe {
-            LLVMRunFunctionPassManager(self.fpm, fn_ref);
-        }
+    ///
+    /// # Safety
+    ///
+    /// The function has to be valid, and belong to the pass manager's module.
+    pub unsafe fn run(&mut self, fn_ref: LLVMValueRef) {
+        LLVMRunFunctionPassManager(self.fpm, fn_ref);
     }
 }
@@ -11,5 +11,8 @@ macro_rules! c_str {
 pub mod classtab;
 pub mod fpm;
 pub mod llvm;
+pub mod mpm;
 pub mod obj;
+pub mod pipeline;
+pub mod target;
 pub mod valtab;
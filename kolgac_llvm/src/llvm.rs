@@ -1,21 +1,27 @@
-use crate::{classtab::ClassTab, valtab::ValTab};
-//use crate::fpm::FPM;
+use crate::{classtab::ClassTab, fpm::FPM, mpm::MPM, target::TargetMachine, valtab::ValTab};
 
 use kolgac_errors::gen::{GenErr, GenErrTy};
 
 use kolgac::{
     ast::Ast,
+    const_eval::{ConstEval, ConstVal},
+    opt::OptLevel,
     token::{TknTy, Token},
     ty_rec::{KolgaTy, TyRecord},
 };
 
 use llvm_sys::{
+    bit_writer::LLVMWriteBitcodeToFile,
     core::*,
     prelude::*,
     {LLVMRealPredicate, LLVMTypeKind},
 };
 
-use std::{collections::HashMap, ffi::CString, ptr, slice};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    ptr,
+};
 
 const LLVM_FALSE: LLVMBool = 0;
 
@@ -66,6 +72,10 @@ pub struct CodeGenerator<'t, 'v> {
     /// Class table stores LLVmStructTypes so we can look them up before allocating.
     classtab: ClassTab,
 
+    /// Values of immutable variables that are known at compile time. This is
+    /// scoped the same way as the value table.
+    consts: ConstEval,
+
     /// LLVM Context.
     context: LLVMContextRef,
 
@@ -80,8 +90,12 @@ pub struct CodeGenerator<'t, 'v> {
 
     /// Vector of potential errors to return.
     pub errors: Vec<GenErr>,
-    // LLVM Function pass manager, for some optimization passes after function codegen.
-    //fpm: FPM
+
+    /// LLVM Function pass manager, for some optimization passes after function codegen.
+    fpm: FPM,
+
+    /// Optimization level, which picks the passes we run.
+    opt_level: OptLevel,
 }
 
 /// We implement Drop for the CodeGenerator to ensure that our LLVM structs are safely
@@ -101,31 +115,56 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
     /// We assume that the symbol table already contains all the required variables in this module,
     /// and that the value table is newly defined and should be empty.
     /// This function also sets up all the required LLVM structures needed to generate the IR:
-    /// the context, the builder, and the module.
-    pub fn new(ast: &'t Ast, valtab: &'v mut ValTab) -> CodeGenerator<'t, 'v> {
+    /// the context, the builder, the module, and the pass manager for the optimization level.
+    /// The module gets the target's triple and data layout, which the passes use to decide what's
+    /// cheap on that target.
+    pub fn new(
+        ast: &'t Ast,
+        valtab: &'v mut ValTab,
+        opt_level: OptLevel,
+        target: &TargetMachine,
+    ) -> CodeGenerator<'t, 'v> {
         unsafe {
             let context = LLVMContextCreate();
             let module = LLVMModuleCreateWithNameInContext(c_str!("kolga"), context);
+            LLVMSetTarget(module, target.triple().as_ptr());
+            LLVMSetDataLayout(module, target.data_layout().as_ptr());
             CodeGenerator {
                 ast: ast,
                 valtab: valtab,
                 classtab: ClassTab::new(),
+                consts: ConstEval::new(),
                 errors: Vec::new(),
                 context: context,
                 builder: LLVMCreateBuilderInContext(context),
                 module: module,
-                strings: Vec::new(), //fpm: FPM::new(module)
+                strings: Vec::new(),
+                fpm: FPM::new(module, opt_level),
+                opt_level,
             }
         }
     }
 
     /// Initial entry point for LLVM IR code generation. Loops through each statement in the
     /// program and generates LLVM IR for each of them. The code is written to the module,
-    /// to be converted to assembly later.
+    /// to be converted to assembly later. Once the whole module is generated, we run the
+    /// module passes over it, unless there were errors.
+    ///
+    /// The top level code goes in a function called main, which returns 0. It's created
+    /// before anything else, so a function the program calls main gets renamed by LLVM.
     pub fn gen_ir(&mut self) {
         let mut cctx = GenClsCtx::new();
         let mut gctx = GenCtx::new(&mut cctx);
 
+        let main_fn = unsafe {
+            let main_ty = LLVMFunctionType(self.i32_ty(), ptr::null_mut(), 0, LLVM_FALSE);
+            let main_fn = LLVMAddFunction(self.module, self.c_str("main"), main_ty);
+            let entry_bb =
+                LLVMAppendBasicBlockInContext(self.context, main_fn, self.c_str("entry"));
+            LLVMPositionBuilderAtEnd(self.builder, entry_bb);
+            main_fn
+        };
+
         match self.ast {
             Ast::Prog { meta: _, stmts } => {
                 for stmt in stmts {
@@ -134,6 +173,17 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
             }
             _ => (),
         }
+
+        unsafe {
+            LLVMBuildRet(self.builder, LLVMConstInt(self.i32_ty(), 0, LLVM_FALSE));
+            if self.errors.is_empty() {
+                self.fpm.run(main_fn);
+            }
+        }
+
+        if self.errors.is_empty() {
+            unsafe { MPM::new(self.opt_level).run(self.module) };
+        }
     }
 
     /// Dumps the current module's IR to stdout.
@@ -143,15 +193,31 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
         }
     }
 
-    /// Saves the current module's IR to a file.
-    pub fn print_ir(&self, filename: String) {
+    /// Saves the current module's IR to a file, as text.
+    pub fn print_ir(&self, filename: &str) -> Result<(), String> {
+        let filename = CString::new(filename).map_err(|e| e.to_string())?;
         unsafe {
-            LLVMPrintModuleToFile(
-                self.module,
-                filename.as_bytes().as_ptr() as *const i8,
-                ptr::null_mut(),
-            );
+            let mut err = ptr::null_mut();
+            if LLVMPrintModuleToFile(self.module, filename.as_ptr(), &mut err) != 0 {
+                let msg = CStr::from_ptr(err).to_string_lossy().into_owned();
+                LLVMDisposeMessage(err);
+                return Err(msg);
+            }
         }
+
+        Ok(())
+    }
+
+    /// Saves the current module's IR to a file, as bitcode.
+    pub fn write_bitcode(&self, filename: &str) -> Result<(), String> {
+        let filename = CString::new(filename).map_err(|e| e.to_string())?;
+        unsafe {
+            if LLVMWriteBitcodeToFile(self.module, filename.as_ptr()) != 0 {
+                return Err(String::from("could not open file"));
+            }
+        }
+
+        Ok(())
     }
 
     pub fn get_mod(&self) -> LLVMModuleRef {
@@ -175,11 +241,17 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 stmts,
                 sc: _,
             } => {
+                // Variables declared in the block go out of scope at its end, and
+                // stop hiding the ones with the same names outside it.
+                self.valtab.init_sc();
+                self.consts.init_sc();
                 let mut generated = Vec::new();
                 for stmt in stmts {
                     let mb_gen = self.gen_stmt(gctx, &stmt.clone());
                     generated.extend(mb_gen);
                 }
+                self.valtab.close_sc();
+                self.consts.close_sc();
 
                 generated
             }
@@ -200,12 +272,24 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 if_stmts,
                 elif_exprs,
                 el_stmts,
-            } => self.if_stmt(gctx, cond_expr, if_stmts, elif_exprs, el_stmts),
+            } => match self.consts.eval(cond_expr) {
+                // When the condition is known at compile time, we only generate the
+                // branch that runs.
+                Some(ConstVal::Bool(true)) => self.gen_stmt(gctx, if_stmts),
+                Some(ConstVal::Bool(false)) if elif_exprs.is_empty() => el_stmts
+                    .iter()
+                    .flat_map(|stmt| self.gen_stmt(gctx, stmt))
+                    .collect(),
+                _ => self.if_stmt(gctx, cond_expr, if_stmts, elif_exprs, el_stmts),
+            },
             Ast::WhileStmt {
                 meta: _,
                 cond_expr,
                 stmts,
-            } => self.while_stmt(gctx, cond_expr, stmts),
+            } => match self.consts.eval(cond_expr) {
+                Some(ConstVal::Bool(false)) => Vec::new(),
+                _ => self.while_stmt(gctx, cond_expr, stmts),
+            },
             Ast::ForStmt {
                 meta: _,
                 for_var_decl,
@@ -219,47 +303,53 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 fn_params,
                 ret_ty,
                 fn_body,
-                sc: _,
+                ..
             } => self.fn_decl_stmt(gctx, ident_tkn, fn_params, ret_ty, fn_body),
             Ast::VarAssignExpr {
                 meta: _,
                 ty_rec,
                 ident_tkn,
-                is_imm: _,
+                is_imm,
                 is_global,
                 value,
-            } => self.var_assign_expr(gctx, ty_rec, ident_tkn, *is_global, value),
+            } => {
+                self.consts.bind(&ident_tkn.get_name(), *is_imm, value);
+                self.var_assign_expr(gctx, ty_rec, ident_tkn, *is_global, value)
+            }
             Ast::VarDeclExpr {
                 meta: _,
                 ty_rec,
                 ident_tkn,
                 is_imm: _,
                 is_global,
-            } => match is_global {
-                // Similar to var assignments, we generate different IR based on
-                // whether the var is global or not. For global declarations, we
-                // add a global without setting the initializer. For locals, we
-                // build an alloca/store pair, but with no expression value
-                // to store.
-                true => unsafe {
-                    let c_name = self.c_str(&ident_tkn.get_name());
-                    let llvm_ty = self.llvm_ty_from_ty_rec(ty_rec, false);
-                    let global = LLVMAddGlobal(self.module, llvm_ty, c_name);
-                    self.valtab.store(&ident_tkn.get_name(), global);
-                    vec![global]
-                },
-                false => unsafe {
-                    let insert_bb = LLVMGetInsertBlock(self.builder);
-                    let llvm_func = LLVMGetBasicBlockParent(insert_bb);
-                    let alloca_instr = self.build_entry_bb_alloca(
-                        llvm_func,
-                        ty_rec.clone(),
-                        &ident_tkn.get_name(),
-                    );
-                    self.valtab.store(&ident_tkn.get_name(), alloca_instr);
-                    vec![alloca_instr]
-                },
-            },
+            } => {
+                self.consts.shadow(&ident_tkn.get_name());
+                match is_global {
+                    // Similar to var assignments, we generate different IR based on
+                    // whether the var is global or not. For global declarations, we
+                    // add a global initialized to zero. For locals, we build an
+                    // alloca/store pair, but with no expression value to store.
+                    true => unsafe {
+                        let c_name = self.c_str(&ident_tkn.get_name());
+                        let llvm_ty = self.llvm_ty_from_ty_rec(ty_rec, false);
+                        let global = LLVMAddGlobal(self.module, llvm_ty, c_name);
+                        LLVMSetInitializer(global, LLVMConstNull(llvm_ty));
+                        self.valtab.store(&ident_tkn.get_name(), global);
+                        vec![global]
+                    },
+                    false => unsafe {
+                        let insert_bb = LLVMGetInsertBlock(self.builder);
+                        let llvm_func = LLVMGetBasicBlockParent(insert_bb);
+                        let alloca_instr = self.build_entry_bb_alloca(
+                            llvm_func,
+                            ty_rec.clone(),
+                            &ident_tkn.get_name(),
+                        );
+                        self.valtab.store(&ident_tkn.get_name(), alloca_instr);
+                        vec![alloca_instr]
+                    },
+                }
+            }
             Ast::ClassDeclStmt {
                 meta: _,
                 ty_rec: _,
@@ -397,6 +487,16 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
         }
     }
 
+    /// Generates a constant LLVM value from a value known at compile time.
+    fn const_val(&self, val: &ConstVal) -> LLVMValueRef {
+        unsafe {
+            match *val {
+                ConstVal::Num(num) => LLVMConstReal(self.double_ty(), num),
+                ConstVal::Bool(b) => LLVMConstInt(self.i8_ty(), b as u64, LLVM_FALSE),
+            }
+        }
+    }
+
     /// Generate LLVM IR for a primary expression. This returns an Option because
     /// it's possible that we can't retrieve an identifier from the value table (if it's
     /// undefined).
@@ -748,6 +848,11 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
         fn_body: &Box<Ast>,
     ) -> Vec<LLVMValueRef> {
         self.valtab.init_sc();
+        self.consts.init_sc();
+
+        // Functions can be declared in the middle of the top level code, which
+        // carries on where it left off once the function is done.
+        let outer_bb = unsafe { LLVMGetInsertBlock(self.builder) };
 
         let fn_name = self.c_str(&ident_tkn.get_name());
         let fn_ty = self.llvm_ty_from_ty_rec(ret_ty, false);
@@ -774,13 +879,12 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
             // we pass in an array of LLVMTypeRef's to the function, but we want
             // LLVMValueRef's to store in the symbol table and to give them names. We need
             // to get the params and loop through them again.
-            let llvm_params: *mut LLVMValueRef = Vec::with_capacity(param_tys.len()).as_mut_ptr();
-            LLVMGetParams(llvm_fn, llvm_params);
-            let param_value_vec: Vec<LLVMValueRef> =
-                slice::from_raw_parts(llvm_params, param_tys.len()).to_vec();
+            let mut param_value_vec: Vec<LLVMValueRef> = vec![ptr::null_mut(); param_tys.len()];
+            LLVMGetParams(llvm_fn, param_value_vec.as_mut_ptr());
 
             for (idx, param) in param_value_vec.iter().enumerate() {
                 let name = &fn_params[idx].tkn.get_name();
+                self.consts.shadow(name);
                 let c_name = self.c_str(name);
                 LLVMSetValueName2(*param, c_name, name.len());
                 if name == "self" {
@@ -822,16 +926,20 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 _ => (),
             }
 
-            // Run the function pass through our manager
-            // TODO: this is commented out because of compile times
-            //self.fpm.run(llvm_fn);
+            // Run the function pass through our manager, as long as we've generated
+            // valid IR for it.
+            if self.errors.is_empty() {
+                self.fpm.run(llvm_fn);
+            }
 
             // Close the function level scope, which will pop off any params and
             // variable declared here (we don't need these anymore, since we aren't
             // going to be making another pass over them later). Add the llvm function
             // to the value table so we can look it up later for a call.
             self.valtab.close_sc();
+            self.consts.close_sc();
             self.valtab.store(&ident_tkn.get_name(), llvm_fn);
+            LLVMPositionBuilderAtEnd(self.builder, outer_bb);
         }
 
         Vec::new()
@@ -905,7 +1013,7 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
         let c_name = self.c_str(&ident_tkn.get_name());
         let var_ident = ident_tkn.get_name();
 
-        // For global class constructors, we add the global without an initializer.
+        // For global class constructors, we add the global initialized to zero.
         // We also have to find the class from the class table to ensure we aren't
         // trying to create a class object that isn't defined.
         match *value.clone() {
@@ -922,21 +1030,26 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 }
                 unsafe {
                     let global = LLVMAddGlobal(self.module, llvm_ty.unwrap(), c_name);
+                    LLVMSetInitializer(global, LLVMConstNull(llvm_ty.unwrap()));
                     self.valtab.store(&var_ident, global);
                     vec![global]
                 }
             }
             _ => unsafe {
-                // For other variabel types, create a global and set the initializer.
-                // We are certain to have an initializer here, because we're parsing
-                // a var assign, and not a var decl.
+                // For other variable types, create a global and set the initializer.
+                // Initializers have to be constant, so we fold the value when we
+                // can. Otherwise the global starts out as zero, and the top level
+                // code stores the value in it when it gets there.
                 let llvm_ty = self.llvm_ty_from_ty_rec(ty_rec, false);
                 let global = LLVMAddGlobal(self.module, llvm_ty, c_name);
-
-                let val = self.gen_expr(gctx, &value.clone()).unwrap();
-                // TODO: this doesn't work for class prop accesses, because it
-                // returns a load instruction
-                LLVMSetInitializer(global, val);
+                match self.consts.eval(value) {
+                    Some(const_val) => LLVMSetInitializer(global, self.const_val(&const_val)),
+                    None => {
+                        LLVMSetInitializer(global, LLVMConstNull(llvm_ty));
+                        let val = self.gen_expr(gctx, &value.clone()).unwrap();
+                        LLVMBuildStore(self.builder, val, global);
+                    }
+                }
                 self.valtab.store(&ident_tkn.get_name(), global);
                 vec![global]
             },
@@ -1048,6 +1161,7 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                     fn_params,
                     ret_ty,
                     fn_body,
+                    is_mut,
                     ..
                 } => {
                     // We need to add the class declaration type to the list of
@@ -1081,6 +1195,7 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                         ret_ty: ret_ty.clone(),
                         fn_body: fn_body.clone(),
                         sc: 0,
+                        is_mut: *is_mut,
                     };
 
                     self.gen_stmt(gctx, &new_method);
@@ -1311,6 +1426,9 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
                 self.classtab.retrieve(&name).unwrap()
             }
             KolgaTy::Symbolic(_) => panic!("Found a type in codegen that wasn't inferred!"),
+            KolgaTy::Error => {
+                panic!("Found a type in codegen from a declaration that failed to parse!")
+            }
         }
     }
 
@@ -1407,6 +1525,10 @@ impl<'t, 'v> CodeGenerator<'t, 'v> {
         unsafe { LLVMPointerType(self.i8_ty(), 0) }
     }
 
+    fn i32_ty(&self) -> LLVMTypeRef {
+        unsafe { LLVMInt32TypeInContext(self.context) }
+    }
+
     fn double_ty(&self) -> LLVMTypeRef {
         unsafe { LLVMDoubleTypeInContext(self.context) }
     }
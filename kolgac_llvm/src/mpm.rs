@@ -0,0 +1,40 @@
+use crate::pipeline;
+
+use kolgac::opt::OptLevel;
+
+use llvm_sys::core::*;
+use llvm_sys::prelude::{LLVMModuleRef, LLVMPassManagerRef};
+
+pub struct MPM {
+    /// A reference to the actual module pass manager
+    mpm: LLVMPassManagerRef,
+}
+
+impl Drop for MPM {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposePassManager(self.mpm) }
+    }
+}
+
+impl MPM {
+    /// Creates a new pass manager with the module passes for an optimization
+    /// level.
+    pub fn new(level: OptLevel) -> MPM {
+        unsafe {
+            let mpm = LLVMCreatePassManager();
+            for pass in pipeline::module_passes(level) {
+                (pass.add)(mpm);
+            }
+            MPM { mpm }
+        }
+    }
+
+    /// Runs the pass manager over a whole module.
+    ///
+    /// # Safety
+    ///
+    /// The module has to be valid.
+    pub unsafe fn run(&mut self, module: LLVMModuleRef) {
+        LLVMRunPassManager(self.mpm, module);
+    }
+}
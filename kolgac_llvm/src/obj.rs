@@ -1,67 +1,60 @@
-use llvm_sys::{prelude::*, target::*, target_machine::*};
+use crate::target::TargetMachine;
+
+use llvm_sys::{core::*, prelude::*, target_machine::*};
 
 use std::{
     ffi::{CStr, CString},
     ptr,
 };
 
+/// Generates machine code from an LLVM module, as an object file or as
+/// assembly.
 pub struct ObjGenerator {
     ir: LLVMModuleRef,
+
+    /// Target machine to generate code for, which has to be the one the module
+    /// was generated with.
+    target: TargetMachine,
 }
 
 impl ObjGenerator {
-    pub fn new(module: LLVMModuleRef) -> ObjGenerator {
-        ObjGenerator { ir: module }
+    pub fn new(module: LLVMModuleRef, target: TargetMachine) -> ObjGenerator {
+        ObjGenerator { ir: module, target }
     }
 
-    pub fn emit(&mut self, filename: &str) {
-        unsafe {
-            let triple = LLVMGetDefaultTargetTriple();
-
-            LLVM_InitializeAllTargetInfos();
-            LLVM_InitializeAllTargets();
-            LLVM_InitializeAllTargetMCs();
-            LLVM_InitializeAllAsmParsers();
-            LLVM_InitializeAllAsmPrinters();
-
-            let mut target = ptr::null_mut();
-            let mut target_err_str = ptr::null_mut();
-            LLVMGetTargetFromTriple(triple, &mut target, &mut target_err_str);
-            if target.is_null() {
-                let cmsg = CStr::from_ptr(target_err_str as *const _);
-                panic!("{:?}", cmsg);
-            }
-
-            let cpu = CString::new("generic").expect("invalid cpu name provided");
-            let features = CString::new("").expect("invalid feature provided");
-
-            let target_machine = LLVMCreateTargetMachine(
-                target,
-                triple,
-                cpu.as_ptr() as *const _,
-                features.as_ptr() as *const _,
-                LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
-                LLVMRelocMode::LLVMRelocDefault,
-                LLVMCodeModel::LLVMCodeModelDefault,
-            );
+    /// Writes an object file.
+    pub fn emit(&mut self, filename: &str) -> Result<(), String> {
+        self.emit_file(filename, LLVMCodeGenFileType::LLVMObjectFile)
+    }
 
-            let output_file = CString::new(filename).expect("invalid filename provided");
-            let mut err_str = CString::new("writing obj file failed").unwrap().as_ptr() as *mut _;
+    /// Writes an assembly file, in the target's usual syntax.
+    pub fn emit_asm(&mut self, filename: &str) -> Result<(), String> {
+        self.emit_file(filename, LLVMCodeGenFileType::LLVMAssemblyFile)
+    }
 
+    /// Generating code changes the module it's given, so we generate from a
+    /// copy. That way the module is the same however many files we write.
+    fn emit_file(&mut self, filename: &str, file_ty: LLVMCodeGenFileType) -> Result<(), String> {
+        let output_file = CString::new(filename).map_err(|e| e.to_string())?;
+        unsafe {
+            let module = LLVMCloneModule(self.ir);
+            let mut err = ptr::null_mut();
             let result = LLVMTargetMachineEmitToFile(
-                target_machine,
-                self.ir,
-                output_file.as_ptr() as *mut i8,
-                LLVMCodeGenFileType::LLVMObjectFile,
-                &mut err_str,
+                self.target.machine_ref(),
+                module,
+                output_file.as_ptr() as *mut _,
+                file_ty,
+                &mut err,
             );
+            LLVMDisposeModule(module);
 
             if result != 0 {
-                let cmsg = CStr::from_ptr(err_str as *const _);
-                panic!("{:?}", cmsg);
+                let msg = CStr::from_ptr(err).to_string_lossy().into_owned();
+                LLVMDisposeMessage(err);
+                return Err(msg);
             }
-
-            LLVMDisposeTargetMachine(target_machine);
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,155 @@
+use kolgac::opt::OptLevel;
+
+use llvm_sys::{
+    prelude::LLVMPassManagerRef,
+    target_machine::LLVMCodeGenOptLevel,
+    transforms::{
+        instcombine::LLVMAddInstructionCombiningPass,
+        ipo::*,
+        scalar::{
+            LLVMAddAggressiveDCEPass, LLVMAddCFGSimplificationPass,
+            LLVMAddCorrelatedValuePropagationPass, LLVMAddDeadStoreEliminationPass,
+            LLVMAddEarlyCSEPass, LLVMAddGVNPass, LLVMAddIndVarSimplifyPass,
+            LLVMAddJumpThreadingPass, LLVMAddLICMPass, LLVMAddLoopDeletionPass,
+            LLVMAddLoopIdiomPass, LLVMAddLoopRotatePass, LLVMAddLoopUnrollPass,
+            LLVMAddMemCpyOptPass, LLVMAddReassociatePass, LLVMAddSCCPPass,
+            LLVMAddScalarReplAggregatesPass,
+        },
+        util::LLVMAddPromoteMemoryToRegisterPass,
+        vectorize::*,
+    },
+};
+
+/// An LLVM pass, and the function that adds it to a pass manager. The name is
+/// the one LLVM's opt tool uses for the same pass.
+#[derive(Clone, Copy)]
+pub struct Pass {
+    pub name: &'static str,
+    pub add: unsafe extern "C" fn(LLVMPassManagerRef),
+}
+
+macro_rules! pass {
+    ($name:expr, $add:ident) => {
+        Pass {
+            name: $name,
+            add: $add,
+        }
+    };
+}
+
+/// Passes run on each function as soon as its IR is generated. Everything above
+/// O0 starts with mem2reg, since we put every variable in an alloca and the
+/// other passes can't do much until they're promoted to registers. Os leaves out
+/// the passes that copy code to make it faster: loop rotation duplicates the
+/// loop test, and jump threading duplicates blocks.
+pub fn fn_passes(level: OptLevel) -> Vec<Pass> {
+    if level == OptLevel::O0 {
+        return Vec::new();
+    }
+
+    let mut passes = vec![
+        pass!("mem2reg", LLVMAddPromoteMemoryToRegisterPass),
+        pass!("instcombine", LLVMAddInstructionCombiningPass),
+        pass!("reassociate", LLVMAddReassociatePass),
+        pass!("early-cse", LLVMAddEarlyCSEPass),
+        pass!("simplifycfg", LLVMAddCFGSimplificationPass),
+    ];
+    if level == OptLevel::O1 {
+        return passes;
+    }
+
+    passes.extend(vec![
+        pass!("sroa", LLVMAddScalarReplAggregatesPass),
+        pass!("sccp", LLVMAddSCCPPass),
+    ]);
+    if level != OptLevel::Os {
+        passes.push(pass!("jump-threading", LLVMAddJumpThreadingPass));
+    }
+    passes.push(pass!(
+        "correlated-propagation",
+        LLVMAddCorrelatedValuePropagationPass
+    ));
+    if level != OptLevel::Os {
+        passes.push(pass!("loop-rotate", LLVMAddLoopRotatePass));
+    }
+    passes.extend(vec![
+        pass!("licm", LLVMAddLICMPass),
+        pass!("indvars", LLVMAddIndVarSimplifyPass),
+        pass!("loop-idiom", LLVMAddLoopIdiomPass),
+        pass!("loop-deletion", LLVMAddLoopDeletionPass),
+    ]);
+    if level == OptLevel::O3 {
+        passes.push(pass!("loop-unroll", LLVMAddLoopUnrollPass));
+    }
+    passes.extend(vec![
+        pass!("gvn", LLVMAddGVNPass),
+        pass!("memcpyopt", LLVMAddMemCpyOptPass),
+        pass!("dse", LLVMAddDeadStoreEliminationPass),
+        pass!("adce", LLVMAddAggressiveDCEPass),
+    ]);
+    if level == OptLevel::O3 {
+        passes.push(pass!("slp-vectorizer", LLVMAddSLPVectorizePass));
+    }
+    passes.extend(vec![
+        pass!("instcombine", LLVMAddInstructionCombiningPass),
+        pass!("simplifycfg", LLVMAddCFGSimplificationPass),
+    ]);
+    passes
+}
+
+/// Passes run on the whole module, once every function has been generated.
+/// These work across functions, so this is where inlining happens. Each
+/// function has already been through its own passes, but inlining gives them
+/// more to do, so the important ones run again afterwards.
+pub fn module_passes(level: OptLevel) -> Vec<Pass> {
+    match level {
+        OptLevel::O0 => Vec::new(),
+        OptLevel::O1 => vec![
+            pass!("always-inline", LLVMAddAlwaysInlinerPass),
+            pass!("globaldce", LLVMAddGlobalDCEPass),
+        ],
+        OptLevel::O2 | OptLevel::O3 | OptLevel::Os => {
+            let mut passes = vec![
+                pass!("ipsccp", LLVMAddIPSCCPPass),
+                pass!("globalopt", LLVMAddGlobalOptimizerPass),
+                pass!("deadargelim", LLVMAddDeadArgEliminationPass),
+                pass!("function-attrs", LLVMAddFunctionAttrsPass),
+            ];
+
+            // Inlining usually makes code bigger, so at Os we only inline
+            // functions that ask for it.
+            match level {
+                OptLevel::Os => passes.push(pass!("always-inline", LLVMAddAlwaysInlinerPass)),
+                _ => passes.push(pass!("inline", LLVMAddFunctionInliningPass)),
+            }
+            if level == OptLevel::O3 {
+                passes.push(pass!("argpromotion", LLVMAddArgumentPromotionPass));
+            }
+
+            passes.extend(vec![
+                pass!("instcombine", LLVMAddInstructionCombiningPass),
+                pass!("gvn", LLVMAddGVNPass),
+                pass!("licm", LLVMAddLICMPass),
+            ]);
+            if level == OptLevel::O3 {
+                passes.push(pass!("loop-vectorize", LLVMAddLoopVectorizePass));
+            }
+            passes.extend(vec![
+                pass!("simplifycfg", LLVMAddCFGSimplificationPass),
+                pass!("globaldce", LLVMAddGlobalDCEPass),
+                pass!("constmerge", LLVMAddConstantMergePass),
+            ]);
+            passes
+        }
+    }
+}
+
+/// How hard the target machine works when turning IR into machine code.
+pub fn codegen_level(level: OptLevel) -> LLVMCodeGenOptLevel {
+    match level {
+        OptLevel::O0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+        OptLevel::O1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+        OptLevel::O2 | OptLevel::Os => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+        OptLevel::O3 => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+    }
+}
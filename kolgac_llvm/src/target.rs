@@ -0,0 +1,297 @@
+use crate::pipeline;
+
+use kolgac::opt::OptLevel;
+
+use llvm_sys::{core::LLVMDisposeMessage, target::*, target_machine::*};
+
+use std::{
+    ffi::{CStr, CString},
+    fmt,
+    os::raw::c_char,
+    ptr,
+    sync::Once,
+};
+
+static INIT: Once = Once::new();
+
+/// Registers every target LLVM was built with, so we can generate code for
+/// any of them, not just the machine we're running on.
+fn init_targets() {
+    INIT.call_once(|| unsafe {
+        LLVM_InitializeAllTargetInfos();
+        LLVM_InitializeAllTargets();
+        LLVM_InitializeAllTargetMCs();
+        LLVM_InitializeAllAsmParsers();
+        LLVM_InitializeAllAsmPrinters();
+    });
+}
+
+/// What to generate code for, from the --target, --cpu and --features flags.
+/// The default is the machine we're running on, with its baseline CPU.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TargetOpts {
+    /// Target triple, like 'aarch64-unknown-linux-gnu'. None means the machine
+    /// we're running on.
+    pub triple: Option<String>,
+
+    /// CPU to generate code for. Empty, or 'generic', means the target's
+    /// baseline CPU, and 'native' means the CPU we're running on, along with
+    /// its features.
+    pub cpu: String,
+
+    /// Comma separated CPU features, each turned on with '+' or off with '-',
+    /// like '+neon,-fp-armv8'.
+    pub features: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TargetErr {
+    /// No registered target handles the triple. Holds the triple, and the
+    /// names of the targets we do have.
+    UnknownTarget(String, Vec<String>),
+
+    /// A target we can't generate machine code for.
+    NoTargetMachine(String),
+
+    /// A feature that isn't of the form '+name' or '-name'.
+    InvalidFeature(String),
+
+    /// A CPU the target doesn't have. Holds the CPU, and the triple.
+    UnknownCpu(String, String),
+
+    /// A feature the target doesn't have. Holds the feature, with its '+' or
+    /// '-', and the triple.
+    UnknownFeature(String, String),
+}
+
+impl fmt::Display for TargetErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetErr::UnknownTarget(triple, known) => write!(
+                f,
+                "no target for '{}', the registered targets are: {}",
+                triple,
+                known.join(", ")
+            ),
+            TargetErr::NoTargetMachine(triple) => {
+                write!(f, "can't generate machine code for '{}'", triple)
+            }
+            TargetErr::InvalidFeature(feature) => write!(
+                f,
+                "invalid CPU feature '{}', expected '+name' or '-name'",
+                feature
+            ),
+            TargetErr::UnknownCpu(cpu, triple) => {
+                write!(f, "unknown CPU '{}' for '{}'", cpu, triple)
+            }
+            TargetErr::UnknownFeature(feature, triple) => {
+                write!(f, "unknown CPU feature '{}' for '{}'", feature, triple)
+            }
+        }
+    }
+}
+
+/// Names of the targets LLVM can generate code for, like 'x86-64' or 'aarch64',
+/// sorted by name.
+pub fn registered_targets() -> Vec<String> {
+    init_targets();
+
+    let mut names = Vec::new();
+    unsafe {
+        let mut target = LLVMGetFirstTarget();
+        while !target.is_null() {
+            let name = CStr::from_ptr(LLVMGetTargetName(target));
+            names.push(name.to_string_lossy().into_owned());
+            target = LLVMGetNextTarget(target);
+        }
+    }
+    names.sort();
+    names
+}
+
+/// An LLVM target machine, which knows the data layout of a target and how to
+/// turn IR into its machine code.
+pub struct TargetMachine {
+    machine: LLVMTargetMachineRef,
+
+    /// Normalized target triple.
+    triple: CString,
+}
+
+impl Drop for TargetMachine {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeTargetMachine(self.machine) }
+    }
+}
+
+impl TargetMachine {
+    /// Creates a target machine, after checking that the triple belongs to a
+    /// registered target and that the features are well formed. The C API has
+    /// no way to check CPU and feature names up front. Instead, LLVM prints a
+    /// warning for each one it doesn't know when the machine is created, and
+    /// ignores it, so we catch those warnings and turn them into errors.
+    pub fn new(opts: &TargetOpts, level: OptLevel) -> Result<TargetMachine, TargetErr> {
+        init_targets();
+
+        for feature in opts.features.split(',').filter(|f| !f.is_empty()) {
+            let valid = feature.len() > 1
+                && (feature.starts_with('+') || feature.starts_with('-'))
+                && feature[1..]
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || "-_.".contains(ch));
+            if !valid {
+                return Err(TargetErr::InvalidFeature(String::from(feature)));
+            }
+        }
+
+        unsafe {
+            let triple = match &opts.triple {
+                Some(triple) => {
+                    let triple = CString::new(triple.as_str())
+                        .map_err(|_| TargetErr::UnknownTarget(triple.clone(), Vec::new()))?;
+                    llvm_string(LLVMNormalizeTargetTriple(triple.as_ptr()))
+                }
+                None => llvm_string(LLVMGetDefaultTargetTriple()),
+            };
+
+            let mut target = ptr::null_mut();
+            let mut err = ptr::null_mut();
+            LLVMGetTargetFromTriple(triple.as_ptr(), &mut target, &mut err);
+            if !err.is_null() {
+                LLVMDisposeMessage(err);
+            }
+            if target.is_null() {
+                let triple = triple.to_string_lossy().into_owned();
+                return Err(TargetErr::UnknownTarget(triple, registered_targets()));
+            }
+            if LLVMTargetHasTargetMachine(target) == 0 {
+                let triple = triple.to_string_lossy().into_owned();
+                return Err(TargetErr::NoTargetMachine(triple));
+            }
+
+            let (cpu, features) = match opts.cpu.as_str() {
+                "native" => {
+                    let cpu = llvm_string(LLVMGetHostCPUName());
+                    let mut features = llvm_string(LLVMGetHostCPUFeatures()).into_bytes();
+                    if !opts.features.is_empty() {
+                        features.push(b',');
+                        features.extend_from_slice(opts.features.as_bytes());
+                    }
+                    (cpu, CString::new(features).unwrap())
+                }
+                // Not every target has a CPU called 'generic' (RISC-V aborts on
+                // it), but they all pick their baseline CPU for an empty name.
+                "generic" => (
+                    CString::default(),
+                    CString::new(opts.features.as_str()).unwrap(),
+                ),
+                cpu => (
+                    CString::new(cpu).unwrap_or_default(),
+                    CString::new(opts.features.as_str()).unwrap(),
+                ),
+            };
+
+            let (machine, warnings) = capture_stderr(|| {
+                LLVMCreateTargetMachine(
+                    target,
+                    triple.as_ptr(),
+                    cpu.as_ptr(),
+                    features.as_ptr(),
+                    pipeline::codegen_level(level),
+                    LLVMRelocMode::LLVMRelocDefault,
+                    LLVMCodeModel::LLVMCodeModelDefault,
+                )
+            });
+            let machine = TargetMachine { machine, triple };
+
+            // Each warning names what it's about in quotes, like "'foo' is not
+            // a recognized processor ...".
+            const UNKNOWN_CPU: &str = "not a recognized processor for this target";
+            const UNKNOWN_FEATURE: &str = "not a recognized feature for this target";
+
+            let triple = machine.triple.to_string_lossy().into_owned();
+            for line in warnings.lines() {
+                let name = String::from(line.split('\'').nth(1).unwrap_or_default());
+                if line.contains(UNKNOWN_CPU) {
+                    return Err(TargetErr::UnknownCpu(name, triple));
+                }
+                if line.contains(UNKNOWN_FEATURE) {
+                    return Err(TargetErr::UnknownFeature(name, triple));
+                }
+                eprintln!("{}", line);
+            }
+
+            Ok(machine)
+        }
+    }
+
+    pub fn triple(&self) -> &CStr {
+        &self.triple
+    }
+
+    /// The data layout string for the target, which tells LLVM the size and
+    /// alignment of each type.
+    pub fn data_layout(&self) -> CString {
+        unsafe {
+            let layout = LLVMCreateTargetDataLayout(self.machine);
+            let layout_str = llvm_string(LLVMCopyStringRepOfTargetData(layout));
+            LLVMDisposeTargetData(layout);
+            layout_str
+        }
+    }
+
+    pub fn machine_ref(&self) -> LLVMTargetMachineRef {
+        self.machine
+    }
+}
+
+/// Runs a function with stderr sent to a file, and returns what it wrote there
+/// along with its result. LLVM prints some of the problems it finds instead of
+/// returning them.
+#[cfg(unix)]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> (T, String) {
+    use std::os::{raw::c_int, unix::io::AsRawFd};
+    use std::{env, fs, process, sync::Mutex};
+
+    extern "C" {
+        fn dup(fd: c_int) -> c_int;
+        fn dup2(src: c_int, dst: c_int) -> c_int;
+        fn close(fd: c_int) -> c_int;
+    }
+    const STDERR_FD: c_int = 2;
+
+    // stderr is shared by the whole process, so only one thread can have it
+    // redirected at a time.
+    static STDERR: Mutex<()> = Mutex::new(());
+    let _lock = STDERR.lock().unwrap_or_else(|e| e.into_inner());
+    let path = env::temp_dir().join(format!("kolgac_llvm_stderr_{}", process::id()));
+    let file = match fs::File::create(&path) {
+        Ok(file) => file,
+        Err(_) => return (f(), String::new()),
+    };
+
+    let val = unsafe {
+        let saved = dup(STDERR_FD);
+        dup2(file.as_raw_fd(), STDERR_FD);
+        let val = f();
+        dup2(saved, STDERR_FD);
+        close(saved);
+        val
+    };
+
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    fs::remove_file(&path).ok();
+    (val, text)
+}
+
+#[cfg(not(unix))]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> (T, String) {
+    (f(), String::new())
+}
+
+/// Copies a string that LLVM allocated, and frees it.
+unsafe fn llvm_string(s: *mut c_char) -> CString {
+    let copy = CStr::from_ptr(s).to_owned();
+    LLVMDisposeMessage(s);
+    copy
+}
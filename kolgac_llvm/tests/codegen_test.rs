@@ -1,5 +1,9 @@
-use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
-use kolgac_llvm::{llvm::CodeGenerator, valtab::ValTab};
+use kolgac::{lexer::Lexer, opt::OptLevel, parser::Parser, symtab::SymbolTable};
+use kolgac_llvm::{
+    llvm::CodeGenerator,
+    target::{TargetMachine, TargetOpts},
+    valtab::ValTab,
+};
 use std::{fs, fs::File, io::prelude::*};
 
 fn run(input_filename: &str, output_filename: &str, expected_filename: &str) {
@@ -8,9 +12,10 @@ fn run(input_filename: &str, output_filename: &str, expected_filename: &str) {
     let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
 
     let mut valtab = ValTab::new();
-    let mut codegen = CodeGenerator::new(&ast, &mut valtab);
+    let target = TargetMachine::new(&TargetOpts::default(), OptLevel::O0).unwrap();
+    let mut codegen = CodeGenerator::new(&ast, &mut valtab, OptLevel::O0, &target);
     codegen.gen_ir();
-    codegen.print_ir(String::from(output_filename));
+    codegen.print_ir(output_filename).unwrap();
 
     diff_files(
         String::from(output_filename),
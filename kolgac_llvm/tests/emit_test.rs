@@ -0,0 +1,80 @@
+use kolgac::{lexer::Lexer, opt::OptLevel, parser::Parser, symtab::SymbolTable};
+use kolgac_llvm::{
+    llvm::CodeGenerator,
+    obj::ObjGenerator,
+    target::{TargetMachine, TargetOpts},
+    valtab::ValTab,
+};
+
+use llvm_sys::core::*;
+
+use std::{fs, fs::File};
+
+#[test]
+fn emit_ir_files() {
+    let mut lexer = Lexer::new(File::open("./tests/target_input/empty.kol").unwrap());
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+    let mut valtab = ValTab::new();
+    let target = TargetMachine::new(&TargetOpts::default(), OptLevel::O0).unwrap();
+    let mut codegen = CodeGenerator::new(&ast, &mut valtab, OptLevel::O0, &target);
+    codegen.gen_ir();
+
+    codegen.print_ir("./tests/emit_ir.ll").unwrap();
+    let ir = fs::read_to_string("./tests/emit_ir.ll").unwrap();
+    fs::remove_file("./tests/emit_ir.ll").ok();
+    assert!(ir.contains("source_filename = \"kolga\""));
+    assert!(ir.contains("target triple = "));
+
+    codegen.write_bitcode("./tests/emit_ir.bc").unwrap();
+    let bc = fs::read("./tests/emit_ir.bc").unwrap();
+    fs::remove_file("./tests/emit_ir.bc").ok();
+    assert_eq!(&bc[..4], b"BC\xc0\xde");
+
+    assert!(codegen.print_ir("./tests/missing/emit_ir.ll").is_err());
+    assert!(codegen.write_bitcode("./tests/missing/emit_ir.bc").is_err());
+}
+
+#[test]
+fn emit_obj_and_asm() {
+    let target = TargetMachine::new(&TargetOpts::default(), OptLevel::O0).unwrap();
+    unsafe {
+        let context = LLVMContextCreate();
+        let module = LLVMModuleCreateWithNameInContext(b"emit\0".as_ptr() as *const _, context);
+        LLVMSetTarget(module, target.triple().as_ptr());
+        LLVMSetDataLayout(module, target.data_layout().as_ptr());
+
+        let num = LLVMDoubleTypeInContext(context);
+        let fn_ty = LLVMFunctionType(num, [].as_mut_ptr(), 0, 0);
+        let func = LLVMAddFunction(module, b"answer\0".as_ptr() as *const _, fn_ty);
+        let entry = LLVMAppendBasicBlockInContext(context, func, b"entry\0".as_ptr() as *const _);
+        let builder = LLVMCreateBuilderInContext(context);
+        LLVMPositionBuilderAtEnd(builder, entry);
+        LLVMBuildRet(builder, LLVMConstReal(num, 42.0));
+        LLVMDisposeBuilder(builder);
+
+        // Both come from the same module, whichever order they're written in.
+        let mut obj_gen = ObjGenerator::new(module, target);
+        obj_gen.emit("./tests/emit_obj.o").unwrap();
+        obj_gen.emit_asm("./tests/emit_obj.s").unwrap();
+        obj_gen.emit("./tests/emit_obj2.o").unwrap();
+        assert!(obj_gen.emit("./tests/missing/emit_obj.o").is_err());
+
+        LLVMDisposeModule(module);
+        LLVMContextDispose(context);
+    }
+
+    let obj = fs::read("./tests/emit_obj.o").unwrap();
+    let obj2 = fs::read("./tests/emit_obj2.o").unwrap();
+    let asm = fs::read_to_string("./tests/emit_obj.s").unwrap();
+    for filename in &[
+        "./tests/emit_obj.o",
+        "./tests/emit_obj2.o",
+        "./tests/emit_obj.s",
+    ] {
+        fs::remove_file(filename).ok();
+    }
+
+    assert_eq!(obj, obj2);
+    assert!(asm.contains("answer:"));
+}
@@ -0,0 +1,191 @@
+use kolgac::opt::OptLevel;
+use kolgac_llvm::{
+    fpm::FPM,
+    mpm::MPM,
+    obj::ObjGenerator,
+    pipeline,
+    target::{TargetMachine, TargetOpts},
+};
+
+use llvm_sys::{core::*, prelude::*, target_machine::LLVMCodeGenOptLevel};
+
+use std::{ffi::CStr, fs};
+
+const LEVELS: [OptLevel; 5] = [
+    OptLevel::O0,
+    OptLevel::O1,
+    OptLevel::O2,
+    OptLevel::O3,
+    OptLevel::Os,
+];
+
+fn names(passes: Vec<pipeline::Pass>) -> Vec<&'static str> {
+    passes.iter().map(|p| p.name).collect()
+}
+
+/// Builds a module the way the code generator does, with every variable in an
+/// alloca:
+///
+/// ```text
+/// fn twice(x~num) ~ num { let y~num = x; return y + y; }
+/// fn main() ~ num { return twice(3); }
+/// ```
+unsafe fn build(context: LLVMContextRef) -> (LLVMModuleRef, LLVMValueRef, LLVMValueRef) {
+    let module = LLVMModuleCreateWithNameInContext(b"opt\0".as_ptr() as *const _, context);
+    let builder = LLVMCreateBuilderInContext(context);
+    let num = LLVMDoubleTypeInContext(context);
+
+    let mut params = [num];
+    let twice_ty = LLVMFunctionType(num, params.as_mut_ptr(), 1, 0);
+    let twice = LLVMAddFunction(module, b"twice\0".as_ptr() as *const _, twice_ty);
+    let entry = LLVMAppendBasicBlockInContext(context, twice, b"entry\0".as_ptr() as *const _);
+    LLVMPositionBuilderAtEnd(builder, entry);
+    let x = LLVMBuildAlloca(builder, num, b"x\0".as_ptr() as *const _);
+    LLVMBuildStore(builder, LLVMGetParam(twice, 0), x);
+    let y = LLVMBuildAlloca(builder, num, b"y\0".as_ptr() as *const _);
+    let x_val = LLVMBuildLoad2(builder, num, x, b"x\0".as_ptr() as *const _);
+    LLVMBuildStore(builder, x_val, y);
+    let lhs = LLVMBuildLoad2(builder, num, y, b"y\0".as_ptr() as *const _);
+    let rhs = LLVMBuildLoad2(builder, num, y, b"y\0".as_ptr() as *const _);
+    let sum = LLVMBuildFAdd(builder, lhs, rhs, b"sum\0".as_ptr() as *const _);
+    LLVMBuildRet(builder, sum);
+
+    let main_ty = LLVMFunctionType(num, [].as_mut_ptr(), 0, 0);
+    let main = LLVMAddFunction(module, b"main\0".as_ptr() as *const _, main_ty);
+    let entry = LLVMAppendBasicBlockInContext(context, main, b"entry\0".as_ptr() as *const _);
+    LLVMPositionBuilderAtEnd(builder, entry);
+    let mut args = [LLVMConstReal(num, 3.0)];
+    let call = LLVMBuildCall2(
+        builder,
+        twice_ty,
+        twice,
+        args.as_mut_ptr(),
+        1,
+        b"call\0".as_ptr() as *const _,
+    );
+    LLVMBuildRet(builder, call);
+
+    LLVMDisposeBuilder(builder);
+    (module, twice, main)
+}
+
+unsafe fn fn_ir(func: LLVMValueRef) -> String {
+    let ir = LLVMPrintValueToString(func);
+    let text = CStr::from_ptr(ir).to_string_lossy().into_owned();
+    LLVMDisposeMessage(ir);
+    text
+}
+
+/// Runs the function and module passes for a level, like the code generator
+/// does, and returns the IR of both functions afterwards.
+fn optimize(level: OptLevel) -> (String, String) {
+    unsafe {
+        let context = LLVMContextCreate();
+        let (module, twice, main) = build(context);
+        {
+            let mut fpm = FPM::new(module, level);
+            fpm.run(twice);
+            fpm.run(main);
+        }
+        MPM::new(level).run(module);
+        let ir = (fn_ir(twice), fn_ir(main));
+        LLVMDisposeModule(module);
+        LLVMContextDispose(context);
+        ir
+    }
+}
+
+#[test]
+fn opt_level_parse() {
+    assert_eq!("0".parse::<OptLevel>(), Ok(OptLevel::O0));
+    assert_eq!("3".parse::<OptLevel>(), Ok(OptLevel::O3));
+    assert_eq!("s".parse::<OptLevel>(), Ok(OptLevel::Os));
+    assert!("z".parse::<OptLevel>().is_err());
+}
+
+#[test]
+fn opt_pipelines() {
+    assert!(pipeline::fn_passes(OptLevel::O0).is_empty());
+    assert!(pipeline::module_passes(OptLevel::O0).is_empty());
+
+    for level in &LEVELS[1..] {
+        assert_eq!(pipeline::fn_passes(*level)[0].name, "mem2reg");
+    }
+
+    let o1 = names(pipeline::fn_passes(OptLevel::O1));
+    let o2 = names(pipeline::fn_passes(OptLevel::O2));
+    let o3 = names(pipeline::fn_passes(OptLevel::O3));
+    assert!(!o1.contains(&"gvn") && o2.contains(&"gvn"));
+    assert!(o2.contains(&"licm") && o2.contains(&"loop-rotate"));
+    assert!(!o2.contains(&"loop-unroll") && o3.contains(&"loop-unroll"));
+
+    // Os leaves out the passes that duplicate code, and keeps the rest of O2.
+    let os = names(pipeline::fn_passes(OptLevel::Os));
+    assert!(!os.contains(&"loop-rotate") && !os.contains(&"jump-threading"));
+    let o2_small: Vec<_> = o2
+        .iter()
+        .filter(|name| **name != "loop-rotate" && **name != "jump-threading")
+        .cloned()
+        .collect();
+    assert_eq!(os, o2_small);
+
+    // Os only inlines functions that ask for it.
+    assert!(names(pipeline::module_passes(OptLevel::O2)).contains(&"inline"));
+    assert!(!names(pipeline::module_passes(OptLevel::Os)).contains(&"inline"));
+    assert!(names(pipeline::module_passes(OptLevel::Os)).contains(&"always-inline"));
+}
+
+#[test]
+fn opt_codegen_level() {
+    assert_eq!(
+        pipeline::codegen_level(OptLevel::O0),
+        LLVMCodeGenOptLevel::LLVMCodeGenLevelNone
+    );
+    assert_eq!(
+        pipeline::codegen_level(OptLevel::Os),
+        LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault
+    );
+    assert_eq!(
+        pipeline::codegen_level(OptLevel::O3),
+        LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive
+    );
+}
+
+#[test]
+fn opt_passes_run() {
+    let (twice, main) = optimize(OptLevel::O0);
+    assert!(twice.contains("alloca"));
+    assert!(main.contains("call"));
+
+    // mem2reg gets rid of the allocas.
+    let (twice, main) = optimize(OptLevel::O1);
+    assert!(!twice.contains("alloca"));
+    assert!(main.contains("call"));
+
+    // Inlining, then folding the result.
+    let (_, main) = optimize(OptLevel::O2);
+    assert!(!main.contains("call"));
+    assert!(main.contains("ret double 6.0"));
+
+    let (_, main) = optimize(OptLevel::Os);
+    assert!(main.contains("call"));
+}
+
+#[test]
+fn opt_emit_obj() {
+    for (idx, level) in LEVELS.iter().enumerate() {
+        let filename = format!("./tests/opt_{}.o", idx);
+        unsafe {
+            let context = LLVMContextCreate();
+            let (module, _, _) = build(context);
+            MPM::new(*level).run(module);
+            let target = TargetMachine::new(&TargetOpts::default(), *level).unwrap();
+            ObjGenerator::new(module, target).emit(&filename).unwrap();
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+        }
+
+        assert!(fs::metadata(&filename).unwrap().len() > 0);
+        fs::remove_file(&filename).ok();
+    }
+}
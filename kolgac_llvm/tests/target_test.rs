@@ -0,0 +1,135 @@
+use kolgac::{lexer::Lexer, opt::OptLevel, parser::Parser, symtab::SymbolTable};
+use kolgac_llvm::{
+    llvm::CodeGenerator,
+    obj::ObjGenerator,
+    target::{self, TargetErr, TargetMachine, TargetOpts},
+    valtab::ValTab,
+};
+
+use llvm_sys::core::*;
+
+use std::{ffi::CStr, fs, fs::File};
+
+fn opts(triple: &str, features: &str) -> TargetOpts {
+    TargetOpts {
+        triple: Some(String::from(triple)),
+        cpu: String::from("generic"),
+        features: String::from(features),
+    }
+}
+
+/// Emits an object file with a single function for a target, and returns its
+/// ELF machine type.
+fn elf_machine(triple: &str, filename: &str) -> u16 {
+    let target = TargetMachine::new(&opts(triple, ""), OptLevel::O2).unwrap();
+    unsafe {
+        let context = LLVMContextCreate();
+        let module = LLVMModuleCreateWithNameInContext(b"cross\0".as_ptr() as *const _, context);
+        LLVMSetTarget(module, target.triple().as_ptr());
+        LLVMSetDataLayout(module, target.data_layout().as_ptr());
+
+        let num = LLVMDoubleTypeInContext(context);
+        let fn_ty = LLVMFunctionType(num, [].as_mut_ptr(), 0, 0);
+        let func = LLVMAddFunction(module, b"main\0".as_ptr() as *const _, fn_ty);
+        let entry = LLVMAppendBasicBlockInContext(context, func, b"entry\0".as_ptr() as *const _);
+        let builder = LLVMCreateBuilderInContext(context);
+        LLVMPositionBuilderAtEnd(builder, entry);
+        LLVMBuildRet(builder, LLVMConstReal(num, 1.0));
+        LLVMDisposeBuilder(builder);
+
+        ObjGenerator::new(module, target).emit(filename).unwrap();
+        LLVMDisposeModule(module);
+        LLVMContextDispose(context);
+    }
+
+    let obj = fs::read(filename).unwrap();
+    fs::remove_file(filename).ok();
+    assert_eq!(&obj[..4], b"\x7fELF");
+    u16::from_le_bytes([obj[18], obj[19]])
+}
+
+#[test]
+fn target_host() {
+    let target = TargetMachine::new(&TargetOpts::default(), OptLevel::O0).unwrap();
+    assert!(!target.triple().to_bytes().is_empty());
+    assert!(!target.data_layout().to_bytes().is_empty());
+
+    let names = target::registered_targets();
+    assert!(names.contains(&String::from("x86-64")));
+    assert!(names.contains(&String::from("aarch64")));
+    assert!(names.contains(&String::from("riscv64")));
+}
+
+#[test]
+fn target_cross() {
+    let target = TargetMachine::new(&opts("aarch64-linux-gnu", "+neon"), OptLevel::O0).unwrap();
+    assert_eq!(target.triple().to_str(), Ok("aarch64-unknown-linux-gnu"));
+    let layout = target.data_layout();
+    assert!(layout.to_str().unwrap().starts_with("e-m:e"));
+
+    // The code generator gives the module the target's triple and layout.
+    let mut lexer = Lexer::new(File::open("./tests/target_input/empty.kol").unwrap());
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+    let mut valtab = ValTab::new();
+    let codegen = CodeGenerator::new(&ast, &mut valtab, OptLevel::O0, &target);
+    unsafe {
+        let module = codegen.get_mod();
+        assert_eq!(CStr::from_ptr(LLVMGetTarget(module)), target.triple());
+        assert_eq!(CStr::from_ptr(LLVMGetDataLayoutStr(module)), &*layout);
+    }
+}
+
+#[test]
+fn target_errors() {
+    match TargetMachine::new(&opts("kolga-unknown-none", ""), OptLevel::O0) {
+        Err(TargetErr::UnknownTarget(triple, known)) => {
+            assert_eq!(triple, "kolga-unknown-none");
+            assert!(known.contains(&String::from("aarch64")));
+        }
+        _ => panic!("expected an unknown target"),
+    }
+
+    for features in &["neon", "+", "+neon,avx"] {
+        assert!(matches!(
+            TargetMachine::new(&opts("aarch64-unknown-linux-gnu", features), OptLevel::O0),
+            Err(TargetErr::InvalidFeature(_))
+        ));
+    }
+
+    // Names LLVM doesn't know for the target would only be warned about.
+    let triple = "aarch64-unknown-linux-gnu";
+    let cpu = TargetOpts {
+        cpu: String::from("cortex-a5e"),
+        ..opts(triple, "")
+    };
+    assert_eq!(
+        TargetMachine::new(&cpu, OptLevel::O0).err(),
+        Some(TargetErr::UnknownCpu(
+            String::from("cortex-a5e"),
+            String::from(triple)
+        ))
+    );
+    assert_eq!(
+        TargetMachine::new(&opts(triple, "+neon,+avx2"), OptLevel::O0).err(),
+        Some(TargetErr::UnknownFeature(
+            String::from("+avx2"),
+            String::from(triple)
+        ))
+    );
+}
+
+#[test]
+fn target_emit_obj() {
+    const EM_AARCH64: u16 = 183;
+    const EM_RISCV: u16 = 243;
+
+    assert_eq!(
+        elf_machine("aarch64-unknown-linux-gnu", "./tests/target_aarch64.o"),
+        EM_AARCH64
+    );
+    assert_eq!(
+        elf_machine("riscv64-unknown-linux-gnu", "./tests/target_riscv64.o"),
+        EM_RISCV
+    );
+}
@@ -0,0 +1,175 @@
+use kolgac::{ast::Ast, lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_ir::{
+    cfg::{self, Function},
+    interp::{Interp, Value},
+    irgen::IRGen,
+    pass::{OptLevel, PassManager},
+};
+use kolgac_types::infer::TyInfer;
+
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Set to skip the tests that need a tool that isn't installed, instead of
+/// failing them.
+pub const SKIP_TOOLS_VAR: &str = "KOLGA_SKIP_TOOL_TESTS";
+
+/// The path of one of the programs every backend is tested with.
+pub fn program(name: &str) -> String {
+    format!("{}/programs/{}.kol", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Parses a program and infers its types.
+pub fn build(path: &str) -> Ast {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+    TyInfer::new().infer(&mut ast).unwrap();
+    ast
+}
+
+/// Generates KIR for a program, and runs the passes of an optimization level
+/// on it.
+pub fn kir(ast: &Ast, level: OptLevel) -> Vec<Function> {
+    let mut irgen = IRGen::new(ast);
+    irgen.gen();
+
+    let mut fns = cfg::build(&irgen.ir);
+    PassManager::for_level(level).run(&mut fns);
+    fns
+}
+
+/// A global left by a program, and how the interpreter prints it.
+pub struct Global {
+    pub name: String,
+    pub val: Value,
+    pub text: String,
+}
+
+/// Runs a program with the KIR interpreter, which the backends are checked
+/// against, and returns the globals it leaves.
+pub fn interp(fns: &[Function]) -> Vec<Global> {
+    let mut interp = Interp::new(fns);
+    interp.run().unwrap();
+    interp
+        .globals()
+        .into_iter()
+        .map(|(name, val)| Global {
+            text: interp.fmt_val(&val),
+            name,
+            val,
+        })
+        .collect()
+}
+
+/// Checks the output of a harness that prints the globals of a program, one
+/// `name = value` line each, against the interpreter. Class instances aren't
+/// printed.
+pub fn check_printed(globals: &[Global], stdout: &str) {
+    let mut lines = stdout.lines();
+    for global in globals {
+        let printed = match global.val {
+            Value::Obj(_) => continue,
+            _ => lines.next().unwrap(),
+        };
+        let expected = format!("{} = ", global.name);
+        assert!(printed.starts_with(&expected), "{}", printed);
+
+        let printed = &printed[expected.len()..];
+        match global.val {
+            Value::Num(n) => assert_eq!(printed.parse::<f64>().unwrap(), n, "{}", global.name),
+            _ => assert_eq!(printed, global.text, "{}", global.name),
+        }
+    }
+}
+
+/// Writes a C file that prints the globals of a compiled program when it exits,
+/// so they can be compared with the interpreter. Class instances are left out.
+/// Each global is found by the symbol `sym` gives it, and bools are read as
+/// `bool_ty`, since the backends store them differently.
+pub fn c_harness(globals: &[Global], bool_ty: &str, sym: impl Fn(&str) -> String) -> String {
+    let mut decls = String::from("#include <stdbool.h>\n#include <stdio.h>\n");
+    let mut prints = String::new();
+    for (idx, global) in globals.iter().enumerate() {
+        let (ty, arg) = match global.val {
+            Value::Num(_) => ("double", format!("\"%.17g\", g{}", idx)),
+            Value::Bool(_) => (bool_ty, format!("\"%s\", g{} ? \"true\" : \"false\"", idx)),
+            Value::Str(_) => ("const char *", format!("\"%s\", g{}", idx)),
+            _ => continue,
+        };
+        decls.push_str(&format!(
+            "extern {} g{} __asm__(\"{}\");\n",
+            ty,
+            idx,
+            sym(&global.name)
+        ));
+        prints.push_str(&format!(
+            "  printf(\"{} = \"); printf({}); printf(\"\\n\");\n",
+            global.name, arg
+        ));
+    }
+
+    format!(
+        "{}__attribute__((destructor)) static void dump(void) {{\n{}}}\n",
+        decls, prints
+    )
+}
+
+/// Builds a program from files in a test's directory with cc, along with a
+/// harness from `c_harness`, runs it, and checks the globals it prints.
+pub fn cc_run(dir: &Path, args: &[&str], harness: &str, globals: &[Global]) {
+    fs::write(dir.join("harness.c"), harness).unwrap();
+    let cc = Command::new("cc")
+        .current_dir(dir)
+        .args(args)
+        .args(["-o", "prog", "harness.c"])
+        .output()
+        .unwrap();
+    assert!(
+        cc.status.success(),
+        "{}",
+        String::from_utf8_lossy(&cc.stderr)
+    );
+
+    check_run(&mut Command::new(dir.join("prog")), globals);
+}
+
+/// Runs a compiled program, and checks the globals it prints.
+pub fn check_run(cmd: &mut Command, globals: &[Global]) {
+    let out = cmd.output().unwrap();
+    assert!(
+        out.status.success(),
+        "{:?} failed: {}",
+        cmd,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    check_printed(globals, &String::from_utf8(out.stdout).unwrap());
+}
+
+/// True if a tool that runs generated code is installed. If it isn't, the test
+/// fails, unless KOLGA_SKIP_TOOL_TESTS is set.
+pub fn has_tool(tool: &str) -> bool {
+    if Command::new(tool).arg("--version").output().is_ok() {
+        return true;
+    }
+
+    if env::var_os(SKIP_TOOLS_VAR).is_some() {
+        eprintln!("{} not found, skipping", tool);
+        return false;
+    }
+    panic!(
+        "{} not found, set {} to skip the tests that need it",
+        tool, SKIP_TOOLS_VAR
+    );
+}
+
+/// A new directory for the files a test writes.
+pub fn temp_dir(backend: &str, path: &str) -> PathBuf {
+    let name = path.rsplit('/').next().unwrap().replace(".kol", "");
+    let dir = env::temp_dir().join(format!("{}_{}_{}", backend, name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,369 @@
+use kolgac_errors::ty::{TypeErr, TypeErrTy};
+
+use kolgac::{
+    ast::{Ast, MetaAst},
+    symtab::SymbolTable,
+    token::{TknTy, Token},
+};
+
+use std::collections::HashMap;
+
+/// Variables are identified by the position of the token that declared them, the
+/// same way the unused variable check does it.
+type SymKey = (usize, usize);
+
+/// A branch in the program that can leave a variable unassigned.
+#[derive(Clone, Debug)]
+enum Branch {
+    IfTrue,
+    IfFalse,
+    NoneTrue,
+    ElifTrue,
+    Else,
+    LoopSkipped,
+}
+
+/// One step on the path that leaves a variable unassigned, pointing at the
+/// statement that chose the branch.
+#[derive(Clone, Debug)]
+struct Step {
+    line: usize,
+    pos: usize,
+    len: usize,
+    branch: Branch,
+}
+
+impl Step {
+    fn new(meta: &MetaAst, len: usize, branch: Branch) -> Step {
+        Step {
+            line: meta.line,
+            pos: meta.pos,
+            len,
+            branch,
+        }
+    }
+
+    fn msg(&self, name: &str) -> String {
+        match self.branch {
+            Branch::IfTrue | Branch::ElifTrue => {
+                format!("'{}' is not assigned when this condition is true", name)
+            }
+            Branch::IfFalse => format!("'{}' is not assigned when this condition is false", name),
+            Branch::NoneTrue => format!(
+                "'{}' is not assigned when none of these conditions are true",
+                name
+            ),
+            Branch::Else => format!("'{}' is not assigned when the else branch runs", name),
+            Branch::LoopSkipped => format!("'{}' is not assigned if this loop doesn't run", name),
+        }
+    }
+}
+
+/// A variable that might not be assigned yet.
+#[derive(Clone, Debug)]
+struct Unassigned {
+    decl_tkn: Token,
+
+    /// The branches taken on the first path we found that leaves the variable
+    /// unassigned, outermost first. Empty if no path assigns it.
+    path: Vec<Step>,
+}
+
+/// What we know at a point in the program.
+#[derive(Clone, Debug)]
+struct State {
+    /// False after a return statement. Nothing after it can run, so it can't read
+    /// anything, and it doesn't count when the branches of an if are joined.
+    reachable: bool,
+
+    /// Variables declared without a value that aren't assigned on every path
+    /// reaching this point.
+    unassigned: HashMap<SymKey, Unassigned>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            reachable: true,
+            unassigned: HashMap::new(),
+        }
+    }
+
+    /// Joins the states at the end of each branch into the state after the
+    /// branches. A variable is unassigned afterwards if any reachable branch
+    /// leaves it unassigned. If some other branch does assign it, the branch that
+    /// didn't is added to the path, so we can report it.
+    fn join(branches: Vec<(State, Option<Step>)>) -> State {
+        let reachable: Vec<(State, Option<Step>)> = branches
+            .into_iter()
+            .filter(|(st, _)| st.reachable)
+            .collect();
+
+        let mut joined = State {
+            reachable: !reachable.is_empty(),
+            unassigned: HashMap::new(),
+        };
+
+        for (st, step) in &reachable {
+            for (key, var) in &st.unassigned {
+                if joined.unassigned.contains_key(key) {
+                    continue;
+                }
+
+                let everywhere = reachable
+                    .iter()
+                    .all(|(other, _)| other.unassigned.contains_key(key));
+
+                let mut var = var.clone();
+                if let (false, Some(step)) = (everywhere, step) {
+                    var.path.insert(0, step.clone());
+                }
+                joined.unassigned.insert(*key, var);
+            }
+        }
+
+        joined
+    }
+}
+
+/// Checks that variables declared without a value are assigned before they're
+/// read. This is flow sensitive: a read is only allowed if the variable is
+/// assigned on every path through ifs and loops that reaches it. Loops might not
+/// run at all, so assignments in a loop body don't count after the loop.
+///
+/// Each function body is checked on its own. Functions can be called from
+/// anywhere, so variables from outside a function are assumed to be assigned.
+pub struct AssignCheck<'t, 's> {
+    ast: &'t Ast,
+    symtab: &'s SymbolTable,
+    state: State,
+    errors: Vec<TypeErr>,
+}
+
+impl<'t, 's> AssignCheck<'t, 's> {
+    pub fn new(ast: &'t Ast, symtab: &'s SymbolTable) -> AssignCheck<'t, 's> {
+        AssignCheck {
+            ast,
+            symtab,
+            state: State::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Entry point for the analysis. Returns an error for each read of a variable
+    /// that might not be assigned. Each variable is only reported once on a path.
+    pub fn check(&mut self) -> Vec<TypeErr> {
+        // Pass in 0 for the global scope.
+        self.walk(self.ast, 0);
+        self.errors.clone()
+    }
+
+    fn walk(&mut self, node: &Ast, final_sc: usize) {
+        match node {
+            Ast::Prog { meta: _, stmts } => {
+                for stmt in stmts {
+                    self.walk(stmt, final_sc);
+                }
+            }
+            Ast::BlckStmt { meta: _, stmts, sc } => {
+                for stmt in stmts {
+                    self.walk(stmt, *sc);
+                }
+            }
+            Ast::IfStmt {
+                meta,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => {
+                self.walk(cond_expr, final_sc);
+                let mut entry = self.state.clone();
+                let mut branches = vec![(
+                    self.branch(if_stmts, final_sc, &entry),
+                    Some(Step::new(meta, 2, Branch::IfTrue)),
+                )];
+
+                for elif in elif_exprs {
+                    if let Ast::ElifStmt {
+                        meta: elif_meta,
+                        cond_expr,
+                        stmts,
+                    } = elif
+                    {
+                        // Each condition is only evaluated when the ones before it
+                        // were false.
+                        self.state = entry;
+                        self.walk(cond_expr, final_sc);
+                        entry = self.state.clone();
+                        branches.push((
+                            self.branch(stmts, final_sc, &entry),
+                            Some(Step::new(elif_meta, 4, Branch::ElifTrue)),
+                        ));
+                    }
+                }
+
+                match el_stmts.first() {
+                    Some(el) => branches.push((
+                        self.branch(el, final_sc, &entry),
+                        Some(Step::new(meta, 2, Branch::Else)),
+                    )),
+                    None if elif_exprs.is_empty() => {
+                        branches.push((entry, Some(Step::new(meta, 2, Branch::IfFalse))))
+                    }
+                    None => branches.push((entry, Some(Step::new(meta, 2, Branch::NoneTrue)))),
+                };
+
+                self.state = State::join(branches);
+            }
+            Ast::ElifStmt {
+                meta: _,
+                cond_expr,
+                stmts,
+            } => {
+                self.walk(cond_expr, final_sc);
+                self.walk(stmts, final_sc);
+            }
+            Ast::WhileStmt {
+                meta,
+                cond_expr,
+                stmts,
+            } => {
+                self.walk(cond_expr, final_sc);
+                let entry = self.state.clone();
+                let body = self.branch(stmts, final_sc, &entry);
+                self.state = State::join(vec![
+                    (body, None),
+                    (entry, Some(Step::new(meta, 5, Branch::LoopSkipped))),
+                ]);
+            }
+            Ast::ForStmt {
+                meta,
+                for_var_decl,
+                for_cond_expr,
+                for_step_expr,
+                stmts,
+            } => {
+                self.walk(for_var_decl, final_sc);
+                self.walk(for_cond_expr, final_sc);
+                let entry = self.state.clone();
+                self.walk(stmts, final_sc);
+                self.walk(for_step_expr, final_sc);
+                let body = std::mem::replace(&mut self.state, State::new());
+                self.state = State::join(vec![
+                    (body, None),
+                    (entry, Some(Step::new(meta, 3, Branch::LoopSkipped))),
+                ]);
+            }
+            Ast::RetStmt { meta: _, ret_expr } => {
+                if let Some(expr) = ret_expr {
+                    self.walk(expr, final_sc);
+                }
+                self.state.reachable = false;
+                self.state.unassigned.clear();
+            }
+            Ast::ExprStmt { meta: _, expr } => self.walk(expr, final_sc),
+            Ast::VarDeclExpr { ident_tkn, .. } => {
+                if self.state.reachable {
+                    let var = Unassigned {
+                        decl_tkn: ident_tkn.clone(),
+                        path: Vec::new(),
+                    };
+                    self.state
+                        .unassigned
+                        .insert((ident_tkn.line, ident_tkn.pos), var);
+                }
+            }
+            Ast::VarAssignExpr {
+                ident_tkn, value, ..
+            } => {
+                self.walk(value, final_sc);
+                self.state
+                    .unassigned
+                    .remove(&(ident_tkn.line, ident_tkn.pos));
+            }
+            Ast::LogicalExpr { lhs, rhs, .. } => {
+                // The rhs might not be evaluated, so it's treated like a branch.
+                self.walk(lhs, final_sc);
+                let entry = self.state.clone();
+                let rhs_state = self.branch(rhs, final_sc, &entry);
+                self.state = State::join(vec![(rhs_state, None), (entry, None)]);
+            }
+            Ast::BinaryExpr { lhs, rhs, .. } => {
+                self.walk(lhs, final_sc);
+                self.walk(rhs, final_sc);
+            }
+            Ast::UnaryExpr { rhs, .. } => self.walk(rhs, final_sc),
+            Ast::PrimaryExpr { ty_rec, .. } => {
+                if let TknTy::Ident(ref name) = ty_rec.tkn.ty {
+                    self.read(name, &ty_rec.tkn, final_sc);
+                }
+            }
+            Ast::FnDeclStmt { fn_body, .. } => {
+                let outer = std::mem::replace(&mut self.state, State::new());
+                self.walk(fn_body, final_sc);
+                self.state = outer;
+            }
+            Ast::ClassDeclStmt { methods, sc, .. } => {
+                for method in methods {
+                    self.walk(method, *sc);
+                }
+            }
+            Ast::FnCallExpr { fn_params, .. } | Ast::ClassFnCallExpr { fn_params, .. } => {
+                for param in fn_params {
+                    self.walk(param, final_sc);
+                }
+            }
+            Ast::ClassConstrExpr { props, .. } => {
+                for val in props.values() {
+                    self.walk(val, final_sc);
+                }
+            }
+            Ast::ClassPropSetExpr { assign_val, .. } => self.walk(assign_val, final_sc),
+            Ast::ClassPropAccessExpr { .. } | Ast::Error { .. } => (),
+        }
+    }
+
+    /// Walks a branch starting from the given state, and returns the state at
+    /// the end of it.
+    fn branch(&mut self, node: &Ast, final_sc: usize, entry: &State) -> State {
+        self.state = entry.clone();
+        self.walk(node, final_sc);
+        std::mem::replace(&mut self.state, State::new())
+    }
+
+    /// Reports a read of a variable that might not be assigned. The variable is
+    /// then treated as assigned, so later reads on the same path aren't reported.
+    fn read(&mut self, name: &str, tkn: &Token, final_sc: usize) {
+        let sym = match self.symtab.retrieve_from_finalized_sc(name, final_sc) {
+            Some(sym) => sym,
+            None => return,
+        };
+
+        let key = (sym.ident_tkn.line, sym.ident_tkn.pos);
+        let var = match self.state.unassigned.remove(&key) {
+            Some(var) => var,
+            None => return,
+        };
+
+        let decl = &var.decl_tkn;
+        let mut err = TypeErr::new(
+            tkn.line,
+            tkn.pos,
+            TypeErrTy::UnassignedVar(name.to_string()),
+        )
+        .with_len(name.len())
+        .with_label(
+            decl.line,
+            decl.pos,
+            name.len(),
+            "declared here without a value",
+        );
+
+        for step in &var.path {
+            err = err.with_label(step.line, step.pos, step.len, &step.msg(name));
+        }
+
+        self.errors.push(err);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::ret::{self, Fallthrough};
+
 use kolgac_errors::ty::{TypeErr, TypeErrTy};
 
 use kolgac::{
@@ -8,12 +10,84 @@ use kolgac::{
     ty_rec::{KolgaTy, TyRecord},
 };
 
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// Symbols are identified by the position of the token that declared them.
+type SymKey = (usize, usize);
+
+/// The class whose methods we're checking, for checking changes to self.
+struct ClassCtx {
+    name: String,
+
+    /// The declaration token of each property, and whether it's immutable.
+    props: Vec<(Token, bool)>,
+
+    /// Methods declared with 'mut fn'.
+    mut_methods: HashSet<SymKey>,
+
+    /// The method we're checking, and whether it's declared 'mut'.
+    method: Option<(Token, bool)>,
+}
+
+impl ClassCtx {
+    fn new(ident_tkn: &Token, props: &[Ast], methods: &[Ast]) -> ClassCtx {
+        let props = props
+            .iter()
+            .filter_map(|prop| match prop {
+                Ast::VarDeclExpr {
+                    ident_tkn, is_imm, ..
+                } => Some((ident_tkn.clone(), *is_imm)),
+                _ => None,
+            })
+            .collect();
+
+        let mut_methods = methods
+            .iter()
+            .filter_map(|method| match method {
+                Ast::FnDeclStmt {
+                    ident_tkn,
+                    is_mut: true,
+                    ..
+                } => Some((ident_tkn.line, ident_tkn.pos)),
+                _ => None,
+            })
+            .collect();
+
+        ClassCtx {
+            name: ident_tkn.get_name(),
+            props,
+            mut_methods,
+            method: None,
+        }
+    }
+
+    /// Finds the property declared by a token, if it is one.
+    fn prop(&self, decl_tkn: &Token) -> Option<&(Token, bool)> {
+        self.props
+            .iter()
+            .find(|(tkn, _)| (tkn.line, tkn.pos) == (decl_tkn.line, decl_tkn.pos))
+    }
+}
 
 pub struct TyCheck<'t, 's> {
     ast: &'t Ast,
     symtab: &'s mut SymbolTable,
     errors: Vec<TypeErr>,
+
+    /// Ids of the nodes that type inference already reported an error in. A failed
+    /// equation usually fails again when the checker compares the same types, so we
+    /// don't check these nodes twice.
+    infer_err_nodes: HashSet<usize>,
+
+    /// The function we're checking, and its return type, for checking return
+    /// statements.
+    curr_fn: Option<(Token, KolgaTy)>,
+
+    /// The class we're checking the methods of.
+    curr_class: Option<ClassCtx>,
 }
 
 impl<'t, 's> TyCheck<'t, 's> {
@@ -22,9 +96,19 @@ impl<'t, 's> TyCheck<'t, 's> {
             ast: ast,
             symtab: symtab,
             errors: Vec::new(),
+            infer_err_nodes: HashSet::new(),
+            curr_fn: None,
+            curr_class: None,
         }
     }
 
+    /// Tells the checker about errors found during type inference, so that the
+    /// same problems aren't reported again.
+    pub fn with_infer_errs(mut self, errs: &[TypeErr]) -> TyCheck<'t, 's> {
+        self.infer_err_nodes = errs.iter().filter_map(|e| e.node).collect();
+        self
+    }
+
     /// Initial entry point into the type checker. Loops through each statement in the
     /// AST and type checks them. Returns a vector of errors encountered during type
     /// checking.
@@ -57,6 +141,7 @@ impl<'t, 's> TyCheck<'t, 's> {
             Ast::ExprStmt { meta: _, expr } => {
                 match *expr.clone() {
                     Ast::FnCallExpr { .. } => {
+                        self.check_mut_call(&expr, final_sc);
                         self.check_fn_params(*expr, final_sc);
                         ()
                     }
@@ -69,6 +154,7 @@ impl<'t, 's> TyCheck<'t, 's> {
                         fn_params: _,
                         sc,
                     } => {
+                        self.check_mut_call(&expr, final_sc);
                         self.check_fn_params(*expr, sc);
                         ()
                     }
@@ -140,7 +226,12 @@ impl<'t, 's> TyCheck<'t, 's> {
                 ret_ty,
                 fn_body,
                 sc,
+                is_mut,
             } => {
+                if let Some(class) = self.curr_class.as_mut() {
+                    class.method = Some((ident_tkn.clone(), is_mut));
+                }
+
                 let fn_ty = ret_ty.ty;
                 let fn_stmts = *fn_body;
                 match fn_stmts {
@@ -157,20 +248,26 @@ impl<'t, 's> TyCheck<'t, 's> {
             Ast::ClassDeclStmt {
                 meta: _,
                 ty_rec: _,
-                ident_tkn: _,
+                ident_tkn,
                 methods,
                 props,
                 prop_pos: _,
                 sc,
             } => {
-                for prop_stmt in props {
+                for prop_stmt in &props {
                     self.check_stmt(prop_stmt.clone(), sc);
                 }
 
+                let class = ClassCtx::new(&ident_tkn, &props, &methods);
+                let prev_class = self.curr_class.replace(class);
                 for stmt in &methods {
                     self.check_stmt(stmt.clone(), sc);
                 }
+                self.curr_class = prev_class;
             }
+            Ast::RetStmt { meta: _, ret_expr } => self.check_ret(ret_expr, final_sc),
+            // Parse errors have already been reported.
+            Ast::Error { .. } => (),
             _ => panic!("{:?} Unrecognized statement type found!", stmt),
         }
     }
@@ -182,34 +279,96 @@ impl<'t, 's> TyCheck<'t, 's> {
         stmts: Vec<Ast>,
         sc_lvl: usize,
     ) {
-        let mut has_ret_stmt = false;
-
-        for maybe_stmt in &stmts {
-            let stmt = maybe_stmt.clone();
-            match stmt {
-                Ast::RetStmt { meta: _, ret_expr } => {
-                    has_ret_stmt = true;
-                    if ret_expr.is_none() {
-                        if fn_ret_ty != KolgaTy::Void {
-                            self.ty_mismatch(&fn_tkn, &fn_ret_ty, &KolgaTy::Void);
-                        }
-                    } else {
-                        let rhs_ty = self.check_expr(&ret_expr.clone().unwrap(), sc_lvl);
-                        if fn_ret_ty != rhs_ty {
-                            self.ty_mismatch(&fn_tkn, &fn_ret_ty, &rhs_ty);
-                        }
-                    }
-                }
-                _ => self.check_stmt(stmt, sc_lvl),
-            };
+        let prev_fn = self.curr_fn.replace((fn_tkn.clone(), fn_ret_ty.clone()));
+        for stmt in &stmts {
+            self.check_stmt(stmt.clone(), sc_lvl);
         }
+        self.curr_fn = prev_fn;
 
-        if fn_ret_ty != KolgaTy::Void && !has_ret_stmt {
-            self.error(
+        // If part of the body failed to parse, the missing return may have been in
+        // that part, so we don't report it.
+        let has_parse_err = stmts.iter().any(|s| matches!(s, Ast::Error { .. }));
+        if fn_ret_ty == KolgaTy::Void || has_parse_err {
+            return;
+        }
+
+        let name = fn_tkn.get_name();
+        let ret_ty = fn_ret_ty.to_string();
+        let (line, pos, len, path) = match ret::fallthrough(&stmts) {
+            None => return,
+            Some(Fallthrough::End) if !stmts.iter().any(ret::has_ret) => {
+                self.error(fn_tkn.line, fn_tkn.pos, TypeErrTy::InvalidRet(name, ret_ty));
+                return;
+            }
+            Some(Fallthrough::End) => (
                 fn_tkn.line,
                 fn_tkn.pos,
-                TypeErrTy::InvalidRet(fn_tkn.get_name(), fn_ret_ty.to_string()),
-            );
+                name.len(),
+                "control can reach the end of this function",
+            ),
+            Some(Fallthrough::Branch(line, pos)) => {
+                (line, pos, 1, "this branch doesn't return a value")
+            }
+            Some(Fallthrough::NoElse(line, pos)) => (
+                line,
+                pos,
+                2,
+                "nothing is returned when this condition is false",
+            ),
+        };
+
+        let err = TypeErr::new(
+            line,
+            pos,
+            TypeErrTy::MissingRet(name.clone(), ret_ty, String::from(path)),
+        )
+        .with_len(len);
+
+        // The function name is already the main span when control reaches the end.
+        let err = match (line, pos) == (fn_tkn.line, fn_tkn.pos) {
+            true => err,
+            false => err.with_label(
+                fn_tkn.line,
+                fn_tkn.pos,
+                name.len(),
+                "return type declared here",
+            ),
+        };
+
+        self.errors.push(err);
+    }
+
+    /// Checks the value of a return statement against the return type of the
+    /// function it's in.
+    fn check_ret(&mut self, ret_expr: Option<Box<Ast>>, sc_lvl: usize) {
+        let (fn_tkn, fn_ret_ty) = match self.curr_fn.clone() {
+            Some(curr_fn) => curr_fn,
+            None => {
+                if let Some(ret_ast) = ret_expr {
+                    self.check_expr(&ret_ast, sc_lvl);
+                }
+                return;
+            }
+        };
+
+        match ret_expr {
+            None => {
+                if fn_ret_ty != KolgaTy::Void {
+                    self.ty_mismatch(&fn_tkn, &fn_ret_ty, &KolgaTy::Void);
+                }
+            }
+            Some(ret_ast) => {
+                let rhs_ty = self.check_expr(&ret_ast, sc_lvl);
+                if tys_differ(&fn_ret_ty, &rhs_ty) {
+                    self.ty_mismatch_w_decl(
+                        &ret_ast,
+                        &fn_ret_ty,
+                        &rhs_ty,
+                        &fn_tkn,
+                        "return type declared here",
+                    );
+                }
+            }
         }
     }
 
@@ -217,11 +376,16 @@ impl<'t, 's> TyCheck<'t, 's> {
         match expr {
             Ast::VarAssignExpr { .. } => self.check_var_assign(expr, final_sc),
             Ast::UnaryExpr {
-                meta: _,
+                meta,
                 ty_rec: _,
                 op_tkn,
                 rhs,
             } => {
+                if self.infer_failed(meta) {
+                    self.check_expr(rhs, final_sc);
+                    return op_ty(&op_tkn.ty);
+                }
+
                 if rhs.is_primary() {
                     let rhs_ty_rec = rhs.get_ty_rec().unwrap();
                     return self.reduce_unary_ty(op_tkn.clone(), rhs_ty_rec.ty);
@@ -231,14 +395,14 @@ impl<'t, 's> TyCheck<'t, 's> {
                 }
             }
             Ast::BinaryExpr {
-                meta: _,
+                meta,
                 ty_rec: _,
                 op_tkn,
                 lhs,
                 rhs,
             }
             | Ast::LogicalExpr {
-                meta: _,
+                meta,
                 ty_rec: _,
                 op_tkn,
                 lhs,
@@ -246,6 +410,9 @@ impl<'t, 's> TyCheck<'t, 's> {
             } => {
                 let lhs_ty_name = self.check_expr(lhs, final_sc);
                 let rhs_ty_name = self.check_expr(rhs, final_sc);
+                if self.infer_failed(meta) {
+                    return op_ty(&op_tkn.ty);
+                }
 
                 self.reduce_bin_ty(op_tkn.clone(), lhs_ty_name, rhs_ty_name)
             }
@@ -263,13 +430,16 @@ impl<'t, 's> TyCheck<'t, 's> {
             }
             | Ast::ClassDeclStmt {
                 meta: _, ty_rec, ..
-            }
-            | Ast::FnCallExpr {
+            } => ty_rec.ty.clone(),
+            Ast::FnCallExpr {
                 meta: _, ty_rec, ..
             }
             | Ast::ClassFnCallExpr {
                 meta: _, ty_rec, ..
-            } => ty_rec.ty.clone(),
+            } => {
+                self.check_mut_call(expr, final_sc);
+                ty_rec.ty.clone()
+            }
             Ast::ClassPropAccessExpr {
                 meta: _,
                 ty_rec: _,
@@ -287,9 +457,10 @@ impl<'t, 's> TyCheck<'t, 's> {
                 owner_class,
                 assign_val,
             } => {
+                self.check_prop_set(ident_tkn, prop_name, owner_class, final_sc);
                 let prop_ty = self.extract_prop_ty(&owner_class, prop_name.clone());
                 let rhs_ty = self.check_expr(assign_val, final_sc);
-                if prop_ty != rhs_ty {
+                if tys_differ(&prop_ty, &rhs_ty) {
                     self.ty_mismatch(&ident_tkn, &prop_ty, &rhs_ty);
                 }
                 prop_ty
@@ -368,12 +539,174 @@ impl<'t, 's> TyCheck<'t, 's> {
             let expected_ty = self.extract_prop_ty(&class_decl_ast, key.clone());
             let provided_ty = val.get_ty_rec().unwrap().ty;
 
-            if expected_ty != provided_ty {
+            if tys_differ(&expected_ty, &provided_ty) {
                 self.prop_mismatch(meta, &expected_ty, &provided_ty);
             }
         }
     }
 
+    /// Checks that a property assignment doesn't change an immutable instance, or
+    /// an immutable property.
+    fn check_prop_set(
+        &mut self,
+        ident_tkn: &Token,
+        prop_name: &str,
+        owner_class: &Ast,
+        final_sc: usize,
+    ) {
+        let var = ident_tkn.get_name();
+        if let Some(sym) = self.symtab.retrieve_from_finalized_sc(&var, final_sc) {
+            if sym.imm {
+                let decl = &sym.ident_tkn;
+                let err = TypeErr::new(ident_tkn.line, ident_tkn.pos, TypeErrTy::ImmAssign(var))
+                    .with_len(decl.get_name().len())
+                    .with_label(
+                        decl.line,
+                        decl.pos,
+                        decl.get_name().len(),
+                        "declared with 'imm' here",
+                    );
+                self.errors.push(err);
+                return;
+            }
+        }
+
+        let class_name = match owner_class {
+            Ast::ClassConstrExpr { class_name, .. } => class_name.clone(),
+            Ast::ClassDeclStmt { ident_tkn, .. } => ident_tkn.get_name(),
+            _ => return,
+        };
+
+        let props = match self.find_class_decl(&class_name) {
+            Some(Ast::ClassDeclStmt { props, .. }) => props,
+            _ => return,
+        };
+
+        for prop in &props {
+            if let Ast::VarDeclExpr {
+                ident_tkn: prop_tkn,
+                is_imm: true,
+                ..
+            } = prop
+            {
+                if prop_tkn.get_name() == prop_name {
+                    let err = TypeErr::new(
+                        ident_tkn.line,
+                        ident_tkn.pos,
+                        TypeErrTy::ImmPropAssign(class_name, String::from(prop_name)),
+                    )
+                    .with_len(var.len())
+                    .with_label(
+                        prop_tkn.line,
+                        prop_tkn.pos,
+                        prop_name.len(),
+                        "declared with 'imm' here",
+                    );
+                    self.errors.push(err);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Checks that a call to a 'mut' method can change the instance it's called
+    /// on. Calls on self are only allowed in 'mut' methods, and calls on other
+    /// instances are only allowed if the instance isn't immutable.
+    fn check_mut_call(&mut self, call: &Ast, final_sc: usize) {
+        match call {
+            Ast::FnCallExpr { fn_tkn, .. } => {
+                let is_mut_method = match (
+                    &self.curr_class,
+                    self.symtab
+                        .retrieve_from_finalized_sc(&fn_tkn.get_name(), final_sc),
+                ) {
+                    (Some(class), Some(sym)) => class
+                        .mut_methods
+                        .contains(&(sym.ident_tkn.line, sym.ident_tkn.pos)),
+                    _ => false,
+                };
+
+                if is_mut_method {
+                    self.imm_self(fn_tkn, "calls a 'mut' method on self");
+                }
+            }
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                ..
+            } => {
+                let methods = match self.find_class_decl(class_name) {
+                    Some(Ast::ClassDeclStmt { methods, .. }) => methods,
+                    _ => return,
+                };
+
+                let is_mut_method = methods.iter().any(|m| match m {
+                    Ast::FnDeclStmt {
+                        ident_tkn, is_mut, ..
+                    } => *is_mut && ident_tkn.get_name() == fn_tkn.get_name(),
+                    _ => false,
+                });
+
+                let var = class_tkn.get_name();
+                let sym = match self.symtab.retrieve_from_finalized_sc(&var, final_sc) {
+                    Some(sym) => sym,
+                    None => return,
+                };
+
+                if is_mut_method && sym.imm {
+                    let decl = &sym.ident_tkn;
+                    let err = TypeErr::new(
+                        class_tkn.line,
+                        class_tkn.pos,
+                        TypeErrTy::ImmMutCall(fn_tkn.get_name(), var.clone()),
+                    )
+                    .with_len(var.len())
+                    .with_label(
+                        decl.line,
+                        decl.pos,
+                        var.len(),
+                        "declared with 'imm' here",
+                    );
+                    self.errors.push(err);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Reports a change to self, at the given token, if we're in a method that isn't
+    /// declared 'mut'.
+    fn imm_self(&mut self, tkn: &Token, change: &str) {
+        let method_tkn = match self.curr_class.as_ref().and_then(|c| c.method.clone()) {
+            Some((method_tkn, false)) => method_tkn,
+            _ => return,
+        };
+
+        let method = method_tkn.get_name();
+        let err = TypeErr::new(
+            tkn.line,
+            tkn.pos,
+            TypeErrTy::ImmSelf(method.clone(), String::from(change)),
+        )
+        .with_len(tkn.get_name().len())
+        .with_label(
+            method_tkn.line,
+            method_tkn.pos,
+            method.len(),
+            "declared without 'mut'",
+        );
+        self.errors.push(err);
+    }
+
+    /// Finds the declaration of a class. Classes are always declared in the global
+    /// scope.
+    fn find_class_decl(&self, name: &str) -> Option<Ast> {
+        self.symtab
+            .retrieve_from_finalized_sc(name, 0)
+            .and_then(|sym| sym.assign_val.clone())
+    }
+
     fn check_fn_params(&mut self, fn_call_ast: Ast, final_sc: usize) {
         match fn_call_ast {
             Ast::FnCallExpr {
@@ -391,12 +724,7 @@ impl<'t, 's> TyCheck<'t, 's> {
                     passed_in_param_tys.push(self.check_expr(&ast, 0));
                 }
 
-                for (idx, mb_ty_rec) in fn_param_tys.iter().enumerate() {
-                    let ty_name = mb_ty_rec.clone().ty;
-                    if passed_in_param_tys[idx] != ty_name {
-                        self.ty_mismatch(&fn_tkn.clone(), &passed_in_param_tys[idx], &ty_name);
-                    }
-                }
+                self.check_param_tys(fn_param_tys, &fn_params, &passed_in_param_tys);
             }
             Ast::ClassFnCallExpr {
                 meta: _,
@@ -405,11 +733,15 @@ impl<'t, 's> TyCheck<'t, 's> {
                 class_name,
                 fn_tkn,
                 fn_params,
-                sc,
+                sc: _,
             } => {
                 let fn_name = fn_tkn.get_name();
-                let class_sym = self.find_class_sym(&class_name, sc);
-                let fn_param_tys = match class_sym.unwrap().assign_val.as_ref().unwrap() {
+                let class_decl = match self.find_class_decl(&class_name) {
+                    Some(class_decl) => class_decl,
+                    None => return,
+                };
+
+                let fn_param_tys = match &class_decl {
                     Ast::ClassDeclStmt {
                         meta: _,
                         ty_rec: _,
@@ -445,28 +777,39 @@ impl<'t, 's> TyCheck<'t, 's> {
                     passed_in_param_tys.push(self.check_expr(&ast, 0));
                 }
 
-                for (idx, mb_ty_rec) in fn_param_tys.unwrap().iter().enumerate() {
-                    let ty_name = mb_ty_rec.clone().ty;
-                    if passed_in_param_tys[idx] != ty_name {
-                        self.ty_mismatch(&fn_tkn.clone(), &passed_in_param_tys[idx], &ty_name);
-                    }
-                }
+                self.check_param_tys(&fn_param_tys.unwrap(), &fn_params, &passed_in_param_tys);
             }
             _ => (),
         }
     }
 
+    /// Compares the types of the arguments passed to a function against the declared
+    /// parameter types. Mismatches point at the argument, with a label on the parameter.
+    fn check_param_tys(&mut self, expected: &[TyRecord], params: &[Ast], passed_in: &[KolgaTy]) {
+        for ((param_ty_rec, param), passed_ty) in expected.iter().zip(params).zip(passed_in) {
+            if tys_differ(passed_ty, &param_ty_rec.ty) {
+                self.ty_mismatch_w_decl(
+                    param,
+                    &param_ty_rec.ty,
+                    passed_ty,
+                    &param_ty_rec.tkn,
+                    "parameter declared here",
+                );
+            }
+        }
+    }
+
     /// Reduce a unary ast to the expected type to be returned by the expression.
     fn reduce_unary_ty(&mut self, op_tkn: Token, rhs_ty: KolgaTy) -> KolgaTy {
         match op_tkn.ty {
             TknTy::Minus => {
-                if rhs_ty != KolgaTy::Num {
+                if tys_differ(&rhs_ty, &KolgaTy::Num) {
                     self.ty_mismatch(&op_tkn, &KolgaTy::Num, &rhs_ty);
                 }
                 KolgaTy::Num
             }
             TknTy::Bang => {
-                if rhs_ty != KolgaTy::Bool {
+                if tys_differ(&rhs_ty, &KolgaTy::Bool) {
                     self.ty_mismatch(&op_tkn, &KolgaTy::Bool, &rhs_ty);
                 }
                 KolgaTy::Bool
@@ -479,6 +822,11 @@ impl<'t, 's> TyCheck<'t, 's> {
     /// given the operator, even if there is an error. The expected type is one which we expect
     /// the given operator to evaluate to.
     fn reduce_bin_ty(&mut self, op_tkn: Token, lhs_ty: KolgaTy, rhs_ty: KolgaTy) -> KolgaTy {
+        // An operand that couldn't be inferred or parsed has already been reported.
+        if lhs_ty.is_symbolic() || rhs_ty.is_symbolic() || lhs_ty.is_error() || rhs_ty.is_error() {
+            return op_ty(&op_tkn.ty);
+        }
+
         match op_tkn.ty {
             TknTy::Plus | TknTy::Minus | TknTy::Star | TknTy::Slash => {
                 // We can only operate on types of the same kind
@@ -525,19 +873,31 @@ impl<'t, 's> TyCheck<'t, 's> {
     fn check_var_assign(&mut self, stmt: &Ast, sc: usize) -> KolgaTy {
         match stmt {
             Ast::VarAssignExpr {
-                meta: _,
+                meta,
                 ty_rec,
                 ident_tkn,
-                is_imm: _,
+                is_imm,
                 is_global: _,
                 value,
             } => {
+                // A declaration points at the variable it declares, anything else
+                // assigns to an existing variable.
+                if (meta.line, meta.pos) != (ident_tkn.line, ident_tkn.pos) {
+                    self.check_reassign(meta, ident_tkn, *is_imm);
+                }
+
                 let lhs_ty = ty_rec.ty.clone();
                 let rhs = value;
                 let rhs_ty = self.check_expr(&rhs, sc);
 
-                if lhs_ty != rhs_ty {
-                    self.ty_mismatch(&ident_tkn, &lhs_ty, &rhs_ty);
+                // Inference checks the value of a call where the call is.
+                let node = match **rhs {
+                    Ast::FnCallExpr { ref meta, .. } => meta,
+                    _ => meta,
+                };
+
+                if tys_differ(&lhs_ty, &rhs_ty) && !self.infer_failed(node) {
+                    self.ty_mismatch_w_decl(rhs, &lhs_ty, &rhs_ty, &ident_tkn, "declared here");
                 }
 
                 return lhs_ty;
@@ -546,12 +906,81 @@ impl<'t, 's> TyCheck<'t, 's> {
         }
     }
 
+    /// Checks an assignment to an existing variable. Inside a method, this might be
+    /// a property of self, which needs the property to be mutable, and the method
+    /// to be declared 'mut'.
+    fn check_reassign(&mut self, meta: &MetaAst, ident_tkn: &Token, is_imm: bool) {
+        let name = ident_tkn.get_name();
+        let prop = self
+            .curr_class
+            .as_ref()
+            .and_then(|class| class.prop(ident_tkn).map(|p| (class.name.clone(), p.1)));
+
+        let err_ty = match prop {
+            Some((class, true)) => TypeErrTy::ImmPropAssign(class, name.clone()),
+            Some((_, false)) => {
+                let at = Token::new(ident_tkn.ty.clone(), meta.line, meta.pos);
+                self.imm_self(&at, "assigns to a property of self");
+                return;
+            }
+            None if is_imm => TypeErrTy::ImmAssign(name.clone()),
+            None => return,
+        };
+
+        let err = TypeErr::new(meta.line, meta.pos, err_ty)
+            .with_len(name.len())
+            .with_label(
+                ident_tkn.line,
+                ident_tkn.pos,
+                name.len(),
+                "declared with 'imm' here",
+            );
+        self.errors.push(err);
+    }
+
     fn ty_mismatch(&mut self, tkn: &Token, lhs: &KolgaTy, rhs: &KolgaTy) {
-        self.error(
+        let err = TypeErr::new(
             tkn.line,
             tkn.pos,
             TypeErrTy::TyMismatch(lhs.to_string(), rhs.to_string()),
-        );
+        )
+        .with_len(tkn.ty.to_string().len());
+
+        self.errors.push(err);
+    }
+
+    /// Reports a type mismatch at the location of an expression, with a secondary label
+    /// pointing at the declaration that decided the expected type.
+    fn ty_mismatch_w_decl(
+        &mut self,
+        expr: &Ast,
+        lhs: &KolgaTy,
+        rhs: &KolgaTy,
+        decl_tkn: &Token,
+        decl_msg: &str,
+    ) {
+        let meta = expr.get_meta();
+
+        // Only primary expressions know how much source text they cover, anything
+        // else just points at the start of the expression.
+        let len = match expr {
+            Ast::PrimaryExpr { ty_rec, .. } => match ty_rec.tkn.ty {
+                TknTy::Str(ref s) => s.len() + 2,
+                ref ty => ty.to_string().len(),
+            },
+            _ => 1,
+        };
+
+        let decl_len = decl_tkn.ty.to_string().len();
+        let err = TypeErr::new(
+            meta.line,
+            meta.pos,
+            TypeErrTy::TyMismatch(lhs.to_string(), rhs.to_string()),
+        )
+        .with_len(len)
+        .with_label(decl_tkn.line, decl_tkn.pos, decl_len, decl_msg);
+
+        self.errors.push(err);
     }
 
     fn prop_mismatch(&mut self, meta: &MetaAst, lhs: &KolgaTy, rhs: &KolgaTy) {
@@ -593,12 +1022,9 @@ impl<'t, 's> TyCheck<'t, 's> {
         }
     }
 
-    fn find_class_sym(&mut self, name: &str, sc: usize) -> Option<Rc<Sym>> {
-        let sym = self.symtab.retrieve_from_finalized_sc(name, sc);
-        match sym {
-            Some(symbol) => Some(symbol),
-            None => None,
-        }
+    /// Whether type inference already reported an error in a node.
+    fn infer_failed(&self, meta: &MetaAst) -> bool {
+        self.infer_err_nodes.contains(&meta.id)
     }
 
     fn error(&mut self, line: usize, pos: usize, ty: TypeErrTy) {
@@ -606,3 +1032,21 @@ impl<'t, 's> TyCheck<'t, 's> {
         self.errors.push(err);
     }
 }
+
+/// The type an operator evaluates to, whatever its operands are.
+fn op_ty(op: &TknTy) -> KolgaTy {
+    if op.is_numerical_op() {
+        KolgaTy::Num
+    } else {
+        KolgaTy::Bool
+    }
+}
+
+/// Types that are still symbolic after inference couldn't be inferred, and
+/// inference has already reported why. Error types belong to variables whose
+/// value failed to parse. Both are treated as matching any type, so the same
+/// mistake isn't reported again everywhere the value is used.
+fn tys_differ(lhs: &KolgaTy, rhs: &KolgaTy) -> bool {
+    let unknown = |ty: &KolgaTy| ty.is_symbolic() || ty.is_error();
+    lhs != rhs && !unknown(lhs) && !unknown(rhs)
+}
@@ -31,12 +31,17 @@ impl TyMatch {
 pub struct TyInfer {
     /// Represents a substitution from a variable name to a type
     subs: HashMap<String, KolgaTy>,
+
+    /// Errors found while generating or unifying type equations. A failed
+    /// equation is skipped, so we can keep going and report all of them.
+    errors: Vec<TypeErr>,
 }
 
 impl TyInfer {
     pub fn new() -> TyInfer {
         TyInfer {
             subs: HashMap::new(),
+            errors: Vec::new(),
         }
     }
 
@@ -51,15 +56,24 @@ impl TyInfer {
     ///    in the AST with the mgu's contained in the type mapping. After this pass,
     ///    our program should have no symbolic types remaining.
     ///
-    /// Returns an empty result, indicating success. There is no result to return,
-    /// as we alter the AST in place in the last step of the function.
-    pub fn infer(&mut self, ast: &mut Ast) -> Result<(), TypeErr> {
+    /// Returns an empty result on success, as we alter the AST in place in the last
+    /// step of the function. Otherwise, returns every equation that couldn't be
+    /// unified. Types that were unified are still written back to the AST, so the
+    /// rest of the program can be type checked.
+    pub fn infer(&mut self, ast: &mut Ast) -> Result<(), Vec<TypeErr>> {
         match ast {
             Ast::Prog { meta: _, stmts } => {
                 let ty_eqs = self.ty_eq(stmts);
-                self.unify_all(ty_eqs)?;
+                self.unify_all(ty_eqs);
+            }
+            _ => {
+                let meta = ast.get_meta();
+                return Err(vec![TypeErr::new(
+                    meta.line,
+                    meta.pos,
+                    TypeErrTy::InvalidInfer,
+                )]);
             }
-            _ => return Err(TypeErr::new(0, 0, TypeErrTy::InvalidInfer)),
         };
 
         match ast {
@@ -71,7 +85,11 @@ impl TyInfer {
             _ => (),
         };
 
-        Ok(())
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors.clone())
+        }
     }
 
     /// Rewrites the type records in the passed in AST. After unification, we
@@ -246,11 +264,12 @@ impl TyInfer {
             Ast::ClassPropSetExpr { .. }
             | Ast::ClassConstrExpr { .. }
             | Ast::Prog { .. }
-            | Ast::ClassPropAccessExpr { .. } => (),
+            | Ast::ClassPropAccessExpr { .. }
+            | Ast::Error { .. } => (),
         }
     }
 
-    fn ty_eq(&self, stmts: &mut Vec<Ast>) -> Vec<TyMatch> {
+    fn ty_eq(&mut self, stmts: &mut Vec<Ast>) -> Vec<TyMatch> {
         let mut ty_eqs = Vec::new();
         for stmt in stmts.iter() {
             ty_eqs.extend(self.gen_ty_eq(stmt));
@@ -259,12 +278,20 @@ impl TyInfer {
         ty_eqs
     }
 
-    fn unify_all(&mut self, ty_eqs: Vec<TyMatch>) -> Result<(), TypeErr> {
+    /// Unifies every equation, recording an error for each one that fails.
+    /// Both operands of an operator are matched at the operator, so if both
+    /// are wrong in the same way, the error is only recorded once.
+    fn unify_all(&mut self, ty_eqs: Vec<TyMatch>) {
         for eq in ty_eqs {
-            self.unify(eq.lhs, eq.rhs, eq.meta)?;
+            if let Err(e) = self.unify(eq.lhs, eq.rhs, eq.meta) {
+                let seen = self.errors.iter().any(|prev| {
+                    (prev.line, prev.pos, prev.node) == (e.line, e.pos, e.node) && prev.ty == e.ty
+                });
+                if !seen {
+                    self.errors.push(e);
+                }
+            }
         }
-
-        Ok(())
     }
 
     /// Unifies two arbitrary types. At least one of the provided types
@@ -281,9 +308,13 @@ impl TyInfer {
     ///
     /// When attempting to unify this program, we would end up with an lhs
     /// argument Num, and a rhs arg String, which we cannot unify. In this case,
-    /// we should return a type error with a type mismatch.
+    /// we should return a type error with a type mismatch. The lhs is the
+    /// type we expected, and the rhs is the type we found, so both types are
+    /// resolved first to keep that order in the error.
     fn unify(&mut self, lhs: KolgaTy, rhs: KolgaTy, meta: MetaAst) -> Result<(), TypeErr> {
-        if lhs == rhs {
+        let lhs = self.resolve(lhs);
+        let rhs = self.resolve(rhs);
+        if lhs == rhs || lhs.is_error() || rhs.is_error() {
             return Ok(());
         }
 
@@ -305,7 +336,8 @@ impl TyInfer {
             meta.line,
             meta.pos,
             TypeErrTy::TyMismatch(lhs.to_string(), rhs.to_string()),
-        ))
+        )
+        .with_node(meta.id))
     }
 
     /// Unifies two variable types. This is done by inserting the type on the rhs
@@ -348,7 +380,9 @@ impl TyInfer {
         // Ensure that the type doesn't contain a reference to itself
         // (ie. let x = x) to prevent infinite unification.
         if self.occurs_check(lhs, rhs.clone()) {
-            return Err(TypeErr::new(meta.line, meta.pos, TypeErrTy::InfiniteType));
+            return Err(
+                TypeErr::new(meta.line, meta.pos, TypeErrTy::InfiniteType).with_node(meta.id)
+            );
         }
 
         // Insert the unified type for the lhs key (the name of the symbolic type)
@@ -356,6 +390,18 @@ impl TyInfer {
         Ok(())
     }
 
+    /// Follows the substitutions for a symbolic type until we reach a concrete
+    /// type, or a symbolic type that hasn't been unified yet.
+    fn resolve(&self, ty: KolgaTy) -> KolgaTy {
+        match ty {
+            KolgaTy::Symbolic(ref name) => match self.subs.get(name) {
+                Some(sub) if *sub != ty => self.resolve(sub.clone()),
+                _ => ty,
+            },
+            _ => ty,
+        }
+    }
+
     /// Checks if the provided lhs type occurs "inside" of the provided rhs type.
     /// This check is needed to avoid infinite recursion during unification
     /// (we would endlessly try to unify a type within itself).
@@ -388,7 +434,7 @@ impl TyInfer {
     /// Walks the entire AST and creates pairs of KolgaTy's to be unified in the next
     /// step of type inference. Typing rules are applied in this step to determine
     /// which types we expect certain expressions to evaluate to.
-    fn gen_ty_eq(&self, ast: &Ast) -> Vec<TyMatch> {
+    fn gen_ty_eq(&mut self, ast: &Ast) -> Vec<TyMatch> {
         let mut ty_eqs = Vec::new();
         match *ast {
             Ast::PrimaryExpr { .. } => ty_eqs,
@@ -408,16 +454,21 @@ impl TyInfer {
             } => {
                 ty_eqs.extend(self.gen_ty_eq(lhs));
                 ty_eqs.extend(self.gen_ty_eq(rhs));
-                // Binary operators expect numbers as their args: strings are not supported
-                // We should be safe to unwrap here, otherwise we have a parsing error
-                // (we're trying to put something in an expression without a type)
-                let lhs_ty_rec = lhs.get_ty_rec().unwrap();
-                let rhs_ty_rec = rhs.get_ty_rec().unwrap();
-
-                ty_eqs.push(TyMatch::new(lhs_ty_rec.ty, KolgaTy::Num, meta.clone()));
-                ty_eqs.push(TyMatch::new(rhs_ty_rec.ty, KolgaTy::Num, meta.clone()));
+                // Logical operators expect bools as their args, and every other binary
+                // operator expects numbers: strings are not supported
+                let arg_ty = if op_tkn.ty.is_logical_op() {
+                    KolgaTy::Bool
+                } else {
+                    KolgaTy::Num
+                };
+                if let Some(lhs_ty) = self.expr_ty(lhs) {
+                    ty_eqs.push(TyMatch::new(arg_ty.clone(), lhs_ty, meta.clone()));
+                }
+                if let Some(rhs_ty) = self.expr_ty(rhs) {
+                    ty_eqs.push(TyMatch::new(arg_ty, rhs_ty, meta.clone()));
+                }
 
-                if op_tkn.ty.is_cmp_op() {
+                if op_tkn.ty.is_cmp_op() || op_tkn.ty.is_logical_op() {
                     ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), KolgaTy::Bool, meta.clone()));
                 } else {
                     ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), KolgaTy::Num, meta.clone()));
@@ -432,14 +483,16 @@ impl TyInfer {
                 ref rhs,
             } => {
                 ty_eqs.extend(self.gen_ty_eq(rhs));
-                let rhs_ty_rec = rhs.get_ty_rec().unwrap();
-                if op_tkn.ty == TknTy::Bang {
-                    ty_eqs.push(TyMatch::new(rhs_ty_rec.ty, KolgaTy::Bool, meta.clone()));
-                    ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), KolgaTy::Bool, meta.clone()));
+                let op_ty = if op_tkn.ty == TknTy::Bang {
+                    KolgaTy::Bool
                 } else {
-                    ty_eqs.push(TyMatch::new(rhs_ty_rec.ty, KolgaTy::Num, meta.clone()));
-                    ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), KolgaTy::Num, meta.clone()));
+                    KolgaTy::Num
+                };
+
+                if let Some(rhs_ty) = self.expr_ty(rhs) {
+                    ty_eqs.push(TyMatch::new(op_ty.clone(), rhs_ty, meta.clone()));
                 }
+                ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), op_ty, meta.clone()));
 
                 ty_eqs
             }
@@ -464,12 +517,9 @@ impl TyInfer {
             } => {
                 ty_eqs.extend(self.gen_ty_eq(if_stmts));
 
-                let cond_expr_ty_rec = cond_expr.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(
-                    cond_expr_ty_rec.ty,
-                    KolgaTy::Bool,
-                    meta.clone(),
-                ));
+                if let Some(cond_ty) = self.expr_ty(cond_expr) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Bool, cond_ty, meta.clone()));
+                }
 
                 for stmt in elif_exprs.iter() {
                     ty_eqs.extend(self.gen_ty_eq(stmt));
@@ -488,12 +538,9 @@ impl TyInfer {
             } => {
                 ty_eqs.extend(self.gen_ty_eq(stmts));
 
-                let cond_expr_ty_rec = cond_expr.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(
-                    cond_expr_ty_rec.ty,
-                    KolgaTy::Bool,
-                    meta.clone(),
-                ));
+                if let Some(cond_ty) = self.expr_ty(cond_expr) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Bool, cond_ty, meta.clone()));
+                }
 
                 ty_eqs
             }
@@ -504,12 +551,9 @@ impl TyInfer {
             } => {
                 ty_eqs.extend(self.gen_ty_eq(stmts));
 
-                let cond_expr_ty_rec = cond_expr.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(
-                    cond_expr_ty_rec.ty,
-                    KolgaTy::Bool,
-                    meta.clone(),
-                ));
+                if let Some(cond_ty) = self.expr_ty(cond_expr) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Bool, cond_ty, meta.clone()));
+                }
 
                 ty_eqs
             }
@@ -523,24 +567,19 @@ impl TyInfer {
                 ty_eqs.extend(self.gen_ty_eq(stmts));
 
                 // The var declaration should be a number
-                let var_decl_ty_rec = for_var_decl.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(var_decl_ty_rec.ty, KolgaTy::Num, meta.clone()));
+                if let Some(var_decl_ty) = self.expr_ty(for_var_decl) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Num, var_decl_ty, meta.clone()));
+                }
 
                 // The cond expr should be a bool
-                let cond_expr_ty_rec = for_cond_expr.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(
-                    cond_expr_ty_rec.ty,
-                    KolgaTy::Bool,
-                    meta.clone(),
-                ));
+                if let Some(cond_ty) = self.expr_ty(for_cond_expr) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Bool, cond_ty, meta.clone()));
+                }
 
                 // The step expression should be a number
-                let step_expr_ty_rec = for_step_expr.get_ty_rec().unwrap();
-                ty_eqs.push(TyMatch::new(
-                    step_expr_ty_rec.ty,
-                    KolgaTy::Num,
-                    meta.clone(),
-                ));
+                if let Some(step_ty) = self.expr_ty(for_step_expr) {
+                    ty_eqs.push(TyMatch::new(KolgaTy::Num, step_ty, meta.clone()));
+                }
 
                 ty_eqs
             }
@@ -566,8 +605,9 @@ impl TyInfer {
                 }
                 _ => {
                     ty_eqs.extend(self.gen_ty_eq(value));
-                    let val_ty_rec = value.get_ty_rec().unwrap();
-                    ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), val_ty_rec.ty, meta.clone()));
+                    if let Some(val_ty) = self.expr_ty(value) {
+                        ty_eqs.push(TyMatch::new(ty_rec.ty.clone(), val_ty, meta.clone()));
+                    }
                     ty_eqs
                 }
             },
@@ -577,7 +617,7 @@ impl TyInfer {
                 fn_params: _,
                 ret_ty: _,
                 ref fn_body,
-                sc: _,
+                ..
             } => {
                 ty_eqs.extend(self.gen_ty_eq(fn_body));
                 ty_eqs
@@ -614,4 +654,25 @@ impl TyInfer {
             _ => ty_eqs,
         }
     }
+
+    /// Finds the type of an expression used in a type equation. Expression
+    /// statements (like the condition of a for loop) have the type of the
+    /// expression they contain. If the expression has no type, we can't
+    /// generate the equation, so we record an error at the expression instead.
+    fn expr_ty(&mut self, expr: &Ast) -> Option<KolgaTy> {
+        let expr = match expr {
+            Ast::ExprStmt { meta: _, expr } => expr,
+            _ => expr,
+        };
+
+        match expr.get_ty_rec() {
+            Some(ty_rec) => Some(ty_rec.ty),
+            None => {
+                let meta = expr.get_meta();
+                self.errors
+                    .push(TypeErr::new(meta.line, meta.pos, TypeErrTy::InvalidInfer));
+                None
+            }
+        }
+    }
 }
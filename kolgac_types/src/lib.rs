@@ -1,5 +1,8 @@
 extern crate kolgac;
 extern crate kolgac_errors;
 
+pub mod assign;
 pub mod check;
 pub mod infer;
+pub mod ret;
+pub mod unused;
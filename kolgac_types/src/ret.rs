@@ -0,0 +1,198 @@
+use kolgac_errors::warn::{Warn, WarnTy};
+
+use kolgac::{
+    ast::Ast,
+    const_eval::{ConstEval, ConstVal},
+};
+
+/// The place where control can reach the end of a function without returning.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fallthrough {
+    /// Nothing on this path returns, so control reaches the end of the function.
+    End,
+
+    /// A branch of an if statement that doesn't return, when other branches of the
+    /// same statement do. Points at the opening brace of the branch.
+    Branch(usize, usize),
+
+    /// An if statement without an else branch, where the other branches return.
+    /// Points at the if token.
+    NoElse(usize, usize),
+}
+
+/// True if a statement returns on every path through it, or never finishes. Loops
+/// might not run, so they only count when their condition is always true: there's
+/// no break, so a loop like that can only be left by returning.
+pub fn always_returns(stmt: &Ast) -> bool {
+    match stmt {
+        Ast::RetStmt { .. } => true,
+        Ast::WhileStmt { cond_expr, .. } => always_true(cond_expr),
+        // The condition of a for loop is parsed as an expression statement.
+        Ast::ForStmt { for_cond_expr, .. } => match &**for_cond_expr {
+            Ast::ExprStmt { expr, .. } => always_true(expr),
+            cond => always_true(cond),
+        },
+        Ast::BlckStmt { stmts, .. } => stmts.iter().any(always_returns),
+        Ast::IfStmt {
+            if_stmts,
+            elif_exprs,
+            el_stmts,
+            ..
+        } => {
+            always_returns(if_stmts)
+                && elif_exprs.iter().all(always_returns)
+                && !el_stmts.is_empty()
+                && el_stmts.iter().all(always_returns)
+        }
+        Ast::ElifStmt { stmts, .. } => always_returns(stmts),
+        _ => false,
+    }
+}
+
+fn always_true(cond: &Ast) -> bool {
+    ConstEval::new().eval(cond) == Some(ConstVal::Bool(true))
+}
+
+/// True if a return statement appears anywhere in a statement.
+pub fn has_ret(stmt: &Ast) -> bool {
+    match stmt {
+        Ast::RetStmt { .. } => true,
+        Ast::BlckStmt { stmts, .. } => stmts.iter().any(has_ret),
+        Ast::IfStmt {
+            if_stmts,
+            elif_exprs,
+            el_stmts,
+            ..
+        } => has_ret(if_stmts) || elif_exprs.iter().chain(el_stmts).any(has_ret),
+        Ast::ElifStmt { stmts, .. } | Ast::WhileStmt { stmts, .. } | Ast::ForStmt { stmts, .. } => {
+            has_ret(stmts)
+        }
+        _ => false,
+    }
+}
+
+/// Finds where control falls through the end of a list of statements without
+/// returning. Returns None if every path returns. If an if statement returns on
+/// some of its branches, we point at the first branch that doesn't, since that's
+/// most likely where the missing return belongs.
+pub fn fallthrough(stmts: &[Ast]) -> Option<Fallthrough> {
+    if stmts.iter().any(always_returns) {
+        return None;
+    }
+
+    for stmt in stmts.iter().rev() {
+        if let Ast::IfStmt {
+            meta,
+            if_stmts,
+            elif_exprs,
+            el_stmts,
+            ..
+        } = stmt
+        {
+            if !has_ret(stmt) {
+                continue;
+            }
+
+            let branches = std::iter::once(&**if_stmts)
+                .chain(elif_exprs.iter().map(|elif| match elif {
+                    Ast::ElifStmt { stmts, .. } => &**stmts,
+                    _ => elif,
+                }))
+                .chain(el_stmts);
+
+            for branch in branches {
+                if let Some(ft) = branch_fallthrough(branch) {
+                    return Some(ft);
+                }
+            }
+
+            if el_stmts.is_empty() {
+                return Some(Fallthrough::NoElse(meta.line, meta.pos));
+            }
+        }
+    }
+
+    Some(Fallthrough::End)
+}
+
+/// Finds where control falls through a branch of an if statement. If the branch
+/// doesn't return at all, it's the branch itself, otherwise we look inside it.
+fn branch_fallthrough(branch: &Ast) -> Option<Fallthrough> {
+    match branch {
+        Ast::BlckStmt { meta, stmts, .. } => match fallthrough(stmts) {
+            Some(Fallthrough::End) => Some(Fallthrough::Branch(meta.line, meta.pos)),
+            ft => ft,
+        },
+        _ => None,
+    }
+}
+
+/// Finds statements that can never run because they follow a return statement
+/// in the same block. Only the first unreachable statement in a block is reported.
+pub struct UnreachableCheck<'t> {
+    ast: &'t Ast,
+    warnings: Vec<Warn>,
+}
+
+impl<'t> UnreachableCheck<'t> {
+    pub fn new(ast: &'t Ast) -> UnreachableCheck<'t> {
+        UnreachableCheck {
+            ast,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self) -> Vec<Warn> {
+        self.walk(self.ast);
+        self.warnings.clone()
+    }
+
+    fn walk(&mut self, node: &Ast) {
+        match node {
+            Ast::Prog { stmts, .. } | Ast::BlckStmt { stmts, .. } => self.walk_stmts(stmts),
+            Ast::IfStmt {
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+                ..
+            } => {
+                self.walk(if_stmts);
+                for stmt in elif_exprs.iter().chain(el_stmts) {
+                    self.walk(stmt);
+                }
+            }
+            Ast::ElifStmt { stmts, .. }
+            | Ast::WhileStmt { stmts, .. }
+            | Ast::ForStmt { stmts, .. } => self.walk(stmts),
+            Ast::FnDeclStmt { fn_body, .. } => self.walk(fn_body),
+            Ast::ClassDeclStmt { methods, .. } => {
+                for method in methods {
+                    self.walk(method);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn walk_stmts(&mut self, stmts: &[Ast]) {
+        for stmt in stmts {
+            self.walk(stmt);
+        }
+
+        if let Some(idx) = stmts.iter().position(always_returns) {
+            let ret_meta = stmts[idx].get_meta();
+            let unreachable = stmts[idx + 1..]
+                .iter()
+                .find(|s| !matches!(s, Ast::Error { .. }));
+
+            if let Some(stmt) = unreachable {
+                let meta = stmt.get_meta();
+                self.warnings.push(Warn::new(
+                    meta.line,
+                    meta.pos,
+                    WarnTy::UnreachableCode(ret_meta.line, ret_meta.pos),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use kolgac_errors::warn::{Warn, WarnTy};
+
+use kolgac::{ast::Ast, sym::SymTy, symtab::SymbolTable, token::TknTy};
+
+use std::collections::{HashMap, HashSet};
+
+/// Symbols are identified by the position of the token that declared them. The
+/// parser can store more than one copy of the same symbol (functions are stored
+/// again after their body is parsed), so the symbols themselves can't be compared.
+type SymKey = (usize, usize);
+
+/// Finds variables, parameters, functions, class properties and methods that are
+/// declared but never used. Every symbol is collected from the finalized symbol
+/// table, and every use is found by walking the AST and looking up each name in
+/// the scope it's used in. Function bodies are scoped inside the global scope, so
+/// a global read in any function counts. Writing to a variable or property
+/// doesn't count as a use, and neither does a function calling itself.
+pub struct UnusedCheck<'t, 's> {
+    ast: &'t Ast,
+    symtab: &'s SymbolTable,
+
+    /// Symbols that have been read at least once.
+    used: HashSet<SymKey>,
+
+    /// Class methods, which are stored in the symbol table like any other function,
+    /// and the name of the class they belong to.
+    methods: HashMap<SymKey, String>,
+
+    /// Class properties, and the name of the class they belong to.
+    props: HashMap<SymKey, String>,
+
+    /// The function we're currently walking, so recursive calls can be ignored.
+    curr_fn: Option<SymKey>,
+}
+
+impl<'t, 's> UnusedCheck<'t, 's> {
+    pub fn new(ast: &'t Ast, symtab: &'s SymbolTable) -> UnusedCheck<'t, 's> {
+        UnusedCheck {
+            ast,
+            symtab,
+            used: HashSet::new(),
+            methods: HashMap::new(),
+            props: HashMap::new(),
+            curr_fn: None,
+        }
+    }
+
+    /// Entry point for the analysis. Returns a warning for each unused symbol, in
+    /// the order they appear in the source.
+    pub fn check(&mut self) -> Vec<Warn> {
+        // Pass in 0 for the global scope.
+        self.walk(self.ast, 0);
+
+        let mut seen = HashSet::new();
+        let mut warnings = Vec::new();
+
+        for sym in self.symtab.finalized_syms() {
+            let tkn = &sym.ident_tkn;
+            let key = (tkn.line, tkn.pos);
+            if !seen.insert(key) || self.used.contains(&key) {
+                continue;
+            }
+
+            let name = tkn.get_name();
+            let ty = match sym.sym_ty {
+                SymTy::Param => WarnTy::UnusedParam(name),
+                SymTy::Fn => match self.methods.get(&key) {
+                    Some(class) => WarnTy::UnusedMethod(class.clone(), name),
+                    None => WarnTy::UnusedFn(name),
+                },
+                SymTy::Var => match self.props.get(&key) {
+                    Some(class) => WarnTy::UnusedProp(class.clone(), name),
+                    None => WarnTy::UnusedVar(name),
+                },
+                SymTy::Class => continue,
+            };
+
+            warnings.push(Warn::new(tkn.line, tkn.pos, ty));
+        }
+
+        warnings.sort_by_key(|w| (w.line, w.pos));
+        warnings
+    }
+
+    /// Walks an AST node, marking any symbols it reads as used. The final_sc
+    /// parameter is the finalized scope level to look up names in.
+    fn walk(&mut self, node: &Ast, final_sc: usize) {
+        match node {
+            Ast::Prog { meta: _, stmts } => {
+                for stmt in stmts {
+                    self.walk(stmt, final_sc);
+                }
+            }
+            Ast::BlckStmt { meta: _, stmts, sc } => {
+                for stmt in stmts {
+                    self.walk(stmt, *sc);
+                }
+            }
+            Ast::IfStmt {
+                meta: _,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => {
+                self.walk(cond_expr, final_sc);
+                self.walk(if_stmts, final_sc);
+
+                for stmt in elif_exprs.iter().chain(el_stmts) {
+                    self.walk(stmt, final_sc);
+                }
+            }
+            Ast::ElifStmt {
+                meta: _,
+                cond_expr,
+                stmts,
+            }
+            | Ast::WhileStmt {
+                meta: _,
+                cond_expr,
+                stmts,
+            } => {
+                self.walk(cond_expr, final_sc);
+                self.walk(stmts, final_sc);
+            }
+            Ast::ForStmt {
+                meta: _,
+                for_var_decl,
+                for_cond_expr,
+                for_step_expr,
+                stmts,
+            } => {
+                self.walk(for_var_decl, final_sc);
+                self.walk(for_cond_expr, final_sc);
+                self.walk(for_step_expr, final_sc);
+                self.walk(stmts, final_sc);
+            }
+            Ast::RetStmt { meta: _, ret_expr } => {
+                if let Some(expr) = ret_expr {
+                    self.walk(expr, final_sc);
+                }
+            }
+            Ast::ExprStmt { meta: _, expr } => self.walk(expr, final_sc),
+            Ast::VarDeclExpr { .. } => (),
+            Ast::VarAssignExpr { value, .. } => self.walk(value, final_sc),
+            Ast::LogicalExpr { lhs, rhs, .. } | Ast::BinaryExpr { lhs, rhs, .. } => {
+                self.walk(lhs, final_sc);
+                self.walk(rhs, final_sc);
+            }
+            Ast::UnaryExpr { rhs, .. } => self.walk(rhs, final_sc),
+            Ast::PrimaryExpr { ty_rec, .. } => {
+                if let TknTy::Ident(ref name) = ty_rec.tkn.ty {
+                    self.use_sym(name, final_sc);
+                }
+            }
+            Ast::FnDeclStmt {
+                ident_tkn, fn_body, ..
+            } => {
+                let prev_fn = self.curr_fn.replace((ident_tkn.line, ident_tkn.pos));
+                self.walk(fn_body, final_sc);
+                self.curr_fn = prev_fn;
+            }
+            Ast::FnCallExpr {
+                fn_tkn, fn_params, ..
+            } => {
+                self.use_sym(&fn_tkn.get_name(), final_sc);
+                for param in fn_params {
+                    self.walk(param, final_sc);
+                }
+            }
+            Ast::ClassDeclStmt {
+                ident_tkn,
+                methods,
+                props,
+                sc,
+                ..
+            } => {
+                for prop in props {
+                    if let Ast::VarDeclExpr {
+                        ident_tkn: prop_tkn,
+                        ..
+                    } = prop
+                    {
+                        self.props
+                            .insert((prop_tkn.line, prop_tkn.pos), ident_tkn.get_name());
+                    }
+                }
+
+                for method in methods {
+                    if let Ast::FnDeclStmt {
+                        ident_tkn: fn_tkn, ..
+                    } = method
+                    {
+                        self.methods
+                            .insert((fn_tkn.line, fn_tkn.pos), ident_tkn.get_name());
+                    }
+                    self.walk(method, *sc);
+                }
+            }
+            Ast::ClassConstrExpr { props, .. } => {
+                for val in props.values() {
+                    self.walk(val, final_sc);
+                }
+            }
+            Ast::ClassPropAccessExpr {
+                ident_tkn,
+                prop_name,
+                owner_class,
+                ..
+            } => {
+                self.use_sym(&ident_tkn.get_name(), final_sc);
+                self.use_prop(owner_class, prop_name);
+            }
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                assign_val,
+                ..
+            } => {
+                self.use_sym(&ident_tkn.get_name(), final_sc);
+                self.walk(assign_val, final_sc);
+            }
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                fn_params,
+                ..
+            } => {
+                self.use_sym(&class_tkn.get_name(), final_sc);
+                self.use_member(class_name, &fn_tkn.get_name());
+                for param in fn_params {
+                    self.walk(param, final_sc);
+                }
+            }
+            Ast::Error { .. } => (),
+        }
+    }
+
+    fn use_sym(&mut self, name: &str, final_sc: usize) {
+        if let Some(sym) = self.symtab.retrieve_from_finalized_sc(name, final_sc) {
+            let key = (sym.ident_tkn.line, sym.ident_tkn.pos);
+            if self.curr_fn != Some(key) {
+                self.used.insert(key);
+            }
+        }
+    }
+
+    /// Marks a class property as used. The owner is either the class declaration
+    /// or a construction of the class.
+    fn use_prop(&mut self, owner: &Ast, prop_name: &str) {
+        let class_name = match owner {
+            Ast::ClassConstrExpr { class_name, .. } => class_name.clone(),
+            Ast::ClassDeclStmt { ident_tkn, .. } => ident_tkn.get_name(),
+            _ => return,
+        };
+
+        self.use_member(&class_name, prop_name);
+    }
+
+    /// Marks a property or method of a class as used. We look up the class
+    /// declaration by name to find the scope its members were declared in.
+    fn use_member(&mut self, class_name: &str, name: &str) {
+        // Classes can only be declared in the global scope.
+        let class_sym = match self.symtab.retrieve_from_finalized_sc(class_name, 0) {
+            Some(sym) => sym,
+            None => return,
+        };
+
+        if let Some(Ast::ClassDeclStmt { sc, .. }) = &class_sym.assign_val {
+            self.use_sym(name, *sc);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_types::assign::AssignCheck;
+
+use std::fs::File;
+
+#[test]
+fn definite_assignment() {
+    let file = File::open("./tests/assign/assign.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let errs = AssignCheck::new(&ast, &symtab).check();
+    let found: Vec<(usize, usize, &str)> = errs.iter().map(|e| (e.line, e.pos, e.code())).collect();
+
+    // 'z' and 's' are assigned on every path, the rest aren't.
+    let expected = vec![
+        (6, 12, "K0308"),
+        (22, 12, "K0308"),
+        (29, 9, "K0308"),
+        (44, 12, "K0308"),
+        (46, 13, "K0308"),
+    ];
+    assert_eq!(found, expected);
+
+    // 'u' is assigned in the else branch, so the path goes through the outer if
+    // being true, and then the inner if being false.
+    let path: Vec<(usize, &str)> = errs[3]
+        .labels
+        .iter()
+        .map(|l| (l.span.line, l.msg.as_str()))
+        .collect();
+    assert_eq!(
+        path,
+        vec![
+            (36, "declared here without a value"),
+            (37, "'u' is not assigned when this condition is true"),
+            (38, "'u' is not assigned when this condition is false"),
+        ]
+    );
+}
@@ -0,0 +1,36 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_types::{check::TyCheck, infer::TyInfer};
+
+use std::fs::File;
+
+#[test]
+fn imm() {
+    let file = File::open("./tests/imm/imm.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+    TyInfer::new().infer(&mut ast).unwrap();
+
+    let errs = TyCheck::new(&ast, &mut symtab).check();
+    let found: Vec<(usize, usize, &str)> = errs.iter().map(|e| (e.line, e.pos, e.code())).collect();
+
+    // Writes to immutable properties are caught through self and through an
+    // instance, and reassignments of immutable variables are caught in nested
+    // scopes. Changing a mutable instance through a 'mut' method is allowed.
+    let expected = vec![
+        (7, 9, "K0311"),
+        (12, 9, "K0312"),
+        (13, 9, "K0312"),
+        (32, 4, "K0310"),
+        (34, 2, "K0310"),
+        (35, 2, "K0311"),
+        (37, 2, "K0313"),
+        (39, 25, "K0313"),
+    ];
+    assert_eq!(found, expected);
+
+    // Errors in methods point back at the method, so it's clear which one needs
+    // to be declared 'mut'.
+    let label = &errs[1].labels[0];
+    assert_eq!((label.span.line, label.span.pos), (11, 5));
+}
@@ -0,0 +1,83 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_types::{check::TyCheck, infer::TyInfer};
+
+use std::fs::File;
+
+#[test]
+fn infer_reports_every_failure() {
+    let file = File::open("./tests/infer/errors.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let infer_errs = TyInfer::new().infer(&mut ast).unwrap_err();
+    let found: Vec<(usize, usize, &str)> = infer_errs
+        .iter()
+        .map(|e| (e.line, e.pos, e.code()))
+        .collect();
+
+    // Each failed equation is reported where it was generated: the assignment,
+    // and the operators with the wrong operand types.
+    let expected = vec![
+        (2, 0, "K0301"),
+        (3, 11, "K0301"),
+        (4, 13, "K0301"),
+        (7, 11, "K0301"),
+    ];
+    assert_eq!(found, expected);
+
+    // The checker still runs, but only reports the bad argument to 'f', which
+    // inference doesn't look at.
+    let check_errs = TyCheck::new(&ast, &mut symtab)
+        .with_infer_errs(&infer_errs)
+        .check();
+    let found: Vec<(usize, usize)> = check_errs.iter().map(|e| (e.line, e.pos)).collect();
+    assert_eq!(found, vec![(9, 2)]);
+}
+
+#[test]
+fn check_reports_other_errors_on_a_line_inference_failed_on() {
+    let file = File::open("./tests/infer/same_line.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    let infer_errs = TyInfer::new().infer(&mut ast).unwrap_err();
+    let found: Vec<(usize, usize, &str)> = infer_errs
+        .iter()
+        .map(|e| (e.line, e.pos, e.code()))
+        .collect();
+    assert_eq!(found, vec![(2, 37, "K0301")]);
+
+    // The bad operands are only reported once, but the assignment to 'k' on the
+    // same line is still reported.
+    let check_errs = TyCheck::new(&ast, &mut symtab)
+        .with_infer_errs(&infer_errs)
+        .check();
+    let found: Vec<(usize, usize, &str)> = check_errs
+        .iter()
+        .map(|e| (e.line, e.pos, e.code()))
+        .collect();
+    assert_eq!(found, vec![(2, 13, "K0310")]);
+}
+
+#[test]
+fn infer_reports_both_bad_operands_once() {
+    let file = File::open("./tests/infer/both_operands.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    // Both operands of '==' are bools, and both are matched at the operator.
+    let infer_errs = TyInfer::new().infer(&mut ast).unwrap_err();
+    assert_eq!(infer_errs.len(), 1);
+    assert_eq!(
+        (infer_errs[0].line, infer_errs[0].pos, infer_errs[0].code()),
+        (5, 26, "K0301")
+    );
+
+    let check_errs = TyCheck::new(&ast, &mut symtab)
+        .with_infer_errs(&infer_errs)
+        .check();
+    assert!(check_errs.is_empty());
+}
@@ -0,0 +1,53 @@
+use kolgac::{ast::Ast, lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_types::{check::TyCheck, infer::TyInfer, ret::UnreachableCheck};
+
+use std::fs::File;
+
+fn parse(path: &str, symtab: &mut SymbolTable) -> Ast {
+    let file = File::open(path).unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut ast = Parser::new(&mut lexer, symtab).parse().ast.unwrap();
+    TyInfer::new().infer(&mut ast).unwrap();
+    ast
+}
+
+#[test]
+fn missing_ret() {
+    let mut symtab = SymbolTable::new();
+    let ast = parse("./tests/ret/missing.kol", &mut symtab);
+
+    let errs = TyCheck::new(&ast, &mut symtab).check();
+    let found: Vec<(usize, usize, &str)> = errs.iter().map(|e| (e.line, e.pos, e.code())).collect();
+
+    // Each error points at the branch that falls through: the if without an else,
+    // the elif block, the nested else block, and the function name when the only
+    // return is in a loop. 'none' has no return at all. Loops that never end don't
+    // fall through, even without a return in them, whether they're while or for
+    // loops.
+    let expected = vec![
+        (2, 2, "K0309"),
+        (10, 16, "K0309"),
+        (21, 11, "K0309"),
+        (38, 3, "K0309"),
+        (44, 3, "K0305"),
+        (72, 3, "K0309"),
+    ];
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn unreachable() {
+    let mut symtab = SymbolTable::new();
+    let ast = parse("./tests/ret/unreachable.kol", &mut symtab);
+    assert!(TyCheck::new(&ast, &mut symtab).check().is_empty());
+
+    let warnings = UnreachableCheck::new(&ast).check();
+    let found: Vec<(usize, usize, &str)> =
+        warnings.iter().map(|w| (w.line, w.pos, w.code())).collect();
+
+    // Only the first unreachable statement in each block is reported.
+    assert_eq!(
+        found,
+        vec![(7, 6, "K0505"), (12, 6, "K0505"), (20, 2, "K0505")]
+    );
+}
@@ -0,0 +1,43 @@
+use kolgac::{lexer::Lexer, parser::Parser, symtab::SymbolTable};
+use kolgac_errors::warn::{LintLevel, LintLevels};
+use kolgac_types::{check::TyCheck, infer::TyInfer, unused::UnusedCheck};
+
+use std::fs::File;
+
+#[test]
+fn unused() {
+    let file = File::open("./tests/unused/unused.kol").unwrap();
+    let mut lexer = Lexer::new(file);
+    let mut symtab = SymbolTable::new();
+    let mut ast = Parser::new(&mut lexer, &mut symtab).parse().ast.unwrap();
+
+    TyInfer::new().infer(&mut ast).unwrap();
+    assert!(TyCheck::new(&ast, &mut symtab).check().is_empty());
+
+    let warnings = UnusedCheck::new(&ast, &symtab).check();
+    let found: Vec<(usize, usize, &str)> =
+        warnings.iter().map(|w| (w.line, w.pos, w.code())).collect();
+
+    // 'b' is only assigned to, 'y' is never read, 'unused' only calls itself,
+    // and 'py' is set by the constructor but never read. The globals read inside
+    // 'globals' are used, and the method 'never' only calls itself.
+    let expected = vec![
+        (2, 4, "K0501"),
+        (5, 15, "K0502"),
+        (9, 3, "K0503"),
+        (15, 6, "K0504"),
+        (28, 4, "K0501"),
+        (31, 6, "K0501"),
+        (56, 5, "K0506"),
+    ];
+    assert_eq!(found, expected);
+
+    let mut levels = LintLevels::new();
+    levels.set("unused", LintLevel::Allow).unwrap();
+    levels.set("unused-fns", LintLevel::Deny).unwrap();
+    let applied = levels.apply(warnings);
+
+    assert_eq!(applied.len(), 2);
+    assert!(applied.iter().all(|w| w.is_denied()));
+    assert!(levels.set("unused-things", LintLevel::Warn).is_err());
+}
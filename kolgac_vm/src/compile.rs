@@ -0,0 +1,628 @@
+use crate::op::Op;
+use crate::program::{Class, Const, Function, Program, MAIN_FN};
+
+use kolgac::{
+    ast::Ast,
+    scope::{self, ClassScope, Globals, Loop, Scopes},
+    token::{TknTy, Token},
+    ty_rec::TyRecord,
+};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Compiles a type checked program to bytecode. The top level code becomes a
+/// function of its own, which runs the program.
+///
+/// Like KIR, every variable declared in the top level code is a global, even
+/// inside a block. Each variable declared in a function gets its own local.
+///
+/// Functions can be called before they're declared, so calls refer to a slot
+/// in the function table that's filled in once we reach the declaration.
+pub fn compile(ast: &Ast) -> Program {
+    let mut compiler = Compiler {
+        consts: Vec::new(),
+        globals: Globals::new(),
+        classes: Vec::new(),
+        prop_pos: HashMap::new(),
+        fns: Vec::new(),
+        fn_idxs: HashMap::new(),
+        curr: FnCtx::new(true),
+        class: None,
+    };
+    let main = compiler.fn_idx(MAIN_FN);
+
+    match ast {
+        Ast::Prog { meta: _, stmts } => {
+            for stmt in stmts {
+                compiler.stmt(stmt);
+            }
+        }
+        _ => panic!("invalid ast provided to bytecode compiler"),
+    }
+
+    compiler.close_fn(MAIN_FN, main, 0);
+    compiler.finish(main)
+}
+
+/// The function we're compiling. Function declarations are compiled
+/// separately from the code around them, so this is saved and restored around
+/// each one.
+struct FnCtx {
+    code: Vec<u8>,
+
+    /// Local of each variable declared in the function.
+    scopes: Scopes<usize>,
+
+    /// Number of locals declared so far.
+    locals: usize,
+
+    /// True for the top level code.
+    is_init: bool,
+}
+
+impl FnCtx {
+    fn new(is_init: bool) -> FnCtx {
+        FnCtx {
+            code: Vec::new(),
+            scopes: Scopes::new(),
+            locals: 0,
+            is_init,
+        }
+    }
+}
+
+struct Compiler {
+    consts: Vec<Const>,
+    globals: Globals<()>,
+    classes: Vec<Class>,
+
+    /// Property positions for each class declared so far.
+    prop_pos: HashMap<String, HashMap<String, usize>>,
+
+    /// Every function we've seen a call to or a declaration of. Functions that
+    /// have only been called so far are None.
+    fns: Vec<Option<Function>>,
+    fn_idxs: HashMap<String, usize>,
+
+    curr: FnCtx,
+
+    /// The class we're compiling methods for.
+    class: Option<ClassScope>,
+}
+
+impl Compiler {
+    fn stmt(&mut self, node: &Ast) {
+        match node {
+            Ast::BlckStmt { .. } => self.scoped(node),
+
+            Ast::VarDeclExpr { ident_tkn, .. } => {
+                // The variable has no value until it's assigned, so there's no
+                // code to generate.
+                let name = ident_tkn.get_name();
+                match self.curr.is_init {
+                    true => {
+                        self.global(&name);
+                    }
+                    false => {
+                        self.declare(&name);
+                    }
+                }
+            }
+
+            Ast::IfStmt {
+                meta: _,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => {
+                let mut ends = Vec::new();
+                let mut next = self.cond_jmp(cond_expr);
+                self.scoped(if_stmts);
+                ends.push(self.jmp(Op::Jmp));
+
+                for elif in elif_exprs {
+                    if let Ast::ElifStmt {
+                        meta: _,
+                        cond_expr,
+                        stmts,
+                    } = elif
+                    {
+                        self.patch(next);
+                        next = self.cond_jmp(cond_expr);
+                        self.scoped(stmts);
+                        ends.push(self.jmp(Op::Jmp));
+                    }
+                }
+
+                self.patch(next);
+                for stmt in el_stmts {
+                    self.scoped(stmt);
+                }
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+
+            Ast::WhileStmt { .. } | Ast::ForStmt { .. } => self.loop_stmt(&Loop::of(node)),
+
+            Ast::RetStmt { meta: _, ret_expr } => {
+                match ret_expr {
+                    Some(expr) if !self.curr.is_init => self.expr(expr),
+                    _ => self.emit(Op::Void),
+                }
+                self.emit(Op::Ret);
+            }
+
+            Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                fn_body,
+                ..
+            } => self.fn_decl(&ident_tkn.get_name(), fn_params, fn_body, false),
+
+            Ast::ClassDeclStmt {
+                ident_tkn,
+                methods,
+                props,
+                prop_pos,
+                ..
+            } => self.class_decl(ident_tkn, methods, props, prop_pos),
+
+            Ast::Error { .. } => (),
+
+            _ => {
+                self.expr(node);
+                self.emit(Op::Pop);
+            }
+        }
+    }
+
+    /// Compiles an expression, which leaves its value on the stack.
+    fn expr(&mut self, node: &Ast) {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr(expr),
+
+            Ast::VarAssignExpr {
+                meta,
+                ident_tkn,
+                value,
+                ..
+            } => {
+                if let Some(idx) = self.self_prop(ident_tkn) {
+                    self.emit_arg(Op::GetLocal, 0);
+                    self.expr(value);
+                    self.emit_arg(Op::SetProp, idx);
+                    return;
+                }
+
+                // The value is compiled first, since it can refer to a variable
+                // with the same name that this declaration hides.
+                self.expr(value);
+                let name = ident_tkn.get_name();
+                if scope::is_decl(meta, ident_tkn) && !self.curr.is_init {
+                    self.declare(&name);
+                }
+                self.set_var(&name);
+            }
+
+            Ast::BinaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            }
+            | Ast::LogicalExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            } => self.bin_op(op_tkn, lhs, rhs),
+
+            Ast::UnaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                rhs,
+            } => {
+                self.expr(rhs);
+                match op_tkn.ty {
+                    TknTy::Minus => self.emit(Op::Neg),
+                    TknTy::Bang => self.emit(Op::Not),
+                    _ => panic!("invalid unary operator token"),
+                }
+            }
+
+            Ast::PrimaryExpr {
+                meta: _,
+                ty_rec,
+                is_self,
+            } => self.primary(ty_rec, *is_self),
+
+            Ast::FnCallExpr {
+                meta: _,
+                ty_rec: _,
+                fn_tkn,
+                fn_params,
+            } => {
+                let name = fn_tkn.get_name();
+
+                let method = self
+                    .class
+                    .as_ref()
+                    .filter(|cls| cls.calls_method(&name))
+                    .map(|cls| format!("{}.{}", cls.name, name));
+
+                match method {
+                    Some(method) => {
+                        self.emit_arg(Op::GetLocal, 0);
+                        self.call(&method, fn_params);
+                    }
+                    None => self.call(&name, fn_params),
+                }
+            }
+
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                fn_params,
+                ..
+            } => {
+                self.get_var(&class_tkn.get_name());
+                let method = format!("{}.{}", class_name, fn_tkn.get_name());
+                self.call(&method, fn_params);
+            }
+
+            Ast::ClassConstrExpr {
+                meta: _,
+                ty_rec: _,
+                class_name,
+                props,
+            } => {
+                let class = self.class_idx(class_name);
+                self.emit_arg(Op::New, class);
+
+                let prop_pos = self.prop_pos.get(class_name).cloned().unwrap_or_default();
+                let mut vals: Vec<(usize, &Ast)> = props
+                    .iter()
+                    .filter_map(|(name, val)| prop_pos.get(name).map(|idx| (*idx, val)))
+                    .collect();
+                vals.sort_by_key(|(idx, _)| *idx);
+
+                for (idx, val) in vals {
+                    self.emit(Op::Dup);
+                    self.expr(val);
+                    self.emit_arg(Op::SetProp, idx);
+                    self.emit(Op::Pop);
+                }
+            }
+
+            Ast::ClassPropAccessExpr { ident_tkn, idx, .. } => {
+                self.get_var(&ident_tkn.get_name());
+                self.emit_arg(Op::GetProp, *idx);
+            }
+
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                idx,
+                assign_val,
+                ..
+            } => {
+                self.get_var(&ident_tkn.get_name());
+                self.expr(assign_val);
+                self.emit_arg(Op::SetProp, *idx);
+            }
+
+            _ => panic!("invalid expression provided to bytecode compiler"),
+        }
+    }
+
+    /// Compiles a binary operator. && and || only evaluate their right operand
+    /// if the left one doesn't decide the result.
+    fn bin_op(&mut self, op_tkn: &Token, lhs: &Ast, rhs: &Ast) {
+        self.expr(lhs);
+
+        let op = match op_tkn.ty {
+            TknTy::Plus => Op::Add,
+            TknTy::Minus => Op::Sub,
+            TknTy::Star => Op::Mul,
+            TknTy::Slash => Op::Div,
+            TknTy::Lt => Op::Lt,
+            TknTy::Gt => Op::Gt,
+            TknTy::EqEq => Op::Eq,
+            TknTy::LtEq => Op::Le,
+            TknTy::GtEq => Op::Ge,
+            TknTy::BangEq => Op::Ne,
+            TknTy::AmpAmp | TknTy::And => {
+                self.emit(Op::Dup);
+                let end = self.jmp(Op::JmpFalse);
+                self.emit(Op::Pop);
+                self.expr(rhs);
+                self.patch(end);
+                return;
+            }
+            TknTy::PipePipe | TknTy::Or => {
+                self.emit(Op::Dup);
+                let rhs_start = self.jmp(Op::JmpFalse);
+                let end = self.jmp(Op::Jmp);
+                self.patch(rhs_start);
+                self.emit(Op::Pop);
+                self.expr(rhs);
+                self.patch(end);
+                return;
+            }
+            _ => panic!("invalid operator token"),
+        };
+
+        self.expr(rhs);
+        self.emit(op);
+    }
+
+    fn primary(&mut self, ty_rec: &TyRecord, is_self: bool) {
+        match &ty_rec.tkn.ty {
+            TknTy::Val(v) => self.constant(Const::Num(*v)),
+            TknTy::Str(s) => self.constant(Const::Str(s.to_string())),
+            TknTy::True => self.emit(Op::True),
+            TknTy::False => self.emit(Op::False),
+            TknTy::Ident(i) => {
+                let prop_idx = self
+                    .class
+                    .as_ref()
+                    .filter(|_| is_self)
+                    .and_then(|cls| cls.prop_pos.get(i))
+                    .cloned();
+
+                match prop_idx {
+                    Some(idx) => {
+                        self.emit_arg(Op::GetLocal, 0);
+                        self.emit_arg(Op::GetProp, idx);
+                    }
+                    None => self.get_var(i),
+                }
+            }
+            _ => panic!("invalid primary tkn ty"),
+        }
+    }
+
+    /// Compiles a call. The instance a method is called on has to be pushed
+    /// already.
+    fn call(&mut self, name: &str, args: &[Ast]) {
+        for arg in args {
+            self.expr(arg);
+        }
+
+        let idx = self.fn_idx(name);
+        self.emit_arg(Op::Call, idx);
+    }
+
+    /// Compiles a while or for loop. The condition is checked at the start, and
+    /// the end of the body jumps back to it.
+    fn loop_stmt(&mut self, lp: &Loop) {
+        if let Some(decl) = lp.var_decl {
+            self.stmt(decl);
+        }
+
+        let start = self.curr.code.len();
+        let end = self.cond_jmp(lp.cond_expr);
+        self.scoped(lp.stmts);
+        if let Some(step) = lp.step_expr {
+            self.stmt(step);
+        }
+        self.emit_arg(Op::Jmp, start);
+        self.patch(end);
+    }
+
+    /// Evaluates a condition, and jumps if it's false. Returns the jump, to be
+    /// patched with its target.
+    fn cond_jmp(&mut self, cond_expr: &Ast) -> usize {
+        self.expr(cond_expr);
+        self.jmp(Op::JmpFalse)
+    }
+
+    /// Compiles a function. Arguments are its first locals, after the instance
+    /// for methods.
+    fn fn_decl(&mut self, name: &str, params: &[TyRecord], body: &Ast, is_method: bool) {
+        let idx = self.fn_idx(name);
+        let outer = std::mem::replace(&mut self.curr, FnCtx::new(false));
+
+        if is_method {
+            self.declare("self");
+        }
+        for param in params {
+            self.declare(&param.tkn.get_name());
+        }
+        let arity = self.curr.locals;
+
+        self.scoped(body);
+        self.close_fn(name, idx, arity);
+        self.curr = outer;
+    }
+
+    /// Compiles the methods of a class. Properties don't need any code, since
+    /// they're created when the class is constructed.
+    fn class_decl(
+        &mut self,
+        ident_tkn: &Token,
+        methods: &[Ast],
+        props: &[Ast],
+        prop_pos: &HashMap<String, usize>,
+    ) {
+        let name = ident_tkn.get_name();
+        let class = self.class_idx(&name);
+        let mut prop_names: Vec<(&usize, &String)> =
+            prop_pos.iter().map(|(name, idx)| (idx, name)).collect();
+        prop_names.sort();
+        self.classes[class].props = prop_names
+            .into_iter()
+            .map(|(_, name)| name.clone())
+            .collect();
+        self.prop_pos.insert(name.clone(), prop_pos.clone());
+
+        let class = ClassScope::new(ident_tkn, props, methods, prop_pos);
+        let prev_class = self.class.replace(class);
+
+        for method in methods {
+            if let Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                fn_body,
+                ..
+            } = method
+            {
+                let method_name = format!("{}.{}", name, ident_tkn.get_name());
+                self.fn_decl(&method_name, fn_params, fn_body, true);
+            }
+        }
+
+        self.class = prev_class;
+    }
+
+    /// Compiles the statements of a block, in a scope of their own.
+    fn scoped(&mut self, node: &Ast) {
+        self.curr.scopes.init_sc();
+        for stmt in scope::block_stmts(node) {
+            self.stmt(stmt);
+        }
+        self.curr.scopes.close_sc();
+    }
+
+    /// Ends the current function, returning nothing if control reaches the
+    /// end, and puts it in its slot in the function table.
+    fn close_fn(&mut self, name: &str, idx: usize, arity: usize) {
+        self.emit(Op::Void);
+        self.emit(Op::Ret);
+
+        self.fns[idx] = Some(Function {
+            name: String::from(name),
+            arity,
+            locals: self.curr.locals,
+            code: std::mem::take(&mut self.curr.code),
+        });
+    }
+
+    fn self_prop(&self, ident_tkn: &Token) -> Option<usize> {
+        self.class.as_ref().and_then(|cls| cls.self_prop(ident_tkn))
+    }
+
+    /// Declares a local variable in the innermost scope, and returns it.
+    fn declare(&mut self, name: &str) -> usize {
+        let local = self.curr.locals;
+        self.curr.locals += 1;
+        *self.curr.scopes.declare(name, |_| local)
+    }
+
+    fn local(&self, name: &str) -> Option<usize> {
+        self.curr.scopes.get(name).cloned()
+    }
+
+    fn get_var(&mut self, name: &str) {
+        match self.local(name) {
+            Some(local) => self.emit_arg(Op::GetLocal, local),
+            None => {
+                let global = self.global(name);
+                self.emit_arg(Op::GetGlobal, global);
+            }
+        }
+    }
+
+    fn set_var(&mut self, name: &str) {
+        match self.local(name) {
+            Some(local) => self.emit_arg(Op::SetLocal, local),
+            None => {
+                let global = self.global(name);
+                self.emit_arg(Op::SetGlobal, global);
+            }
+        }
+    }
+
+    fn global(&mut self, name: &str) -> usize {
+        self.globals.declare(name, ())
+    }
+
+    fn fn_idx(&mut self, name: &str) -> usize {
+        let fns = &mut self.fns;
+        *self.fn_idxs.entry(String::from(name)).or_insert_with(|| {
+            fns.push(None);
+            fns.len() - 1
+        })
+    }
+
+    fn class_idx(&mut self, name: &str) -> usize {
+        match self.classes.iter().position(|class| class.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.classes.push(Class {
+                    name: String::from(name),
+                    props: Vec::new(),
+                });
+                self.classes.len() - 1
+            }
+        }
+    }
+
+    /// Pushes a constant, adding it to the pool if it isn't there already.
+    /// Numbers are compared by their bits, so 0 and -0 stay apart.
+    fn constant(&mut self, val: Const) {
+        let idx = self.consts.iter().position(|c| match (c, &val) {
+            (Const::Num(a), Const::Num(b)) => a.to_bits() == b.to_bits(),
+            _ => *c == val,
+        });
+
+        let idx = idx.unwrap_or_else(|| {
+            self.consts.push(val);
+            self.consts.len() - 1
+        });
+        self.emit_arg(Op::Const, idx);
+    }
+
+    fn emit(&mut self, op: Op) {
+        self.curr.code.push(op as u8);
+    }
+
+    /// Adds an instruction with an operand. The format only has room for 65536
+    /// of anything but code offsets.
+    fn emit_arg(&mut self, op: Op, arg: usize) {
+        self.emit(op);
+        match op {
+            Op::Jmp | Op::JmpFalse => {
+                let arg = u32::try_from(arg).expect("function too large for bytecode");
+                self.curr.code.extend_from_slice(&arg.to_le_bytes());
+            }
+            _ => {
+                let arg = u16::try_from(arg)
+                    .unwrap_or_else(|_| panic!("operand of '{}' too large for bytecode", op));
+                self.curr.code.extend_from_slice(&arg.to_le_bytes());
+            }
+        }
+    }
+
+    /// Adds a jump whose target isn't known yet, and returns its offset, to be
+    /// patched once it is.
+    fn jmp(&mut self, op: Op) -> usize {
+        let at = self.curr.code.len();
+        self.emit_arg(op, 0);
+        at
+    }
+
+    /// Points a jump at the end of the code so far.
+    fn patch(&mut self, at: usize) {
+        let target = self.curr.code.len() as u32;
+        self.curr.code[at + 1..at + 5].copy_from_slice(&target.to_le_bytes());
+    }
+
+    fn finish(self, main: usize) -> Program {
+        let fns = self.fns.into_iter().collect::<Option<Vec<Function>>>();
+
+        Program {
+            consts: self.consts,
+            globals: self.globals.iter().map(|(name, _)| name.clone()).collect(),
+            classes: self.classes,
+            fns: fns.expect("call to a function that was never declared"),
+            main,
+        }
+    }
+}
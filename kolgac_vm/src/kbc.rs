@@ -0,0 +1,220 @@
+use crate::program::{Class, Const, Function, Program};
+
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The first bytes of every .kbc file.
+pub const MAGIC: &[u8; 4] = b"KBC\0";
+
+/// Version of the format we write, and the only one we read. This changes
+/// whenever the format or the instruction set does.
+pub const VERSION: u16 = 1;
+
+const CONST_NUM: u8 = 0;
+const CONST_STR: u8 = 1;
+
+/// Serializes a program. The file is laid out as:
+///
+/// ```text
+/// magic    "KBC\0"
+/// version  u16
+/// consts   u32 count, then each as a u8 tag: 0 and an f64, or 1 and a string
+/// globals  u32 count, then each name as a string
+/// classes  u32 count, then each name, and a u32 count of property names
+/// fns      u32 count, then each name, u16 arity, u16 locals, and u32 length
+///          of code followed by the code
+/// main     u32 index of the main function
+/// ```
+///
+/// Numbers are little endian, and strings are a u32 length followed by UTF-8.
+pub fn write(program: &Program) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+
+    write_len(&mut out, program.consts.len());
+    for val in &program.consts {
+        match val {
+            Const::Num(n) => {
+                out.push(CONST_NUM);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Const::Str(s) => {
+                out.push(CONST_STR);
+                write_str(&mut out, s);
+            }
+        }
+    }
+
+    write_len(&mut out, program.globals.len());
+    for name in &program.globals {
+        write_str(&mut out, name);
+    }
+
+    write_len(&mut out, program.classes.len());
+    for class in &program.classes {
+        write_str(&mut out, &class.name);
+        write_len(&mut out, class.props.len());
+        for prop in &class.props {
+            write_str(&mut out, prop);
+        }
+    }
+
+    write_len(&mut out, program.fns.len());
+    for func in &program.fns {
+        write_str(&mut out, &func.name);
+        let locals = u16::try_from(func.locals).expect("too many locals for bytecode");
+        out.extend_from_slice(&(func.arity as u16).to_le_bytes());
+        out.extend_from_slice(&locals.to_le_bytes());
+        write_len(&mut out, func.code.len());
+        out.extend_from_slice(&func.code);
+    }
+
+    write_len(&mut out, program.main);
+    out
+}
+
+/// Deserializes a program, and checks that it's safe to run.
+pub fn read(bytes: &[u8]) -> Result<Program, KbcErr> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(KbcErr::BadMagic);
+    }
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(KbcErr::UnsupportedVersion(version));
+    }
+
+    let mut consts = Vec::new();
+    for _ in 0..reader.u32()? {
+        let val = match reader.u8()? {
+            CONST_NUM => Const::Num(f64::from_le_bytes(reader.array()?)),
+            CONST_STR => Const::Str(reader.str()?),
+            tag => return Err(KbcErr::InvalidConst(tag)),
+        };
+        consts.push(val);
+    }
+
+    let mut globals = Vec::new();
+    for _ in 0..reader.u32()? {
+        globals.push(reader.str()?);
+    }
+
+    let mut classes = Vec::new();
+    for _ in 0..reader.u32()? {
+        let name = reader.str()?;
+        let mut props = Vec::new();
+        for _ in 0..reader.u32()? {
+            props.push(reader.str()?);
+        }
+        classes.push(Class { name, props });
+    }
+
+    let mut fns = Vec::new();
+    for _ in 0..reader.u32()? {
+        let name = reader.str()?;
+        let arity = reader.u16()? as usize;
+        let locals = reader.u16()? as usize;
+        let len = reader.u32()? as usize;
+        let code = reader.take(len)?.to_vec();
+        fns.push(Function {
+            name,
+            arity,
+            locals,
+            code,
+        });
+    }
+
+    let main = reader.u32()? as usize;
+    if reader.pos != bytes.len() {
+        return Err(KbcErr::TrailingBytes);
+    }
+
+    let program = Program {
+        consts,
+        globals,
+        classes,
+        fns,
+        main,
+    };
+    program.check().map_err(KbcErr::InvalidCode)?;
+    Ok(program)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KbcErr {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd,
+    TrailingBytes,
+    InvalidConst(u8),
+    InvalidUtf8,
+    InvalidCode(String),
+}
+
+impl fmt::Display for KbcErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KbcErr::BadMagic => write!(f, "not a kolga bytecode file"),
+            KbcErr::UnsupportedVersion(v) => write!(
+                f,
+                "bytecode version {} isn't supported, expected {}",
+                v, VERSION
+            ),
+            KbcErr::UnexpectedEnd => write!(f, "file ends unexpectedly"),
+            KbcErr::TrailingBytes => write!(f, "unexpected data after the end of the program"),
+            KbcErr::InvalidConst(tag) => write!(f, "invalid constant tag {}", tag),
+            KbcErr::InvalidUtf8 => write!(f, "string isn't valid UTF-8"),
+            KbcErr::InvalidCode(msg) => write!(f, "invalid code: {}", msg),
+        }
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).expect("table too large for bytecode");
+    out.extend_from_slice(&len.to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, len: usize) -> Result<&'b [u8], KbcErr> {
+        let end = self.pos.checked_add(len).ok_or(KbcErr::UnexpectedEnd)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(KbcErr::UnexpectedEnd)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], KbcErr> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, KbcErr> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, KbcErr> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, KbcErr> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn str(&mut self) -> Result<String, KbcErr> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| KbcErr::InvalidUtf8)
+    }
+}
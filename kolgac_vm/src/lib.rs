@@ -0,0 +1,7 @@
+extern crate kolgac;
+
+pub mod compile;
+pub mod kbc;
+pub mod op;
+pub mod program;
+pub mod vm;
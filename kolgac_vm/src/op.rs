@@ -0,0 +1,199 @@
+use std::fmt;
+
+/// A bytecode instruction. Each is encoded as a single byte, followed by its
+/// operand, if it has one. Operands are little endian.
+///
+/// Instructions work on a stack of values. Binary operators pop their right
+/// operand, then their left one, and push the result. Instructions that store
+/// a value leave it on the stack, since assignments are expressions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    /// Pushes a constant, by its index in the constant pool.
+    Const = 0,
+    True = 1,
+    False = 2,
+
+    /// Pushes the value of a function that returns nothing.
+    Void = 3,
+
+    Pop = 4,
+    Dup = 5,
+
+    GetGlobal = 6,
+    SetGlobal = 7,
+
+    /// Locals are numbered from the start of the call's frame. Arguments are
+    /// the first locals.
+    GetLocal = 8,
+    SetLocal = 9,
+
+    /// Pops an instance, and pushes one of its properties, by position.
+    GetProp = 10,
+
+    /// Pops a value, then an instance, and sets one of the instance's
+    /// properties to the value.
+    SetProp = 11,
+
+    /// Pushes a new instance of a class, by its index in the program, with
+    /// every property unset.
+    New = 12,
+
+    Add = 13,
+    Sub = 14,
+    Mul = 15,
+    Div = 16,
+    Neg = 17,
+    Not = 18,
+    Eq = 19,
+    Ne = 20,
+    Lt = 21,
+    Gt = 22,
+    Le = 23,
+    Ge = 24,
+
+    /// Jumps to an offset in the function's code.
+    Jmp = 25,
+
+    /// Pops a bool, and jumps if it's false.
+    JmpFalse = 26,
+
+    /// Calls a function, by its index in the program. Its arguments are on top
+    /// of the stack, first argument deepest.
+    Call = 27,
+
+    /// Pops the return value, drops the frame, and pushes the value for the
+    /// caller.
+    Ret = 28,
+}
+
+/// What an instruction's operand refers to, which decides its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    Const,
+    Global,
+    Local,
+    Prop,
+    Class,
+    Fn,
+
+    /// An offset in the function's code.
+    Target,
+}
+
+impl Operand {
+    /// Number of bytes the operand takes up after the opcode.
+    pub fn size(self) -> usize {
+        match self {
+            Operand::None => 0,
+            Operand::Target => 4,
+            _ => 2,
+        }
+    }
+}
+
+const OPS: [Op; 29] = [
+    Op::Const,
+    Op::True,
+    Op::False,
+    Op::Void,
+    Op::Pop,
+    Op::Dup,
+    Op::GetGlobal,
+    Op::SetGlobal,
+    Op::GetLocal,
+    Op::SetLocal,
+    Op::GetProp,
+    Op::SetProp,
+    Op::New,
+    Op::Add,
+    Op::Sub,
+    Op::Mul,
+    Op::Div,
+    Op::Neg,
+    Op::Not,
+    Op::Eq,
+    Op::Ne,
+    Op::Lt,
+    Op::Gt,
+    Op::Le,
+    Op::Ge,
+    Op::Jmp,
+    Op::JmpFalse,
+    Op::Call,
+    Op::Ret,
+];
+
+impl Op {
+    pub fn from_byte(byte: u8) -> Option<Op> {
+        OPS.get(byte as usize).cloned()
+    }
+
+    pub fn operand(self) -> Operand {
+        match self {
+            Op::Const => Operand::Const,
+            Op::GetGlobal | Op::SetGlobal => Operand::Global,
+            Op::GetLocal | Op::SetLocal => Operand::Local,
+            Op::GetProp | Op::SetProp => Operand::Prop,
+            Op::New => Operand::Class,
+            Op::Call => Operand::Fn,
+            Op::Jmp | Op::JmpFalse => Operand::Target,
+            _ => Operand::None,
+        }
+    }
+
+    /// Number of bytes the instruction takes up, with its operand.
+    pub fn size(self) -> usize {
+        1 + self.operand().size()
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Op::Const => "const",
+            Op::True => "true",
+            Op::False => "false",
+            Op::Void => "void",
+            Op::Pop => "pop",
+            Op::Dup => "dup",
+            Op::GetGlobal => "getg",
+            Op::SetGlobal => "setg",
+            Op::GetLocal => "getl",
+            Op::SetLocal => "setl",
+            Op::GetProp => "getp",
+            Op::SetProp => "setp",
+            Op::New => "new",
+            Op::Add => "add",
+            Op::Sub => "sub",
+            Op::Mul => "mul",
+            Op::Div => "div",
+            Op::Neg => "neg",
+            Op::Not => "not",
+            Op::Eq => "eq",
+            Op::Ne => "ne",
+            Op::Lt => "lt",
+            Op::Gt => "gt",
+            Op::Le => "le",
+            Op::Ge => "ge",
+            Op::Jmp => "jmp",
+            Op::JmpFalse => "jmpf",
+            Op::Call => "call",
+            Op::Ret => "ret",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Reads the operand of an instruction at an offset, or 0 if it doesn't have
+/// one. The operand has to be there.
+pub fn read_operand(code: &[u8], offset: usize, op: Op) -> usize {
+    let bytes = &code[offset + 1..offset + op.size()];
+    match op.operand() {
+        Operand::None => 0,
+        Operand::Target => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+        _ => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+    }
+}
@@ -0,0 +1,192 @@
+use crate::op::{self, Op, Operand};
+
+use std::fmt;
+
+/// Name of the function that runs the top level code. '@' isn't allowed in
+/// identifiers, so it can't clash with a function in the program.
+pub const MAIN_FN: &str = "@main";
+
+/// A compiled program. Everything the code refers to is by index: constants,
+/// globals, classes and functions each have a table of their own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub consts: Vec<Const>,
+
+    /// Names of the globals, which the VM uses to report their values.
+    pub globals: Vec<String>,
+
+    pub classes: Vec<Class>,
+    pub fns: Vec<Function>,
+
+    /// Index of the function that runs the top level code.
+    pub main: usize,
+}
+
+/// A constant in the pool. Bools have instructions of their own.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Const {
+    Num(f64),
+    Str(String),
+}
+
+/// A class, with the names of its properties in declaration order. Methods are
+/// functions, named after the class, like 'point.len'.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Class {
+    pub name: String,
+    pub props: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+
+    /// Number of arguments, including the instance for methods.
+    pub arity: usize,
+
+    /// Number of locals, including the arguments.
+    pub locals: usize,
+
+    pub code: Vec<u8>,
+}
+
+impl Program {
+    /// Checks that the code can be run safely: every instruction is valid, its
+    /// operand refers to something that exists, every jump lands on an
+    /// instruction, and no function can run past the end of its code. The
+    /// compiler's output always passes, but a file we load might not.
+    pub fn check(&self) -> Result<(), String> {
+        if self.main >= self.fns.len() {
+            return Err(format!("main function {} doesn't exist", self.main));
+        }
+
+        for func in &self.fns {
+            self.check_fn(func)
+                .map_err(|msg| format!("in '{}': {}", func.name, msg))?;
+        }
+
+        Ok(())
+    }
+
+    fn check_fn(&self, func: &Function) -> Result<(), String> {
+        if func.arity > func.locals {
+            return Err(format!(
+                "{} arguments don't fit in {} locals",
+                func.arity, func.locals
+            ));
+        }
+
+        let mut starts = Vec::new();
+        let mut offset = 0;
+        while offset < func.code.len() {
+            let op = match Op::from_byte(func.code[offset]) {
+                Some(op) => op,
+                None => {
+                    return Err(format!(
+                        "invalid opcode {} at {}",
+                        func.code[offset], offset
+                    ))
+                }
+            };
+            if offset + op.size() > func.code.len() {
+                return Err(format!("'{}' at {} is missing its operand", op, offset));
+            }
+            starts.push(offset);
+            offset += op.size();
+        }
+
+        match starts
+            .last()
+            .map(|last| Op::from_byte(func.code[*last]).unwrap())
+        {
+            Some(Op::Ret) | Some(Op::Jmp) => (),
+            _ => return Err(String::from("code doesn't end with 'ret' or 'jmp'")),
+        }
+
+        for start in starts.iter().cloned() {
+            let op = Op::from_byte(func.code[start]).unwrap();
+            let operand = op::read_operand(&func.code, start, op);
+            let in_range = match op.operand() {
+                Operand::None | Operand::Prop => true,
+                Operand::Const => operand < self.consts.len(),
+                Operand::Global => operand < self.globals.len(),
+                Operand::Local => operand < func.locals,
+                Operand::Class => operand < self.classes.len(),
+                Operand::Fn => operand < self.fns.len(),
+                Operand::Target => starts.binary_search(&operand).is_ok(),
+            };
+
+            if !in_range {
+                return Err(format!("'{} {}' at {} is out of range", op, operand, start));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the tables, then the code of each function, one instruction per
+/// line, with its offset. Operands that refer to something by index are
+/// followed by what they refer to.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, val) in self.consts.iter().enumerate() {
+            writeln!(f, "const {} = {}", idx, val)?;
+        }
+        for (idx, name) in self.globals.iter().enumerate() {
+            writeln!(f, "global {} = {}", idx, name)?;
+        }
+        for (idx, class) in self.classes.iter().enumerate() {
+            writeln!(
+                f,
+                "class {} = {} {{ {} }}",
+                idx,
+                class.name,
+                class.props.join(", ")
+            )?;
+        }
+
+        for (idx, func) in self.fns.iter().enumerate() {
+            writeln!(
+                f,
+                "\nfn {} = {} (args {}, locals {})",
+                idx, func.name, func.arity, func.locals
+            )?;
+
+            let mut offset = 0;
+            while offset < func.code.len() {
+                let op = Op::from_byte(func.code[offset]).unwrap();
+                let operand = op::read_operand(&func.code, offset, op);
+                let note = match op.operand() {
+                    Operand::None => {
+                        writeln!(f, "{:5}  {}", offset, op)?;
+                        offset += op.size();
+                        continue;
+                    }
+                    Operand::Const => format!("{}", self.consts[operand]),
+                    Operand::Global => self.globals[operand].clone(),
+                    Operand::Class => self.classes[operand].name.clone(),
+                    Operand::Fn => self.fns[operand].name.clone(),
+                    Operand::Local | Operand::Prop | Operand::Target => String::new(),
+                };
+
+                match note.is_empty() {
+                    true => writeln!(f, "{:5}  {} {}", offset, op, operand)?,
+                    false => writeln!(f, "{:5}  {} {} ; {}", offset, op, operand, note)?,
+                }
+                offset += op.size();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Const::Num(n) => write!(f, "{}", n),
+            Const::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
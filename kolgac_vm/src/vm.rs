@@ -0,0 +1,305 @@
+use crate::op::{self, Op};
+use crate::program::{Const, Program};
+
+use std::fmt;
+use std::rc::Rc;
+
+/// Most calls that can be waiting on `frames` at once. Each one also keeps its
+/// locals on the value stack, so this bounds both. The same as the KIR
+/// interpreter's limit, so a program overflows the same way on each.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// A runtime value. Class instances live on the VM's heap, and are referred to
+/// by their index there.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Bool(bool),
+    Str(Rc<str>),
+    Obj(usize),
+
+    /// The result of a function that returns nothing, or a variable or
+    /// property that hasn't been set.
+    Void,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VmErr {
+    InvalidOperands(Op),
+    InvalidProp(usize),
+    StackOverflow,
+    StackUnderflow,
+}
+
+impl fmt::Display for VmErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmErr::InvalidOperands(op) => write!(f, "invalid operands for '{}'", op),
+            VmErr::InvalidProp(idx) => write!(f, "instance has no property {}", idx),
+            VmErr::StackOverflow => write!(f, "call stack overflow"),
+            VmErr::StackUnderflow => write!(f, "value stack underflow"),
+        }
+    }
+}
+
+/// A class instance: the index of its class, and its property values in
+/// declaration order.
+#[derive(Debug)]
+struct Object {
+    class: usize,
+    props: Vec<Value>,
+}
+
+/// State of a single call. The call's locals are on the value stack, starting
+/// at `base`, with its temporaries above them.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    func: usize,
+    ip: usize,
+    base: usize,
+}
+
+/// Runs bytecode with a dispatch loop. Execution starts at the program's main
+/// function, which calls into everything else. Globals keep their values after
+/// the program ends, so they can be inspected as its result.
+pub struct Vm<'p> {
+    program: &'p Program,
+
+    /// The constant pool, with strings ready to be pushed without copying.
+    consts: Vec<Value>,
+
+    stack: Vec<Value>,
+
+    /// Frames of the calls the current one was made from. The current frame is
+    /// kept out of here while running.
+    frames: Vec<Frame>,
+
+    heap: Vec<Object>,
+    globals: Vec<Value>,
+}
+
+impl<'p> Vm<'p> {
+    /// Creates a VM for a program, which has to pass `Program::check`. The
+    /// compiler's output always does, and so does anything `kbc::read` returns.
+    pub fn new(program: &'p Program) -> Vm<'p> {
+        debug_assert_eq!(program.check(), Ok(()));
+
+        Vm {
+            program,
+            consts: program
+                .consts
+                .iter()
+                .map(|c| match c {
+                    Const::Num(n) => Value::Num(*n),
+                    Const::Str(s) => Value::Str(Rc::from(s.as_str())),
+                })
+                .collect(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            heap: Vec::new(),
+            globals: vec![Value::Void; program.globals.len()],
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), VmErr> {
+        let program = self.program;
+        let main = &program.fns[program.main];
+        self.stack.resize(main.locals, Value::Void);
+        let mut frame = Frame {
+            func: program.main,
+            ip: 0,
+            base: 0,
+        };
+        let mut code = &main.code[..];
+
+        loop {
+            let op = Op::from_byte(code[frame.ip]).unwrap();
+            let arg = op::read_operand(code, frame.ip, op);
+            frame.ip += op.size();
+
+            match op {
+                Op::Const => self.stack.push(self.consts[arg].clone()),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Void => self.stack.push(Value::Void),
+                Op::Pop => {
+                    self.pop()?;
+                }
+                Op::Dup => {
+                    let top = self.peek()?.clone();
+                    self.stack.push(top);
+                }
+
+                Op::GetGlobal => self.stack.push(self.globals[arg].clone()),
+                Op::SetGlobal => self.globals[arg] = self.peek()?.clone(),
+                Op::GetLocal => {
+                    let val = self.stack.get(frame.base + arg).cloned();
+                    self.stack.push(val.ok_or(VmErr::StackUnderflow)?);
+                }
+                Op::SetLocal => {
+                    let val = self.peek()?.clone();
+                    match self.stack.get_mut(frame.base + arg) {
+                        Some(local) => *local = val,
+                        None => return Err(VmErr::StackUnderflow),
+                    }
+                }
+
+                Op::GetProp => {
+                    let obj = self.pop()?;
+                    let val = self.prop(op, &obj, arg)?.clone();
+                    self.stack.push(val);
+                }
+                Op::SetProp => {
+                    let val = self.pop()?;
+                    let obj = self.pop()?;
+                    *self.prop(op, &obj, arg)? = val.clone();
+                    self.stack.push(val);
+                }
+                Op::New => {
+                    self.heap.push(Object {
+                        class: arg,
+                        props: vec![Value::Void; program.classes[arg].props.len()],
+                    });
+                    self.stack.push(Value::Obj(self.heap.len() - 1));
+                }
+
+                Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                    let (lhs, rhs) = self.nums(op)?;
+                    self.stack.push(Value::Num(match op {
+                        Op::Add => lhs + rhs,
+                        Op::Sub => lhs - rhs,
+                        Op::Mul => lhs * rhs,
+                        _ => lhs / rhs,
+                    }));
+                }
+                Op::Lt | Op::Gt | Op::Le | Op::Ge => {
+                    let (lhs, rhs) = self.nums(op)?;
+                    self.stack.push(Value::Bool(match op {
+                        Op::Lt => lhs < rhs,
+                        Op::Gt => lhs > rhs,
+                        Op::Le => lhs <= rhs,
+                        _ => lhs >= rhs,
+                    }));
+                }
+                Op::Eq | Op::Ne => {
+                    let rhs = self.pop()?;
+                    let lhs = self.pop()?;
+                    let eq = match (&lhs, &rhs) {
+                        (Value::Num(l), Value::Num(r)) => l == r,
+                        (Value::Bool(l), Value::Bool(r)) => l == r,
+                        _ => return Err(VmErr::InvalidOperands(op)),
+                    };
+                    self.stack.push(Value::Bool(eq == (op == Op::Eq)));
+                }
+                Op::Neg => match self.pop()? {
+                    Value::Num(n) => self.stack.push(Value::Num(-n)),
+                    _ => return Err(VmErr::InvalidOperands(op)),
+                },
+                Op::Not => match self.pop()? {
+                    Value::Bool(b) => self.stack.push(Value::Bool(!b)),
+                    _ => return Err(VmErr::InvalidOperands(op)),
+                },
+
+                Op::Jmp => frame.ip = arg,
+                Op::JmpFalse => match self.pop()? {
+                    Value::Bool(true) => (),
+                    Value::Bool(false) => frame.ip = arg,
+                    _ => return Err(VmErr::InvalidOperands(op)),
+                },
+
+                Op::Call => {
+                    if self.frames.len() >= MAX_CALL_DEPTH {
+                        return Err(VmErr::StackOverflow);
+                    }
+
+                    let callee = &program.fns[arg];
+                    let base = match self.stack.len().checked_sub(callee.arity) {
+                        Some(base) if base >= frame.base => base,
+                        _ => return Err(VmErr::StackUnderflow),
+                    };
+                    self.stack.resize(base + callee.locals, Value::Void);
+
+                    self.frames.push(frame);
+                    frame = Frame {
+                        func: arg,
+                        ip: 0,
+                        base,
+                    };
+                    code = &callee.code[..];
+                }
+                Op::Ret => {
+                    let val = self.pop()?;
+                    self.stack.truncate(frame.base);
+                    frame = match self.frames.pop() {
+                        Some(caller) => caller,
+                        None => return Ok(()),
+                    };
+                    code = &program.fns[frame.func].code[..];
+                    self.stack.push(val);
+                }
+            }
+        }
+    }
+
+    /// Every global that's been set, and its final value, in the order they're
+    /// declared.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.program
+            .globals
+            .iter()
+            .zip(&self.globals)
+            .filter(|(_, val)| **val != Value::Void)
+            .map(|(name, val)| (name.clone(), val.clone()))
+            .collect()
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        let idx = self.program.globals.iter().position(|g| g == name)?;
+        Some(&self.globals[idx]).filter(|val| **val != Value::Void)
+    }
+
+    /// Formats a value for printing, the same way the KIR interpreter does.
+    /// Class instances show their class and the values of their properties.
+    pub fn fmt_val(&self, val: &Value) -> String {
+        match val {
+            Value::Num(n) => format!("{}", n),
+            Value::Bool(b) => format!("{}", b),
+            Value::Str(s) => s.to_string(),
+            Value::Obj(idx) => {
+                let obj = &self.heap[*idx];
+                let props: Vec<String> = obj.props.iter().map(|p| self.fmt_val(p)).collect();
+                let class = &self.program.classes[obj.class].name;
+                format!("{} {{ {} }}", class, props.join(", "))
+            }
+            Value::Void => String::from("void"),
+        }
+    }
+
+    fn pop(&mut self) -> Result<Value, VmErr> {
+        self.stack.pop().ok_or(VmErr::StackUnderflow)
+    }
+
+    fn peek(&self) -> Result<&Value, VmErr> {
+        self.stack.last().ok_or(VmErr::StackUnderflow)
+    }
+
+    /// Pops the operands of an arithmetic or comparison operator.
+    fn nums(&mut self, op: Op) -> Result<(f64, f64), VmErr> {
+        let rhs = self.pop()?;
+        let lhs = self.pop()?;
+        match (lhs, rhs) {
+            (Value::Num(l), Value::Num(r)) => Ok((l, r)),
+            _ => Err(VmErr::InvalidOperands(op)),
+        }
+    }
+
+    fn prop(&mut self, op: Op, obj: &Value, idx: usize) -> Result<&mut Value, VmErr> {
+        match obj {
+            Value::Obj(obj) => self.heap[*obj]
+                .props
+                .get_mut(idx)
+                .ok_or(VmErr::InvalidProp(idx)),
+            _ => Err(VmErr::InvalidOperands(op)),
+        }
+    }
+}
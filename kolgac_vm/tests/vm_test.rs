@@ -0,0 +1,156 @@
+use kolgac_ir::pass::OptLevel;
+use kolgac_testutil as util;
+use kolgac_vm::{
+    compile,
+    kbc::{self, KbcErr},
+    op::Op,
+    program::Program,
+    vm::{Value, Vm, VmErr},
+};
+
+/// Compiles a program, and reads it back from its .kbc file.
+fn load(name: &str) -> Program {
+    let program = compile::compile(&util::build(&util::program(name)));
+    let loaded = kbc::read(&kbc::write(&program)).unwrap();
+    assert_eq!(loaded, program);
+    loaded
+}
+
+/// Runs a program with the VM, and checks that it leaves its globals with the
+/// same values as the KIR interpreter.
+fn run(name: &str) {
+    let ast = util::build(&util::program(name));
+    let mut expected: Vec<(String, String)> = util::interp(&util::kir(&ast, OptLevel::O0))
+        .into_iter()
+        .map(|global| (global.name, global.text))
+        .collect();
+
+    let program = load(name);
+    let mut vm = Vm::new(&program);
+    vm.run().unwrap();
+    let mut globals: Vec<(String, String)> = vm
+        .globals()
+        .iter()
+        .map(|(name, val)| (name.clone(), vm.fmt_val(val)))
+        .collect();
+
+    expected.sort();
+    globals.sort();
+    assert_eq!(globals, expected);
+}
+
+#[test]
+fn vm_recursion() {
+    run("fib");
+}
+
+#[test]
+fn vm_loops() {
+    run("loops");
+}
+
+#[test]
+fn vm_classes() {
+    run("classes");
+}
+
+#[test]
+fn vm_many_args() {
+    run("regs");
+}
+
+#[test]
+fn vm_equality() {
+    run("equality");
+}
+
+#[test]
+fn vm_scopes() {
+    run("scopes");
+
+    // Each declaration of a name gets its own local.
+    let program = load("scopes");
+    let sum = program.fns.iter().find(|f| f.name == "sum").unwrap();
+    assert_eq!((sum.arity, sum.locals), (1, 5));
+}
+
+#[test]
+fn vm_eval_order() {
+    run("order");
+}
+
+#[test]
+fn vm_values() {
+    let program = load("loops");
+    let mut vm = Vm::new(&program);
+    vm.run().unwrap();
+
+    assert_eq!(vm.global("done"), Some(&Value::Bool(true)));
+    assert_eq!(vm.global("neg"), Some(&Value::Num(-10.0)));
+    assert_eq!(vm.global("name"), Some(&Value::Str("kolga".into())));
+    assert_eq!(vm.global("missing"), None);
+}
+
+#[test]
+fn vm_stack_overflow() {
+    let program = load("overflow");
+    let mut vm = Vm::new(&program);
+    assert_eq!(vm.run(), Err(VmErr::StackOverflow));
+}
+
+#[test]
+fn vm_disassembly() {
+    let program = load("fib");
+    let text = format!("{}", program);
+    assert!(text.contains("\nfn 1 = fib (args 1, locals 1)\n"));
+    assert!(text.contains("  getl 0\n"));
+    assert!(text.contains("  call 1 ; fib\n"));
+    assert!(text.contains("  setg 0 ; f10\n"));
+}
+
+#[test]
+fn kbc_rejects_bad_files() {
+    let bytes = kbc::write(&load("fib"));
+
+    assert_eq!(kbc::read(b"ELF\0"), Err(KbcErr::BadMagic));
+    assert_eq!(
+        kbc::read(&bytes[..bytes.len() - 1]),
+        Err(KbcErr::UnexpectedEnd)
+    );
+
+    let mut version = bytes.clone();
+    version[4] = 9;
+    assert_eq!(kbc::read(&version), Err(KbcErr::UnsupportedVersion(9)));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(kbc::read(&trailing), Err(KbcErr::TrailingBytes));
+}
+
+#[test]
+fn kbc_rejects_bad_code() {
+    let program = load("fib");
+
+    let mut bad_op = program.clone();
+    bad_op.fns[0].code[0] = 0xff;
+    assert!(matches!(
+        kbc::read(&kbc::write(&bad_op)),
+        Err(KbcErr::InvalidCode(_))
+    ));
+
+    // A jump into the middle of an instruction.
+    let mut bad_jmp = program.clone();
+    bad_jmp.fns[0].code = vec![Op::Jmp as u8, 1, 0, 0, 0];
+    assert!(matches!(
+        kbc::read(&kbc::write(&bad_jmp)),
+        Err(KbcErr::InvalidCode(_))
+    ));
+
+    // Code that runs off the end.
+    let mut no_ret = program;
+    no_ret.fns[0].code = vec![Op::Void as u8];
+    assert!(matches!(
+        kbc::read(&kbc::write(&no_ret)),
+        Err(KbcErr::InvalidCode(_))
+    ));
+}
@@ -0,0 +1,879 @@
+use kolgac::{
+    ast::Ast,
+    scope::{self, ClassScope, Globals, Loop, Scopes},
+    token::{TknTy, Token},
+    ty_rec::{KolgaTy, TyRecord},
+};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Size of a page of linear memory.
+const PAGE_SIZE: usize = 65536;
+
+/// Where string literals start in linear memory. Address 0 is left alone, so
+/// it can't be mistaken for a real object.
+const DATA_START: usize = 8;
+
+/// Generates a WebAssembly module, in the text format, for a type checked
+/// program. The top level code becomes a function exported as `main`, which
+/// the host calls to run the program. The module doesn't import anything.
+///
+/// Numbers are f64s, and bools are i32s. Strings are i32 addresses of NUL
+/// terminated data in linear memory, which is exported as `memory`. Class
+/// instances are i32 addresses of their properties, which take 8 bytes each,
+/// allocated from a bump allocator when they're constructed. Nothing is ever
+/// freed. Methods take the instance as their first parameter, `$self`.
+///
+/// Functions are named `$kol.fn.NAME`, methods `$kol.CLASS.NAME`, and globals
+/// `$kol.NAME`. Globals are exported with the same names, without the '$',
+/// so the host can read them once the program has run.
+///
+/// Like the C backend, every variable declared in the top level code is a
+/// global, and each declaration in a function gets its own local.
+pub fn gen(ast: &Ast) -> String {
+    let mut watgen = WatGen {
+        classes: HashMap::new(),
+        strs: Vec::new(),
+        data_end: DATA_START,
+        globals: Globals::new(),
+        fns: String::new(),
+        curr: FnCtx::new(),
+        class: None,
+        lbl_count: 0,
+        uses_new: false,
+    };
+
+    match ast {
+        Ast::Prog { meta: _, stmts } => {
+            for stmt in stmts {
+                watgen.stmt(stmt);
+            }
+        }
+        _ => panic!("invalid ast provided to wat gen"),
+    }
+
+    watgen.finish()
+}
+
+/// Name of a global, which is also the name it's exported with, after the '$'.
+pub fn global_sym(name: &str) -> String {
+    format!("$kol.{}", name)
+}
+
+pub fn fn_sym(name: &str) -> String {
+    format!("$kol.fn.{}", name)
+}
+
+pub fn method_sym(class: &str, name: &str) -> String {
+    format!("$kol.{}.{}", class, name)
+}
+
+/// The properties of a class, by name.
+#[derive(Clone, Default)]
+struct ClassInfo {
+    prop_pos: HashMap<String, usize>,
+    prop_tys: HashMap<String, KolgaTy>,
+}
+
+impl ClassInfo {
+    /// Bytes an instance takes up. Every instance gets its own address, even
+    /// without any properties.
+    fn size(&self) -> usize {
+        8 * self.prop_pos.len().max(1)
+    }
+
+    /// The load or store instruction for a property, with its offset.
+    fn access(&self, op: &str, prop: &str) -> String {
+        let ty = val_ty(&self.prop_tys[prop]);
+        format!("{}.{} offset={}", ty, op, 8 * self.prop_pos[prop])
+    }
+}
+
+/// A function whose body is being generated. Wasm declares every local before
+/// the first instruction, so the body is kept apart from its header, and the
+/// params and locals it needs are collected on the way, to be written in front
+/// of it once it's done. The top level code is the body of main.
+struct FnCtx {
+    out: String,
+    indent: usize,
+
+    /// Wasm name of each variable declared in the function.
+    scopes: Scopes<String>,
+
+    params: Vec<String>,
+
+    /// Locals that aren't parameters, with their types. Wasm declares these at
+    /// the start of the function.
+    locals: Vec<(String, &'static str)>,
+
+    /// True for the top level code.
+    is_init: bool,
+}
+
+impl FnCtx {
+    fn new() -> FnCtx {
+        FnCtx {
+            out: String::new(),
+            indent: 2,
+            scopes: Scopes::new(),
+            params: Vec::new(),
+            locals: Vec::new(),
+            is_init: true,
+        }
+    }
+}
+
+/// The class we're generating methods for, and its layout.
+struct ClassCtx {
+    scope: ClassScope,
+    info: ClassInfo,
+}
+
+struct WatGen {
+    classes: HashMap<String, ClassInfo>,
+
+    /// String literals, with their addresses.
+    strs: Vec<(String, usize)>,
+    data_end: usize,
+
+    /// Every global, with its type.
+    globals: Globals<KolgaTy>,
+
+    fns: String,
+
+    curr: FnCtx,
+    class: Option<ClassCtx>,
+
+    /// Counts labels, so each loop gets its own.
+    lbl_count: usize,
+
+    /// True once we need the function that allocates class instances.
+    uses_new: bool,
+}
+
+impl WatGen {
+    fn stmt(&mut self, node: &Ast) {
+        match node {
+            Ast::BlckStmt { .. } => self.scoped(node),
+
+            Ast::VarDeclExpr {
+                ty_rec, ident_tkn, ..
+            } => {
+                let name = ident_tkn.get_name();
+                match self.curr.is_init {
+                    true => self.global(&name, &ty_rec.ty),
+                    false => {
+                        self.declare(&name, &ty_rec.ty);
+                    }
+                }
+            }
+
+            Ast::IfStmt {
+                meta: _,
+                cond_expr,
+                if_stmts,
+                elif_exprs,
+                el_stmts,
+            } => {
+                let mut branches = vec![(cond_expr.as_ref(), if_stmts.as_ref())];
+                for elif in elif_exprs {
+                    if let Ast::ElifStmt {
+                        meta: _,
+                        cond_expr,
+                        stmts,
+                    } = elif
+                    {
+                        branches.push((cond_expr, stmts));
+                    }
+                }
+                self.if_stmt(&branches, el_stmts);
+            }
+
+            Ast::WhileStmt { .. } | Ast::ForStmt { .. } => self.loop_stmt(&Loop::of(node)),
+
+            Ast::RetStmt { meta: _, ret_expr } => match ret_expr {
+                Some(expr) if !self.curr.is_init => {
+                    let val = self.expr(expr);
+                    self.line(&format!("(return {})", val));
+                }
+                _ => self.line("(return)"),
+            },
+
+            Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                ret_ty,
+                fn_body,
+                ..
+            } => {
+                let sym = fn_sym(&ident_tkn.get_name());
+                self.fn_decl(&sym, fn_params, ret_ty, fn_body, false);
+            }
+
+            Ast::ClassDeclStmt {
+                ident_tkn,
+                methods,
+                props,
+                prop_pos,
+                ..
+            } => self.class_decl(ident_tkn, methods, props, prop_pos),
+
+            Ast::Error { .. } => (),
+
+            _ => self.expr_stmt(node),
+        }
+    }
+
+    /// Emits an expression as a statement. Assignments don't leave their value
+    /// on the stack, and any other value is dropped.
+    fn expr_stmt(&mut self, node: &Ast) {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr_stmt(expr),
+
+            Ast::VarAssignExpr {
+                meta,
+                ty_rec,
+                ident_tkn,
+                value,
+                ..
+            } => {
+                let val = self.expr(value);
+                if let Some(prop) = self.self_prop(ident_tkn) {
+                    let store = self.class.as_ref().unwrap().info.access("store", &prop);
+                    self.line(&format!("({} (local.get $self) {})", store, val));
+                    return;
+                }
+
+                let name = ident_tkn.get_name();
+                if scope::is_decl(meta, ident_tkn) {
+                    match self.curr.is_init {
+                        true => self.global(&name, &ty_rec.ty),
+                        false => {
+                            self.declare(&name, &ty_rec.ty);
+                        }
+                    }
+                }
+
+                let set = self.set(&name, &val);
+                self.line(&set);
+            }
+
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                prop_name,
+                owner_class,
+                assign_val,
+                ..
+            } => {
+                let val = self.expr(assign_val);
+                let store = self.class_of(owner_class).access("store", prop_name);
+                let obj = self.get(&ident_tkn.get_name());
+                self.line(&format!("({} {} {})", store, obj, val));
+            }
+
+            _ => {
+                let expr = self.expr(node);
+                match node.expr_ty() {
+                    KolgaTy::Void => self.line(&expr),
+                    _ => self.line(&format!("(drop {})", expr)),
+                }
+            }
+        }
+    }
+
+    /// Generates an expression, as a single folded instruction that leaves its
+    /// value on the stack.
+    fn expr(&mut self, node: &Ast) -> String {
+        match node {
+            Ast::ExprStmt { meta: _, expr } => self.expr(expr),
+
+            Ast::VarAssignExpr {
+                meta,
+                ty_rec,
+                ident_tkn,
+                value,
+                ..
+            } => {
+                let val = self.expr(value);
+                let ty = val_ty(&ty_rec.ty);
+
+                if let Some(prop) = self.self_prop(ident_tkn) {
+                    let info = &self.class.as_ref().unwrap().info;
+                    return format!(
+                        "(block (result {}) ({} (local.get $self) {}) ({} (local.get $self)))",
+                        ty,
+                        info.access("store", &prop),
+                        val,
+                        info.access("load", &prop)
+                    );
+                }
+
+                let name = ident_tkn.get_name();
+                if self.curr.is_init && scope::is_decl(meta, ident_tkn) {
+                    self.global(&name, &ty_rec.ty);
+                }
+
+                match self.local(&name) {
+                    Some(local) => format!("(local.tee {} {})", local, val),
+                    None => {
+                        let sym = global_sym(&name);
+                        format!(
+                            "(block (result {}) (global.set {} {}) (global.get {}))",
+                            ty, sym, val, sym
+                        )
+                    }
+                }
+            }
+
+            Ast::BinaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            } => {
+                let op = match (&op_tkn.ty, lhs.expr_ty()) {
+                    (TknTy::EqEq, KolgaTy::Num) => "f64.eq",
+                    (TknTy::BangEq, KolgaTy::Num) => "f64.ne",
+                    (TknTy::EqEq, _) => "i32.eq",
+                    (TknTy::BangEq, _) => "i32.ne",
+                    (TknTy::Plus, _) => "f64.add",
+                    (TknTy::Minus, _) => "f64.sub",
+                    (TknTy::Star, _) => "f64.mul",
+                    (TknTy::Slash, _) => "f64.div",
+                    (TknTy::Lt, _) => "f64.lt",
+                    (TknTy::Gt, _) => "f64.gt",
+                    (TknTy::LtEq, _) => "f64.le",
+                    (TknTy::GtEq, _) => "f64.ge",
+                    _ => panic!("invalid operator token"),
+                };
+                let (lhs, rhs) = (self.expr(lhs), self.expr(rhs));
+                format!("({} {} {})", op, lhs, rhs)
+            }
+
+            Ast::LogicalExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                lhs,
+                rhs,
+            } => {
+                // The right side is only evaluated if it's needed.
+                let (lhs, rhs) = (self.expr(lhs), self.expr(rhs));
+                match op_tkn.ty {
+                    TknTy::AmpAmp | TknTy::And => format!(
+                        "(if (result i32) {} (then {}) (else (i32.const 0)))",
+                        lhs, rhs
+                    ),
+                    TknTy::PipePipe | TknTy::Or => format!(
+                        "(if (result i32) {} (then (i32.const 1)) (else {}))",
+                        lhs, rhs
+                    ),
+                    _ => panic!("invalid operator token"),
+                }
+            }
+
+            Ast::UnaryExpr {
+                meta: _,
+                ty_rec: _,
+                op_tkn,
+                rhs,
+            } => {
+                let op = match op_tkn.ty {
+                    TknTy::Minus => "f64.neg",
+                    TknTy::Bang => "i32.eqz",
+                    _ => panic!("invalid unary operator token"),
+                };
+                format!("({} {})", op, self.expr(rhs))
+            }
+
+            Ast::PrimaryExpr {
+                meta: _,
+                ty_rec,
+                is_self,
+            } => self.primary(ty_rec, *is_self),
+
+            Ast::FnCallExpr {
+                meta: _,
+                ty_rec: _,
+                fn_tkn,
+                fn_params,
+            } => {
+                let name = fn_tkn.get_name();
+
+                let method = self
+                    .class
+                    .as_ref()
+                    .filter(|cls| cls.scope.calls_method(&name))
+                    .map(|cls| method_sym(&cls.scope.name, &name));
+
+                match method {
+                    Some(method) => {
+                        self.call(&method, Some(String::from("(local.get $self)")), fn_params)
+                    }
+                    None => self.call(&fn_sym(&name), None, fn_params),
+                }
+            }
+
+            Ast::ClassFnCallExpr {
+                class_tkn,
+                class_name,
+                fn_tkn,
+                fn_params,
+                ..
+            } => {
+                let obj = self.get(&class_tkn.get_name());
+                let method = method_sym(class_name, &fn_tkn.get_name());
+                self.call(&method, Some(obj), fn_params)
+            }
+
+            Ast::ClassConstrExpr {
+                meta: _,
+                ty_rec: _,
+                class_name,
+                props,
+            } => {
+                // New instances are zeroed, so we only store the properties
+                // that are given.
+                self.uses_new = true;
+                let info = self.classes.get(class_name).cloned().unwrap_or_default();
+                let mut vals: Vec<(usize, &String, &Ast)> = props
+                    .iter()
+                    .filter_map(|(name, val)| info.prop_pos.get(name).map(|idx| (*idx, name, val)))
+                    .collect();
+                vals.sort_by_key(|(idx, _, _)| *idx);
+
+                let obj = self.temp("new", "i32");
+                let mut instrs = vec![format!(
+                    "(local.set {} (call $kol.new (i32.const {})))",
+                    obj,
+                    info.size()
+                )];
+                for (_, name, val) in vals {
+                    let val = self.expr(val);
+                    let store = info.access("store", name);
+                    instrs.push(format!("({} (local.get {}) {})", store, obj, val));
+                }
+                instrs.push(format!("(local.get {})", obj));
+
+                format!("(block (result i32) {})", instrs.join(" "))
+            }
+
+            Ast::ClassPropAccessExpr {
+                ident_tkn,
+                prop_name,
+                owner_class,
+                ..
+            } => {
+                let load = self.class_of(owner_class).access("load", prop_name);
+                format!("({} {})", load, self.get(&ident_tkn.get_name()))
+            }
+
+            Ast::ClassPropSetExpr {
+                ident_tkn,
+                prop_name,
+                owner_class,
+                assign_val,
+                ..
+            } => {
+                let val = self.expr(assign_val);
+                let info = self.class_of(owner_class);
+                let obj = self.get(&ident_tkn.get_name());
+                format!(
+                    "(block (result {}) ({} {} {}) ({} {}))",
+                    val_ty(&info.prop_tys[prop_name]),
+                    info.access("store", prop_name),
+                    obj,
+                    val,
+                    info.access("load", prop_name),
+                    obj
+                )
+            }
+
+            _ => panic!("invalid expression provided to wat gen"),
+        }
+    }
+
+    fn primary(&mut self, ty_rec: &TyRecord, is_self: bool) -> String {
+        match &ty_rec.tkn.ty {
+            // Debug prints the shortest string that reads back as the same
+            // double, which the text format accepts.
+            TknTy::Val(v) => format!("(f64.const {:?})", v),
+            TknTy::Str(s) => format!("(i32.const {})", self.str_addr(s)),
+            TknTy::True => String::from("(i32.const 1)"),
+            TknTy::False => String::from("(i32.const 0)"),
+            TknTy::Ident(i) => {
+                let prop = self
+                    .class
+                    .as_ref()
+                    .filter(|cls| is_self && cls.info.prop_pos.contains_key(i));
+
+                match prop {
+                    Some(cls) => format!("({} (local.get $self))", cls.info.access("load", i)),
+                    None => self.get(i),
+                }
+            }
+            _ => panic!("invalid primary tkn ty"),
+        }
+    }
+
+    fn call(&mut self, sym: &str, obj: Option<String>, args: &[Ast]) -> String {
+        let mut instr = format!("(call {}", sym);
+        for arg in obj.into_iter().chain(args.iter().map(|arg| self.expr(arg))) {
+            write!(instr, " {}", arg).unwrap();
+        }
+        instr.push(')');
+        instr
+    }
+
+    /// Generates an if statement. Each elif goes in the else branch of the one
+    /// before it.
+    fn if_stmt(&mut self, branches: &[(&Ast, &Ast)], el_stmts: &[Ast]) {
+        let (cond_expr, stmts) = branches[0];
+        let cond = self.expr(cond_expr);
+        self.line(&format!("(if {}", cond));
+        self.curr.indent += 1;
+
+        self.line("(then");
+        self.block_body(stmts);
+        self.line(")");
+
+        if branches.len() > 1 || !el_stmts.is_empty() {
+            self.line("(else");
+            self.curr.indent += 1;
+            match branches.len() > 1 {
+                true => self.if_stmt(&branches[1..], el_stmts),
+                false => {
+                    for stmt in el_stmts {
+                        self.block_body(stmt);
+                    }
+                }
+            }
+            self.curr.indent -= 1;
+            self.line(")");
+        }
+
+        self.curr.indent -= 1;
+        self.line(")");
+    }
+
+    /// Generates a while or for loop. The loop is wrapped in a block, which the
+    /// condition breaks out of when it's false.
+    fn loop_stmt(&mut self, lp: &Loop) {
+        if let Some(decl) = lp.var_decl {
+            self.expr_stmt(decl);
+        }
+
+        let idx = self.lbl_count;
+        self.lbl_count += 1;
+
+        self.line(&format!("(block $brk.{}", idx));
+        self.curr.indent += 1;
+        self.line(&format!("(loop $loop.{}", idx));
+        self.curr.indent += 1;
+
+        let cond = self.expr(lp.cond_expr);
+        self.line(&format!("(br_if $brk.{} (i32.eqz {}))", idx, cond));
+        self.scoped(lp.stmts);
+        if let Some(step) = lp.step_expr {
+            self.expr_stmt(step);
+        }
+        self.line(&format!("(br $loop.{})", idx));
+
+        self.curr.indent -= 1;
+        self.line(")");
+        self.curr.indent -= 1;
+        self.line(")");
+    }
+
+    /// Generates a function. Methods take the instance they're called on as
+    /// `$self`.
+    fn fn_decl(
+        &mut self,
+        sym: &str,
+        params: &[TyRecord],
+        ret_ty: &TyRecord,
+        body: &Ast,
+        is_method: bool,
+    ) {
+        let outer = std::mem::replace(&mut self.curr, FnCtx::new());
+        self.curr.is_init = false;
+        self.curr.indent = 1;
+
+        if is_method {
+            self.curr.scopes.declare("self", |_| String::from("$self"));
+            self.curr.params.push(String::from("(param $self i32)"));
+        }
+        for param in params {
+            let local = self.bind(&param.tkn.get_name());
+            let param = format!("(param {} {})", local, val_ty(&param.ty));
+            self.curr.params.push(param);
+        }
+
+        self.block_body(body);
+
+        let f = std::mem::replace(&mut self.curr, outer);
+        let mut header = format!("(func {}", sym);
+        for param in &f.params {
+            write!(header, " {}", param).unwrap();
+        }
+        if ret_ty.ty != KolgaTy::Void {
+            write!(header, " (result {})", val_ty(&ret_ty.ty)).unwrap();
+        }
+        self.func(&header, &f);
+
+        // Every path through the body returns, but wasm doesn't know that
+        // when the last statement is an if.
+        if ret_ty.ty != KolgaTy::Void {
+            self.fns.push_str("    unreachable\n");
+        }
+        self.fns.push_str("  )\n");
+    }
+
+    /// Writes a function, with its locals, to the output. The closing paren is
+    /// left to the caller.
+    fn func(&mut self, header: &str, f: &FnCtx) {
+        writeln!(self.fns, "  {}", header).unwrap();
+        for (local, ty) in &f.locals {
+            writeln!(self.fns, "    (local {} {})", local, ty).unwrap();
+        }
+        self.fns.push_str(&f.out);
+    }
+
+    /// Records the layout of a class, and generates its methods.
+    fn class_decl(
+        &mut self,
+        ident_tkn: &Token,
+        methods: &[Ast],
+        props: &[Ast],
+        prop_pos: &HashMap<String, usize>,
+    ) {
+        let name = ident_tkn.get_name();
+        let mut info = ClassInfo {
+            prop_pos: prop_pos.clone(),
+            prop_tys: HashMap::new(),
+        };
+
+        for prop in props {
+            if let Ast::VarDeclExpr {
+                ty_rec, ident_tkn, ..
+            } = prop
+            {
+                info.prop_tys
+                    .insert(ident_tkn.get_name(), ty_rec.ty.clone());
+            }
+        }
+        self.classes.insert(name.clone(), info.clone());
+
+        let prev_class = self.class.replace(ClassCtx {
+            scope: ClassScope::new(ident_tkn, props, methods, prop_pos),
+            info,
+        });
+
+        for method in methods {
+            if let Ast::FnDeclStmt {
+                ident_tkn,
+                fn_params,
+                ret_ty,
+                fn_body,
+                ..
+            } = method
+            {
+                let sym = method_sym(&name, &ident_tkn.get_name());
+                self.fn_decl(&sym, fn_params, ret_ty, fn_body, true);
+            }
+        }
+
+        self.class = prev_class;
+    }
+
+    /// Emits the statements of a block one level further in.
+    fn block_body(&mut self, node: &Ast) {
+        self.curr.indent += 1;
+        self.scoped(node);
+        self.curr.indent -= 1;
+    }
+
+    /// Emits the statements of a block, in a scope of their own.
+    fn scoped(&mut self, node: &Ast) {
+        self.curr.scopes.init_sc();
+        for stmt in scope::block_stmts(node) {
+            self.stmt(stmt);
+        }
+        self.curr.scopes.close_sc();
+    }
+
+    /// The class a property belongs to, from the class declaration or the
+    /// construction the property access points at.
+    fn class_of(&self, owner_class: &Ast) -> ClassInfo {
+        let name = match owner_class {
+            Ast::ClassConstrExpr { class_name, .. } => class_name.clone(),
+            Ast::ClassDeclStmt { ident_tkn, .. } => ident_tkn.get_name(),
+            _ => panic!("invalid class for property"),
+        };
+        self.classes[&name].clone()
+    }
+
+    /// The name of the property of self that an assignment assigns to, if it
+    /// does.
+    fn self_prop(&self, ident_tkn: &Token) -> Option<String> {
+        let cls = &self.class.as_ref()?.scope;
+        let idx = cls.self_prop(ident_tkn)?;
+        Some(String::from(cls.prop_name(idx)))
+    }
+
+    /// Declares a local variable in the innermost scope, and returns its name.
+    fn declare(&mut self, name: &str, ty: &KolgaTy) -> String {
+        let local = self.bind(name);
+        self.curr.locals.push((local.clone(), val_ty(ty)));
+        local
+    }
+
+    /// Picks the name of a new local or parameter in the innermost scope. The
+    /// first declaration of a name in a function gets the name itself, later
+    /// ones get a number after it.
+    fn bind(&mut self, name: &str) -> String {
+        let local = self.curr.scopes.declare(name, |n| match n {
+            0 => format!("${}", name),
+            n => format!("${}.{}", name, n),
+        });
+        local.clone()
+    }
+
+    /// Adds a local for a value we need to refer to more than once. Its name
+    /// has two dots, so it can't clash with a variable's.
+    fn temp(&mut self, prefix: &str, ty: &'static str) -> String {
+        let local = format!("$tmp.{}.{}", prefix, self.curr.locals.len());
+        self.curr.locals.push((local.clone(), ty));
+        local
+    }
+
+    fn global(&mut self, name: &str, ty: &KolgaTy) {
+        self.globals.declare(name, ty.clone());
+    }
+
+    /// The innermost local declared with a name, if there is one.
+    fn local(&self, name: &str) -> Option<String> {
+        self.curr.scopes.get(name).cloned()
+    }
+
+    fn get(&self, name: &str) -> String {
+        match self.local(name) {
+            Some(local) => format!("(local.get {})", local),
+            None => format!("(global.get {})", global_sym(name)),
+        }
+    }
+
+    fn set(&self, name: &str, val: &str) -> String {
+        match self.local(name) {
+            Some(local) => format!("(local.set {} {})", local, val),
+            None => format!("(global.set {} {})", global_sym(name), val),
+        }
+    }
+
+    /// The address of a string literal. Each one is only stored once.
+    fn str_addr(&mut self, s: &str) -> usize {
+        if let Some((_, addr)) = self.strs.iter().find(|(lit, _)| lit == s) {
+            return *addr;
+        }
+
+        let addr = self.data_end;
+        self.strs.push((String::from(s), addr));
+        self.data_end += s.len() + 1;
+        addr
+    }
+
+    fn line(&mut self, line: &str) {
+        let indent = "  ".repeat(self.curr.indent);
+        writeln!(self.curr.out, "{}{}", indent, line).unwrap();
+    }
+
+    fn finish(mut self) -> String {
+        // The heap starts after the strings, lined up for f64s.
+        let heap_start = self.data_end.div_ceil(8) * 8;
+        let pages = heap_start.div_ceil(PAGE_SIZE).max(1);
+
+        let mut out = String::from("(module\n");
+        writeln!(out, "  (memory (export \"memory\") {})", pages).unwrap();
+        for (s, addr) in &self.strs {
+            writeln!(out, "  (data (i32.const {}) \"{}\\00\")", addr, escape(s)).unwrap();
+        }
+        writeln!(
+            out,
+            "  (global $kol.heap (mut i32) (i32.const {}))",
+            heap_start
+        )
+        .unwrap();
+
+        for (name, ty) in self.globals.iter() {
+            let sym = global_sym(name);
+            let ty = val_ty(ty);
+            writeln!(
+                out,
+                "  (global {} (export \"{}\") (mut {}) ({}.const 0))",
+                sym,
+                &sym[1..],
+                ty,
+                ty
+            )
+            .unwrap();
+        }
+
+        if self.uses_new {
+            out.push_str(NEW_FN);
+        }
+
+        let init = std::mem::replace(&mut self.curr, FnCtx::new());
+        self.func("(func $main (export \"main\")", &init);
+        self.fns.push_str("  )\n");
+        out.push_str(&self.fns);
+        out.push_str(")\n");
+        out
+    }
+}
+
+/// Allocates memory for a class instance, growing memory when the heap runs
+/// out. Memory starts out zeroed and is never reused, so instances are too.
+const NEW_FN: &str = "  (func $kol.new (param $size i32) (result i32)
+    (local $obj i32)
+    (local.set $obj (global.get $kol.heap))
+    (global.set $kol.heap (i32.add (local.get $obj) (local.get $size)))
+    (if (i32.gt_u (global.get $kol.heap) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1))
+          (then
+            unreachable
+          )
+        )
+      )
+    )
+    (local.get $obj)
+  )
+";
+
+/// The wasm type a value is kept in.
+fn val_ty(ty: &KolgaTy) -> &'static str {
+    match ty {
+        KolgaTy::Num => "f64",
+        KolgaTy::String | KolgaTy::Bool | KolgaTy::Class(_) => "i32",
+        KolgaTy::Void => panic!("void values can't be stored"),
+        KolgaTy::Symbolic(_) => panic!("Found a type in codegen that wasn't inferred!"),
+        KolgaTy::Error => {
+            panic!("Found a type in codegen from a declaration that failed to parse!")
+        }
+    }
+}
+
+/// Escapes a string for the text format.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' => escaped.push_str(&format!("\\{}", byte as char)),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,121 @@
+use kolgac_ir::{interp::Value, pass::OptLevel};
+use kolgac_testutil::{self as util, Global};
+use kolgac_wasm::wat as watgen;
+
+use std::fs;
+use std::process::Command;
+
+/// Assembles the generated text, and checks that the module is valid.
+fn assemble(src: &str) -> Vec<u8> {
+    let bin = wat::parse_str(src).unwrap_or_else(|e| panic!("{}\n{}", e, src));
+    wasmparser::Validator::new()
+        .validate_all(&bin)
+        .unwrap_or_else(|e| panic!("{}\n{}", e, src));
+    bin
+}
+
+/// Writes a node script that runs a module and prints its globals, so we can
+/// compare them with the interpreter. Class instances are left out.
+fn harness(globals: &[Global]) -> String {
+    let mut prints = String::new();
+    for global in globals {
+        let export = format!("inst[\"{}\"].value", &watgen::global_sym(&global.name)[1..]);
+        let arg = match global.val {
+            Value::Num(_) => export,
+            Value::Bool(_) => format!("({} ? \"true\" : \"false\")", export),
+            Value::Str(_) => format!("str({})", export),
+            _ => continue,
+        };
+        prints.push_str(&format!("console.log(\"{} = \" + {});\n", global.name, arg));
+    }
+
+    format!(
+        "const bin = require(\"fs\").readFileSync(process.argv[2]);\n\
+         const inst = new WebAssembly.Instance(new WebAssembly.Module(bin)).exports;\n\
+         const str = (addr) => {{\n\
+         \x20 const mem = new Uint8Array(inst.memory.buffer);\n\
+         \x20 let end = addr;\n\
+         \x20 while (mem[end] != 0) end++;\n\
+         \x20 return Buffer.from(mem.subarray(addr, end)).toString();\n\
+         }};\n\
+         inst.main();\n\
+         {}",
+        prints
+    )
+}
+
+/// Compiles a program to WebAssembly, runs it with node, and checks that it
+/// leaves its globals with the same values as the KIR interpreter. The module
+/// is validated before it's run.
+fn run(name: &str) {
+    let path = util::program(name);
+    let ast = util::build(&path);
+    let bin = assemble(&watgen::gen(&ast));
+
+    if !util::has_tool("node") {
+        return;
+    }
+
+    let globals = util::interp(&util::kir(&ast, OptLevel::O0));
+
+    let dir = util::temp_dir("kolgac_wasm", &path);
+    fs::write(dir.join("prog.wasm"), bin).unwrap();
+    fs::write(dir.join("harness.cjs"), harness(&globals)).unwrap();
+
+    util::check_run(
+        Command::new("node")
+            .current_dir(&dir)
+            .args(["harness.cjs", "prog.wasm"]),
+        &globals,
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn wat_recursion() {
+    run("fib");
+}
+
+#[test]
+fn wat_loops() {
+    run("loops");
+}
+
+#[test]
+fn wat_classes() {
+    run("classes");
+
+    // Properties are 8 byte slots, and instances come from the allocator.
+    let out = watgen::gen(&util::build(&util::program("classes")));
+    assert!(out.contains("(func $kol.counter.tick (param $self i32) (result f64)\n"));
+    assert!(out.contains("(f64.store offset=8 (global.get $kol.c) (f64.const 10.0))"));
+    assert!(out.contains("(call $kol.new (i32.const 16))"));
+}
+
+#[test]
+fn wat_many_args() {
+    run("regs");
+}
+
+#[test]
+fn wat_equality() {
+    run("equality");
+}
+
+#[test]
+fn wat_scopes() {
+    run("scopes");
+
+    // Each declaration of a name gets its own local, and the loop variable
+    // outlives the loop.
+    let out = watgen::gen(&util::build(&util::program("scopes")));
+    assert!(out.contains("(local $i f64)"));
+    assert!(out.contains("(local $i.1 f64)"));
+    assert!(out.contains("(global $kol.big (export \"kol.big\") (mut i32) (i32.const 0))"));
+}
+
+#[test]
+fn wat_eval_order() {
+    run("order");
+}
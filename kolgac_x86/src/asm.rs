@@ -0,0 +1,496 @@
+use kolgac_ir::{
+    cfg::Function,
+    instr::{IRArg, IROp, Instr},
+    irgen::INIT_LBL,
+    regalloc::{self, Allocation, Target},
+};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Registers that hold the first six arguments of a call, in order.
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Registers handed to the allocator. rax and r10 aren't in here, since
+/// instructions are computed in them, and the argument registers are only
+/// loaded right before a call, from the outgoing argument area.
+pub const X86_64: Target = Target {
+    callee_saved: &["%rbx", "%r12", "%r13", "%r14", "%r15"],
+    caller_saved: &["%rdi", "%rsi", "%rdx", "%rcx", "%r8"],
+    scratch: &["%r9", "%r11"],
+};
+
+/// Generates x86-64 assembly for a program, in GAS (AT&T) syntax, following
+/// the System V ABI. The top level code becomes `main`, so the output can be
+/// assembled and linked with the C runtime.
+///
+/// Every value is a 64 bit word: numbers are doubles, kept in general purpose
+/// registers and moved into SSE registers to do arithmetic, bools are 0 or 1,
+/// strings are pointers to constant data, and class instances are pointers to
+/// their properties, allocated with calloc. Functions take every argument as
+/// an integer, in rdi, rsi, rdx, rcx, r8 and r9 and then on the stack, and
+/// return in rax.
+///
+/// Functions are named `kol.fn.NAME`, and globals `kol.NAME`, so they can't
+/// clash with each other or with C functions. The input must not be in SSA
+/// form.
+pub fn gen(fns: &[Function]) -> String {
+    let mut asm = AsmGen {
+        out: String::new(),
+        strs: Vec::new(),
+        globals: Vec::new(),
+    };
+
+    writeln!(asm.out, "\t.text").unwrap();
+    for (idx, func) in fns.iter().enumerate() {
+        asm.func(func, idx);
+    }
+    asm.data();
+    asm.out
+}
+
+/// Symbol for a function.
+pub fn fn_sym(name: &str) -> String {
+    match name {
+        INIT_LBL => String::from("main"),
+        _ => format!("kol.fn.{}", name),
+    }
+}
+
+/// Symbol for a global.
+pub fn global_sym(name: &str) -> String {
+    format!("kol.{}", name)
+}
+
+struct AsmGen {
+    out: String,
+
+    /// String literals, labeled by their index.
+    strs: Vec<String>,
+
+    /// Every global used, in the order they're first seen.
+    globals: Vec<String>,
+}
+
+/// Layout of the stack frame of the function we're generating. Everything but
+/// the outgoing arguments is addressed from rbp.
+struct Frame {
+    /// Callee-saved registers we have to restore, with their offsets.
+    saved: Vec<(&'static str, i64)>,
+
+    /// Offsets of arguments. The ones passed in registers are copied to the
+    /// stack when the function starts, so they aren't clobbered by calls.
+    args: Vec<i64>,
+
+    /// Offsets of local variables, including the ones regalloc adds for
+    /// spilled registers.
+    locals: HashMap<String, i64>,
+    size: i64,
+
+    /// Number of arguments passed on the stack by the call with the most.
+    /// Arguments are stored at the bottom of the frame as they're given with
+    /// 'param': the ones passed on the stack go first, where the callee
+    /// expects them, and the ones passed in registers go after.
+    stack_args: usize,
+
+    /// Arguments given with 'param' so far, for the next call.
+    params: usize,
+
+    /// Assembly labels for each block, by their KIR label.
+    lbls: HashMap<String, String>,
+}
+
+impl Frame {
+    fn new(func: &Function, alloc: &Allocation, fn_idx: usize) -> Frame {
+        let mut arg_cnt = 0;
+        let mut param_cnt = 0;
+        let mut local_names = Vec::new();
+        for instr in func.blocks.iter().flat_map(|b| b.instrs.iter()) {
+            match (instr.opcode, &instr.op1, &instr.op2) {
+                (IROp::Arg, Some(IRArg::Num(idx)), _) => arg_cnt = arg_cnt.max(*idx as usize + 1),
+                (IROp::Call, _, Some(IRArg::Num(cnt))) => param_cnt = param_cnt.max(*cnt as usize),
+                (IROp::St, Some(IRArg::Var(name)), _) | (IROp::Ld, Some(IRArg::Var(name)), _)
+                    if !local_names.contains(name) =>
+                {
+                    local_names.push(name.clone());
+                }
+                _ => (),
+            }
+        }
+
+        let mut size = 0;
+        let mut slot = || {
+            size += 8;
+            -size
+        };
+
+        let saved = alloc
+            .callee_saved
+            .iter()
+            .map(|reg| (*reg, slot()))
+            .collect();
+        let args = (0..arg_cnt)
+            .map(|idx| match idx < ARG_REGS.len() {
+                true => slot(),
+                false => 16 + 8 * (idx - ARG_REGS.len()) as i64,
+            })
+            .collect();
+        let locals = local_names.into_iter().map(|name| (name, slot())).collect();
+        let size = size + 8 * param_cnt as i64;
+
+        let lbls = func
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, block)| (block.lbl.clone(), format!(".LBB{}_{}", fn_idx, idx)))
+            .collect();
+
+        Frame {
+            saved,
+            args,
+            locals,
+            // Keeps the stack 16 byte aligned at calls.
+            size: (size + 15) / 16 * 16,
+            stack_args: param_cnt.saturating_sub(ARG_REGS.len()),
+            params: 0,
+            lbls,
+        }
+    }
+
+    /// Where an argument to the next call is stored, as an operand.
+    fn param(&self, idx: usize) -> String {
+        match idx < ARG_REGS.len() {
+            true => format!("{}(%rsp)", 8 * (self.stack_args + idx)),
+            false => format!("{}(%rsp)", 8 * (idx - ARG_REGS.len())),
+        }
+    }
+}
+
+impl AsmGen {
+    fn func(&mut self, func: &Function, fn_idx: usize) {
+        let mut func = func.clone();
+        let alloc = regalloc::allocate(&mut func, &X86_64);
+        let mut frame = Frame::new(&func, &alloc, fn_idx);
+        let sym = fn_sym(&func.name);
+
+        if func.name == INIT_LBL {
+            writeln!(self.out, "\t.globl {}", sym).unwrap();
+        }
+        writeln!(self.out, "\t.type {}, @function", sym).unwrap();
+        writeln!(self.out, "{}:", sym).unwrap();
+        self.emit("pushq %rbp");
+        self.emit("movq %rsp, %rbp");
+        if frame.size > 0 {
+            self.emit(&format!("subq ${}, %rsp", frame.size));
+        }
+        for (reg, offset) in &frame.saved {
+            self.emit(&format!("movq {}, {}(%rbp)", reg, offset));
+        }
+        for (idx, reg) in ARG_REGS.iter().enumerate().take(frame.args.len()) {
+            self.emit(&format!("movq {}, {}(%rbp)", reg, frame.args[idx]));
+        }
+
+        for block in &func.blocks {
+            writeln!(self.out, "{}:", frame.lbls[&block.lbl]).unwrap();
+            for instr in &block.instrs {
+                self.instr(&mut frame, &func, instr);
+            }
+        }
+
+        writeln!(self.out, "\t.size {}, .-{}", sym, sym).unwrap();
+        writeln!(self.out).unwrap();
+    }
+
+    fn instr(&mut self, frame: &mut Frame, func: &Function, instr: &Instr) {
+        let op1 = instr.op1.as_ref();
+        let op2 = instr.op2.as_ref();
+
+        match instr.opcode {
+            IROp::Mv => self.load(frame, op1.unwrap(), dest(instr)),
+            IROp::Ld => {
+                let src = self.mem(frame, op1.unwrap());
+                self.emit(&format!("movq {}, {}", src, dest(instr)));
+            }
+            IROp::St => {
+                self.load(frame, op2.unwrap(), "%rax");
+                let dest = self.mem(frame, op1.unwrap());
+                self.emit(&format!("movq %rax, {}", dest));
+            }
+            IROp::Add | IROp::Sub | IROp::Mul | IROp::Div => {
+                let op = match instr.opcode {
+                    IROp::Add => "addsd",
+                    IROp::Sub => "subsd",
+                    IROp::Mul => "mulsd",
+                    _ => "divsd",
+                };
+                self.load_sse(frame, op1.unwrap(), op2.unwrap());
+                self.emit(&format!("{} %xmm1, %xmm0", op));
+                self.emit("movq %xmm0, %rax");
+                self.store(instr, "%rax");
+            }
+            IROp::Lt | IROp::Gt | IROp::LtEq | IROp::GtEq => {
+                // 'a' and 'ae' are false when either side is NaN, so we swap
+                // the operands for less than, instead of using 'b' and 'be'.
+                let (cmp, set) = match instr.opcode {
+                    IROp::Lt => ("%xmm0, %xmm1", "seta"),
+                    IROp::LtEq => ("%xmm0, %xmm1", "setae"),
+                    IROp::Gt => ("%xmm1, %xmm0", "seta"),
+                    _ => ("%xmm1, %xmm0", "setae"),
+                };
+                self.load_sse(frame, op1.unwrap(), op2.unwrap());
+                self.emit(&format!("ucomisd {}", cmp));
+                self.set_flag(instr, set);
+            }
+            IROp::EqEq | IROp::Neq => {
+                // Only numbers can be compared for equality, so 0 equals -0,
+                // and NaN isn't equal to anything. The parity flag is set when
+                // either side is NaN.
+                let (set, parity, join) = match instr.opcode {
+                    IROp::EqEq => ("sete", "setnp", "andb"),
+                    _ => ("setne", "setp", "orb"),
+                };
+                self.load_sse(frame, op1.unwrap(), op2.unwrap());
+                self.emit("ucomisd %xmm1, %xmm0");
+                self.emit(&format!("{} %r10b", parity));
+                self.emit(&format!("{} %al", set));
+                self.emit(&format!("{} %r10b, %al", join));
+                self.emit("movzbl %al, %eax");
+                self.store(instr, "%rax");
+            }
+            IROp::LogAnd | IROp::LogOr => {
+                let op = match instr.opcode {
+                    IROp::LogAnd => "andq",
+                    _ => "orq",
+                };
+                self.load(frame, op1.unwrap(), "%rax");
+                self.load(frame, op2.unwrap(), "%r10");
+                self.emit(&format!("{} %r10, %rax", op));
+                self.store(instr, "%rax");
+            }
+            IROp::Neg | IROp::Not => {
+                let op = match instr.opcode {
+                    IROp::Neg => "btcq $63, %rax",
+                    _ => "xorq $1, %rax",
+                };
+                self.load(frame, op1.unwrap(), "%rax");
+                self.emit(op);
+                self.store(instr, "%rax");
+            }
+            IROp::Jmp => {
+                let target = &frame.lbls[instr.jmp_target().unwrap()];
+                self.emit(&format!("jmp {}", target));
+            }
+            IROp::JmpE | IROp::JmpNe => {
+                let jmp = match instr.opcode {
+                    IROp::JmpE => "jne",
+                    _ => "je",
+                };
+                self.load(frame, op1.unwrap(), "%rax");
+                self.emit("testq %rax, %rax");
+                let target = &frame.lbls[instr.jmp_target().unwrap()];
+                self.emit(&format!("{} {}", jmp, target));
+            }
+            IROp::Param => {
+                self.load(frame, op1.unwrap(), "%rax");
+                let slot = frame.param(frame.params);
+                self.emit(&format!("movq %rax, {}", slot));
+                frame.params += 1;
+            }
+            IROp::Call => {
+                let callee = match op1 {
+                    Some(IRArg::Lbl(name)) => fn_sym(name),
+                    _ => panic!("invalid call '{}'", instr.body()),
+                };
+                self.call(frame, &callee);
+                self.store(instr, "%rax");
+            }
+            IROp::Arg => {
+                let idx = match op1 {
+                    Some(IRArg::Num(idx)) => *idx as usize,
+                    _ => panic!("invalid argument '{}'", instr.body()),
+                };
+                let offset = frame.args[idx];
+                self.emit(&format!("movq {}(%rbp), {}", offset, dest(instr)));
+            }
+            IROp::New => {
+                // Properties start out zeroed, like they do in the interpreter.
+                let cnt = match op2 {
+                    Some(IRArg::Num(cnt)) => *cnt as usize,
+                    _ => panic!("invalid class instance '{}'", instr.body()),
+                };
+                self.emit(&format!("movq ${}, %rdi", cnt));
+                self.emit("movq $8, %rsi");
+                self.emit("call calloc@PLT");
+                self.store(instr, "%rax");
+            }
+            IROp::LdField => {
+                let (base, idx) = field(instr);
+                self.load(frame, &IRArg::Reg(base), "%rax");
+                self.emit(&format!("movq {}(%rax), %rax", 8 * idx));
+                self.store(instr, "%rax");
+            }
+            IROp::StField => {
+                let (base, idx) = field(instr);
+                self.load(frame, &IRArg::Reg(base), "%rax");
+                self.load(frame, op2.unwrap(), "%r10");
+                self.emit(&format!("movq %r10, {}(%rax)", 8 * idx));
+            }
+            IROp::Ret => {
+                match op1 {
+                    Some(val) => self.load(frame, val, "%rax"),
+                    None if func.name == INIT_LBL => self.emit("xorl %eax, %eax"),
+                    None => (),
+                }
+                for (reg, offset) in &frame.saved {
+                    self.emit(&format!("movq {}(%rbp), {}", offset, reg));
+                }
+                self.emit("leave");
+                self.emit("ret");
+            }
+            IROp::Phi => {
+                panic!("phis must be removed with ssa::destruct before generating assembly")
+            }
+        }
+    }
+
+    /// Moves the arguments given so far into their registers, and calls a
+    /// function. The ones passed on the stack are already in place.
+    fn call(&mut self, frame: &mut Frame, callee: &str) {
+        let params = std::mem::take(&mut frame.params);
+        for (idx, reg) in ARG_REGS.iter().enumerate().take(params) {
+            let slot = frame.param(idx);
+            self.emit(&format!("movq {}, {}", slot, reg));
+        }
+        self.emit(&format!("call {}", callee));
+    }
+
+    /// Loads both operands into xmm0 and xmm1.
+    fn load_sse(&mut self, frame: &Frame, lhs: &IRArg, rhs: &IRArg) {
+        self.load(frame, lhs, "%rax");
+        self.load(frame, rhs, "%r10");
+        self.emit("movq %rax, %xmm0");
+        self.emit("movq %r10, %xmm1");
+    }
+
+    /// Stores the result of a comparison, after the flags have been set.
+    fn set_flag(&mut self, instr: &Instr, set: &str) {
+        self.emit(&format!("{} %al", set));
+        self.emit("movzbl %al, %eax");
+        self.store(instr, "%rax");
+    }
+
+    /// Loads a value into a register.
+    fn load(&mut self, frame: &Frame, arg: &IRArg, dest: &str) {
+        let src = match arg {
+            IRArg::Num(n) => {
+                self.emit(&format!("movabsq ${}, {}", n.to_bits() as i64, dest));
+                return;
+            }
+            IRArg::Bool(b) => format!("${}", *b as u8),
+            IRArg::Str(s) => {
+                let idx = self.str_idx(s);
+                self.emit(&format!("leaq .Lstr{}(%rip), {}", idx, dest));
+                return;
+            }
+            IRArg::Reg(reg) => reg.clone(),
+            IRArg::Var(_) | IRArg::Global(_) => self.mem(frame, arg),
+            _ => panic!("can't load '{}' into a register", arg),
+        };
+
+        if src != dest {
+            self.emit(&format!("movq {}, {}", src, dest));
+        }
+    }
+
+    /// Moves the result of an instruction from a register to where it lives.
+    fn store(&mut self, instr: &Instr, src: &str) {
+        if let Some(dest) = instr.def() {
+            if dest != src {
+                self.emit(&format!("movq {}, {}", src, dest));
+            }
+        }
+    }
+
+    /// The memory operand for a variable.
+    fn mem(&mut self, frame: &Frame, arg: &IRArg) -> String {
+        match arg {
+            IRArg::Var(name) => format!("{}(%rbp)", frame.locals[name]),
+            IRArg::Global(name) => {
+                if !self.globals.contains(name) {
+                    self.globals.push(name.clone());
+                }
+                format!("{}(%rip)", global_sym(name))
+            }
+            _ => panic!("'{}' isn't a variable", arg),
+        }
+    }
+
+    fn str_idx(&mut self, s: &str) -> usize {
+        match self.strs.iter().position(|lit| lit == s) {
+            Some(idx) => idx,
+            None => {
+                self.strs.push(String::from(s));
+                self.strs.len() - 1
+            }
+        }
+    }
+
+    /// Emits globals, which start out zeroed, and string literals.
+    fn data(&mut self) {
+        if !self.globals.is_empty() {
+            writeln!(self.out, "\t.data").unwrap();
+            writeln!(self.out, "\t.p2align 3").unwrap();
+        }
+        for name in &self.globals {
+            let sym = global_sym(name);
+            writeln!(self.out, "\t.globl {}", sym).unwrap();
+            writeln!(self.out, "{}:", sym).unwrap();
+            writeln!(self.out, "\t.quad 0").unwrap();
+        }
+
+        if !self.strs.is_empty() {
+            writeln!(self.out, "\t.section .rodata").unwrap();
+        }
+        for (idx, s) in self.strs.iter().enumerate() {
+            writeln!(self.out, ".Lstr{}:", idx).unwrap();
+            writeln!(self.out, "\t.asciz \"{}\"", escape(s)).unwrap();
+        }
+
+        // Marks the stack as non-executable.
+        writeln!(self.out, "\t.section .note.GNU-stack,\"\",@progbits").unwrap();
+    }
+
+    fn emit(&mut self, line: &str) {
+        writeln!(self.out, "\t{}", line).unwrap();
+    }
+}
+
+/// The register an instruction writes to. Registers have all been allocated, so
+/// it's a physical one.
+fn dest(instr: &Instr) -> &str {
+    match instr.def() {
+        Some(reg) => reg,
+        None => panic!("'{}' has no result", instr.body()),
+    }
+}
+
+fn field(instr: &Instr) -> (String, usize) {
+    match &instr.op1 {
+        Some(IRArg::Field(reg, idx)) => (reg.clone(), *idx),
+        _ => panic!("invalid property access '{}'", instr.body()),
+    }
+}
+
+/// Escapes a string for a GAS string directive.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,3 @@
+extern crate kolgac_ir;
+
+pub mod asm;
@@ -0,0 +1,104 @@
+use kolgac_ir::regalloc;
+use kolgac_ir::{pass::OptLevel, text};
+use kolgac_testutil as util;
+use kolgac_x86::asm;
+
+use std::fs;
+
+/// Compiles a program to assembly at every optimization level, links it, and
+/// checks that it leaves its globals with the same values as the interpreter.
+fn run(name: &str) {
+    let path = util::program(name);
+    if !util::has_tool("cc") {
+        return;
+    }
+
+    let ast = util::build(&path);
+    for level in &[OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let fns = util::kir(&ast, *level);
+        let globals = util::interp(&fns);
+
+        let dir = util::temp_dir(&format!("kolgac_x86_{:?}", level), &path);
+        fs::write(dir.join("prog.s"), asm::gen(&fns)).unwrap();
+
+        // Values are 64 bit words, bools included.
+        let harness = util::c_harness(&globals, "long", asm::global_sym);
+        util::cc_run(&dir, &["prog.s"], &harness, &globals);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn asm_recursion() {
+    run("fib");
+}
+
+#[test]
+fn asm_loops() {
+    run("loops");
+}
+
+#[test]
+fn asm_classes() {
+    run("classes");
+}
+
+#[test]
+fn asm_scopes() {
+    run("scopes");
+}
+
+#[test]
+fn asm_eval_order() {
+    run("order");
+}
+
+#[test]
+fn asm_equality() {
+    run("equality");
+}
+
+#[test]
+fn asm_stack_args_and_spills() {
+    run("regs");
+
+    let fns = util::kir(&util::build(&util::program("regs")), OptLevel::O0);
+    // Seven values are live across the call to nested, and only five
+    // callee-saved registers are allocatable.
+    let mut across = fns.iter().find(|f| f.name == "across").unwrap().clone();
+    let alloc = regalloc::allocate(&mut across, &asm::X86_64);
+    assert!(alloc.spills > 0);
+
+    let out = asm::gen(&fns);
+    assert!(out.contains("\t.globl main\n"));
+    assert!(out.contains("\nmain:\n"));
+    assert!(out.contains("kol.fn.sum8:\n"));
+    assert!(out.contains("\tmovq 24(%rbp), "));
+    assert!(out.contains("\tmovq %rax, 8(%rsp)\n"));
+    assert!(out.contains("\t.globl kol.big\nkol.big:\n\t.quad 0\n"));
+}
+
+#[test]
+fn asm_saves_callee_saved_regs() {
+    // r1 is live across the call, so it needs a callee-saved register, which
+    // has to be saved and restored. r3 isn't, so it gets a caller-saved one.
+    let src = "r1 = arg 0 : f\n\
+               param r1 : f\n\
+               r2 = call g 1 : f\n\
+               r3 = add r1 r2 : f\n\
+               ret r3 : f\n\
+               r4 = arg 0 : g\n\
+               ret r4 : g\n";
+    let fns = text::parse_fns(src).unwrap();
+    let out = asm::gen(&fns);
+
+    assert!(out.contains("\tmovq %rbx, -8(%rbp)\n"));
+    assert!(out.contains("\tmovq -16(%rbp), %rbx\n"));
+    assert!(out.contains("\tmovq %rax, 0(%rsp)\n\tmovq 0(%rsp), %rdi\n\tcall kol.fn.g\n"));
+    assert!(out.contains("\tmovq -8(%rbp), %rbx\n\tleave\n"));
+
+    // g doesn't call anything, so it doesn't use callee-saved registers.
+    let g = &out[out.find("kol.fn.g:").unwrap()..];
+    assert!(!g.contains("%rbx"));
+}